@@ -0,0 +1,97 @@
+//! 取得した HTML の保存 (変換結果の確認や、取得し直さずに別のオプションで変換し直す用)
+//!
+//! 配置は localfile::replay と同じ <ホスト>/<パス> で、保存したディレクトリをそのまま REPLAY_DIR / --replay-dir に使える。
+//! 本文は UTF-8 に変換済みなので、元の <meta charset> より優先して UTF-8 として読まれるよう BOM を付ける。
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use url::Url;
+
+use crate::localfile;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// 1 回の変換で取得した HTML
+#[derive(Debug, Default)]
+pub struct HtmlArchive {
+    /// 書き出すディレクトリ (None ならメモリに持つ)
+    dir: Option<PathBuf>,
+    /// 相対パス → 本文 (ディレクトリに書き出したものは本文を持たない)
+    pages: Mutex<BTreeMap<String, Option<String>>>,
+}
+
+impl HtmlArchive {
+    /// メモリに持つ (サーバーのジョブ。ZIP で返す)
+    pub fn in_memory() -> Self {
+        HtmlArchive::default()
+    }
+
+    /// 取得するたびにディレクトリに書き出す (CLI)
+    pub fn in_dir(dir: impl Into<PathBuf>) -> Self {
+        HtmlArchive {
+            dir: Some(dir.into()),
+            pages: Mutex::default(),
+        }
+    }
+
+    /// 1 ページ分を保存 (同じパスは後のもので上書き。書き出しの失敗はログだけ)
+    pub async fn add(&self, url: &Url, html: &str) {
+        let Some(name) = localfile::replay_file(url) else {
+            return;
+        };
+        let Some(dir) = &self.dir else {
+            self.pages.lock().unwrap().insert(name, Some(html.to_string()));
+            return;
+        };
+        let path = dir.join(&name);
+        let result = async {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&path, with_bom(html)).await
+        }
+        .await;
+        match result {
+            Ok(()) => {
+                self.pages.lock().unwrap().insert(name, None);
+            }
+            Err(e) => tracing::warn!(url = %url, path = %path.display(), error = %e, "saving HTML failed"),
+        }
+    }
+
+    /// 書き出すディレクトリ
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// 保存したページ数
+    pub fn len(&self) -> usize {
+        self.pages.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// (prefix 付きの相対パス, 中身) の一覧 (ZIP 用。書き出したものはファイルから読み直す)
+    pub fn files(&self, prefix: &str) -> Vec<(String, Vec<u8>)> {
+        let pages = self.pages.lock().unwrap();
+        pages
+            .iter()
+            .filter_map(|(name, html)| {
+                let bytes = match (html, &self.dir) {
+                    (Some(html), _) => with_bom(html),
+                    (None, Some(dir)) => std::fs::read(dir.join(name)).ok()?,
+                    (None, None) => return None,
+                };
+                Some((format!("{prefix}{name}"), bytes))
+            })
+            .collect()
+    }
+}
+
+fn with_bom(html: &str) -> Vec<u8> {
+    [UTF8_BOM, html.as_bytes()].concat()
+}
@@ -0,0 +1,87 @@
+//! 内蔵サーバーのアクセス制限 (API キー / Basic 認証)
+//!
+//! 設定の API_KEY・BASIC_AUTH のどちらかが指定されていれば、死活監視以外のすべてのルートで認証を求める。
+//! どちらもなければ、他の人の結果が見える履歴はこのマシン (ループバック) からしか使えない。
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use base64::Engine;
+
+use web_to_json::Settings;
+
+/// API キーを送るヘッダ (Authorization: Bearer でも可)
+const API_KEY_HEADER: &str = "X-API-Key";
+
+/// 認証なしで応答するパス (オーケストレーターの死活監視用)
+const PUBLIC_PATHS: &[&str] = &["/healthz", "/readyz"];
+
+/// 認証がなければループバックからだけ応答するパスの先頭 (保存した結果の一覧・ダウンロード・削除)
+const LOCAL_ONLY_PATHS: &[&str] = &["/history", "/api/history"];
+
+/// 認証を確認し、通らなければ 401 を返すミドルウェア
+pub async fn require_auth(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    if let Some(settings) = req.app_data::<web::Data<Settings>>() {
+        let local = req.peer_addr().is_some_and(|addr| addr.ip().is_loopback());
+        if !has_auth(settings) && !local && is_local_only(req.path()) {
+            let response = HttpResponse::Forbidden().body("History is only available from this machine unless API_KEY or BASIC_AUTH is set");
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+    }
+    let challenge = match req.app_data::<web::Data<Settings>>() {
+        Some(settings) if !PUBLIC_PATHS.contains(&req.path()) && !is_authorized(settings, req.headers()) => {
+            !settings.basic_auth.is_empty()
+        }
+        _ => return next.call(req).await.map(ServiceResponse::map_into_left_body),
+    };
+    let mut response = HttpResponse::Unauthorized();
+    // ブラウザにユーザー名・パスワードの入力を出させる
+    if challenge {
+        response.insert_header((header::WWW_AUTHENTICATE, r#"Basic realm="web-to-json", charset="UTF-8""#));
+    }
+    let response = response.body("Authentication required");
+    Ok(req.into_response(response).map_into_right_body())
+}
+
+/// API キーか Basic 認証が設定されている
+fn has_auth(settings: &Settings) -> bool {
+    !settings.api_key.is_empty() || !settings.basic_auth.is_empty()
+}
+
+fn is_local_only(path: &str) -> bool {
+    LOCAL_ONLY_PATHS.iter().any(|prefix| path == *prefix || path.starts_with(&format!("{prefix}/")))
+}
+
+/// 認証が不要 (どちらも未設定) か、ヘッダが設定と一致するか
+fn is_authorized(settings: &Settings, headers: &HeaderMap) -> bool {
+    if !has_auth(settings) {
+        return true;
+    }
+    let header_text = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    if !settings.api_key.is_empty() {
+        let key = header_text(API_KEY_HEADER)
+            .or_else(|| header_text(header::AUTHORIZATION.as_str()).and_then(|v| v.strip_prefix("Bearer ")));
+        if key.is_some_and(|key| constant_time_eq(key.trim().as_bytes(), settings.api_key.as_bytes())) {
+            return true;
+        }
+    }
+    if !settings.basic_auth.is_empty() {
+        let decoded = header_text(header::AUTHORIZATION.as_str())
+            .and_then(|v| v.strip_prefix("Basic "))
+            .and_then(|v| base64::engine::general_purpose::STANDARD.decode(v.trim()).ok());
+        if decoded.is_some_and(|pair| constant_time_eq(&pair, settings.basic_auth.as_bytes())) {
+            return true;
+        }
+    }
+    false
+}
+
+/// 一致するまでの時間から中身を推測されないよう、全バイトを比べる
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,98 @@
+//! ETag / Last-Modified 付きレスポンスのメモリキャッシュ (条件付きリクエスト用)
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use reqwest::header::{HeaderMap, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use tokio::time::Instant;
+use url::Url;
+
+/// キャッシュ済みレスポンス
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub body: String,
+    pub content_type: Option<String>,
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    stored_at: Instant,
+}
+
+impl CachedResponse {
+    /// 検証用ヘッダ付きのレスポンスだけキャッシュ対象
+    pub fn from_response(headers: &HeaderMap, body: &str) -> Option<Self> {
+        let etag = headers.get(ETAG).cloned();
+        let last_modified = headers.get(LAST_MODIFIED).cloned();
+        if etag.is_none() && last_modified.is_none() {
+            return None;
+        }
+        Some(CachedResponse {
+            body: body.to_string(),
+            content_type: headers
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+            etag,
+            last_modified,
+            stored_at: Instant::now(),
+        })
+    }
+
+    /// 条件付きリクエストのヘッダ
+    pub fn conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = &self.etag {
+            headers.insert(IF_NONE_MATCH, etag.clone());
+        }
+        if let Some(last_modified) = &self.last_modified {
+            headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+        }
+        headers
+    }
+}
+
+/// URL をキーにしたキャッシュ (ジョブをまたいで共有。Cookie・認証情報・追加ヘッダを送るジョブは別のキー)
+#[derive(Debug)]
+pub struct ResponseCache {
+    entries: Mutex<HashMap<String, CachedResponse>>,
+    max_entries: usize,
+}
+
+impl ResponseCache {
+    pub fn new(max_entries: usize) -> Self {
+        ResponseCache {
+            entries: Mutex::new(HashMap::new()),
+            max_entries,
+        }
+    }
+
+    pub fn get(&self, url: &Url, scope: Option<&str>) -> Option<CachedResponse> {
+        self.entries.lock().unwrap().get(&cache_key(url, scope)).cloned()
+    }
+
+    /// 上限を超える場合は最も古いものを捨てる
+    pub fn insert(&self, url: &Url, scope: Option<&str>, response: CachedResponse) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let key = cache_key(url, scope);
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, r)| r.stored_at)
+                .map(|(k, _)| k.clone());
+            if let Some(key) = oldest {
+                entries.remove(&key);
+            }
+        }
+        entries.insert(key, response);
+    }
+}
+
+/// scope (Fetcher の cache_scope) があれば URL の後ろに付ける
+fn cache_key(url: &Url, scope: Option<&str>) -> String {
+    match scope {
+        Some(scope) => format!("{url}\n{scope}"),
+        None => url.to_string(),
+    }
+}
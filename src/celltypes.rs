@@ -0,0 +1,173 @@
+//! テーブルのセル値の型推定 (整数・小数・パーセント・真偽値・日付)
+
+use serde_json::{Number, Value};
+
+/// セルの文字列を型付きの JSON 値に
+///
+/// - 整数 / 小数 (3 桁区切りのカンマ可, "007" のような先頭 0 は文字列のまま) → 数値
+/// - "12.5%" → 0.125
+/// - true / false (大文字小文字問わず) → 真偽値
+/// - ISO 日付 "2024-01-02" / "2024/1/2" → "2024-01-02" (日時はそのまま文字列)
+/// - 空のセル → null
+pub fn infer(text: &str) -> Value {
+    let text = text.trim();
+    if text.is_empty() {
+        return Value::Null;
+    }
+    if text.eq_ignore_ascii_case("true") {
+        return Value::Bool(true);
+    }
+    if text.eq_ignore_ascii_case("false") {
+        return Value::Bool(false);
+    }
+    if let Some(number) = parse_number(text) {
+        return number;
+    }
+    if let Some(percent) = text.strip_suffix('%').and_then(|n| parse_float(n.trim_end())) {
+        if let Some(n) = Number::from_f64(percent / 100.0) {
+            return Value::Number(n);
+        }
+    }
+    if let Some(date) = parse_date(text) {
+        return Value::String(date);
+    }
+    Value::String(text.to_string())
+}
+
+/// 整数 (i64 に収まる場合) か小数
+fn parse_number(text: &str) -> Option<Value> {
+    let digits = plain_digits(text)?;
+    if !digits.contains('.') {
+        if let Ok(n) = digits.parse::<i64>() {
+            return Some(Value::Number(n.into()));
+        }
+    }
+    digits.parse::<f64>().ok().and_then(Number::from_f64).map(Value::Number)
+}
+
+fn parse_float(text: &str) -> Option<f64> {
+    plain_digits(text)?.parse().ok()
+}
+
+/// 符号・3 桁区切り・小数点だけの数値表記からカンマを除いたもの
+fn plain_digits(text: &str) -> Option<String> {
+    let unsigned = text.strip_prefix(['-', '+']).unwrap_or(text);
+    let (int_part, frac_part) = match unsigned.split_once('.') {
+        Some((i, f)) => (i, Some(f)),
+        None => (unsigned, None),
+    };
+    if let Some(frac) = frac_part {
+        if frac.is_empty() || !frac.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+    }
+    let groups: Vec<&str> = int_part.split(',').collect();
+    let first = groups[0];
+    if first.is_empty() || !first.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if groups.len() > 1 && (first.len() > 3 || groups[1..].iter().any(|g| g.len() != 3 || !g.bytes().all(|b| b.is_ascii_digit()))) {
+        return None;
+    }
+    // 先頭 0 の整数部 (郵便番号・コードなど) は数値にしない
+    if first.len() > 1 && first.starts_with('0') {
+        return None;
+    }
+    Some(text.replace(',', ""))
+}
+
+/// YYYY-MM-DD / YYYY/M/D を YYYY-MM-DD に (存在しない月日は対象外)
+fn parse_date(text: &str) -> Option<String> {
+    let sep = if text.contains('-') { '-' } else { '/' };
+    let parts: Vec<&str> = text.split(sep).collect();
+    let [y, m, d] = parts[..] else {
+        return None;
+    };
+    if y.len() != 4 || !(1..=2).contains(&m.len()) || !(1..=2).contains(&d.len()) {
+        return None;
+    }
+    let (y, m, d): (u32, u32, u32) = (y.parse().ok()?, m.parse().ok()?, d.parse().ok()?);
+    let leap = (y % 4 == 0 && y % 100 != 0) || y % 400 == 0;
+    let days = match m {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return None,
+    };
+    (1..=days).contains(&d).then(|| format!("{y:04}-{m:02}-{d:02}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn integers_and_decimals() {
+        assert_eq!(infer("42"), json!(42));
+        assert_eq!(infer(" -7 "), json!(-7));
+        assert_eq!(infer("+5"), json!(5));
+        assert_eq!(infer("0"), json!(0));
+        assert_eq!(infer("0.5"), json!(0.5));
+        assert_eq!(infer("-3.25"), json!(-3.25));
+        // i64 に収まらなければ小数
+        assert_eq!(infer("99999999999999999999"), json!(1e20));
+    }
+
+    #[test]
+    fn thousands_separators() {
+        assert_eq!(infer("1,234"), json!(1234));
+        assert_eq!(infer("12,345,678.9"), json!(12345678.9));
+        // 区切りの位置が 3 桁ごとでなければ文字列
+        for text in ["1,23", "1234,567", "1,,234", ",123", "1,234,"] {
+            assert_eq!(infer(text), json!(text), "{text}");
+        }
+    }
+
+    #[test]
+    fn leading_zeros_and_partial_numbers_stay_strings() {
+        for text in ["007", "0123.4", "1.", ".5", "1.2.3", "1e5", "12abc", "-", "NaN", "inf"] {
+            assert_eq!(infer(text), json!(text), "{text}");
+        }
+    }
+
+    #[test]
+    fn percentages() {
+        assert_eq!(infer("12.5%"), json!(0.125));
+        assert_eq!(infer("50 %"), json!(0.5));
+        assert_eq!(infer("-1,000%"), json!(-10.0));
+        assert_eq!(infer("abc%"), json!("abc%"));
+        assert_eq!(infer("%"), json!("%"));
+    }
+
+    #[test]
+    fn booleans_and_empty_cells() {
+        assert_eq!(infer("true"), json!(true));
+        assert_eq!(infer("FALSE"), json!(false));
+        assert_eq!(infer("True "), json!(true));
+        assert_eq!(infer("yes"), json!("yes"));
+        assert_eq!(infer(""), Value::Null);
+        assert_eq!(infer(" \n\t"), Value::Null);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(infer("2024-01-02"), json!("2024-01-02"));
+        assert_eq!(infer("2024/1/2"), json!("2024-01-02"));
+        assert_eq!(infer("2024-2-29"), json!("2024-02-29"));
+        assert_eq!(infer("2000/02/29"), json!("2000-02-29"));
+        // 存在しない日付・日時・区切りの混在は文字列のまま
+        for text in ["2023-02-29", "1900-02-29", "2024-04-31", "2024-13-01", "2024-00-10", "2024-01-00"] {
+            assert_eq!(infer(text), json!(text), "{text}");
+        }
+        for text in ["2024-01-02T10:00", "2024-1/2", "24-01-02", "2024-001-02", "2024/01"] {
+            assert_eq!(infer(text), json!(text), "{text}");
+        }
+    }
+
+    #[test]
+    fn other_text_is_trimmed() {
+        assert_eq!(infer("  hello world \n"), json!("hello world"));
+    }
+}
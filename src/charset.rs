@@ -0,0 +1,86 @@
+//! 本文の文字コード判定とデコード (Shift_JIS / EUC-JP などの非 UTF-8 ページ用)
+
+use encoding_rs::{Encoding, EUC_JP, SHIFT_JIS, UTF_8};
+
+/// <meta charset> / XML 宣言を探す範囲 (HTML の仕様では先頭 1024 バイト)
+const PRESCAN_BYTES: usize = 1024;
+
+/// 本文を文字列に
+///
+/// BOM → Content-Type の charset → <meta charset> / <meta http-equiv> / <?xml encoding?> の順に判定し、
+/// どれもなければ UTF-8 → Shift_JIS → EUC-JP のうち不正なバイト列なしで読めるもの (全滅なら UTF-8 で置換)
+pub fn decode(bytes: &[u8], content_type: Option<&str>) -> String {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding.decode_with_bom_removal(bytes).0.into_owned();
+    }
+    let declared = content_type
+        .and_then(charset_param)
+        .or_else(|| prescan(&bytes[..bytes.len().min(PRESCAN_BYTES)]));
+    if let Some(encoding) = declared {
+        return encoding.decode_without_bom_handling(bytes).0.into_owned();
+    }
+    for encoding in [UTF_8, SHIFT_JIS, EUC_JP] {
+        if let Some(text) = encoding.decode_without_bom_handling_and_without_replacement(bytes) {
+            return text.into_owned();
+        }
+    }
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// "text/html; charset=Shift_JIS" の charset
+fn charset_param(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| label_encoding(value))
+            .flatten()
+    })
+}
+
+/// 先頭部分の <meta charset="..."> / <meta content="text/html; charset=..."> / <?xml encoding="..."?>
+fn prescan(head: &[u8]) -> Option<&'static Encoding> {
+    // 判定対象は ASCII 部分だけなので、そのまま小文字の文字列として扱う
+    let head: String = head.iter().map(|&b| (b as char).to_ascii_lowercase()).collect();
+    if let Some(decl) = head.strip_prefix("<?xml").and_then(|rest| rest.split("?>").next()) {
+        if let Some(encoding) = attr_value(decl, "encoding").and_then(label_encoding) {
+            return Some(encoding);
+        }
+    }
+    head.split("<meta").skip(1).find_map(|tag| {
+        let tag = tag.split('>').next().unwrap_or(tag);
+        attr_value(tag, "charset")
+            .and_then(label_encoding)
+            .or_else(|| attr_value(tag, "content").and_then(|c| charset_param(&c)))
+    })
+}
+
+/// タグ内の name="value" / name='value' / name=value の値
+fn attr_value(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag;
+    while let Some(pos) = rest.find(name) {
+        let before = rest[..pos].chars().next_back();
+        let after = rest[pos + name.len()..].trim_start();
+        rest = &rest[pos + name.len()..];
+        // "http-equiv" 中の文字列などを除く (直前が空白か区切りのときだけ)
+        if before.is_some_and(|c| !c.is_whitespace() && c != '"' && c != '\'') {
+            continue;
+        }
+        let Some(value) = after.strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        let value = match value.chars().next() {
+            Some(q @ ('"' | '\'')) => value[1..].split(q).next().unwrap_or(""),
+            _ => value.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or(""),
+        };
+        return Some(value.to_string());
+    }
+    None
+}
+
+/// "shift_jis" などのラベルから (UTF-16 の宣言は ASCII 互換でないため UTF-8 とみなす)
+fn label_encoding(label: impl AsRef<str>) -> Option<&'static Encoding> {
+    let encoding = Encoding::for_label(label.as_ref().trim().trim_matches(['"', '\'']).as_bytes())?;
+    Some(encoding.output_encoding())
+}
@@ -0,0 +1,847 @@
+//! コマンドライン (サーバーを起動しないバッチ変換)
+
+use std::io::{Read, Write};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::{Parser, Subcommand};
+
+use crate::templates::{self, TemplateStore};
+use crate::urlfile;
+use web_to_json::dedup::DedupMode;
+use web_to_json::archive::HtmlArchive;
+use web_to_json::diskcache::DiskCache;
+use web_to_json::fetch::{parse_header_lines, Credentials};
+use web_to_json::history::HistoryStore;
+use web_to_json::localfile::LocalFiles;
+use web_to_json::search::{IndexMode, SearchIndex};
+use web_to_json::warc::WarcWriter;
+use web_to_json::wayback;
+use web_to_json::output::{self, OutputFormat};
+use web_to_json::{charset, columnar, links, schema, tables};
+use web_to_json::{
+    non_empty, options_from_form, parse_attribute_list, parse_patterns, run_job_each, mb_to_bytes, secs_to_duration,
+    DomContent, JobOptions, RawHtml, Settings, SubpageScope, TableHeaders, UrlForm, MAX_ATTEMPTS_LIMIT, MAX_CONCURRENCY_LIMIT, RAW_HTML_URL,
+};
+
+/// 引数なしで起動した場合は Web サーバーを起動する
+#[derive(Parser)]
+#[command(name = "web-to-json", version, about = "HTML ページを JSON に変換")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// サーバーの待ち受けアドレス (ホスト:ポート。例: 0.0.0.0:9090)。
+    /// 省略時は 127.0.0.1:8080 で、使用中なら空いているポートを探す
+    #[arg(long, env = "WEB_TO_JSON_BIND")]
+    pub bind: Option<String>,
+}
+
+// 起動時に 1 回作るだけなのでサイズ差は問題にならない
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+pub enum Command {
+    /// URL リスト (1 行 1 URL、または CSV / TSV) を変換して出力 (既定は JSON)
+    Convert {
+        /// URL リストのファイル (.csv / .tsv は --url-column の列。--html 指定時は変換する HTML のファイル)。
+        /// 省略時または "-" は標準入力
+        input: Option<PathBuf>,
+
+        /// 入力ファイルを URL リストではなく HTML として取得せずに変換
+        #[arg(long)]
+        html: bool,
+
+        /// --html の HTML の元の URL (相対リンクの基準。省略時は about:blank)
+        #[arg(long, requires = "html")]
+        base_url: Option<String>,
+
+        /// CSV の URL の列 (見出しか 1 始まりの番号。省略時は見出しが url の列、なければ最初に URL が入っている列)
+        #[arg(long)]
+        url_column: Option<String>,
+
+        /// 出力先ファイル (省略時は標準出力)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+
+        /// 出力形式 (省略時は JSON)
+        #[arg(long, value_enum)]
+        format: Option<OutputFormat>,
+
+        /// 保存済みテンプレート (web-to-json-templates.json) を適用 (以降のオプションで上書き可)
+        #[arg(long)]
+        template: Option<String>,
+
+        /// テキスト出力 (--format text) で見出しに "#" を付ける
+        #[arg(long)]
+        heading_markers: bool,
+
+        /// JSON / YAML / NDJSON をフラットなレコード ({page, path, tag, text, href}) の配列で出力
+        #[arg(long)]
+        flatten: bool,
+
+        /// 抽出したテーブルを CSV にして ZIP で保存するファイル
+        #[arg(long)]
+        tables_zip: Option<PathBuf>,
+
+        /// 抽出したテーブルを保存する SQLite のファイル (テーブルごとに 1 つのテーブル)
+        #[arg(long)]
+        tables_sqlite: Option<PathBuf>,
+
+        /// テーブルごとの Parquet とフラットなレコードの records.parquet を保存するディレクトリ
+        #[arg(long)]
+        parquet_dir: Option<PathBuf>,
+
+        /// 結果全体と URL ごとのファイルを設定ファイルのバケット (OUTPUT_BUCKET) にアップロード
+        #[arg(long)]
+        upload: bool,
+
+        /// 結果を設定ファイルの Elasticsearch / OpenSearch (ELASTICSEARCH_URL) に投入 (pages: 1 URL 1 件、records: フラットなレコード 1 件ずつ)
+        #[arg(long, value_enum)]
+        search_index: Option<IndexMode>,
+
+        /// DOM ツリーの代わりにページごとの外向きリンク (アンカーテキスト・rel・内部 / 外部) を出力
+        #[arg(long)]
+        link_graph: bool,
+
+        /// リンクグラフのエッジリスト (source,target,text,rel,internal) を CSV で保存するファイル (--link-graph 指定時)
+        #[arg(long)]
+        edges_csv: Option<PathBuf>,
+
+        /// リンクグラフの各リンク先を HEAD / GET で確かめ、ステータス・リダイレクト・接続できないものを出力 (--link-graph を含む)
+        #[arg(long)]
+        check_links: bool,
+
+        /// 1階層リンク先を含める
+        #[arg(long)]
+        include_subpages: bool,
+
+        /// 1階層リンク先の範囲 (省略時は設定ファイルの値)
+        #[arg(long, value_enum)]
+        subpage_scope: Option<SubpageScope>,
+
+        /// 1階層リンク先で元ページ・先のリンク先と同じブロック (ヘッダ・メニュー等) を参照に置き換える / 削除する
+        #[arg(long, value_enum)]
+        dedup_subpages: Option<DedupMode>,
+
+        /// meta robots の nofollow・rel="nofollow" を無視して 1階層リンク先をたどる (--skip-noindex も効かない)
+        #[arg(long)]
+        ignore_robots: bool,
+
+        /// meta robots が noindex の 1階層リンク先を出力しない
+        #[arg(long)]
+        skip_noindex: bool,
+
+        /// 1階層リンク先の URL が一致すべき正規表現 (複数指定可)
+        #[arg(long = "include-pattern")]
+        include_patterns: Vec<String>,
+
+        /// 1階層リンク先の URL が一致してはならない正規表現 (複数指定可)
+        #[arg(long = "exclude-pattern")]
+        exclude_patterns: Vec<String>,
+
+        /// 本文と推定される部分だけを変換する (ナビ・広告等を除く)
+        #[arg(long)]
+        main_content_only: bool,
+
+        /// 画像 (<img> の src / alt / width / height / srcset) も抽出する
+        #[arg(long)]
+        include_images: bool,
+
+        /// 動画・音声 (<video> / <audio> の src / source / poster / 字幕トラック) も抽出する
+        #[arg(long)]
+        include_media: bool,
+
+        /// <a> の href を絶対 URL にする (<base href> があればそれを基準に)
+        #[arg(long)]
+        absolute_hrefs: bool,
+
+        /// フォーム (<form>) の action / method / 入力項目も抽出する
+        #[arg(long)]
+        include_forms: bool,
+
+        /// ページごとの語数・文字数・見出し・リンク・テーブル・画像の数を page_meta.stats に含める
+        #[arg(long)]
+        include_stats: bool,
+
+        /// ノードに残す属性名 (複数指定・カンマ区切り可, "data-*" で前方一致)
+        #[arg(long = "attr")]
+        attributes: Vec<String>,
+
+        /// 読み飛ばすタグ (複数指定・カンマ区切り可, 省略時は設定ファイルの値)
+        #[arg(long = "skip-tag")]
+        skip_tags: Vec<String>,
+
+        /// ノードとして出力するタグ (複数指定・カンマ区切り可, 省略時は設定ファイルの値)
+        #[arg(long = "target-tag")]
+        target_tags: Vec<String>,
+
+        /// ノードの入れ子の深さの上限 (超える要素は中身だけ取り出す。0 で無制限、省略時は設定ファイルの値)
+        #[arg(long)]
+        max_depth: Option<usize>,
+
+        /// テーブルの見出し行の判定
+        #[arg(long, value_enum)]
+        table_headers: Option<TableHeaders>,
+
+        /// 見出しのないテーブルの行を配列で出力 (省略時は col0, col1 ... のキー)
+        #[arg(long)]
+        headerless_arrays: bool,
+
+        /// テーブルのすべての行を見出しと同じ並びの配列で出力 (重複した見出しも連番を付けずに残す)
+        #[arg(long)]
+        array_rows: bool,
+
+        /// 複数の見出し行を "上 / 下" の複合名にする (省略時は最後の行)
+        #[arg(long)]
+        join_header_rows: bool,
+
+        /// テーブルのセルの整数・小数・パーセント・真偽値・日付を型付きの値で出力
+        #[arg(long)]
+        infer_cell_types: bool,
+
+        /// テーブルごとの行数の上限 (超えた行は出力せず truncated を付ける。0 で無制限、省略時は設定ファイルの値)
+        #[arg(long)]
+        max_table_rows: Option<usize>,
+
+        /// 抽出ルールのファイル ("キー: CSS セレクタ" を 1 行ずつ)。指定するとページごとにフラットなオブジェクトを出力
+        #[arg(long)]
+        rules: Option<PathBuf>,
+
+        /// 結果のうちこのパスの部分木だけを出力 (--flatten の path と同じ /html[1]/table[2]、//h2[1] のように文書全体から、
+        /// または JSON Pointer。見出しはその節)
+        #[arg(long, value_name = "PATH")]
+        select: Option<String>,
+
+        /// 結果を置き換える WASM プラグイン (プラグインのディレクトリの名前か、.wasm / .wat のパス)
+        #[arg(long, value_name = "NAME|PATH")]
+        plugin: Option<String>,
+
+        /// URL ごとの結果を出力前に書き換える Rhai スクリプトのファイル (doc が結果、最後の式の値が出力)
+        #[arg(long)]
+        script: Option<PathBuf>,
+
+        /// URL ごとの結果に適用する JMESPath 式 (一致した部分だけを出力。例: page_meta.title)
+        #[arg(long)]
+        filter: Option<String>,
+
+        /// RSS / Atom フィードの各記事のリンク先も変換する
+        #[arg(long)]
+        follow_feed_items: bool,
+
+        /// URL ごとの取得の情報 (最終 URL・ステータス・サイズ・所要時間・日時) を含める
+        #[arg(long)]
+        include_fetch_info: bool,
+
+        /// URL ごとに {url, document} / {url, error: {kind, message}} で出力 (エラーを擬似ノードにしない)
+        #[arg(long)]
+        typed_results: bool,
+
+        /// 末尾に集計 (成功・失敗・スキップの件数、受信バイト数、所要時間) を付ける
+        #[arg(long)]
+        include_summary: bool,
+
+        /// canonical URL が同じページは最初の 1 件だけ変換結果を残す (以降は参照ノード)
+        #[arg(long)]
+        dedup_canonical: bool,
+
+        /// 入力 URL・リンク先の URL からトラッキング用のクエリ (utm_* / fbclid / gclid など) を除く
+        #[arg(long)]
+        strip_tracking_params: bool,
+
+        /// 結果の代わりに履歴 (サーバーの変換履歴) の結果との差分を出力 (履歴の id または latest)
+        #[arg(long, value_name = "ID|latest")]
+        diff_against: Option<String>,
+
+        /// サイトマップから展開する URL 数の上限 (0 で無制限)
+        #[arg(long)]
+        sitemap_max_urls: Option<usize>,
+
+        /// 同時取得数 (省略時は設定ファイルの値)
+        #[arg(long)]
+        concurrency: Option<usize>,
+
+        /// 1階層リンク先の同時取得数 (省略時は設定ファイルの値)
+        #[arg(long)]
+        subpage_concurrency: Option<usize>,
+
+        /// 1階層リンク先の同一ホストへの同時取得数 (0 で無制限。省略時は設定ファイルの値)
+        #[arg(long)]
+        subpage_per_host: Option<usize>,
+
+        /// 1 ページから取得するリンク先の数の上限 (0 で無制限。省略時は設定ファイルの値)
+        #[arg(long)]
+        max_subpages: Option<usize>,
+
+        /// 1 回の変換全体で取得するリンク先の数の上限 (0 で無制限。省略時は設定ファイルの値)
+        #[arg(long)]
+        max_subpages_per_job: Option<usize>,
+
+        /// 1 リクエストのタイムアウト秒 (0 で無制限)
+        #[arg(long)]
+        timeout: Option<u64>,
+
+        /// ジョブ全体の期限秒 (0 で無制限)
+        #[arg(long)]
+        deadline: Option<u64>,
+
+        /// 取得の最大試行回数 (1 でリトライなし)
+        #[arg(long)]
+        max_attempts: Option<u32>,
+
+        /// リトライ初回の待ち時間 (ミリ秒, 以降倍々)
+        #[arg(long)]
+        retry_backoff_ms: Option<u64>,
+
+        /// User-Agent
+        #[arg(long)]
+        user_agent: Option<String>,
+
+        /// 追加ヘッダ "Name: value" (複数指定可)
+        #[arg(short = 'H', long = "header")]
+        headers: Vec<String>,
+
+        /// 入力 URL のホストに送る Cookie ("name=value; name2=value2")
+        #[arg(long)]
+        cookie: Option<String>,
+
+        /// 入力 URL のホストに送る認証情報 ("username:password" または Bearer トークン)
+        #[arg(long, env = "WEB_TO_JSON_CREDENTIALS", hide_env_values = true)]
+        credentials: Option<String>,
+
+        /// 同一ホストへのリクエスト間隔 (ミリ秒)
+        #[arg(long)]
+        host_delay_ms: Option<u64>,
+
+        /// レスポンス本文の上限 (MB, 0 で無制限。省略時は設定ファイルの値)
+        #[arg(long)]
+        max_response_mb: Option<u64>,
+
+        /// たどるリダイレクトの数の上限 (0 でたどらない。省略時は設定ファイルの値)
+        #[arg(long)]
+        max_redirects: Option<usize>,
+
+        /// 別のホストへのリダイレクトをたどらない (エラーにする)
+        #[arg(long)]
+        no_cross_host_redirects: bool,
+
+        /// 取得した本文を保存・再利用するディレクトリ (省略時は設定ファイルの CACHE_DIR)
+        #[arg(long)]
+        cache_dir: Option<PathBuf>,
+
+        /// ディスクキャッシュの有効期間 (秒, 0 で期限なし。省略時は設定ファイルの値)
+        #[arg(long)]
+        cache_ttl: Option<u64>,
+
+        /// ディスクキャッシュを使わない (常に取得し直す)
+        #[arg(long)]
+        no_cache: bool,
+
+        /// ネットワークに出ず、保存したページとディスクキャッシュだけで変換する (ないものはすぐにエラー)
+        #[arg(long)]
+        offline: bool,
+
+        /// オフライン再生用に保存したページのディレクトリ (<ホスト>/<パス> の配置。--offline を含む)
+        #[arg(long)]
+        replay_dir: Option<PathBuf>,
+
+        /// 取得した HTML を保存するディレクトリ (--replay-dir と同じ配置。--tables-zip の ZIP にも html/ として入れる)
+        #[arg(long)]
+        save_html: Option<PathBuf>,
+
+        /// HTTP のリクエスト・レスポンスを WARC に書き出すファイル (.warc.gz ならレコードごとに gzip)
+        #[arg(long)]
+        warc: Option<PathBuf>,
+
+        /// この日時に最も近い Wayback Machine のスナップショットを変換する (YYYY-MM-DD / YYYYMMDDhhmmss)
+        #[arg(long)]
+        wayback_date: Option<String>,
+
+        /// ライブの取得に失敗したら Wayback Machine のスナップショットを変換する
+        #[arg(long)]
+        wayback_fallback: bool,
+
+        /// ヘッドレスブラウザ (Chrome / Chromium) で描画してから変換する
+        #[arg(long)]
+        render_js: bool,
+
+        /// 描画でネットワークが落ち着くまで待つ最大時間 (ミリ秒, 省略時は設定ファイルの値)
+        #[arg(long)]
+        render_wait_ms: Option<u64>,
+
+        /// 描画でこの要素が現れるまで待つ (CSS セレクタ)
+        #[arg(long)]
+        wait_selector: Option<String>,
+    },
+
+    /// 出力 (JSON) の JSON Schema を出力
+    Schema {
+        /// 出力先ファイル (省略時は標準出力)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+}
+
+/// サブコマンドを実行
+pub async fn run(command: Command, settings: &Settings) -> std::io::Result<()> {
+    match command {
+        Command::Convert {
+            input,
+            html,
+            base_url,
+            url_column,
+            out,
+            format,
+            template,
+            heading_markers,
+            flatten,
+            tables_zip,
+            tables_sqlite,
+            parquet_dir,
+            upload,
+            search_index,
+            link_graph,
+            edges_csv,
+            check_links,
+            include_subpages,
+            subpage_scope,
+            dedup_subpages,
+            ignore_robots,
+            skip_noindex,
+            include_patterns,
+            exclude_patterns,
+            main_content_only,
+            include_images,
+            include_media,
+            absolute_hrefs,
+            include_forms,
+            include_stats,
+            attributes,
+            skip_tags,
+            target_tags,
+            max_depth,
+            table_headers,
+            headerless_arrays,
+            array_rows,
+            join_header_rows,
+            infer_cell_types,
+            max_table_rows,
+            rules,
+            select,
+            plugin,
+            script,
+            filter,
+            follow_feed_items,
+            include_fetch_info,
+            typed_results,
+            include_summary,
+            dedup_canonical,
+            strip_tracking_params,
+            diff_against,
+            sitemap_max_urls,
+            concurrency,
+            subpage_concurrency,
+            subpage_per_host,
+            max_subpages,
+            max_subpages_per_job,
+            timeout,
+            deadline,
+            max_attempts,
+            retry_backoff_ms,
+            user_agent,
+            headers,
+            cookie,
+            credentials,
+            host_delay_ms,
+            max_response_mb,
+            max_redirects,
+            no_cross_host_redirects,
+            cache_dir,
+            cache_ttl,
+            no_cache,
+            offline,
+            replay_dir,
+            save_html,
+            warc,
+            wayback_date,
+            wayback_fallback,
+            render_js,
+            render_wait_ms,
+            wait_selector,
+        } => {
+            let (input, bytes) = read_input(input)?;
+            let raw_html = html.then(|| RawHtml {
+                url: base_url.unwrap_or_else(|| RAW_HTML_URL.to_string()),
+                html: charset::decode(&bytes, None),
+            });
+            let url_list = match &raw_html {
+                Some(raw) => {
+                    url::Url::parse(&raw.url).map_err(|e| invalid_input(format!("Invalid base URL \"{}\": {e}", raw.url)))?;
+                    vec![raw.url.clone()]
+                }
+                None => urlfile::urls_from_file(&input, &bytes, url_column.as_deref().unwrap_or_default())
+                    .map_err(invalid_input)?
+                    .into_iter()
+                    .map(local_path_url)
+                    .collect(),
+            };
+
+            let mut options = match template {
+                Some(name) => template_options(&name, settings)?,
+                None => JobOptions::from_settings(settings),
+            };
+            if let Some(format) = format {
+                options.output_format = format;
+            }
+            options.raw_html = raw_html.map(Arc::new);
+            // CLI はローカルファイルをどこでも読める
+            options.local_files = LocalFiles::Any;
+            options.text_heading_markers |= heading_markers;
+            options.flatten |= flatten;
+            options.include_subpages |= include_subpages;
+            options.follow_feed_items |= follow_feed_items;
+            options.include_fetch_info |= include_fetch_info;
+            options.typed_results |= typed_results;
+            options.include_summary |= include_summary;
+            options.dedup_canonical |= dedup_canonical;
+            options.strip_tracking_params |= strip_tracking_params;
+            options.parse.main_content_only |= main_content_only;
+            options.parse.include_images |= include_images;
+            options.parse.include_media |= include_media;
+            options.parse.absolute_hrefs |= absolute_hrefs;
+            options.parse.include_forms |= include_forms;
+            options.parse.include_stats |= include_stats;
+            options.check_links |= check_links;
+            options.parse.link_graph |= link_graph || check_links;
+            if let Some(mode) = search_index {
+                options.search_index = Some(SearchIndex::from_settings(settings, mode).map_err(invalid_input)?);
+            }
+            if upload {
+                let bucket = web_to_json::storage::Bucket::from_settings(settings).map_err(invalid_input)?;
+                options.upload = Some(bucket.ok_or_else(|| invalid_input("OUTPUT_BUCKET is not configured".to_string()))?);
+            }
+            if let Some(text) = diff_against {
+                options.diff_against = web_to_json::diff::Baseline::parse(&text).map_err(invalid_input)?;
+                options.history = Some(Arc::new(HistoryStore::open(&settings.history_db).map_err(invalid_input)?));
+            }
+            if let Some(mode) = table_headers {
+                options.parse.table.headers = mode;
+            }
+            options.parse.table.headerless_arrays |= headerless_arrays;
+            options.parse.table.array_rows |= array_rows;
+            options.parse.table.join_header_rows |= join_header_rows;
+            options.parse.table.infer_types |= infer_cell_types;
+            if let Some(n) = max_table_rows {
+                options.parse.table.max_rows = n;
+            }
+            if !attributes.is_empty() {
+                options.parse.capture_attributes = parse_attribute_list(&attributes.join(","));
+            }
+            if !skip_tags.is_empty() {
+                options.parse.skip_tags = parse_attribute_list(&skip_tags.join(","));
+            }
+            if !target_tags.is_empty() {
+                options.parse.target_tags = parse_attribute_list(&target_tags.join(","));
+            }
+            if let Some(n) = max_depth {
+                options.parse.max_depth = n;
+            }
+            if let Some(path) = rules {
+                options.parse.rules = web_to_json::rules::parse_rules(&std::fs::read_to_string(path)?).map_err(invalid_input)?;
+            }
+            if let Some(path) = select {
+                options.select = Some(web_to_json::subtree::SubtreePath::parse(&path).map_err(invalid_input)?);
+            }
+            if let Some(spec) = plugin {
+                options.plugin = Some(web_to_json::plugin::load_spec(&settings.plugin_dir, &spec).map_err(invalid_input)?);
+            }
+            if let Some(path) = script {
+                options.script = Some(web_to_json::script::Script::compile(&std::fs::read_to_string(path)?).map_err(invalid_input)?);
+            }
+            if let Some(expression) = filter {
+                options.filter = Some(web_to_json::filter::Filter::compile(&expression).map_err(invalid_input)?);
+            }
+            if let Some(scope) = subpage_scope {
+                options.subpage_scope = scope;
+            }
+            if let Some(mode) = dedup_subpages {
+                options.dedup_subpages = mode;
+            }
+            options.ignore_robots |= ignore_robots;
+            options.skip_noindex |= skip_noindex;
+            if !include_patterns.is_empty() {
+                options.link_include = parse_patterns(&include_patterns.join("\n")).map_err(invalid_input)?;
+            }
+            if let Some(n) = sitemap_max_urls {
+                options.sitemap_max_urls = n;
+            }
+            if !exclude_patterns.is_empty() {
+                options.link_exclude = parse_patterns(&exclude_patterns.join("\n")).map_err(invalid_input)?;
+            }
+            if let Some(n) = concurrency {
+                options.concurrency = n.clamp(1, MAX_CONCURRENCY_LIMIT);
+            }
+            if let Some(n) = subpage_concurrency {
+                options.subpage_concurrency = n.clamp(1, MAX_CONCURRENCY_LIMIT);
+            }
+            if let Some(n) = subpage_per_host {
+                options.subpage_per_host = n;
+            }
+            if let Some(n) = max_subpages {
+                options.max_subpages = n;
+            }
+            if let Some(n) = max_subpages_per_job {
+                options.max_subpages_per_job = n;
+            }
+            if let Some(n) = timeout {
+                options.request_timeout = secs_to_duration(n);
+            }
+            if let Some(n) = deadline {
+                options.job_deadline = secs_to_duration(n);
+            }
+            if let Some(n) = max_attempts {
+                options.retry.max_attempts = n.clamp(1, MAX_ATTEMPTS_LIMIT);
+            }
+            if let Some(ms) = retry_backoff_ms {
+                options.retry.backoff = std::time::Duration::from_millis(ms);
+            }
+            if user_agent.is_some() {
+                options.user_agent = user_agent;
+            }
+            if !headers.is_empty() {
+                options.headers = parse_header_lines(&headers.join("\n")).map_err(invalid_input)?;
+            }
+            options.cookies = cookie;
+            options.credentials = credentials.as_deref().and_then(Credentials::parse);
+            if let Some(ms) = host_delay_ms {
+                options.host_delay = std::time::Duration::from_millis(ms);
+            }
+            if let Some(mb) = max_response_mb {
+                options.max_response_bytes = mb_to_bytes(mb);
+            }
+            if let Some(n) = max_redirects {
+                options.max_redirects = n;
+            }
+            if no_cross_host_redirects {
+                options.cross_host_redirects = false;
+            }
+            if cache_dir.is_some() || cache_ttl.is_some() {
+                let dir = cache_dir.or_else(|| non_empty(&settings.cache_dir).map(PathBuf::from));
+                let ttl = cache_ttl.unwrap_or(settings.cache_ttl_secs);
+                options.disk_cache = dir.map(|dir| Arc::new(DiskCache::new(dir, ttl)));
+            }
+            if no_cache {
+                options.disk_cache = None;
+            }
+            if let Some(dir) = replay_dir {
+                options.replay_dir = Some(dir);
+                options.offline = true;
+            }
+            if offline {
+                options.offline = true;
+            }
+            if let Some(dir) = save_html {
+                options.html_archive = Some(Arc::new(HtmlArchive::in_dir(dir)));
+            }
+            if let Some(path) = &warc {
+                options.warc = Some(Arc::new(WarcWriter::create(path)?));
+            }
+            if let Some(date) = &wayback_date {
+                options.wayback.date = Some(wayback::parse_date(date).map_err(invalid_input)?);
+            }
+            options.wayback.fallback |= wayback_fallback;
+            if options.offline && options.check_links {
+                return Err(invalid_input("--check-links cannot be used offline".to_string()));
+            }
+            if render_js || render_wait_ms.is_some() || wait_selector.is_some() {
+                let render = options.render.get_or_insert_with(|| options.render_defaults.clone());
+                if let Some(ms) = render_wait_ms {
+                    render.wait = std::time::Duration::from_millis(ms);
+                }
+                if let Some(selector) = wait_selector {
+                    render.wait_selector = Some(
+                        scraper::Selector::parse(&selector)
+                            .map_err(|e| invalid_input(format!("Invalid wait selector \"{selector}\": {e:?}")))?,
+                    );
+                }
+            }
+
+            // 1 URL 終わるごとに書き出す (結果全体をメモリにためない)
+            let to_stdout = out.is_none();
+            let mut writer: Box<dyn Write> = match &out {
+                Some(path) => Box::new(std::fs::File::create(path)?),
+                None => Box::new(std::io::stdout()),
+            };
+            let mut renderer = output::StreamRenderer::new(&options);
+            writer.write_all(renderer.start().as_bytes())?;
+            let mut results = Vec::new();
+            let mut written = Ok(());
+            let summary = run_job_each(&url_list, &options, |content| {
+                if written.is_ok() {
+                    written = renderer
+                        .page(&content)
+                        .map_err(std::io::Error::other)
+                        .and_then(|piece| writer.write_all(piece.as_bytes()))
+                        .and_then(|_| writer.flush());
+                }
+                if tables_zip.is_some() || tables_sqlite.is_some() || parquet_dir.is_some() || edges_csv.is_some() || upload {
+                    results.push(content);
+                }
+                // 書き出せなくなったら (パイプが閉じたなど) 残りの URL は取得しない
+                std::future::ready(if written.is_ok() { ControlFlow::Continue(()) } else { ControlFlow::Break(()) })
+            })
+            .await
+            .map_err(std::io::Error::other)?;
+            written?;
+            writer.write_all(renderer.finish().as_bytes())?;
+            // 標準出力は末尾を改行で終える (NDJSON は各行が改行付き)
+            if to_stdout && options.output_format != OutputFormat::Ndjson {
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+            write_tables_zip(tables_zip, &results, options.html_archive.as_deref())?;
+            write_tables_sqlite(tables_sqlite, &results)?;
+            write_parquet_dir(parquet_dir, &results)?;
+            write_edges_csv(edges_csv, &results)?;
+            if let Some(index) = &summary.index {
+                eprintln!("Indexed {} documents to {} ({} failed)", index.indexed, index.index, index.failed);
+                for error in &index.errors {
+                    eprintln!("  {error}");
+                }
+            }
+            if let (Some(writer), Some(path)) = (&options.warc, &warc) {
+                writer.finish()?;
+                eprintln!("Wrote WARC to {}", path.display());
+            }
+            if let Some((archive, dir)) = options.html_archive.as_deref().and_then(|a| Some((a, a.dir()?))) {
+                eprintln!("Saved {} HTML files to {}", archive.len(), dir.display());
+            }
+            if let Some(bucket) = &options.upload {
+                let name = web_to_json::storage::upload_name(None);
+                for url in bucket.upload_results(&name, &url_list, &results, &options).await.map_err(std::io::Error::other)? {
+                    eprintln!("Uploaded {url}");
+                }
+            }
+            Ok(())
+        }
+        Command::Schema { out } => {
+            let text = serde_json::to_string_pretty(&schema::output_schema()).map_err(std::io::Error::other)?;
+            write_text(out, &text)
+        }
+    }
+}
+
+/// 入力ファイルの名前と中身 (省略時または "-" は標準入力)
+fn read_input(input: Option<PathBuf>) -> std::io::Result<(String, Vec<u8>)> {
+    match input {
+        Some(path) if path.as_os_str() != "-" => Ok((path.to_string_lossy().into_owned(), std::fs::read(&path)?)),
+        _ => {
+            let mut bytes = Vec::new();
+            std::io::stdin().lock().read_to_end(&mut bytes)?;
+            Ok(("-".to_string(), bytes))
+        }
+    }
+}
+
+/// 標準入力がパイプかファイル (cat urls.txt | web-to-json など)。端末・/dev/null は含めない
+pub fn stdin_is_piped() -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::fd::AsFd;
+        use std::os::unix::fs::FileTypeExt;
+        let Ok(fd) = std::io::stdin().as_fd().try_clone_to_owned() else {
+            return false;
+        };
+        std::fs::File::from(fd)
+            .metadata()
+            .is_ok_and(|m| m.file_type().is_fifo() || m.file_type().is_file())
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+/// 標準入力の URL リストを既定のオプションで変換するサブコマンド (web-to-json convert と同じ)
+pub fn stdin_command() -> Option<Command> {
+    Cli::parse_from(["web-to-json", "convert"]).command
+}
+
+/// ファイル (省略時は標準出力) に書き出す
+fn write_text(out: Option<PathBuf>, text: &str) -> std::io::Result<()> {
+    match out {
+        Some(path) => std::fs::write(path, text),
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(text.as_bytes())?;
+            stdout.write_all(b"\n")
+        }
+    }
+}
+
+/// 保存済みテンプレートの設定
+fn template_options(name: &str, settings: &Settings) -> std::io::Result<JobOptions> {
+    let store = TemplateStore::load(templates::TEMPLATES_FILE);
+    let template = store
+        .get(name)
+        .ok_or_else(|| invalid_input(format!("Unknown template \"{name}\"")))?;
+    let form: UrlForm = serde_json::from_value(serde_json::Value::Object(template))
+        .map_err(|e| invalid_input(format!("Invalid template: {e}")))?;
+    options_from_form(&form, settings).map_err(invalid_input)
+}
+
+/// URL リストの行のうち、存在するローカルのパスは file:// URL に
+fn local_path_url(line: String) -> String {
+    let path = std::path::Path::new(&line);
+    if !path.exists() {
+        return line;
+    }
+    path.canonicalize()
+        .ok()
+        .and_then(|path| url::Url::from_file_path(path).ok())
+        .map_or(line, |url| url.to_string())
+}
+
+/// --tables-zip 指定時はテーブルの CSV を ZIP で保存 (--save-html 指定時は取得した HTML も html/ に)
+fn write_tables_zip(path: Option<PathBuf>, results: &[DomContent], html: Option<&HtmlArchive>) -> std::io::Result<()> {
+    if let Some(path) = path {
+        let mut files = tables::csv_files(&tables::collect_tables(results));
+        files.extend(html.map(|archive| archive.files("html/")).unwrap_or_default());
+        let zip = tables::zip_files(&files).map_err(std::io::Error::other)?;
+        std::fs::write(path, zip)?;
+    }
+    Ok(())
+}
+
+/// --tables-sqlite 指定時はテーブルを SQLite のファイルに保存
+fn write_tables_sqlite(path: Option<PathBuf>, results: &[DomContent]) -> std::io::Result<()> {
+    if let Some(path) = path {
+        let db = tables::to_sqlite(&tables::collect_tables(results)).map_err(std::io::Error::other)?;
+        std::fs::write(path, db)?;
+    }
+    Ok(())
+}
+
+/// --parquet-dir 指定時はテーブルごとの Parquet と records.parquet を保存 (ディレクトリがなければ作る)
+fn write_parquet_dir(dir: Option<PathBuf>, results: &[DomContent]) -> std::io::Result<()> {
+    if let Some(dir) = dir {
+        std::fs::create_dir_all(&dir)?;
+        for (name, bytes) in columnar::parquet_files(results).map_err(std::io::Error::other)? {
+            std::fs::write(dir.join(name), bytes)?;
+        }
+    }
+    Ok(())
+}
+
+/// --edges-csv 指定時はリンクグラフのエッジリストを保存
+fn write_edges_csv(path: Option<PathBuf>, results: &[DomContent]) -> std::io::Result<()> {
+    if let Some(path) = path {
+        std::fs::write(path, links::edges_csv(results))?;
+    }
+    Ok(())
+}
+
+/// 引数エラー
+fn invalid_input(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
@@ -0,0 +1,43 @@
+//! <pre> / <code> の抽出 (空白・改行は元のまま)
+
+use schemars::JsonSchema;
+use scraper::ElementRef;
+use serde::{Deserialize, Serialize};
+
+/// コードブロック (pre) またはインラインコード (code)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CodeBlock {
+    /// "pre" または "code"
+    pub tag: String,
+    /// class="language-xxx" / "lang-xxx" の xxx
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    pub text: String,
+}
+
+/// pre / code 要素を変換 (pre 内の code の言語も拾う)
+pub fn parse_code(el: ElementRef) -> CodeBlock {
+    let tag = if el.value().name().eq_ignore_ascii_case("pre") { "pre" } else { "code" };
+    let language = language(el).or_else(|| {
+        el.descendants()
+            .filter_map(ElementRef::wrap)
+            .filter(|e| e.value().name().eq_ignore_ascii_case("code"))
+            .find_map(language)
+    });
+    let text: String = el.text().collect();
+    CodeBlock {
+        tag: tag.to_string(),
+        language,
+        text: text.trim_end_matches(['\n', '\r']).to_string(),
+    }
+}
+
+fn language(el: ElementRef) -> Option<String> {
+    el.value().classes().find_map(|class| {
+        class
+            .strip_prefix("language-")
+            .or_else(|| class.strip_prefix("lang-"))
+            .filter(|lang| !lang.is_empty())
+            .map(str::to_string)
+    })
+}
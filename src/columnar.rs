@@ -0,0 +1,128 @@
+//! Parquet 出力 (抽出したテーブルごとのファイルと、フラットなテキストのレコードのファイル)
+
+use std::sync::Arc;
+
+use parquet::basic::{Compression, LogicalType, Repetition, Type as PhysicalType};
+use parquet::column::writer::ColumnWriterImpl;
+use parquet::data_type::{ByteArray, ByteArrayType, DataType, DoubleType, Int64Type};
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::Type;
+
+use crate::tables::{self, ColumnType, TypedTable};
+use crate::{flat, DomContent, TableData};
+
+/// フラットなレコードのファイル名
+pub const RECORDS_FILE: &str = "records.parquet";
+
+/// 1 列分の値 (すべて OPTIONAL。None は null)
+enum Column {
+    Int64(Vec<Option<i64>>),
+    Double(Vec<Option<f64>>),
+    Text(Vec<Option<String>>),
+}
+
+/// records.parquet とテーブルごとのファイル (page001_table01.parquet など。列のないテーブルは除く)
+pub fn parquet_files(results: &[DomContent]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut files = vec![(RECORDS_FILE.to_string(), records_to_parquet(results)?)];
+    for (name, table) in tables::collect_tables(results) {
+        if let Some(bytes) = table_to_parquet(table)? {
+            files.push((name.replace(".csv", ".parquet"), bytes));
+        }
+    }
+    Ok(files)
+}
+
+/// フラットな出力のレコード (page, path, tag, text, href) を 1 つのファイルに
+pub fn records_to_parquet(results: &[DomContent]) -> Result<Vec<u8>, String> {
+    let records: Vec<flat::FlatRecord> = results
+        .iter()
+        .enumerate()
+        .flat_map(|(page, content)| flat::flatten_page(page, content))
+        .collect();
+    let columns = vec![
+        ("page".to_string(), Column::Int64(records.iter().map(|r| Some(r.page as i64)).collect())),
+        ("path".to_string(), Column::Text(records.iter().map(|r| Some(r.path.clone())).collect())),
+        ("tag".to_string(), Column::Text(records.iter().map(|r| Some(r.tag.clone())).collect())),
+        ("text".to_string(), Column::Text(records.iter().map(|r| r.text.clone()).collect())),
+        ("href".to_string(), Column::Text(records.iter().map(|r| r.href.clone()).collect())),
+    ];
+    write_columns(&columns).map_err(parquet_error)
+}
+
+/// テーブル 1 つ (列の型は SQLite 出力と同じ推定で INT64 / DOUBLE / 文字列。列がなければ None)
+pub fn table_to_parquet(table: &TableData) -> Result<Option<Vec<u8>>, String> {
+    let TypedTable { columns, types, rows } = tables::typed_table(table);
+    if columns.is_empty() {
+        return Ok(None);
+    }
+    let columns: Vec<(String, Column)> = columns
+        .into_iter()
+        .zip(types)
+        .enumerate()
+        .map(|(i, (name, column_type))| {
+            let cells = rows.iter().map(|row| row[i].as_str());
+            let column = match column_type {
+                ColumnType::Integer => Column::Int64(cells.map(|c| c.trim().parse().ok()).collect()),
+                ColumnType::Real => Column::Double(cells.map(|c| c.trim().parse().ok()).collect()),
+                ColumnType::Text => Column::Text(cells.map(|c| Some(c.to_string())).collect()),
+            };
+            (name, column)
+        })
+        .collect();
+    write_columns(&columns).map(Some).map_err(parquet_error)
+}
+
+fn parquet_error(e: ParquetError) -> String {
+    format!("Parquet write error: {e}")
+}
+
+/// 1 つの行グループに全行を書く (Snappy 圧縮)
+fn write_columns(columns: &[(String, Column)]) -> Result<Vec<u8>, ParquetError> {
+    let fields = columns
+        .iter()
+        .map(|(name, column)| {
+            let builder = match column {
+                Column::Int64(_) => Type::primitive_type_builder(name, PhysicalType::INT64),
+                Column::Double(_) => Type::primitive_type_builder(name, PhysicalType::DOUBLE),
+                Column::Text(_) => {
+                    Type::primitive_type_builder(name, PhysicalType::BYTE_ARRAY).with_logical_type(Some(LogicalType::String))
+                }
+            };
+            builder.with_repetition(Repetition::OPTIONAL).build().map(Arc::new)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let schema = Arc::new(Type::group_type_builder("schema").with_fields(fields).build()?);
+    let properties = Arc::new(WriterProperties::builder().set_compression(Compression::SNAPPY).build());
+
+    let mut buffer = Vec::new();
+    let mut writer = SerializedFileWriter::new(&mut buffer, schema, properties)?;
+    let mut row_group = writer.next_row_group()?;
+    for (_, column) in columns {
+        let Some(mut column_writer) = row_group.next_column()? else {
+            break;
+        };
+        match column {
+            Column::Int64(values) => write_optional(column_writer.typed::<Int64Type>(), values, |v| *v)?,
+            Column::Double(values) => write_optional(column_writer.typed::<DoubleType>(), values, |v| *v)?,
+            Column::Text(values) => write_optional(column_writer.typed::<ByteArrayType>(), values, |v| ByteArray::from(v.as_str()))?,
+        }
+        column_writer.close()?;
+    }
+    row_group.close()?;
+    writer.close()?;
+    Ok(buffer)
+}
+
+/// null でない値と定義レベル (1 = 値あり、0 = null) を書く
+fn write_optional<T: DataType, V>(
+    writer: &mut ColumnWriterImpl<'_, T>,
+    values: &[Option<V>],
+    convert: impl Fn(&V) -> T::T,
+) -> Result<(), ParquetError> {
+    let levels: Vec<i16> = values.iter().map(|v| i16::from(v.is_some())).collect();
+    let present: Vec<T::T> = values.iter().flatten().map(convert).collect();
+    writer.write_batch(&present, Some(&levels), None)?;
+    Ok(())
+}
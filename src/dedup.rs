@@ -0,0 +1,151 @@
+//! 1階層リンク先の重複ブロック (同じサイトのヘッダ・メニュー・フッタなど) の除去
+//!
+//! 元ページ → リンク先 (文書順) の順に見て、同じホストの先に出たページと同じ内容のブロックを
+//! 参照ノード {"tag": "duplicate", "href": 最初に出たページの URL} に置き換えるか削除する。
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use clap::ValueEnum;
+use url::Url;
+
+use crate::{DomContent, DomNode};
+
+/// これより短い (テキストの文字数) ブロックは重複とみなさない (「ホーム」だけのリンクなど)
+const MIN_BLOCK_CHARS: usize = 40;
+
+/// 重複ブロックの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum DedupMode {
+    /// そのまま残す
+    #[default]
+    Off,
+    /// 最初に出たページへの参照ノードに置き換える
+    Reference,
+    /// 削除する
+    Drop,
+}
+
+/// (ホスト, 内容のハッシュ) → 最初に出たページの URL
+type Seen = HashMap<(String, u64), String>;
+
+/// root (page_url のページ) の link_subpage から重複ブロックを除く
+pub fn dedup_subpages(root: &mut DomContent, page_url: &Url, link_base: &Url, mode: DedupMode) {
+    if mode == DedupMode::Off {
+        return;
+    }
+    let mut seen = Seen::new();
+    // 元ページのブロックを先に登録 (元ページ自体は変更しない)
+    let mut blocks = vec![];
+    collect_blocks(root, &mut blocks);
+    register(&mut seen, page_url, blocks);
+    visit_subpages(root, link_base, &mut seen, mode);
+}
+
+/// 文書順にリンク先のページを処理
+fn visit_subpages(content: &mut DomContent, link_base: &Url, seen: &mut Seen, mode: DedupMode) {
+    let DomContent::Node(node) = content else {
+        return;
+    };
+    for child in &mut node.children {
+        visit_subpages(child, link_base, seen, mode);
+    }
+    let (Some(href), Some(subpage)) = (&node.href, &mut node.link_subpage) else {
+        return;
+    };
+    let Ok(url) = link_base.join(href) else {
+        return;
+    };
+    let mut blocks = vec![];
+    dedup_page(subpage, &url, seen, mode, &mut blocks);
+    register(seen, &url, blocks);
+}
+
+/// ページ内の子ブロックを上から見て、他のページで出たものを置き換える (残したブロックは blocks に)
+fn dedup_page(content: &mut DomContent, url: &Url, seen: &Seen, mode: DedupMode, blocks: &mut Vec<u64>) {
+    let DomContent::Node(node) = content else {
+        return;
+    };
+    let host = url.host_str().unwrap_or_default().to_string();
+    let mut kept = Vec::with_capacity(node.children.len());
+    for mut child in std::mem::take(&mut node.children) {
+        let (hash, chars) = fingerprint(&child);
+        let first = (chars >= MIN_BLOCK_CHARS)
+            .then(|| seen.get(&(host.clone(), hash)))
+            .flatten()
+            .filter(|first| first.as_str() != url.as_str());
+        match (first, mode) {
+            (Some(_), DedupMode::Drop) => {}
+            (Some(first), _) => kept.push(reference(first)),
+            (None, _) => {
+                if chars >= MIN_BLOCK_CHARS {
+                    blocks.push(hash);
+                }
+                dedup_page(&mut child, url, seen, mode, blocks);
+                kept.push(child);
+            }
+        }
+    }
+    node.children = kept;
+}
+
+/// 元ページの候補ブロック (リンク先の中は除く)
+fn collect_blocks(content: &DomContent, blocks: &mut Vec<u64>) {
+    let (hash, chars) = fingerprint(content);
+    if chars >= MIN_BLOCK_CHARS {
+        blocks.push(hash);
+    }
+    if let DomContent::Node(node) = content {
+        for child in &node.children {
+            collect_blocks(child, blocks);
+        }
+    }
+}
+
+fn register(seen: &mut Seen, url: &Url, blocks: Vec<u64>) {
+    let host = url.host_str().unwrap_or_default().to_string();
+    for hash in blocks {
+        seen.entry((host.clone(), hash)).or_insert_with(|| url.to_string());
+    }
+}
+
+/// 内容のハッシュとテキストの文字数 (link_subpage・ページのメタ情報は含めない)
+fn fingerprint(content: &DomContent) -> (u64, usize) {
+    let mut hasher = DefaultHasher::new();
+    let chars = hash_content(content, &mut hasher);
+    (hasher.finish(), chars)
+}
+
+fn hash_content(content: &DomContent, hasher: &mut DefaultHasher) -> usize {
+    match content {
+        DomContent::Node(node) => {
+            node.tag.hash(hasher);
+            node.href.hash(hasher);
+            node.attrs.hash(hasher);
+            node.text.hash(hasher);
+            node.children.len().hash(hasher);
+            let own = node.text.as_deref().map_or(0, |t| t.chars().count());
+            own + node.children.iter().map(|c| hash_content(c, hasher)).sum::<usize>()
+        }
+        // ノード以外は JSON の文字列で比べる
+        other => {
+            let json = serde_json::to_string(other).unwrap_or_default();
+            json.hash(hasher);
+            json.chars().count()
+        }
+    }
+}
+
+fn reference(first: &str) -> DomContent {
+    DomContent::Node(DomNode {
+        tag: Some("duplicate".to_string()),
+        href: Some(first.to_string()),
+        attrs: None,
+        text: None,
+        children: vec![],
+        link_subpage: None,
+        page_meta: None,
+        structured_data: None,
+    })
+}
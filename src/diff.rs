@@ -0,0 +1,421 @@
+//! 履歴に保存した結果との差分 (テキストノード・テーブルの行・その他の値の追加・削除・変更。変化の監視用)
+
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use similar::{Algorithm, DiffOp};
+
+use crate::history::HistoryStore;
+use crate::output::OutputFormat;
+use crate::DomContent;
+
+/// 比べない項目 (取得の時刻・所要時間などジョブごとに変わる値)
+const IGNORED_KEYS: &[&str] = &["page_meta", "fetch"];
+
+/// 比較の基準
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Baseline {
+    /// 入力 URL にその URL を含む最新の履歴
+    Latest,
+    /// 履歴の id
+    Entry(i64),
+}
+
+impl Baseline {
+    /// "latest" または履歴の id (空なら None)
+    pub fn parse(text: &str) -> Result<Option<Self>, String> {
+        match text.trim() {
+            "" => Ok(None),
+            "latest" => Ok(Some(Baseline::Latest)),
+            id => id
+                .parse()
+                .map(|id| Some(Baseline::Entry(id)))
+                .map_err(|_| format!("Invalid history id \"{id}\" (expected a number or \"latest\")")),
+        }
+    }
+}
+
+/// 1 ページ分の差分
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PageDiff {
+    pub url: String,
+    /// 比べた履歴の id
+    pub history_id: i64,
+    /// 比べた履歴の日時 (UTC, ISO 8601)
+    pub history_created_at: String,
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    /// 今回の結果の順 (削除は前回の位置)
+    pub changes: Vec<Change>,
+}
+
+/// 変化 1 件
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Change {
+    /// added / removed / changed
+    pub change: String,
+    /// text (テキストノード) / row (テーブルの行) / field (その他の値)
+    pub kind: String,
+    /// ルートからのパス (例: /html[1]/body[1]/p[3]、/html[1]/table[1]/tr[2])。削除は前回の結果のパス
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<Value>,
+}
+
+/// 比べる単位 (テキスト・行・値 1 つ)
+struct Item {
+    kind: &'static str,
+    /// タグ名またはキー (これも同じでないと同じ項目とみなさない)
+    name: String,
+    path: String,
+    value: Value,
+}
+
+impl Item {
+    fn key(&self) -> String {
+        format!("{}\u{0}{}\u{0}{}", self.kind, self.name, self.value)
+    }
+}
+
+/// 保存した結果 (ページごとの値)
+struct StoredEntry {
+    created_at: String,
+    urls: Vec<String>,
+    pages: Vec<Value>,
+}
+
+/// ジョブ内で履歴を読み直さないためのキャッシュ
+pub struct Baselines<'a> {
+    store: &'a HistoryStore,
+    baseline: Baseline,
+    entries: HashMap<i64, Result<StoredEntry, String>>,
+}
+
+impl<'a> Baselines<'a> {
+    pub fn new(store: &'a HistoryStore, baseline: Baseline) -> Self {
+        Baselines {
+            store,
+            baseline,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// 今回の結果 (エラーでないもの) と基準の履歴の同じ URL の結果を比べる
+    pub fn diff_page(&mut self, url: &str, content: &DomContent) -> Result<PageDiff, String> {
+        let ids = match self.baseline {
+            Baseline::Entry(id) => vec![id],
+            Baseline::Latest => self.store.ids_with_url(url)?,
+        };
+        for id in ids {
+            let entry = self.entries.entry(id).or_insert_with(|| load_entry(self.store, id));
+            let entry = match (entry, self.baseline) {
+                (Ok(entry), _) => entry,
+                (Err(e), Baseline::Entry(_)) => return Err(e.clone()),
+                // 最新を探す場合は比べられない履歴 (Markdown など) を飛ばす
+                (Err(_), Baseline::Latest) => continue,
+            };
+            if let Some(old) = find_page(entry, url) {
+                let new = serde_json::to_value(content).map_err(|e| e.to_string())?;
+                return Ok(diff_values(url, id, &entry.created_at, old, &new));
+            }
+        }
+        Err(match self.baseline {
+            Baseline::Entry(id) => format!("History #{id} has no result for {url}"),
+            Baseline::Latest => format!("No earlier result in history for {url}"),
+        })
+    }
+}
+
+/// 履歴を読んでページごとの値に (JSON / NDJSON / YAML のツリーのみ)
+fn load_entry(store: &HistoryStore, id: i64) -> Result<StoredEntry, String> {
+    let (entry, output) = store.entry(id)?.ok_or_else(|| format!("History #{id} not found"))?;
+    if entry.options.get("flatten").and_then(Value::as_str) == Some("true") {
+        return Err(format!("History #{id} is flattened and cannot be compared"));
+    }
+    if entry.options.get("diff_against").is_some() {
+        return Err(format!("History #{id} is a diff and cannot be compared"));
+    }
+    let values: Vec<Value> = match output.format {
+        OutputFormat::Json => serde_json::from_str(&output.output).map_err(|e| e.to_string())?,
+        OutputFormat::Ndjson => output
+            .output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?,
+        OutputFormat::Yaml => serde_yaml::from_str(&output.output).map_err(|e| e.to_string())?,
+        OutputFormat::Markdown | OutputFormat::Text => {
+            return Err(format!("History #{id} is {} and cannot be compared", output.format.label()))
+        }
+    };
+    Ok(StoredEntry {
+        created_at: entry.created_at,
+        urls: entry.urls,
+        // 末尾の集計は除く
+        pages: values.into_iter().filter(|v| v.get("summary").is_none()).collect(),
+    })
+}
+
+/// 保存した結果から URL のページを探す ({url, document} / リンクグラフは url で、それ以外は入力 URL と同じ位置)
+fn find_page<'e>(entry: &'e StoredEntry, url: &str) -> Option<&'e Value> {
+    let by_url = entry.pages.iter().find(|page| page.get("url").and_then(Value::as_str) == Some(url));
+    if let Some(page) = by_url {
+        return match page.get("error") {
+            Some(_) => None,
+            None => Some(page.get("document").unwrap_or(page)),
+        };
+    }
+    if entry.pages.len() != entry.urls.len() {
+        return None;
+    }
+    let page = &entry.pages[entry.urls.iter().position(|u| u == url)?];
+    (!is_error_node(page)).then_some(page)
+}
+
+fn is_error_node(page: &Value) -> bool {
+    page.get("tag").and_then(Value::as_str).is_some_and(|tag| tag.starts_with("Error"))
+}
+
+fn diff_values(url: &str, history_id: i64, created_at: &str, old: &Value, new: &Value) -> PageDiff {
+    let mut old_items = vec![];
+    collect_siblings(std::slice::from_ref(old), "", &mut old_items);
+    let mut new_items = vec![];
+    collect_siblings(std::slice::from_ref(new), "", &mut new_items);
+
+    let mut diff = PageDiff {
+        url: url.to_string(),
+        history_id,
+        history_created_at: created_at.to_string(),
+        added: 0,
+        removed: 0,
+        changed: 0,
+        changes: vec![],
+    };
+    let old_keys: Vec<String> = old_items.iter().map(Item::key).collect();
+    let new_keys: Vec<String> = new_items.iter().map(Item::key).collect();
+    for op in similar::capture_diff_slices(Algorithm::Myers, &old_keys, &new_keys) {
+        let (old_range, new_range) = match op {
+            DiffOp::Equal { .. } => continue,
+            op => (op.old_range(), op.new_range()),
+        };
+        // 置き換えは同じ種類の項目を前から組にして changed、残りは removed / added
+        let mut olds: Vec<&Item> = old_items[old_range].iter().collect();
+        for item in &new_items[new_range] {
+            match olds.iter().position(|o| o.kind == item.kind && o.name == item.name) {
+                Some(i) => {
+                    let old = olds.remove(i);
+                    diff.changed += 1;
+                    diff.changes.push(change("changed", item, Some(&old.value), Some(&item.value)));
+                }
+                None => {
+                    diff.added += 1;
+                    diff.changes.push(change("added", item, None, Some(&item.value)));
+                }
+            }
+        }
+        for old in olds {
+            diff.removed += 1;
+            diff.changes.push(change("removed", old, Some(&old.value), None));
+        }
+    }
+    diff
+}
+
+fn change(change: &'static str, item: &Item, old: Option<&Value>, new: Option<&Value>) -> Change {
+    Change {
+        change: change.to_string(),
+        kind: item.kind.to_string(),
+        path: item.path.clone(),
+        old: old.cloned(),
+        new: new.cloned(),
+    }
+}
+
+/// 兄弟の並びをたどる (フラットな出力と同じく、タグごとに 1 始まりの番号を振る)
+fn collect_siblings(values: &[Value], parent: &str, items: &mut Vec<Item>) {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for value in values {
+        let name = name_of(value);
+        let n = counts.entry(name.clone()).or_default();
+        *n += 1;
+        collect(value, &name, &format!("{parent}/{name}[{n}]"), items);
+    }
+}
+
+fn collect(value: &Value, name: &str, path: &str, items: &mut Vec<Item>) {
+    match value {
+        // テーブルは 1 行単位
+        Value::Object(map) if map.contains_key("table_headers") => {
+            let rows = map.get("rows").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+            for (i, row) in rows.iter().enumerate() {
+                items.push(Item {
+                    kind: "row",
+                    name: "tr".to_string(),
+                    path: format!("{path}/tr[{}]", i + 1),
+                    value: row.clone(),
+                });
+            }
+        }
+        Value::Object(map) => {
+            if let Some(text) = map.get("text").filter(|t| t.is_string()) {
+                items.push(Item {
+                    kind: "text",
+                    name: name.to_string(),
+                    path: path.to_string(),
+                    value: text.clone(),
+                });
+            }
+            for (key, child) in map {
+                if !["tag", "text", "children", "link_subpage"].contains(&key.as_str()) && !IGNORED_KEYS.contains(&key.as_str()) {
+                    collect(child, key, &format!("{path}/{key}"), items);
+                }
+            }
+            if let Some(children) = map.get("children").and_then(Value::as_array) {
+                collect_siblings(children, path, items);
+            }
+            if let Some(subpage) = map.get("link_subpage") {
+                collect_siblings(std::slice::from_ref(subpage), path, items);
+            }
+        }
+        Value::Array(values) => {
+            for (i, value) in values.iter().enumerate() {
+                collect(value, name, &format!("{path}[{}]", i + 1), items);
+            }
+        }
+        Value::Null => {}
+        scalar => items.push(Item {
+            kind: "field",
+            name: name.to_string(),
+            path: path.to_string(),
+            value: scalar.clone(),
+        }),
+    }
+}
+
+/// パスに使う名前 (ノードはタグ)
+fn name_of(value: &Value) -> String {
+    if let Some(tag) = value.get("tag").and_then(Value::as_str) {
+        return tag.to_string();
+    }
+    if let Some(feed_type) = value.get("feed_type").and_then(Value::as_str) {
+        return feed_type.to_string();
+    }
+    let name = match value {
+        v if v.get("table_headers").is_some() => "table",
+        v if v.get("links").is_some() => "links",
+        v if v.get("text").is_some() => "text",
+        _ => "node",
+    };
+    name.to_string()
+}
+
+/// 1 行の要約 (Markdown・テキスト出力用)
+pub fn summary_line(diff: &PageDiff) -> String {
+    format!(
+        "{} added, {} removed, {} changed since history #{} ({})",
+        diff.added, diff.removed, diff.changed, diff.history_id, diff.history_created_at
+    )
+}
+
+/// 変化 1 件の表示 (例: "changed /html[1]/p[2]: \"old\" -> \"new\"")
+pub fn change_text(change: &Change) -> String {
+    let values: Vec<String> = [&change.old, &change.new].into_iter().flatten().map(Value::to_string).collect();
+    format!("{} {}: {}", change.change, change.path, values.join(" -> "))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn diff(old: Value, new: Value) -> PageDiff {
+        diff_values("https://example.com/", 7, "2024-05-01T09:00:00Z", &old, &new)
+    }
+
+    fn page(texts: &[&str], rows: Value) -> Value {
+        let mut children: Vec<Value> = texts.iter().map(|t| json!({"tag": "p", "text": t})).collect();
+        children.push(json!({"table_headers": ["品目", "価格"], "rows": rows}));
+        json!({"tag": "html", "children": children, "page_meta": {"title": "t"}})
+    }
+
+    #[test]
+    fn parses_baselines() {
+        assert_eq!(Baseline::parse(" ").unwrap(), None);
+        assert_eq!(Baseline::parse("latest").unwrap(), Some(Baseline::Latest));
+        assert_eq!(Baseline::parse(" 12 ").unwrap(), Some(Baseline::Entry(12)));
+        assert!(Baseline::parse("last").unwrap_err().contains("Invalid history id"));
+    }
+
+    #[test]
+    fn same_results_have_no_changes() {
+        let value = page(&["a", "b"], json!([{"品目": "りんご", "価格": "120"}]));
+        let diff = diff(value.clone(), value);
+        assert_eq!((diff.added, diff.removed, diff.changed), (0, 0, 0));
+        assert!(diff.changes.is_empty());
+    }
+
+    #[test]
+    fn reports_changed_text_and_added_rows_with_paths() {
+        let old = page(&["a", "b"], json!([{"品目": "りんご", "価格": "120"}]));
+        let new = page(&["a", "B"], json!([{"品目": "りんご", "価格": "120"}, {"品目": "みかん", "価格": "80"}]));
+        let diff = diff(old, new);
+        assert_eq!((diff.added, diff.removed, diff.changed), (1, 0, 1));
+        let changed = &diff.changes[0];
+        assert_eq!((changed.change.as_str(), changed.kind.as_str()), ("changed", "text"));
+        assert_eq!(changed.path, "/html[1]/p[2]");
+        assert_eq!(change_text(changed), "changed /html[1]/p[2]: \"b\" -> \"B\"");
+        let added = &diff.changes[1];
+        assert_eq!((added.change.as_str(), added.kind.as_str()), ("added", "row"));
+        assert_eq!(added.path, "/html[1]/table[1]/tr[2]");
+        assert_eq!(added.new, Some(json!({"品目": "みかん", "価格": "80"})));
+    }
+
+    #[test]
+    fn insertions_do_not_shift_later_items_into_changes() {
+        let diff = diff(page(&["a", "c"], json!([])), page(&["a", "b", "c"], json!([])));
+        assert_eq!((diff.added, diff.removed, diff.changed), (1, 0, 0));
+        assert_eq!(diff.changes[0].new, Some(json!("b")));
+    }
+
+    #[test]
+    fn removed_items_use_the_old_path_and_page_meta_is_ignored() {
+        let old = json!({"tag": "html", "children": [{"tag": "a", "href": "/x", "text": "x"}], "page_meta": {"title": "old"}});
+        let new = json!({"tag": "html", "children": [], "page_meta": {"title": "new"}});
+        let diff = diff(old, new);
+        assert_eq!((diff.added, diff.removed, diff.changed), (0, 2, 0));
+        let paths: Vec<(&str, &str)> = diff.changes.iter().map(|c| (c.kind.as_str(), c.path.as_str())).collect();
+        assert_eq!(paths, vec![("text", "/html[1]/a[1]"), ("field", "/html[1]/a[1]/href")]);
+        assert_eq!(summary_line(&diff), "0 added, 2 removed, 0 changed since history #7 (2024-05-01T09:00:00Z)");
+    }
+
+    #[test]
+    fn finds_pages_by_url_or_input_position() {
+        let entry = StoredEntry {
+            created_at: String::new(),
+            urls: vec!["https://a/".to_string(), "https://b/".to_string()],
+            pages: vec![json!({"tag": "html", "text": "a"}), json!({"tag": "ErrorFetch", "text": "failed"})],
+        };
+        assert_eq!(find_page(&entry, "https://a/"), Some(&json!({"tag": "html", "text": "a"})));
+        // エラーの結果・入力にない URL とは比べない
+        assert_eq!(find_page(&entry, "https://b/"), None);
+        assert_eq!(find_page(&entry, "https://c/"), None);
+
+        let typed = StoredEntry {
+            created_at: String::new(),
+            urls: vec![],
+            pages: vec![
+                json!({"url": "https://a/", "document": {"tag": "html"}}),
+                json!({"url": "https://b/", "error": {"tag": "ErrorFetch"}}),
+            ],
+        };
+        assert_eq!(find_page(&typed, "https://a/"), Some(&json!({"tag": "html"})));
+        assert_eq!(find_page(&typed, "https://b/"), None);
+    }
+}
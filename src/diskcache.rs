@@ -0,0 +1,156 @@
+//! 取得した本文のディスクキャッシュ (Web・CLI で共有。抽出のオプションを変えて同じページを変換し直すときに取得し直さない)
+//!
+//! URL (Cookie・認証情報・追加ヘッダを送ったジョブはそのハッシュも) の SHA-256 をファイル名にして <dir>/<先頭 2 文字>/<ハッシュ>.json にメタ情報、.body に本文
+//! (テキストはデコード済みの UTF-8、PDF はそのまま) を保存する。TTL を過ぎたものは取得し直して上書きする。
+//! ファイルは 0600、ディレクトリは 0700 で作る。
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+use url::Url;
+
+use crate::fetch::{Body, FetchInfo, Fetched};
+use crate::privatefile;
+
+/// キャッシュの場所と有効期間
+#[derive(Debug)]
+pub struct DiskCache {
+    dir: PathBuf,
+    /// None なら期限なし
+    ttl: Option<Duration>,
+}
+
+/// メタ情報のファイル (<ハッシュ>.json)
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    url: String,
+    status: u16,
+    content_type: Option<String>,
+    final_url: String,
+    #[serde(default)]
+    redirects: Vec<String>,
+    /// 保存した日時 (UNIX 秒)
+    stored_at: u64,
+    /// 本文が PDF のバイト列 (false ならテキスト)
+    #[serde(default)]
+    pdf: bool,
+}
+
+impl DiskCache {
+    /// ttl_secs が 0 なら期限なし
+    pub fn new(dir: impl Into<PathBuf>, ttl_secs: u64) -> Self {
+        DiskCache {
+            dir: dir.into(),
+            ttl: (ttl_secs > 0).then(|| Duration::from_secs(ttl_secs)),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 期限内のキャッシュ (なければ None。読めないファイル・max_body を超える本文もないものとして扱う。scope は Fetcher の cache_scope)
+    pub async fn get(&self, url: &Url, scope: Option<&str>, max_body: Option<u64>) -> Option<Fetched> {
+        let (meta_path, body_path) = self.paths(url, scope);
+        let entry: Entry = serde_json::from_slice(&tokio::fs::read(&meta_path).await.ok()?).ok()?;
+        let stored_at = UNIX_EPOCH + Duration::from_secs(entry.stored_at);
+        let age = SystemTime::now().duration_since(stored_at).unwrap_or_default();
+        if entry.url != url.as_str() || self.ttl.is_some_and(|ttl| age > ttl) {
+            return None;
+        }
+        let len = tokio::fs::metadata(&body_path).await.ok()?.len();
+        if max_body.is_some_and(|limit| len > limit) {
+            tracing::debug!(url = %url, len, "disk cache entry exceeds the body limit");
+            return None;
+        }
+        let bytes = tokio::fs::read(&body_path).await.ok()?;
+        let size = bytes.len() as u64;
+        let body = if entry.pdf {
+            Body::Pdf(bytes)
+        } else {
+            Body::Text(String::from_utf8(bytes).ok()?)
+        };
+        tracing::debug!(url = %url, age_secs = age.as_secs(), "disk cache hit");
+        Some(Fetched {
+            info: Some(FetchInfo {
+                final_url: entry.final_url,
+                status: entry.status,
+                content_type: entry.content_type.clone(),
+                size: Some(size),
+                duration_ms: 0,
+                fetched_at: humantime::format_rfc3339_seconds(stored_at).to_string(),
+                redirects: entry.redirects,
+                cached: true,
+                wayback: None,
+            }),
+            content_type: entry.content_type,
+            body,
+        })
+    }
+
+    /// 2xx のテキスト・PDF の本文を保存 (失敗はログだけ)
+    pub async fn insert(&self, url: &Url, scope: Option<&str>, fetched: &Fetched) {
+        let Some(info) = fetched.info.as_ref().filter(|info| (200..300).contains(&info.status) && !info.cached) else {
+            return;
+        };
+        let (body, pdf) = match &fetched.body {
+            Body::Text(text) => (text.as_bytes(), false),
+            Body::Pdf(bytes) => (bytes.as_slice(), true),
+            Body::Binary { .. } => return,
+        };
+        let entry = Entry {
+            url: url.to_string(),
+            status: info.status,
+            content_type: fetched.content_type.clone(),
+            final_url: info.final_url.clone(),
+            redirects: info.redirects.clone(),
+            stored_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            pdf,
+        };
+        let (meta_path, body_path) = self.paths(url, scope);
+        // 本文を先に書き、メタ情報があれば本文も揃っているように
+        let result = async {
+            if let Some(parent) = meta_path.parent() {
+                privatefile::create_dir_all(parent)?;
+            }
+            write_atomic(&body_path, body).await?;
+            write_atomic(&meta_path, &serde_json::to_vec(&entry)?).await
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::warn!(url = %url, dir = %self.dir.display(), error = %e, "disk cache write failed");
+        }
+    }
+
+    /// (メタ情報, 本文) のパス (scope があれば URL と別のキー)
+    fn paths(&self, url: &Url, scope: Option<&str>) -> (PathBuf, PathBuf) {
+        let key = match scope {
+            Some(scope) => format!("{url}\n{scope}"),
+            None => url.to_string(),
+        };
+        let hash: String = Sha256::digest(key.as_bytes()).iter().map(|b| format!("{b:02x}")).collect();
+        let dir = self.dir.join(&hash[..2]);
+        (dir.join(format!("{hash}.json")), dir.join(format!("{hash}.body")))
+    }
+}
+
+/// 一時ファイル (0600) に書いてから置き換える (読み手が書きかけのファイルを見ないように)
+async fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let tmp = path.with_extension(format!("tmp-{}-{}", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&tmp).await?;
+    file.write_all(bytes).await?;
+    file.flush().await?;
+    drop(file);
+    tokio::fs::rename(&tmp, path).await.inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp);
+    })
+}
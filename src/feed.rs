@@ -0,0 +1,166 @@
+//! RSS / Atom フィードの解析
+
+use futures_util::stream::{self, StreamExt};
+use schemars::JsonSchema;
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+use url::Url;
+
+use crate::fetch::{FetchInfo, Fetcher};
+use crate::xml::{self, XmlElement};
+use crate::{clean_text, error_node, parse_html_sync, DomContent, JobOptions};
+
+/// フィード (RSS 2.0 / RSS 1.0 / Atom)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FeedData {
+    /// "rss" または "atom"
+    pub feed_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feed_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feed_link: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feed_description: Option<String>,
+    pub items: Vec<FeedItem>,
+    /// フィードの取得の情報 (取得情報を含める指定時)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetch: Option<FetchInfo>,
+}
+
+/// フィードの 1 記事
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FeedItem {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    #[serde(rename = "pubDate", skip_serializing_if = "Option::is_none")]
+    pub pub_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// 記事ページの変換結果 (記事リンクをたどった場合)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub article: Option<Box<DomContent>>,
+}
+
+/// 本文がフィードらしいか (最初の要素が rss / feed / rdf:RDF)
+pub fn looks_like_feed(body: &str) -> bool {
+    let mut rest = body.trim_start_matches('\u{feff}').trim_start();
+    // XML 宣言・コメント・DOCTYPE を読み飛ばす
+    while rest.starts_with("<?") || rest.starts_with("<!") {
+        let end = if rest.starts_with("<!--") { rest.find("-->").map(|i| i + 3) } else { rest.find('>').map(|i| i + 1) };
+        let Some(end) = end else {
+            return false;
+        };
+        rest = rest[end..].trim_start();
+    }
+    let name: String = rest
+        .trim_start_matches('<')
+        .chars()
+        .take_while(|c| !c.is_whitespace() && *c != '>' && *c != '/')
+        .collect();
+    matches!(name.as_str(), "rss" | "feed" | "rdf:RDF" | "RDF")
+}
+
+/// フィードを解析
+pub fn parse_feed(body: &str) -> Result<FeedData, String> {
+    let root = xml::parse(body)?;
+    match root.name.as_str() {
+        "rss" => {
+            let channel = root.child("channel").ok_or("RSS has no <channel>")?;
+            Ok(rss_feed(channel, channel.children_named("item")))
+        }
+        // RSS 1.0 は item が channel の兄弟
+        "RDF" => {
+            let channel = root.child("channel").ok_or("RSS 1.0 has no <channel>")?;
+            Ok(rss_feed(channel, root.children_named("item")))
+        }
+        "feed" => Ok(atom_feed(&root)),
+        other => Err(format!("Not a feed (root element <{other}>)")),
+    }
+}
+
+fn rss_feed<'a>(channel: &XmlElement, items: impl Iterator<Item = &'a XmlElement>) -> FeedData {
+    FeedData {
+        feed_type: "rss".to_string(),
+        feed_title: channel.child_text("title").map(clean_text),
+        feed_link: channel.child_text("link").map(str::to_string),
+        feed_description: channel.child_text("description").map(html_to_text),
+        items: items
+            .map(|item| FeedItem {
+                title: item.child_text("title").map(clean_text),
+                link: item.child_text("link").map(str::to_string),
+                // RSS 1.0 は dc:date
+                pub_date: item.child_text("pubDate").or_else(|| item.child_text("date")).map(str::to_string),
+                description: item
+                    .child_text("description")
+                    .or_else(|| item.child_text("encoded"))
+                    .map(html_to_text),
+                article: None,
+            })
+            .collect(),
+        fetch: None,
+    }
+}
+
+fn atom_feed(feed: &XmlElement) -> FeedData {
+    FeedData {
+        feed_type: "atom".to_string(),
+        feed_title: feed.child_text("title").map(clean_text),
+        feed_link: atom_link(feed),
+        feed_description: feed.child_text("subtitle").map(html_to_text),
+        items: feed
+            .children_named("entry")
+            .map(|entry| FeedItem {
+                title: entry.child_text("title").map(clean_text),
+                link: atom_link(entry),
+                pub_date: entry.child_text("published").or_else(|| entry.child_text("updated")).map(str::to_string),
+                description: entry
+                    .child_text("summary")
+                    .or_else(|| entry.child_text("content"))
+                    .map(html_to_text),
+                article: None,
+            })
+            .collect(),
+        fetch: None,
+    }
+}
+
+/// Atom の <link rel="alternate"> (rel 省略は alternate 扱い)
+fn atom_link(el: &XmlElement) -> Option<String> {
+    el.children_named("link")
+        .find(|l| matches!(l.attr("rel"), None | Some("alternate")))
+        .and_then(|l| l.attr("href"))
+        .map(str::to_string)
+}
+
+/// 説明文に含まれる HTML をテキストに
+fn html_to_text(raw: &str) -> String {
+    let fragment = Html::parse_fragment(raw);
+    clean_text(&fragment.root_element().text().collect::<String>())
+}
+
+/// 各記事のリンク先を取得して article に格納 (順序は保持)
+pub async fn fetch_articles(fetcher: &Fetcher, feed: &mut FeedData, base_url: &Url, options: &JobOptions) {
+    let articles: Vec<Option<DomContent>> = stream::iter(feed.items.iter().map(|item| item.link.clone()))
+        .map(|link| async move {
+            let url = base_url.join(&link?).ok()?;
+            let body = match fetcher.get_text(&url).await {
+                Ok(b) => b,
+                Err(e) => return Some(error_node(e.error_tag(), e.to_string())),
+            };
+            let parse_options = options.parse.clone();
+            Some(
+                spawn_blocking(move || parse_html_sync(&body, &parse_options, &url))
+                    .await
+                    .unwrap_or_else(|e| error_node("ErrorSpawnBlock", format!("spawn_blocking error: {e:?}"))),
+            )
+        })
+        .buffered(options.concurrency)
+        .collect()
+        .await;
+    for (item, article) in feed.items.iter_mut().zip(articles) {
+        item.article = article.map(Box::new);
+    }
+}
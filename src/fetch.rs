@@ -0,0 +1,983 @@
+//! HTTP 取得 (ジョブ単位のクライアント・タイムアウト・リトライ・Cookie・認証・ホスト毎の間隔・キャッシュ)
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use reqwest::cookie::Jar;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use tokio::sync::{OnceCell, OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+use tracing::Instrument;
+use url::{Origin, Url};
+
+use crate::cache::{CachedResponse, ResponseCache};
+use crate::charset;
+use crate::diskcache::DiskCache;
+use crate::localfile::{self, LocalFiles};
+use crate::metrics::Metrics;
+use crate::normalize;
+use crate::warc::{RequestHead, ResponseHead, WarcWriter};
+use crate::wayback::{self, WaybackOptions, WaybackSnapshot};
+use crate::{DomContent, JobOptions, RetryPolicy};
+
+/// バックオフ待ち時間の上限
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// たどるリダイレクトの数の既定値
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// 取得エラー
+#[derive(Debug)]
+pub enum FetchError {
+    /// 接続・送信エラー (タイムアウト含む)
+    Request(reqwest::Error),
+    /// 本文読み込みエラー
+    Body(reqwest::Error),
+    /// リトライ対象のステータス (5xx / 429) が続いた
+    Status(reqwest::StatusCode),
+    /// ジョブ全体の期限切れ
+    Deadline,
+    /// 本文が上限 (バイト) を超えた
+    TooLarge { limit: u64 },
+    /// リダイレクトが多すぎる・別のホストへのリダイレクトを許可していない
+    Redirect(String),
+    /// ローカルファイル (file://) を読めない・読んではいけない
+    Local(String),
+    /// オフラインで保存したページにもディスクキャッシュにもない (URL)
+    Offline(String),
+    /// Wayback Machine にスナップショットがない
+    Wayback(String),
+    /// リトライしても失敗
+    Retried {
+        last: Box<FetchError>,
+        attempts: u32,
+    },
+}
+
+impl FetchError {
+    /// 一時的な失敗か (リトライ対象)
+    fn is_retryable(&self) -> bool {
+        match self {
+            FetchError::Request(e) => e.is_timeout() || e.is_connect(),
+            FetchError::Body(e) => e.is_timeout(),
+            FetchError::Status(_) => true,
+            FetchError::Deadline
+            | FetchError::TooLarge { .. }
+            | FetchError::Redirect(_)
+            | FetchError::Local(_)
+            | FetchError::Offline(_)
+            | FetchError::Wayback(_)
+            | FetchError::Retried { .. } => false,
+        }
+    }
+
+    /// エラーノードのタグ
+    pub fn error_tag(&self) -> &'static str {
+        match self {
+            FetchError::TooLarge { .. } => "ErrorTooLarge",
+            FetchError::Redirect(_) => "ErrorRedirect",
+            FetchError::Local(_) => "ErrorFile",
+            FetchError::Offline(_) => "ErrorOffline",
+            FetchError::Wayback(_) => "ErrorWayback",
+            FetchError::Retried { last, .. } => last.error_tag(),
+            _ => "ErrorFetch",
+        }
+    }
+
+    /// 試行回数を付与 (1 回なら元のまま)
+    fn with_attempts(self, attempts: u32) -> FetchError {
+        if attempts > 1 {
+            FetchError::Retried { last: Box::new(self), attempts }
+        } else {
+            self
+        }
+    }
+}
+
+/// リトライ対象のステータス
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Request(e) => write!(f, "Request error: {e}"),
+            FetchError::Body(e) => write!(f, "Error reading response: {e}"),
+            FetchError::Status(status) => write!(f, "HTTP status {status}"),
+            FetchError::Deadline => write!(f, "Job deadline exceeded"),
+            FetchError::TooLarge { limit } => write!(f, "Response body exceeds the limit of {limit} bytes"),
+            FetchError::Redirect(message) => f.write_str(message),
+            FetchError::Local(e) => write!(f, "File error: {e}"),
+            FetchError::Offline(url) => write!(f, "Not in the saved pages or the cache (offline): {url}"),
+            FetchError::Wayback(message) => f.write_str(message),
+            FetchError::Retried { last, attempts } => write!(f, "{last} (after {attempts} attempts)"),
+        }
+    }
+}
+
+/// 入力 URL の取得に付与する認証情報
+#[derive(Clone)]
+pub enum Credentials {
+    Basic { username: String, password: String },
+    Bearer(String),
+}
+
+impl Credentials {
+    /// "username:password" は Basic、"Bearer xxx" やコロンなしはトークンとして解釈
+    pub fn parse(text: &str) -> Option<Credentials> {
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        if let Some(token) = text.strip_prefix("Bearer ") {
+            return Some(Credentials::Bearer(token.trim().to_string()));
+        }
+        match text.split_once(':') {
+            Some((username, password)) => Some(Credentials::Basic {
+                username: username.to_string(),
+                password: password.to_string(),
+            }),
+            None => Some(Credentials::Bearer(text.to_string())),
+        }
+    }
+}
+
+/// キャッシュを共有してよい範囲 (Cookie・認証情報・追加ヘッダを送るジョブはそれらのハッシュ。他のジョブに本文を渡さない)
+fn cache_scope(options: &JobOptions) -> Option<String> {
+    if !options.sends_secrets() {
+        return None;
+    }
+    let mut hasher = Sha256::new();
+    for (name, value) in &options.headers {
+        hasher.update(name.as_str().as_bytes());
+        hasher.update(b": ");
+        hasher.update(value.as_bytes());
+        hasher.update(b"\n");
+    }
+    if let Some(cookies) = &options.cookies {
+        hasher.update(b"cookie: ");
+        hasher.update(cookies.as_bytes());
+        hasher.update(b"\n");
+    }
+    match &options.credentials {
+        Some(Credentials::Basic { username, password }) => hasher.update(format!("basic: {username}:{password}\n")),
+        Some(Credentials::Bearer(token)) => hasher.update(format!("bearer: {token}\n")),
+        None => {}
+    }
+    Some(hasher.finalize().iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// ログ等に秘密情報を出さない
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Basic { username, .. } => write!(f, "Basic({username}:***)"),
+            Credentials::Bearer(_) => write!(f, "Bearer(***)"),
+        }
+    }
+}
+
+/// Fetcher::subpage_permit の枠 (drop で返す)
+pub struct SubpagePermit {
+    _host: Option<OwnedSemaphorePermit>,
+    _total: Option<OwnedSemaphorePermit>,
+}
+
+/// 1 ジョブ内で共有する取得クライアント (Cookie もジョブ内で共有)
+pub struct Fetcher {
+    client: reqwest::Client,
+    credentials: Option<Credentials>,
+    /// 認証情報・追加ヘッダを送る入力 URL のオリジン
+    auth_origins: HashSet<Origin>,
+    /// 入力 URL のオリジンにだけ送る追加ヘッダ
+    headers: HeaderMap,
+    /// 同一ホストへのリクエスト間隔
+    host_delay: Duration,
+    /// たどるリダイレクトの数の上限
+    max_redirects: usize,
+    /// 別のホストへのリダイレクトをたどる
+    cross_host_redirects: bool,
+    /// ホスト毎の次にリクエストしてよい時刻
+    host_next_slot: Mutex<HashMap<String, Instant>>,
+    /// 1階層リンク先の同時取得数の枠 (ジョブ全体)
+    subpage_slots: Arc<Semaphore>,
+    /// 1階層リンク先の同一ホストへの同時取得数 (0 で無制限)
+    subpage_per_host: usize,
+    /// ホスト毎の 1階層リンク先の同時取得数の枠
+    subpage_host_slots: Mutex<HashMap<String, Arc<Semaphore>>>,
+    /// 取得・変換済みの 1階層リンク先 (normalize::dedup_key ごと。取得できなかったものは None)
+    subpages: Mutex<HashMap<String, Arc<OnceCell<Option<DomContent>>>>>,
+    /// ジョブ全体で取得するリンク先の数 (0 で無制限)
+    max_subpages: usize,
+    /// ジョブをまたいで共有するレスポンスキャッシュ
+    response_cache: Option<Arc<ResponseCache>>,
+    /// 取得した本文のディスクキャッシュ
+    disk_cache: Option<Arc<DiskCache>>,
+    /// Cookie・認証情報・追加ヘッダのハッシュ (送るジョブは別のキャッシュのキーに。なければ None)
+    cache_scope: Option<String>,
+    /// ネットワークに出ず、保存したページとディスクキャッシュだけを使う
+    offline: bool,
+    /// オフライン再生用に保存したページのディレクトリ
+    replay_dir: Option<PathBuf>,
+    /// リクエスト・レスポンスを書き出す WARC
+    warc: Option<Arc<WarcWriter>>,
+    /// Wayback Machine のスナップショットを使う条件
+    wayback: WaybackOptions,
+    /// WARC のリクエストに書く既定のヘッダー (User-Agent を含む)
+    default_headers: HeaderMap,
+    deadline: Option<Instant>,
+    retry: RetryPolicy,
+    /// 本文の上限 (バイト, None で無制限)
+    max_body: Option<u64>,
+    /// サーバーのメトリクス (CLI では None)
+    metrics: Option<Arc<Metrics>>,
+    /// このクライアントで受け取った本文の合計 (バイト)
+    downloaded: AtomicU64,
+    /// リンク切れチェック用 (リダイレクトを 1 つずつたどる。チェックしないジョブでは None)
+    link_client: Option<reqwest::Client>,
+    /// チェック済みのリンク (ジョブ内で同じ URL を何度も確かめない)
+    link_checks: Mutex<HashMap<String, LinkCheck>>,
+    /// file:// URL を読んでよい範囲
+    local_files: LocalFiles,
+}
+
+impl Fetcher {
+    /// ジョブのオプションからクライアントを作成 (期限はここから計測)
+    ///
+    /// Cookie・認証情報・追加ヘッダは input_urls のホスト (オリジン) にだけ送る
+    pub fn new(options: &JobOptions, input_urls: &[Url]) -> Result<Self, reqwest::Error> {
+        let cookie_jar = Arc::new(Jar::default());
+        if let Some(cookies) = &options.cookies {
+            for url in input_urls {
+                add_cookies(&cookie_jar, cookies, url);
+            }
+        }
+        let mut default_headers = HeaderMap::new();
+        if let Some(ua) = options.user_agent.as_deref().and_then(|ua| HeaderValue::from_str(ua).ok()) {
+            default_headers.insert(reqwest::header::USER_AGENT, ua);
+        }
+        // リダイレクトは request で 1 つずつたどる (経路の記録・上限・別ホストの判定)
+        let mut builder = reqwest::Client::builder()
+            .cookie_provider(cookie_jar)
+            .redirect(reqwest::redirect::Policy::none());
+        if let Some(ua) = &options.user_agent {
+            builder = builder.user_agent(ua);
+        }
+        if let Some(timeout) = options.request_timeout {
+            builder = builder.timeout(timeout);
+        }
+        // リンクのチェックには Cookie・認証情報・追加ヘッダを送らない
+        let link_client = if options.check_links {
+            let mut builder = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none());
+            if let Some(ua) = &options.user_agent {
+                builder = builder.user_agent(ua);
+            }
+            if let Some(timeout) = options.request_timeout {
+                builder = builder.timeout(timeout);
+            }
+            Some(builder.build()?)
+        } else {
+            None
+        };
+        Ok(Fetcher {
+            client: builder.build()?,
+            credentials: options.credentials.clone(),
+            auth_origins: input_urls.iter().map(Url::origin).collect(),
+            headers: options.headers.clone(),
+            host_delay: options.host_delay,
+            max_redirects: options.max_redirects,
+            cross_host_redirects: options.cross_host_redirects,
+            host_next_slot: Mutex::new(HashMap::new()),
+            subpage_slots: Arc::new(Semaphore::new(options.subpage_concurrency.max(1))),
+            subpage_per_host: options.subpage_per_host,
+            subpage_host_slots: Mutex::new(HashMap::new()),
+            subpages: Mutex::new(HashMap::new()),
+            max_subpages: options.max_subpages_per_job,
+            response_cache: options.response_cache.clone(),
+            disk_cache: options.disk_cache.clone(),
+            cache_scope: cache_scope(options),
+            offline: options.offline,
+            replay_dir: options.replay_dir.clone(),
+            warc: options.warc.clone(),
+            wayback: options.wayback.clone(),
+            default_headers,
+            deadline: options.job_deadline.map(|d| Instant::now() + d),
+            retry: options.retry.clone(),
+            max_body: options.max_response_bytes,
+            metrics: options.metrics.clone(),
+            downloaded: AtomicU64::new(0),
+            link_client,
+            link_checks: Mutex::new(HashMap::new()),
+            local_files: options.local_files.clone(),
+        })
+    }
+
+    /// これまでに受け取った本文の合計 (バイト。サブページ・リトライ分を含む)
+    pub fn downloaded(&self) -> u64 {
+        self.downloaded.load(Ordering::Relaxed)
+    }
+
+    /// 期限までの残り時間 (期限なしは None)
+    fn remaining(&self) -> Option<Duration> {
+        self.deadline.map(|d| d.saturating_duration_since(Instant::now()))
+    }
+
+    /// GET して本文をテキストで返す (バイナリは空文字列)
+    pub async fn get_text(&self, url: &Url) -> Result<String, FetchError> {
+        Ok(match self.get(url).await?.body {
+            Body::Text(text) => text,
+            Body::Binary { .. } | Body::Pdf(_) => String::new(),
+        })
+    }
+
+    /// GET して Content-Type と本文を返す (一時的な失敗は指数バックオフでリトライ)
+    ///
+    /// Wayback の指定があれば、日時に最も近いスナップショット・ライブの取得に失敗したときのスナップショット
+    pub async fn get(&self, url: &Url) -> Result<Fetched, FetchError> {
+        if self.offline && url.scheme() != "file" {
+            return self.replay(url).await;
+        }
+        if url.scheme() == "file" || !self.wayback.is_enabled() {
+            return self.get_live(url).await;
+        }
+        if let Some(date) = &self.wayback.date {
+            return self.get_snapshot(url, Some(date), "date").await;
+        }
+        let live = self.get_live(url).await;
+        let failed = match &live {
+            Ok(fetched) => fetched.info.as_ref().is_some_and(|info| info.status >= 400),
+            Err(e) => !matches!(e, FetchError::Deadline),
+        };
+        if !failed {
+            return live;
+        }
+        // スナップショットもなければライブの結果のまま
+        self.get_snapshot(url, None, "fallback")
+            .await
+            .inspect_err(|e| tracing::debug!(url = %url, error = %e, "no Wayback fallback"))
+            .or(live)
+    }
+
+    /// Wayback Machine のスナップショット (date なしは最新。見つからなければ ErrorWayback)
+    async fn get_snapshot(&self, url: &Url, date: Option<&str>, reason: &str) -> Result<Fetched, FetchError> {
+        let snapshot = wayback::snapshot_url(&self.wayback.base, date, url)
+            .ok_or_else(|| FetchError::Wayback(format!("Invalid Wayback URL for {url}")))?;
+        let mut fetched = self.get_live(&snapshot).await?;
+        if let Some(info) = &mut fetched.info {
+            if info.status >= 400 {
+                let near = date.map(|d| format!(" near {d}")).unwrap_or_default();
+                return Err(FetchError::Wayback(format!("No snapshot of {url}{near} (HTTP {})", info.status)));
+            }
+            info.wayback = Some(WaybackSnapshot {
+                url: info.final_url.clone(),
+                timestamp: wayback::snapshot_timestamp(&info.final_url),
+                reason: reason.to_string(),
+            });
+        }
+        Ok(fetched)
+    }
+
+    /// ライブの取得 (ディスクキャッシュに期限内の本文があれば取得しない。WARC に書くときはキャッシュを読まない)
+    async fn get_live(&self, url: &Url) -> Result<Fetched, FetchError> {
+        let disk_cache = self.disk_cache.as_deref().filter(|_| url.scheme() != "file");
+        if let Some(cache) = disk_cache.filter(|_| self.warc.is_none()) {
+            if let Some(fetched) = self.cached_body(cache, url).await {
+                return Ok(fetched);
+            }
+        }
+        let mut attempt = 1;
+        let mut backoff = self.retry.backoff;
+        loop {
+            match self.get_once(url).await {
+                Ok(fetched) => {
+                    if let Some(cache) = disk_cache {
+                        cache.insert(url, self.cache_scope.as_deref(), &fetched).await;
+                    }
+                    return Ok(fetched);
+                }
+                Err(e) if e.is_retryable() && attempt < self.retry.max_attempts => {
+                    // 待っている間に期限が来るなら諦める
+                    if self.remaining().is_some_and(|r| r <= backoff) {
+                        return Err(e.with_attempts(attempt));
+                    }
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.with_attempts(attempt)),
+            }
+        }
+    }
+
+    /// オフラインの取得 (保存したページ、ディスクキャッシュの順。どちらにもなければすぐにエラー)
+    async fn replay(&self, url: &Url) -> Result<Fetched, FetchError> {
+        if let Some(dir) = &self.replay_dir {
+            if let Some(fetched) = localfile::replay(dir, url, self.max_body).await? {
+                return Ok(fetched);
+            }
+        }
+        if let Some(cache) = &self.disk_cache {
+            if let Some(fetched) = self.cached_body(cache, url).await {
+                return Ok(fetched);
+            }
+        }
+        Err(FetchError::Offline(url.to_string()))
+    }
+
+    /// ディスクキャッシュの本文 (このジョブの本文の上限・リダイレクトの制限に合わないものは使わない)
+    async fn cached_body(&self, cache: &DiskCache, url: &Url) -> Option<Fetched> {
+        let fetched = cache.get(url, self.cache_scope.as_deref(), self.max_body).await?;
+        let info = fetched.info.as_ref()?;
+        let final_host = Url::parse(&info.final_url).ok().and_then(|u| u.host_str().map(str::to_string));
+        if info.redirects.len() > self.max_redirects
+            || (!self.cross_host_redirects && !info.redirects.is_empty() && final_host.as_deref() != url.host_str())
+        {
+            return None;
+        }
+        Some(fetched)
+    }
+
+    /// 同一ホストへの間隔を空けるため、順番が来るまで待つ
+    async fn wait_for_host_slot(&self, url: &Url) {
+        if self.host_delay.is_zero() {
+            return;
+        }
+        let Some(host) = url.host_str() else {
+            return;
+        };
+        // 並行リクエストでも間隔が保たれるよう、ロック中に枠を予約してから待つ
+        let start = {
+            let mut slots = self.host_next_slot.lock().unwrap();
+            let now = Instant::now();
+            let slot = slots.entry(host.to_string()).or_insert(now);
+            let start = (*slot).max(now);
+            *slot = start + self.host_delay;
+            start
+        };
+        tokio::time::sleep_until(start).await;
+    }
+
+    /// 1階層リンク先を取得してよい枠 (ホストの枠、ジョブ全体の枠の順に空くまで待つ。drop で返す)
+    pub async fn subpage_permit(&self, url: &Url) -> SubpagePermit {
+        let host_slots = match url.host_str() {
+            Some(host) if self.subpage_per_host > 0 => {
+                let mut slots = self.subpage_host_slots.lock().unwrap();
+                Some(slots.entry(host.to_string()).or_insert_with(|| Arc::new(Semaphore::new(self.subpage_per_host))).clone())
+            }
+            _ => None,
+        };
+        // Semaphore は閉じないので acquire は失敗しない
+        let host = match host_slots {
+            Some(slots) => slots.acquire_owned().await.ok(),
+            None => None,
+        };
+        let total = self.subpage_slots.clone().acquire_owned().await.ok();
+        SubpagePermit { _host: host, _total: total }
+    }
+
+    /// 1階層リンク先 (ジョブ内で同じ URL は load で 1 回だけ取得・変換し、以降はその結果の複製。
+    /// 取得中の URL を求められたら、その取得が終わるのを待つ)
+    ///
+    /// ジョブ全体の上限に達した後の新しい URL は取得せずに Err(上限)
+    pub async fn subpage(&self, url: &Url, load: impl Future<Output = Option<DomContent>>) -> Result<Option<DomContent>, usize> {
+        let key = normalize::dedup_key(url);
+        let cell = {
+            let mut subpages = self.subpages.lock().unwrap();
+            if self.max_subpages > 0 && subpages.len() >= self.max_subpages && !subpages.contains_key(&key) {
+                return Err(self.max_subpages);
+            }
+            subpages.entry(key).or_default().clone()
+        };
+        Ok(cell.get_or_init(|| load).await.clone())
+    }
+
+    /// 1 回分の GET
+    async fn get_once(&self, url: &Url) -> Result<Fetched, FetchError> {
+        let fetch = async {
+            self.wait_for_host_slot(url).await;
+            let span = tracing::debug_span!("fetch", url = %url, status = tracing::field::Empty);
+            let started = Instant::now();
+            let fetched = self.request(url).instrument(span.clone()).await;
+            let duration = started.elapsed();
+            let _entered = span.enter();
+            let mut fetched = fetched.inspect_err(|e| tracing::debug!(error = %e, "fetch failed"))?;
+            let bytes = fetched.body.size() as u64;
+            tracing::debug!(bytes, duration_ms = duration.as_millis() as u64, "fetched");
+            if let Some(info) = &mut fetched.info {
+                info.size = match fetched.body {
+                    Body::Binary { size } => size,
+                    _ => Some(bytes),
+                };
+                info.duration_ms = duration.as_millis() as u64;
+                info.fetched_at = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+            }
+            self.downloaded.fetch_add(bytes, Ordering::Relaxed);
+            if let Some(metrics) = &self.metrics {
+                metrics.record_fetch(bytes, duration);
+            }
+            Ok(fetched)
+        };
+        match self.remaining() {
+            Some(remaining) if remaining.is_zero() => Err(FetchError::Deadline),
+            Some(remaining) => tokio::time::timeout(remaining, fetch)
+                .await
+                .unwrap_or(Err(FetchError::Deadline)),
+            None => fetch.await,
+        }
+    }
+
+    /// リンク先が取得できるかを確かめる (HEAD、未対応なら GET。本文は読まない)
+    pub async fn check_link(&self, url: &Url) -> LinkCheck {
+        if self.offline {
+            return LinkCheck::unreachable(vec![], "Link checking is not available offline".to_string());
+        }
+        if let Some(check) = self.link_checks.lock().unwrap().get(url.as_str()) {
+            return check.clone();
+        }
+        let check = match self.remaining() {
+            Some(remaining) if remaining.is_zero() => LinkCheck::unreachable(vec![], FetchError::Deadline.to_string()),
+            Some(remaining) => tokio::time::timeout(remaining, self.follow_redirects(url))
+                .await
+                .unwrap_or_else(|_| LinkCheck::unreachable(vec![], FetchError::Deadline.to_string())),
+            None => self.follow_redirects(url).await,
+        };
+        self.link_checks.lock().unwrap().insert(url.to_string(), check.clone());
+        check
+    }
+
+    /// リダイレクトを 1 つずつたどり、最後のステータスを返す
+    async fn follow_redirects(&self, url: &Url) -> LinkCheck {
+        let Some(client) = &self.link_client else {
+            return LinkCheck::unreachable(vec![], "Link checking is not enabled".to_string());
+        };
+        let mut current = url.clone();
+        let mut redirects = vec![];
+        loop {
+            self.wait_for_host_slot(&current).await;
+            let mut method = "HEAD";
+            let mut result = client.head(current.clone()).send().await;
+            // HEAD を受け付けないサーバーには GET で (本文は読まずに捨てる)
+            if result.as_ref().is_ok_and(|r| {
+                matches!(r.status(), reqwest::StatusCode::METHOD_NOT_ALLOWED | reqwest::StatusCode::NOT_IMPLEMENTED)
+            }) {
+                method = "GET";
+                result = client.get(current.clone()).send().await;
+            }
+            let resp = match result {
+                Ok(resp) => resp,
+                Err(e) => return LinkCheck::unreachable(redirects, FetchError::Request(e).to_string()),
+            };
+            let status = resp.status();
+            let location = resp
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|loc| current.join(loc).ok());
+            match location {
+                Some(next) if status.is_redirection() => {
+                    if redirects.len() >= self.max_redirects {
+                        return LinkCheck::unreachable(redirects, too_many_redirects(self.max_redirects));
+                    }
+                    redirects.push(next.to_string());
+                    current = next;
+                }
+                _ => {
+                    return LinkCheck {
+                        status: Some(status.as_u16()),
+                        ok: status.is_success(),
+                        method: Some(method.to_string()),
+                        redirects,
+                        error: None,
+                    }
+                }
+            }
+        }
+    }
+
+    /// リクエストを送って本文まで読む (リダイレクトはたどって redirects に記録)
+    async fn request(&self, url: &Url) -> Result<Fetched, FetchError> {
+        if url.scheme() == "file" {
+            return localfile::read(url, &self.local_files, self.max_body).await;
+        }
+        // キャッシュがあれば条件付きリクエスト (最初の URL のみ)
+        let cached = self.response_cache.as_ref().and_then(|c| c.get(url, self.cache_scope.as_deref()));
+        let mut current = url.clone();
+        let mut redirects = vec![];
+        let (resp, warc_request) = loop {
+            let mut request = self.client.get(current.clone());
+            // 認証情報・追加ヘッダは入力 URL のオリジンにだけ (リダイレクト先でも判定し直す)
+            if self.auth_origins.contains(&current.origin()) {
+                request = request.headers(self.headers.clone());
+                request = match &self.credentials {
+                    Some(Credentials::Basic { username, password }) => request.basic_auth(username, Some(password)),
+                    Some(Credentials::Bearer(token)) => request.bearer_auth(token),
+                    None => request,
+                };
+            }
+            if let (Some(cached), true) = (&cached, redirects.is_empty()) {
+                request = request.headers(cached.conditional_headers());
+            }
+            let request = request.build().map_err(FetchError::Request)?;
+            let warc_request = self.warc.as_ref().map(|_| RequestHead::of(&request, &self.default_headers));
+            let resp = self.client.execute(request).await.map_err(FetchError::Request)?;
+            let Some(next) = redirect_location(&resp, &current) else {
+                break (resp, warc_request);
+            };
+            let status = resp.status().as_u16();
+            if let (Some(warc), Some(warc_request)) = (&self.warc, &warc_request) {
+                let head = ResponseHead::of(&resp);
+                // 本文の上限を超えたら本文なし (WARC-Truncated) で記録してリダイレクトは続ける
+                let body = read_body(resp, self.max_body).await.ok();
+                warc.write_exchange(warc_request, &head, body.as_deref());
+            }
+            if redirects.len() >= self.max_redirects {
+                return Err(FetchError::Redirect(too_many_redirects(self.max_redirects)));
+            }
+            if !self.cross_host_redirects && next.host_str() != url.host_str() {
+                return Err(FetchError::Redirect(format!(
+                    "Redirect to another host is not allowed: {} -> {next}",
+                    url.host_str().unwrap_or_default()
+                )));
+            }
+            tracing::debug!(status, to = %next, "redirect");
+            redirects.push(next.to_string());
+            self.wait_for_host_slot(&next).await;
+            current = next;
+        };
+        tracing::Span::current().record("status", resp.status().as_u16());
+        // WARC には本文を読んだらその本文、読まなければヘッダーだけ
+        let warc_response = self.warc.as_ref().map(|_| ResponseHead::of(&resp));
+        let archive = |body: Option<&[u8]>| {
+            if let (Some(warc), Some(request), Some(response)) = (&self.warc, &warc_request, &warc_response) {
+                warc.write_exchange(request, response, body);
+            }
+        };
+        // サイズ・所要時間は get_once で埋める
+        let info = |content_type: &Option<String>| FetchInfo {
+            final_url: resp.url().to_string(),
+            status: resp.status().as_u16(),
+            content_type: content_type.clone(),
+            size: None,
+            duration_ms: 0,
+            fetched_at: String::new(),
+            redirects: redirects.clone(),
+            cached: false,
+            wayback: None,
+        };
+        if resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+            archive(Some(&[]));
+            if let Some(cached) = cached {
+                return Ok(Fetched {
+                    info: Some(info(&cached.content_type)),
+                    content_type: cached.content_type,
+                    body: Body::Text(cached.body),
+                });
+            }
+        }
+        // リトライ無効時は従来どおりステータスを問わず本文を返す
+        if self.retry.max_attempts > 1 && is_retryable_status(resp.status()) {
+            archive(None);
+            return Err(FetchError::Status(resp.status()));
+        }
+        let cacheable = resp.status().is_success();
+        let headers = resp.headers().clone();
+        let content_type = headers
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        // PDF はテキスト抽出用にバイト列のまま
+        let info = Some(info(&content_type));
+        if content_type.as_deref().is_some_and(is_pdf_type) {
+            let bytes = read_body(resp, self.max_body).await?;
+            archive(Some(&bytes));
+            return Ok(Fetched {
+                info,
+                content_type,
+                body: Body::Pdf(bytes),
+            });
+        }
+        // 画像などは本文を読まずにサイズだけ
+        if content_type.as_deref().is_some_and(is_binary_type) {
+            archive(None);
+            return Ok(Fetched {
+                info,
+                content_type,
+                body: Body::Binary {
+                    size: resp.content_length(),
+                },
+            });
+        }
+        let bytes = read_body(resp, self.max_body).await?;
+        archive(Some(&bytes));
+        let body = charset::decode(&bytes, content_type.as_deref());
+        if let (true, Some(cache)) = (cacheable, &self.response_cache) {
+            if let Some(entry) = CachedResponse::from_response(&headers, &body) {
+                cache.insert(url, self.cache_scope.as_deref(), entry);
+            }
+        }
+        Ok(Fetched {
+            content_type,
+            body: Body::Text(body),
+            info,
+        })
+    }
+}
+
+/// リダイレクトのレスポンスなら Location の URL (304 は除く)
+fn redirect_location(resp: &reqwest::Response, current: &Url) -> Option<Url> {
+    let status = resp.status();
+    if !status.is_redirection() || status == reqwest::StatusCode::NOT_MODIFIED {
+        return None;
+    }
+    let location = resp.headers().get(reqwest::header::LOCATION)?.to_str().ok()?;
+    current.join(location).ok()
+}
+
+fn too_many_redirects(max: usize) -> String {
+    format!("Too many redirects (more than {max})")
+}
+
+/// 本文を読む (limit を超えたらそこで打ち切って TooLarge)
+async fn read_body(mut resp: reqwest::Response, limit: Option<u64>) -> Result<Vec<u8>, FetchError> {
+    let Some(limit) = limit else {
+        return resp.bytes().await.map(|b| b.to_vec()).map_err(FetchError::Body);
+    };
+    if resp.content_length().is_some_and(|len| len > limit) {
+        return Err(FetchError::TooLarge { limit });
+    }
+    // Content-Length がない・偽っている場合に備えて読みながら数える
+    let mut body = Vec::new();
+    while let Some(chunk) = resp.chunk().await.map_err(FetchError::Body)? {
+        if (body.len() + chunk.len()) as u64 > limit {
+            return Err(FetchError::TooLarge { limit });
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// 取得結果
+#[derive(Debug)]
+pub struct Fetched {
+    /// Content-Type ヘッダの値 (なければ None)
+    pub content_type: Option<String>,
+    pub body: Body,
+    /// HTTP での取得の情報 (ブラウザで描画した場合は None)
+    pub info: Option<FetchInfo>,
+}
+
+/// URL ごとの取得の情報 (出力の page_meta.fetch など)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FetchInfo {
+    /// リダイレクト後の URL
+    pub final_url: String,
+    /// HTTP ステータス (304 でキャッシュを使った場合は 304)
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// 本文のバイト数 (本文を読まないバイナリは Content-Length)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// 取得にかかった時間 (ミリ秒, 同一ホストの間隔待ちは含まない)
+    pub duration_ms: u64,
+    /// 取得を終えた日時 (UTC, RFC 3339)
+    pub fetched_at: String,
+    /// たどったリダイレクト先 (順に。最後が final_url)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redirects: Vec<String>,
+    /// ディスクキャッシュの本文 (fetched_at は保存した日時)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+    /// Wayback Machine のスナップショットを使った場合のスナップショット (final_url もスナップショットの URL)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wayback: Option<WaybackSnapshot>,
+}
+
+/// リンク切れチェックの結果
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LinkCheck {
+    /// 最後 (リダイレクト後) の HTTP ステータス。接続できなければ None
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// 最後のステータスが 2xx
+    pub ok: bool,
+    /// HEAD / GET (HEAD が 405 / 501 なら GET)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// たどったリダイレクト先 (順に。最後が最終 URL)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redirects: Vec<String>,
+    /// 接続できない・タイムアウト・リダイレクトが多すぎるなど
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl LinkCheck {
+    fn unreachable(redirects: Vec<String>, error: String) -> Self {
+        LinkCheck {
+            status: None,
+            ok: false,
+            method: None,
+            redirects,
+            error: Some(error),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Body {
+    /// 文字コードを判定してデコードした本文
+    Text(String),
+    /// 本文は読まない (size は Content-Length)
+    Binary { size: Option<u64> },
+    /// PDF の本文
+    Pdf(Vec<u8>),
+}
+
+impl Body {
+    /// 読み込んだ本文のバイト数 (Binary は読まないので 0)
+    pub fn size(&self) -> usize {
+        match self {
+            Body::Text(text) => text.len(),
+            Body::Binary { .. } => 0,
+            Body::Pdf(bytes) => bytes.len(),
+        }
+    }
+}
+
+/// "text/html; charset=..." の "text/html" 部分 (小文字)
+pub fn mime_type(content_type: &str) -> String {
+    content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase()
+}
+
+/// PDF
+pub fn is_pdf_type(content_type: &str) -> bool {
+    mime_type(content_type) == "application/pdf"
+}
+
+/// JSON (application/json, application/ld+json など)
+pub fn is_json_type(content_type: &str) -> bool {
+    let mime = mime_type(content_type);
+    mime == "application/json" || mime == "text/json" || mime.ends_with("+json")
+}
+
+/// テキストとして読む必要のないバイナリ (画像・音声・動画・フォント・PDF・アーカイブなど)
+pub fn is_binary_type(content_type: &str) -> bool {
+    let mime = mime_type(content_type);
+    let Some((kind, subtype)) = mime.split_once('/') else {
+        return false;
+    };
+    match kind {
+        "image" => subtype != "svg+xml",
+        "audio" | "video" | "font" => true,
+        "application" => !(is_json_type(&mime)
+            || subtype.ends_with("xml")
+            || subtype.ends_with("+xml")
+            || subtype.contains("javascript")
+            || subtype.contains("ecmascript")
+            || subtype == "x-www-form-urlencoded"),
+        _ => false,
+    }
+}
+
+/// "name=value; name2=value2" 形式の Cookie を url のホスト宛てに登録
+fn add_cookies(jar: &Jar, cookie_header: &str, url: &Url) {
+    for pair in cookie_header.split(';').map(str::trim).filter(|p| p.contains('=')) {
+        jar.add_cookie_str(&format!("{pair}; Path=/"), url);
+    }
+}
+
+/// "Name: value" を改行区切りで並べたテキストをヘッダに変換 (空行は無視)
+pub fn parse_header_lines(text: &str) -> Result<HeaderMap, String> {
+    let mut headers = HeaderMap::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(format!("Invalid header line (expected \"Name: value\"): {line}"));
+        };
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|e| format!("Invalid header name in \"{line}\": {e}"))?;
+        let value = HeaderValue::from_str(value.trim())
+            .map_err(|e| format!("Invalid header value in \"{line}\": {e}"))?;
+        headers.append(name, value);
+    }
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_cookies(cookies: &str) -> JobOptions {
+        JobOptions {
+            cookies: Some(cookies.to_string()),
+            ..JobOptions::default()
+        }
+    }
+
+    #[test]
+    fn cache_scope_separates_jobs_by_secrets() {
+        assert_eq!(cache_scope(&JobOptions::default()), None);
+        let alice = cache_scope(&with_cookies("session=alice"));
+        assert!(alice.is_some());
+        assert_ne!(alice, cache_scope(&with_cookies("session=bob")));
+        assert_eq!(alice, cache_scope(&with_cookies("session=alice")));
+
+        let credentials = |text: &str| JobOptions {
+            credentials: Credentials::parse(text),
+            ..JobOptions::default()
+        };
+        assert_ne!(cache_scope(&credentials("user:pass")), cache_scope(&credentials("user:other")));
+        assert_ne!(cache_scope(&credentials("Bearer a")), cache_scope(&credentials("Bearer b")));
+
+        let headers = JobOptions {
+            headers: parse_header_lines("X-Token: 1").unwrap(),
+            ..JobOptions::default()
+        };
+        assert!(cache_scope(&headers).is_some());
+        assert_ne!(cache_scope(&headers), alice);
+    }
+
+    #[tokio::test]
+    async fn disk_cache_entries_are_not_shared_across_scopes() {
+        let dir = std::env::temp_dir().join(format!("web-to-json-cache-test-{}", std::process::id()));
+        let cache = DiskCache::new(&dir, 0);
+        let url = Url::parse("https://example.com/private").unwrap();
+        let alice = cache_scope(&with_cookies("session=alice"));
+        let bob = cache_scope(&with_cookies("session=bob"));
+        let fetched = Fetched {
+            content_type: Some("text/html".to_string()),
+            body: Body::Text("<p>alice</p>".to_string()),
+            info: Some(FetchInfo {
+                final_url: url.to_string(),
+                status: 200,
+                content_type: Some("text/html".to_string()),
+                size: None,
+                duration_ms: 0,
+                fetched_at: String::new(),
+                redirects: vec![],
+                cached: false,
+                wayback: None,
+            }),
+        };
+        cache.insert(&url, alice.as_deref(), &fetched).await;
+        let hit = cache.get(&url, alice.as_deref(), None).await;
+        let other = cache.get(&url, bob.as_deref(), None).await;
+        let shared = cache.get(&url, None, None).await;
+        let too_large = cache.get(&url, alice.as_deref(), Some(4)).await;
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(matches!(hit, Some(Fetched { body: Body::Text(text), .. }) if text == "<p>alice</p>"));
+        assert!(other.is_none());
+        assert!(shared.is_none());
+        // 本文の上限を超えるキャッシュは使わない
+        assert!(too_large.is_none());
+    }
+}
@@ -0,0 +1,59 @@
+//! 出力のフィルタ (JMESPath。URL ごとの結果から必要な部分だけを取り出す)
+
+use std::fmt;
+use std::sync::Arc;
+
+use jmespath::{Expression, JmespathError};
+use serde_json::Value;
+
+use crate::{error_node, DomContent};
+
+/// コンパイル済みの JMESPath 式
+#[derive(Clone)]
+pub struct Filter {
+    expression: Arc<Expression<'static>>,
+}
+
+impl fmt::Debug for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Filter({})", self.expression)
+    }
+}
+
+impl Filter {
+    pub fn compile(expression: &str) -> Result<Self, String> {
+        let expression = jmespath::compile(expression.trim()).map_err(|e| format!("Invalid filter: {}", error_text(&e)))?;
+        Ok(Filter {
+            expression: Arc::new(expression),
+        })
+    }
+
+    /// 結果に式を適用し、その値 (任意の JSON。一致しなければ null) で置き換える
+    pub fn apply(&self, content: &DomContent) -> DomContent {
+        let value = self
+            .expression
+            .search(content)
+            .map_err(|e| format!("Filter error: {}", error_text(&e)))
+            .and_then(|result| serde_json::to_value(&*result).map_err(|e| format!("Filter result error: {e}")));
+        match value {
+            Ok(value) => DomContent::Filtered(value),
+            Err(e) => error_node("ErrorFilter", e),
+        }
+    }
+}
+
+/// エラーの 1 行の説明 (Display は式とキャレットの複数行)
+fn error_text(e: &JmespathError) -> String {
+    format!("{} (column {})", e.reason, e.column + 1)
+}
+
+/// フィルタの結果のテキスト (文字列・数値はそのまま、配列は 1 行 1 要素、オブジェクトは JSON)
+pub fn value_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(value_text).collect::<Vec<_>>().join("\n"),
+        Value::Object(_) => serde_json::to_string_pretty(value).unwrap_or_default(),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,228 @@
+//! フラットな出力 (ツリーをたどって {page, path, tag, text, href} のレコードの配列に)
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::diff;
+use crate::links::{self, PageLinks};
+use crate::url_result::UrlResult;
+use crate::{DomContent, TableData};
+
+/// 1 レコード
+#[derive(Debug, Serialize)]
+pub struct FlatRecord {
+    /// 何番目の URL (サイトマップ展開後) の結果か
+    pub page: usize,
+    /// ルートからのタグのパス (例: /html[1]/ul[2]/li[1])。番号は同じタグの兄弟の中での順番 (1 始まり)
+    pub path: String,
+    pub tag: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+}
+
+/// 1 ページ分のレコード
+pub fn flatten_page(page: usize, content: &DomContent) -> Vec<FlatRecord> {
+    let mut walker = Walker { page, records: vec![] };
+    walker.contents(std::slice::from_ref(content), "");
+    walker.records
+}
+
+struct Walker {
+    page: usize,
+    records: Vec<FlatRecord>,
+}
+
+impl Walker {
+    /// 兄弟の並びをたどる (タグごとに番号を振る)
+    fn contents(&mut self, contents: &[DomContent], parent: &str) {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for content in contents {
+            // タグのないテキストは親のレコードに含めている
+            if is_bare_text(content) {
+                continue;
+            }
+            // URL ごとの結果は中身をそのまま (エラーは error レコード 1 件)
+            if let DomContent::Result(result) = content {
+                match &**result {
+                    UrlResult::Document { document, .. } => self.contents(std::slice::from_ref(document), parent),
+                    UrlResult::Error { url, error } => {
+                        self.push(&format!("{parent}/error[1]"), "error".to_string(), Some(error.message.clone()), Some(url.clone()))
+                    }
+                }
+                continue;
+            }
+            let tag = tag_of(content);
+            let n = counts.entry(tag.clone()).or_default();
+            *n += 1;
+            let path = format!("{parent}/{tag}[{n}]");
+            self.content(content, tag, &path);
+        }
+    }
+
+    fn content(&mut self, content: &DomContent, tag: String, path: &str) {
+        match content {
+            DomContent::Node(node) => {
+                // 直下のタグのないテキストもまとめてこのノードのテキストに
+                let bare_texts: Vec<&str> = node
+                    .children
+                    .iter()
+                    .filter(|c| is_bare_text(c))
+                    .filter_map(|c| match c {
+                        DomContent::Node(child) => child.text.as_deref(),
+                        _ => None,
+                    })
+                    .collect();
+                let text = match (&node.text, bare_texts.is_empty()) {
+                    (Some(text), _) => Some(text.clone()),
+                    (None, false) => Some(bare_texts.join(" ")),
+                    (None, true) => None,
+                };
+                if text.is_some() || node.href.is_some() {
+                    self.push(path, tag, text, node.href.clone());
+                }
+                self.contents(&node.children, path);
+                if let Some(subpage) = &node.link_subpage {
+                    self.contents(std::slice::from_ref(&**subpage), path);
+                }
+            }
+            DomContent::Table(table) => self.table(table, path),
+            DomContent::Feed(feed) => {
+                self.push(path, tag, feed.feed_title.clone(), feed.feed_link.clone());
+                for (i, item) in feed.items.iter().enumerate() {
+                    let item_path = format!("{path}/item[{}]", i + 1);
+                    self.push(&item_path, "item".to_string(), item.title.clone(), item.link.clone());
+                    if let Some(article) = &item.article {
+                        self.contents(std::slice::from_ref(&**article), &item_path);
+                    }
+                }
+            }
+            DomContent::Image(image) => self.push(path, tag, image.alt.clone(), image.src.clone()),
+            DomContent::Media(media) => {
+                let src = media.src.clone().or_else(|| media.sources.first().map(|s| s.src.clone()));
+                self.push(path, tag, None, src);
+            }
+            DomContent::Code(code) => self.push(path, tag, Some(code.text.clone()), None),
+            DomContent::Form(form) => {
+                self.push(path, tag, form.name.clone(), Some(form.action.clone()));
+                let mut counts: HashMap<&str, usize> = HashMap::new();
+                for field in &form.fields {
+                    let n = counts.entry(&field.tag).or_default();
+                    *n += 1;
+                    let text = field.label.clone().or_else(|| field.name.clone());
+                    self.push(&format!("{path}/{}[{n}]", field.tag), field.tag.clone(), text, None);
+                }
+            }
+            DomContent::Extracted(map) => {
+                for (key, value) in map {
+                    match value {
+                        Value::Array(items) => {
+                            for (i, item) in items.iter().enumerate() {
+                                self.push(&format!("{path}/{key}[{}]", i + 1), key.clone(), scalar_text(item), None);
+                            }
+                        }
+                        _ => self.push(&format!("{path}/{key}[1]"), key.clone(), scalar_text(value), None),
+                    }
+                }
+            }
+            DomContent::Resource(resource) => {
+                self.push(path, tag, resource.data.as_ref().map(Value::to_string), None);
+            }
+            // 配列は 1 要素 1 レコード
+            DomContent::Filtered(Value::Array(items)) => {
+                for (i, item) in items.iter().enumerate() {
+                    self.push(&format!("{path}/item[{}]", i + 1), "item".to_string(), scalar_text(item), None);
+                }
+            }
+            DomContent::Filtered(value) => self.push(path, tag, scalar_text(value), None),
+            // contents で展開済み
+            DomContent::Result(_) => {}
+            DomContent::Links(page) => self.links(page, path),
+            // 変化 1 件 1 レコード (tag は added / removed / changed)
+            DomContent::Diff(diff) => {
+                self.push(path, tag, Some(diff::summary_line(diff)), Some(diff.url.clone()));
+                for (i, change) in diff.changes.iter().enumerate() {
+                    let text = Some(diff::change_text(change));
+                    self.push(&format!("{path}/{}[{}]", change.change, i + 1), change.change.to_string(), text, None);
+                }
+            }
+            // 集計は 1 行、失敗・スキップした URL は 1 件 1 レコード
+            DomContent::Summary(block) => {
+                self.push(path, tag, Some(block.summary.line()), None);
+                for (i, failure) in block.summary.failures.iter().enumerate() {
+                    let text = format!("{}: {}", failure.kind, failure.message);
+                    self.push(&format!("{path}/failure[{}]", i + 1), "failure".to_string(), Some(text), Some(failure.url.clone()));
+                }
+            }
+        }
+    }
+
+    /// リンクグラフは 1 リンク 1 レコード (リンク先のページは subpage[n] の下に)
+    fn links(&mut self, page: &PageLinks, path: &str) {
+        for (i, link) in page.links.iter().enumerate() {
+            // チェック結果はテキストの後ろに [ステータス]
+            let check = link.check.as_ref().map(|c| format!(" [{}]", links::check_text(c))).unwrap_or_default();
+            let text = Some(format!("{}{check}", link.text).trim().to_string()).filter(|t| !t.is_empty());
+            self.push(&format!("{path}/a[{}]", i + 1), "a".to_string(), text, Some(link.href.clone()));
+        }
+        for (i, subpage) in page.subpages.iter().enumerate() {
+            self.links(subpage, &format!("{path}/subpage[{}]", i + 1));
+        }
+    }
+
+    /// テーブルは 1 行 1 レコード (セルは " | " 区切り)
+    fn table(&mut self, table: &TableData, path: &str) {
+        let columns = table.columns();
+        for (i, row) in table.rows.iter().enumerate() {
+            let cells = TableData::row_cells(row, &columns);
+            self.push(&format!("{path}/tr[{}]", i + 1), "tr".to_string(), Some(cells.join(" | ")), None);
+        }
+    }
+
+    fn push(&mut self, path: &str, tag: String, text: Option<String>, href: Option<String>) {
+        self.records.push(FlatRecord {
+            page: self.page,
+            path: path.to_string(),
+            tag,
+            text,
+            href,
+        });
+    }
+}
+
+/// タグも子もないテキストだけのノード
+pub(crate) fn is_bare_text(content: &DomContent) -> bool {
+    matches!(content, DomContent::Node(node) if node.tag.is_none() && node.children.is_empty() && node.link_subpage.is_none())
+}
+
+/// パスに使うタグ名
+pub(crate) fn tag_of(content: &DomContent) -> String {
+    match content {
+        DomContent::Node(node) => node.tag.clone().unwrap_or_else(|| "node".to_string()),
+        DomContent::Table(_) => "table".to_string(),
+        DomContent::Feed(feed) => feed.feed_type.clone(),
+        DomContent::Image(image) => image.tag.to_string(),
+        DomContent::Media(media) => media.tag.to_string(),
+        DomContent::Code(code) => code.tag.to_string(),
+        DomContent::Form(form) => form.tag.to_string(),
+        DomContent::Extracted(_) => "extracted".to_string(),
+        DomContent::Resource(resource) => resource.tag.to_string(),
+        DomContent::Result(_) => "result".to_string(),
+        DomContent::Summary(_) => "summary".to_string(),
+        DomContent::Links(_) => "links".to_string(),
+        DomContent::Diff(_) => "diff".to_string(),
+        DomContent::Filtered(_) => "filtered".to_string(),
+    }
+}
+
+/// 抽出ルールの値をテキストに (null は None)
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
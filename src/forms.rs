@@ -0,0 +1,158 @@
+//! <form> の構造 (action / method / 入力項目・ラベル・選択肢) の抽出
+
+use schemars::JsonSchema;
+use scraper::{ElementRef, Selector};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::clean_text;
+
+/// フォーム
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FormData {
+    /// 常に "form"
+    pub tag: String,
+    /// 送信先 (絶対 URL, 省略時はページの URL)
+    pub action: String,
+    /// "GET" / "POST" など (大文字)
+    pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enctype: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub fields: Vec<FormField>,
+}
+
+/// 入力項目 (input / select / textarea / button)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FormField {
+    pub tag: String,
+    /// input / button の type (省略時は "text" / "submit")
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub input_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// <label for> / 囲んでいる <label> / aria-label
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub required: bool,
+    /// select の選択肢
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<SelectOption>,
+}
+
+/// select の選択肢
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SelectOption {
+    pub value: String,
+    pub label: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub selected: bool,
+}
+
+/// <form> 要素を FormData に
+pub fn parse_form(form_el: ElementRef, page_url: &Url) -> FormData {
+    let e = form_el.value();
+    let attr = |name: &str| e.attr(name).map(str::trim).filter(|v| !v.is_empty()).map(str::to_string);
+    let action = match attr("action") {
+        Some(action) => page_url.join(&action).map(String::from).unwrap_or(action),
+        None => page_url.to_string(),
+    };
+    let sel_fields = Selector::parse("input, select, textarea, button").unwrap();
+    let fields = form_el.select(&sel_fields).map(|field| parse_field(field, form_el)).collect();
+    FormData {
+        tag: "form".to_string(),
+        action,
+        method: attr("method").unwrap_or_else(|| "get".to_string()).to_ascii_uppercase(),
+        enctype: attr("enctype"),
+        id: attr("id"),
+        name: attr("name"),
+        fields,
+    }
+}
+
+fn parse_field(field: ElementRef, form_el: ElementRef) -> FormField {
+    let e = field.value();
+    let attr = |name: &str| e.attr(name).map(str::trim).filter(|v| !v.is_empty()).map(str::to_string);
+    let tag = e.name().to_ascii_lowercase();
+    let input_type = match tag.as_str() {
+        "input" => Some(attr("type").map(|t| t.to_ascii_lowercase()).unwrap_or_else(|| "text".to_string())),
+        "button" => Some(attr("type").map(|t| t.to_ascii_lowercase()).unwrap_or_else(|| "submit".to_string())),
+        _ => None,
+    };
+    let value = match tag.as_str() {
+        "textarea" => Some(field.text().collect::<String>()).filter(|v| !v.is_empty()),
+        "button" => attr("value").or_else(|| Some(clean_text(&field.text().collect::<String>())).filter(|v| !v.is_empty())),
+        _ => attr("value"),
+    };
+    let options = if tag == "select" { parse_options(field) } else { vec![] };
+    FormField {
+        input_type,
+        name: attr("name"),
+        id: attr("id"),
+        value,
+        label: find_label(field, form_el).or_else(|| attr("aria-label")),
+        placeholder: attr("placeholder"),
+        required: e.attr("required").is_some(),
+        options,
+        tag,
+    }
+}
+
+/// select 内の option (optgroup 内も含む)
+fn parse_options(select: ElementRef) -> Vec<SelectOption> {
+    let sel_option = Selector::parse("option").unwrap();
+    select
+        .select(&sel_option)
+        .map(|option| {
+            let label = clean_text(&option.text().collect::<String>());
+            SelectOption {
+                value: option.value().attr("value").map(str::to_string).unwrap_or_else(|| label.clone()),
+                label,
+                selected: option.value().attr("selected").is_some(),
+            }
+        })
+        .collect()
+}
+
+/// <label for="id"> (フォーム内) または項目を囲む <label> のテキスト
+fn find_label(field: ElementRef, form_el: ElementRef) -> Option<String> {
+    if let Some(id) = field.value().attr("id") {
+        let sel_label = Selector::parse("label[for]").unwrap();
+        if let Some(label) = form_el.select(&sel_label).find(|l| l.value().attr("for") == Some(id)) {
+            return label_text(label);
+        }
+    }
+    field
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .take_while(|a| a.id() != form_el.id())
+        .find(|a| a.value().name().eq_ignore_ascii_case("label"))
+        .and_then(label_text)
+}
+
+/// ラベルのテキスト (中の select の選択肢などは除く)
+fn label_text(label: ElementRef) -> Option<String> {
+    let text: String = label
+        .descendants()
+        .filter_map(|node| node.value().as_text().map(|t| (node, t)))
+        .filter(|(node, _)| {
+            !node
+                .ancestors()
+                .take_while(|a| a.id() != label.id())
+                .filter_map(ElementRef::wrap)
+                .any(|a| matches!(a.value().name(), "select" | "textarea" | "button" | "option"))
+        })
+        .map(|(_, t)| &**t)
+        .collect();
+    Some(clean_text(&text)).filter(|t| !t.is_empty())
+}
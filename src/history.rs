@@ -0,0 +1,214 @@
+//! 変換結果の履歴 (SQLite に保存し、一覧・再ダウンロード・削除する)
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use clap::ValueEnum;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::output::OutputFormat;
+use crate::privatefile;
+use crate::UrlForm;
+
+/// 履歴に保存しない項目 (URL は別の列・貼り付けた HTML・秘密情報)
+const EXCLUDED_FIELDS: &[&str] = &["urls", "html", "credentials", "cookies", "headers", "save_template"];
+
+/// 履歴 1 件 (一覧用。結果本体は含まない)
+#[derive(Debug, Serialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    /// 変換が完了した日時 (UTC, ISO 8601)
+    pub created_at: String,
+    pub urls: Vec<String>,
+    /// 変換に使ったフォームの項目
+    pub options: Value,
+    pub output_format: String,
+    /// 結果の文字数
+    pub size: i64,
+}
+
+/// 保存した結果
+pub struct HistoryOutput {
+    pub format: OutputFormat,
+    pub output: String,
+}
+
+/// 履歴の保存先 (パスが空なら保存しない)
+#[derive(Debug)]
+pub struct HistoryStore {
+    conn: Option<Mutex<Connection>>,
+}
+
+impl HistoryStore {
+    /// データベースを開く (無ければ作成)
+    pub fn open(path: &str) -> Result<Self, String> {
+        if path.is_empty() {
+            return Ok(HistoryStore { conn: None });
+        }
+        // 取得した結果を含むので所有者だけが読めるファイルにしてから開く (ジャーナルも同じ権限になる)
+        let file = Path::new(path);
+        if let Some(dir) = file.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            privatefile::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+        }
+        privatefile::open(file, true).map_err(|e| format!("Failed to open {path}: {e}"))?;
+        let conn = Connection::open(path).map_err(|e| format!("Failed to open {path}: {e}"))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+                urls TEXT NOT NULL,
+                options TEXT NOT NULL,
+                output_format TEXT NOT NULL,
+                output TEXT NOT NULL
+            )",
+        )
+        .map_err(|e| format!("Failed to initialize {path}: {e}"))?;
+        Ok(HistoryStore { conn: Some(Mutex::new(conn)) })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.conn.is_some()
+    }
+
+    /// 完了した変換を記録 (無効なら何もしない)
+    pub fn record(&self, urls: &[String], options: &Value, format: OutputFormat, output: &str) -> Result<(), String> {
+        let Some(conn) = &self.conn else {
+            return Ok(());
+        };
+        let urls = serde_json::to_string(urls).map_err(|e| e.to_string())?;
+        conn.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .execute(
+                "INSERT INTO history (urls, options, output_format, output) VALUES (?1, ?2, ?3, ?4)",
+                params![urls, options.to_string(), format_name(format), output],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("Failed to save history: {e}"))
+    }
+
+    /// データベースに問い合わせできるか (無効なら常に Ok)
+    pub fn check(&self) -> Result<(), String> {
+        let Some(conn) = &self.conn else {
+            return Ok(());
+        };
+        conn.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .query_row("SELECT 1", [], |_| Ok(()))
+            .map_err(|e| e.to_string())
+    }
+
+    /// 新しい順の一覧
+    pub fn list(&self) -> Result<Vec<HistoryEntry>, String> {
+        let Some(conn) = &self.conn else {
+            return Ok(vec![]);
+        };
+        let conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, created_at, urls, options, output_format, length(output)
+                 FROM history ORDER BY id DESC",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([], entry_from_row).map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+
+    /// 1 件の項目と保存した結果 (無ければ None)
+    pub fn entry(&self, id: i64) -> Result<Option<(HistoryEntry, HistoryOutput)>, String> {
+        let Some(conn) = &self.conn else {
+            return Ok(None);
+        };
+        conn.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .query_row(
+                "SELECT id, created_at, urls, options, output_format, length(output), output FROM history WHERE id = ?1",
+                [id],
+                |row| {
+                    let entry = entry_from_row(row)?;
+                    let output = HistoryOutput {
+                        format: OutputFormat::from_str(&entry.output_format, true).unwrap_or_default(),
+                        output: row.get(6)?,
+                    };
+                    Ok((entry, output))
+                },
+            )
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+
+    /// 入力 URL に url を含む履歴の id (新しい順)
+    pub fn ids_with_url(&self, url: &str) -> Result<Vec<i64>, String> {
+        let Some(conn) = &self.conn else {
+            return Ok(vec![]);
+        };
+        let conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stmt = conn
+            .prepare(
+                "SELECT id FROM history
+                 WHERE EXISTS (SELECT 1 FROM json_each(history.urls) WHERE value = ?1)
+                 ORDER BY id DESC",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([url], |row| row.get(0)).map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+
+    /// 保存した結果 (無ければ None)
+    pub fn output(&self, id: i64) -> Result<Option<HistoryOutput>, String> {
+        let Some(conn) = &self.conn else {
+            return Ok(None);
+        };
+        conn.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .query_row("SELECT output_format, output FROM history WHERE id = ?1", [id], |row| {
+                let format: String = row.get(0)?;
+                Ok(HistoryOutput {
+                    format: OutputFormat::from_str(&format, true).unwrap_or_default(),
+                    output: row.get(1)?,
+                })
+            })
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+
+    /// 削除 (存在しなければ false)
+    pub fn delete(&self, id: i64) -> Result<bool, String> {
+        let Some(conn) = &self.conn else {
+            return Ok(false);
+        };
+        conn.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .execute("DELETE FROM history WHERE id = ?1", [id])
+            .map(|n| n > 0)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// 履歴に残すフォームの項目 (未指定と秘密情報を除く)
+pub fn form_options(form: &UrlForm) -> Value {
+    let mut value = serde_json::to_value(form).unwrap_or_default();
+    if let Value::Object(fields) = &mut value {
+        fields.retain(|k, v| !EXCLUDED_FIELDS.contains(&k.as_str()) && !v.is_null());
+    }
+    value
+}
+
+/// id, created_at, urls, options, output_format, length(output) の行
+fn entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<HistoryEntry> {
+    let urls: String = row.get(2)?;
+    let options: String = row.get(3)?;
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        created_at: row.get(1)?,
+        urls: serde_json::from_str(&urls).unwrap_or_default(),
+        options: serde_json::from_str(&options).unwrap_or_default(),
+        output_format: row.get(4)?,
+        size: row.get(5)?,
+    })
+}
+
+fn format_name(format: OutputFormat) -> String {
+    format.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default()
+}
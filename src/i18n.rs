@@ -0,0 +1,279 @@
+//! 画面の表示言語 (日本語・英語)
+//!
+//! テンプレートの文言は日本語をそのままキーにして、英語はカタログ (EN) で引く (ないものは日本語のまま)。
+//! 言語は ?lang=ja|en (Cookie に保存) → Cookie → Accept-Language の順に決め、どれもなければ日本語。
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::LazyLock;
+
+use actix_web::cookie::{time::Duration, Cookie, SameSite};
+use actix_web::{http::header, HttpRequest};
+
+/// 言語を保存する Cookie の名前
+const LANG_COOKIE: &str = "lang";
+
+/// 表示言語
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    Ja,
+    En,
+}
+
+impl Lang {
+    /// リクエストの表示言語
+    pub fn from_request(req: &HttpRequest) -> Lang {
+        query_lang(req)
+            .or_else(|| req.cookie(LANG_COOKIE).and_then(|c| Lang::parse(c.value())))
+            .or_else(|| {
+                let accept = req.headers().get(header::ACCEPT_LANGUAGE)?.to_str().ok()?;
+                accept_language(accept)
+            })
+            .unwrap_or(Lang::Ja)
+    }
+
+    /// ?lang= で選んだ言語を保存する Cookie (指定がなければ None)
+    pub fn cookie(req: &HttpRequest) -> Option<Cookie<'static>> {
+        let lang = query_lang(req)?;
+        Some(
+            Cookie::build(LANG_COOKIE, lang.code())
+                .path("/")
+                .max_age(Duration::days(365))
+                .same_site(SameSite::Lax)
+                .finish(),
+        )
+    }
+
+    /// ja / en (<html lang>・Cookie の値)
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::Ja => "ja",
+            Lang::En => "en",
+        }
+    }
+
+    /// 文言 (日本語がキー)
+    pub fn t(self, ja: &str) -> &str {
+        match self {
+            Lang::Ja => ja,
+            Lang::En => EN.get(ja).copied().unwrap_or(ja),
+        }
+    }
+
+    /// 文言の {} を arg で置き換えたもの
+    pub fn tf(self, ja: &str, arg: impl Display) -> String {
+        self.t(ja).replacen("{}", &arg.to_string(), 1)
+    }
+
+    fn parse(code: &str) -> Option<Lang> {
+        match code.trim().to_ascii_lowercase().as_str() {
+            "ja" => Some(Lang::Ja),
+            "en" => Some(Lang::En),
+            _ => None,
+        }
+    }
+}
+
+fn query_lang(req: &HttpRequest) -> Option<Lang> {
+    url::form_urlencoded::parse(req.query_string().as_bytes())
+        .find(|(key, _)| key == "lang")
+        .and_then(|(_, value)| Lang::parse(&value))
+}
+
+/// Accept-Language で最も優先度の高い ja / en ("en-US,en;q=0.9,ja;q=0.8" → En)
+fn accept_language(accept: &str) -> Option<Lang> {
+    let mut best: Option<(f32, Lang)> = None;
+    for item in accept.split(',') {
+        let mut parts = item.split(';');
+        let tag = parts.next().unwrap_or("").trim();
+        let q = parts
+            .find_map(|p| p.trim().strip_prefix("q="))
+            .and_then(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        let Some(lang) = Lang::parse(tag.split('-').next().unwrap_or("")) else {
+            continue;
+        };
+        // 同じ優先度なら先に書かれたもの
+        if q > 0.0 && best.is_none_or(|(best_q, _)| q > best_q) {
+            best = Some((q, lang));
+        }
+    }
+    best.map(|(_, lang)| lang)
+}
+
+/// 英語のカタログ (日本語 → 英語)
+static EN: LazyLock<HashMap<&'static str, &'static str>> = LazyLock::new(|| {
+    HashMap::from([
+        // トップ画面・フォーム
+        ("複数URLを改行区切りで入力", "Enter URLs, one per line"),
+        ("過去の変換結果 (履歴)", "Past results (history)"),
+        (
+            "https://example.com\nhttps://example.org\nhttps://example.net/sitemap.xml (サイトマップは展開)",
+            "https://example.com\nhttps://example.org\nhttps://example.net/sitemap.xml (sitemaps are expanded)",
+        ),
+        ("URL リストのファイル (1 行 1 URL の .txt、または .csv / .tsv)", "URL list file (.txt with one URL per line, or .csv / .tsv)"),
+        ("CSV の列 (見出しか番号)", "CSV column (header or number)"),
+        ("HTML を直接変換 (取得しない)", "Convert HTML directly (no fetching)"),
+        ("HTML のファイル", "HTML file"),
+        ("元の URL (相対リンクの基準、省略可)", "Original URL (base for relative links, optional)"),
+        ("1階層リンク先を含める", "Include linked pages (one level deep)"),
+        ("すべてのリンク", "All links"),
+        ("同じホストのみ", "Same host only"),
+        ("同じドメインのみ", "Same domain only"),
+        ("重複ブロックをそのまま", "Keep repeated blocks"),
+        ("重複ブロックを参照に置き換え", "Replace repeated blocks with references"),
+        ("重複ブロックを削除", "Drop repeated blocks"),
+        ("noindex のリンク先を除く", "Exclude noindex linked pages"),
+        ("nofollow・noindex を無視", "Ignore nofollow / noindex"),
+        ("RSS/Atom の記事ページも変換", "Also convert RSS/Atom item pages"),
+        ("取得情報 (ステータス・最終 URL・サイズ・時間) を含める", "Include fetch info (status, final URL, size, time)"),
+        ("URL ごとに {url, document} / {url, error} で出力", "Output {url, document} / {url, error} per URL"),
+        ("末尾に集計 (成功・失敗・スキップ件数など) を付ける", "Append a summary (succeeded / failed / skipped counts, etc.)"),
+        ("canonical URL が同じページは最初の 1 件だけ", "Keep only the first page per canonical URL"),
+        ("URL からトラッキング用のクエリ (utm_* など) を除く", "Strip tracking parameters (utm_* etc.) from URLs"),
+        ("結果をバケット (S3 / GCS) にアップロード", "Upload results to the bucket (S3 / GCS)"),
+        ("Elasticsearch に投入", "Index into Elasticsearch"),
+        ("しない", "No"),
+        ("ページごと", "Per page"),
+        ("レコードごと", "Per record"),
+        ("完了時の通知先 (Webhook)", "Completion webhook"),
+        ("通知に結果を含める", "Include results in the notification"),
+        ("履歴との差分", "Diff against history"),
+        ("本文のみ (ナビ・広告等を除く)", "Main content only (no navigation, ads, etc.)"),
+        ("画像 (src / alt / srcset) も抽出", "Also extract images (src / alt / srcset)"),
+        ("動画・音声 (src / source / poster / 字幕) も抽出", "Also extract video / audio (src / source / poster / captions)"),
+        ("リンクを絶対 URL に", "Make links absolute"),
+        ("フォームの構造も抽出", "Also extract form structure"),
+        ("ページごとの語数・文字数・見出し・リンク・テーブル数を含める", "Include per-page word, character, heading, link and table counts"),
+        ("リンクグラフ (ページごとの外向きリンクのみ)", "Link graph (outgoing links per page only)"),
+        ("リンク切れチェック (リンク先のステータス・リダイレクト)", "Check for broken links (status and redirects of each link)"),
+        ("出力形式", "Output format"),
+        ("NDJSON (1 行 1 URL, 完了順に送信)", "NDJSON (one URL per line, sent as completed)"),
+        ("テキスト", "Text"),
+        ("テキストの見出しに # を付ける", "Prefix text headings with #"),
+        ("フラットな配列で出力 (path / tag / text / href)", "Output a flat array (path / tag / text / href)"),
+        ("結果画面を作らず直接ダウンロード (大量の URL 向け)", "Download directly without a result page (for many URLs)"),
+        ("残す属性", "Attributes to keep"),
+        ("読み飛ばすタグ", "Tags to skip"),
+        ("出力するタグ", "Tags to output"),
+        ("入れ子の深さの上限", "Max nesting depth"),
+        ("テーブルの見出し", "Table headers"),
+        ("自動 (thead / th の行)", "Auto (thead / th rows)"),
+        ("先頭行", "First row"),
+        ("なし", "None"),
+        ("見出しのない行は配列で", "Rows without headers as arrays"),
+        ("すべての行を配列で", "All rows as arrays"),
+        ("複数の見出し行をつなぐ", "Join multiple header rows"),
+        ("セルの数値・日付などを型付きで", "Type cell numbers, dates, etc."),
+        ("テーブルの行数の上限", "Max rows per table"),
+        (
+            "抽出ルール (指定するとページごとに キー: 値 のオブジェクトを出力)\ntitle: h1.product-name\nprice: .price span\nimage: img.main @src\ntags[]: .tag",
+            "Extraction rules (outputs a key: value object per page)\ntitle: h1.product-name\nprice: .price span\nimage: img.main @src\ntags[]: .tag",
+        ),
+        (
+            "後処理スクリプト (Rhai。doc が URL ごとの結果、最後の式の値が出力)\ndoc.children = doc.children.filter(|n| n.tag != \"ul\");\ndoc",
+            "Post-processing script (Rhai. doc is the result per URL; the last expression is the output)\ndoc.children = doc.children.filter(|n| n.tag != \"ul\");\ndoc",
+        ),
+        ("部分木", "Subtree"),
+        ("/html[1]/table[2] や //h2[1]", "/html[1]/table[2] or //h2[1]"),
+        ("フィルタ (JMESPath)", "Filter (JMESPath)"),
+        ("リンク先URLの対象パターン (正規表現)\n/docs/", "Linked URL include patterns (regex)\n/docs/"),
+        ("リンク先URLの除外パターン (正規表現)\n/logout", "Linked URL exclude patterns (regex)\n/logout"),
+        ("同時取得数", "Concurrency"),
+        ("リンク先の同時取得数", "Linked page concurrency"),
+        ("うち同一ホスト", "per host"),
+        ("リンク先の上限 (1 ページ)", "Linked page limit (per page)"),
+        ("(全体)", "(total)"),
+        ("タイムアウト(秒)", "Timeout (s)"),
+        ("全体の期限(秒)", "Overall deadline (s)"),
+        ("サイトマップの上限件数", "Sitemap URL limit"),
+        ("同一ホストの間隔(ミリ秒)", "Per-host delay (ms)"),
+        ("レスポンスの上限(MB)", "Response size limit (MB)"),
+        ("リダイレクトの上限", "Redirect limit"),
+        ("別のホストへのリダイレクトはたどらない (エラーにする)", "Do not follow redirects to other hosts (error)"),
+        ("ディスクキャッシュを使わない (常に取得し直す)", "Bypass the disk cache (always refetch)"),
+        ("オフライン (保存したページとディスクキャッシュだけで変換する)", "Offline (convert only from saved pages and the disk cache)"),
+        ("取得した HTML を保存する (結果画面から ZIP でダウンロード)", "Save fetched HTML (download as ZIP from the result page)"),
+        ("リクエスト・レスポンスを WARC に記録する (結果画面からダウンロード)", "Record requests and responses as WARC (download from the result page)"),
+        ("Wayback Machine の日時", "Wayback Machine date"),
+        ("YYYY-MM-DD (省略可)", "YYYY-MM-DD (optional)"),
+        ("取得に失敗したら Wayback Machine のスナップショットを使う", "Use a Wayback Machine snapshot when fetching fails"),
+        ("ブラウザで描画 (JavaScript のページ)", "Render in a browser (JavaScript pages)"),
+        ("待ち時間(ミリ秒)", "Wait (ms)"),
+        ("待つ要素", "Wait for element"),
+        ("最大試行回数", "Max attempts"),
+        (
+            "追加ヘッダ (1 行 1 つ)\nAccept-Language: ja\nReferer: https://example.com/",
+            "Extra headers (one per line)\nAccept-Language: en\nReferer: https://example.com/",
+        ),
+        ("Cookie (入力URLのホストに送信)\nsession=abc123; theme=dark", "Cookies (sent to the input URL hosts)\nsession=abc123; theme=dark"),
+        ("認証 (入力URLのホストに送信)", "Credentials (sent to the input URL hosts)"),
+        ("username:password または Bearer トークン", "username:password or a Bearer token"),
+        ("WASM プラグイン", "WASM plugin"),
+        ("(なし)", "(none)"),
+        ("テンプレート", "Template"),
+        ("この設定をテンプレートとして保存", "Save these settings as a template"),
+        ("テンプレート名", "Template name"),
+        ("変換", "Convert"),
+        // 結果画面
+        ("結果", "Results"),
+        ("複数URLを解析し、{} を生成しました。", "Parsed the URLs and generated {}."),
+        ("配列形式の JSON", "a JSON array"),
+        ("プレーンテキスト", "plain text"),
+        ("（1階層リンク先含む）", " (including linked pages one level deep)"),
+        ("総文字数: ", "Total characters: "),
+        ("集計", "Summary"),
+        ("成功 ", "succeeded "),
+        (" / 失敗 ", " / failed "),
+        (" / スキップ ", " / skipped "),
+        ("、受信 ", ", received "),
+        (" バイト、", " bytes, "),
+        (" 秒", " s"),
+        ("投入 ", "indexed "),
+        ("スキップ", "Skipped"),
+        ("失敗", "Failed"),
+        ("アップロード", "Upload"),
+        ("失敗: ", "Failed: "),
+        ("{}をダウンロード", "Download {}"),
+        ("クリップボードにコピーしました。", "Copied to the clipboard."),
+        ("コピー失敗: ", "Copy failed: "),
+        ("コピー", "Copy"),
+        ("テーブル (CSV)", "Tables (CSV)"),
+        ("{} 行", "{} rows"),
+        ("すべてのテーブルを SQLite でダウンロード", "Download all tables as SQLite"),
+        ("SQLite の作成に失敗: ", "Failed to create SQLite: "),
+        ("リンクのエッジリスト (CSV)", "Link edge list (CSV)"),
+        ("取得した HTML", "Fetched HTML"),
+        ("{} 件を ZIP でダウンロード", "Download {} files as ZIP"),
+        ("リクエスト・レスポンスを WARC でダウンロード", "Download requests and responses as WARC"),
+        ("URL ごとの結果", "Results per URL"),
+        ("再度URLを入力", "Enter URLs again"),
+        // 進捗画面
+        ("変換中", "Converting"),
+        ("他の変換の完了を待っています...", "Waiting for other conversions to finish..."),
+        ("待機中...", "Waiting..."),
+        ("待機中", "Queued"),
+        ("取得中", "Fetching"),
+        ("完了", "Done"),
+        ("エラー", "Error"),
+        (" 件完了", " done"),
+        ("リンク先 {} 件完了", "{} subpages done"),
+        ("状態", "Status"),
+        ("一時停止", "Pause"),
+        ("再開", "Resume"),
+        ("一時停止中", "Paused"),
+        ("保存していない項目です。再開するには入力し直してください", "These fields were not saved. Enter them again to resume"),
+        // 履歴
+        ("履歴", "History"),
+        ("URL の入力に戻る", "Back to URL input"),
+        ("履歴は保存しない設定です (設定ファイルの HISTORY_DB)。", "History is disabled (HISTORY_DB in the settings file)."),
+        ("履歴はありません。", "No history yet."),
+        ("削除しますか?", "Delete this entry?"),
+        ("削除失敗: ", "Delete failed: "),
+        ("日時 (UTC)", "Date (UTC)"),
+        ("形式", "Format"),
+        ("文字数", "Characters"),
+        ("ダウンロード", "Download"),
+        ("削除", "Delete"),
+    ])
+});
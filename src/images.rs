@@ -0,0 +1,80 @@
+//! <img> 要素の抽出 (src は絶対 URL に解決)
+
+use schemars::JsonSchema;
+use scraper::node::Element;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::clean_text;
+
+/// 画像ノード
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImageData {
+    /// 常に "img" (通常ノードと区別するため)
+    pub tag: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub src: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub srcset: Vec<SrcsetCandidate>,
+}
+
+/// srcset の候補 1 つ
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SrcsetCandidate {
+    pub url: String,
+    /// "2x" / "640w" など (省略時は None = 1x)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub descriptor: Option<String>,
+}
+
+/// <img> 要素を ImageData に (遅延読み込みの data-src / data-srcset も参照)
+pub fn parse_image(e: &Element, base_url: &Url) -> ImageData {
+    let attr = |name: &str| e.attr(name).map(str::trim).filter(|v| !v.is_empty());
+    let src = attr("src")
+        .filter(|s| !s.starts_with("data:"))
+        .or_else(|| attr("data-src"))
+        .map(|s| resolve(base_url, s));
+    let srcset = attr("srcset")
+        .or_else(|| attr("data-srcset"))
+        .map(|s| parse_srcset(s, base_url))
+        .unwrap_or_default();
+    ImageData {
+        tag: "img".to_string(),
+        src,
+        alt: attr("alt").map(clean_text),
+        width: attr("width").and_then(parse_dimension),
+        height: attr("height").and_then(parse_dimension),
+        srcset,
+    }
+}
+
+/// 相対 URL を絶対 URL に (解決できなければそのまま)
+pub fn resolve(base_url: &Url, href: &str) -> String {
+    base_url.join(href).map(String::from).unwrap_or_else(|_| href.to_string())
+}
+
+/// "100" / "100px" を数値に
+pub fn parse_dimension(value: &str) -> Option<u32> {
+    value.trim_end_matches("px").trim().parse().ok()
+}
+
+/// "a.jpg 1x, b.jpg 2x" / "s.jpg 640w, l.jpg 1280w" を候補に分解
+fn parse_srcset(srcset: &str, base_url: &Url) -> Vec<SrcsetCandidate> {
+    srcset
+        .split(',')
+        .filter_map(|candidate| {
+            let mut parts = candidate.split_ascii_whitespace();
+            let url = parts.next()?;
+            Some(SrcsetCandidate {
+                url: resolve(base_url, url),
+                descriptor: parts.next().map(str::to_string),
+            })
+        })
+        .collect()
+}
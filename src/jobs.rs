@@ -0,0 +1,582 @@
+//! バックグラウンドで実行する変換ジョブ (同時実行数の上限つきキュー) と URL ごとの進捗
+//!
+//! 保存先のディレクトリがあれば、ジョブのフォームと URL (<ID>.json)、終わった URL の結果 (<ID>.ndjson) を書き出し、
+//! 再起動後に一時停止した状態で読み戻す (再開すると終わった URL は取得し直さない)。
+//! 秘密情報と HTML そのものは書き出さず、再開時に入力し直してもらう。
+
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{watch, Semaphore};
+
+use crate::summary::{FailedUrl, JobSummary};
+use crate::{normalize, privatefile, webhook};
+use crate::{DomContent, JobOptions, UrlForm};
+
+/// 保持するジョブ数の上限 (超えたら古い完了済みジョブから捨てる)
+const MAX_JOBS: usize = 50;
+
+/// 状態に保存しない項目 (HTML そのもの・秘密情報。再開時に入力し直す)
+pub const SECRET_FIELDS: &[&str] = &["html", "credentials", "cookies", "headers"];
+
+/// URL ごとの進捗段階
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    Queued,
+    Fetching,
+    Parsed,
+    Error,
+    /// 結果を GET /jobs/{id}/result/{index} で取得できる (入力順に出る)
+    Ready,
+}
+
+/// 進捗イベント 1 件
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressEvent {
+    /// 入力 (サイトマップ展開後) の何番目か
+    pub index: usize,
+    pub url: String,
+    pub stage: Stage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// 取得中の 1階層リンク先のうち終わった件数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subpages: Option<usize>,
+}
+
+/// ジョブ全体の状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    /// 実行待ち (他のジョブの完了待ち)
+    Queued,
+    Running,
+    /// 一時停止中 (再起動で読み戻したジョブも)
+    Paused,
+    Done,
+    /// ジョブ全体の失敗 (URL ごとのエラーは Done の結果に含まれる)
+    Failed,
+}
+
+/// GET /jobs/{id} の JSON
+#[derive(Debug, Serialize)]
+pub struct JobReport {
+    pub id: String,
+    pub status: JobStatus,
+    /// URL 数 (サイトマップ展開後。実行前は 0)
+    pub total: usize,
+    pub parsed: usize,
+    pub errors: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// 完了後の結果の取得先
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_url: Option<String>,
+    /// 完了後の取得した HTML (ZIP) の取得先 (保存の指定時)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub html_url: Option<String>,
+    /// 完了後の WARC の取得先 (書き出しの指定時)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warc_url: Option<String>,
+    /// 完了後の集計 (成功・失敗・スキップの件数など)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<JobSummary>,
+    /// アップロードしたオブジェクトの URL (結果全体、URL ごとの順)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub uploaded: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upload_error: Option<String>,
+}
+
+/// 保持しているジョブの状態ごとの件数 (/healthz 用)
+#[derive(Debug, Default, Serialize)]
+pub struct JobCounts {
+    pub queued: usize,
+    pub running: usize,
+    pub paused: usize,
+    pub done: usize,
+    pub failed: usize,
+}
+
+/// ジョブの結果 (Err はジョブ全体の失敗)
+pub type JobOutcome = Result<Vec<DomContent>, String>;
+
+/// 保存したジョブ (<ID>.json)
+#[derive(Serialize, Deserialize)]
+pub struct SavedJob {
+    #[serde(skip)]
+    pub id: String,
+    pub form: UrlForm,
+    /// 入力 URL (サイトマップ展開前)
+    pub urls: Vec<String>,
+    /// 保存しなかった項目 (SECRET_FIELDS のうち入力されていたもの)
+    #[serde(default)]
+    pub missing: Vec<String>,
+}
+
+/// 再開時に入力し直す項目 (POST /jobs/{id}/resume のフォーム)
+#[derive(Default, Deserialize)]
+pub struct ResumeForm {
+    pub html: Option<String>,
+    pub headers: Option<String>,
+    pub cookies: Option<String>,
+    pub credentials: Option<String>,
+}
+
+/// 秘密情報なので入力された項目名だけ
+impl std::fmt::Debug for ResumeForm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let given: Vec<&str> = SECRET_FIELDS.iter().copied().filter(|name| self.field(name).is_some()).collect();
+        write!(f, "ResumeForm({})", given.join(", "))
+    }
+}
+
+impl ResumeForm {
+    fn field(&self, name: &str) -> Option<&str> {
+        let value = match name {
+            "html" => &self.html,
+            "headers" => &self.headers,
+            "cookies" => &self.cookies,
+            "credentials" => &self.credentials,
+            _ => &None,
+        };
+        value.as_deref().filter(|v| !v.trim().is_empty())
+    }
+
+    /// 保存しなかった項目を saved のフォームに戻す (足りなければその項目名のエラー)
+    pub fn fill(self, saved: &mut SavedJob) -> Result<(), String> {
+        let absent: Vec<&str> = saved.missing.iter().map(String::as_str).filter(|name| self.field(name).is_none()).collect();
+        if !absent.is_empty() {
+            return Err(format!("Enter {} again to resume", absent.join(", ")));
+        }
+        let form = &mut saved.form;
+        for name in &saved.missing {
+            let value = self.field(name).map(str::to_string);
+            match name.as_str() {
+                "html" => form.html = value,
+                "headers" => form.headers = value,
+                "cookies" => form.cookies = value,
+                "credentials" => form.credentials = value,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// フォームには秘密情報も入るので URL の数だけ
+impl std::fmt::Debug for SavedJob {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SavedJob({}, {} urls)", self.id, self.urls.len())
+    }
+}
+
+/// 保存した 1 URL 分の結果 (<ID>.ndjson の 1 行。型付きの結果にする前)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedPage {
+    /// 正規化した入力 URL (再開時はこの URL で結果を対応させる)
+    pub url: String,
+    pub content: DomContent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<FailedUrl>,
+}
+
+/// 1 回の変換
+#[derive(Debug)]
+pub struct Job {
+    pub id: String,
+    /// 結果の出力に使うオプション
+    pub options: JobOptions,
+    events: Mutex<Vec<ProgressEvent>>,
+    /// 実行中に終わった URL の結果 (入力順。完了後は outcome に)
+    pages: Mutex<Vec<DomContent>>,
+    running: AtomicBool,
+    outcome: Mutex<Option<JobOutcome>>,
+    summary: Mutex<Option<JobSummary>>,
+    upload: Mutex<Option<Result<Vec<String>, String>>>,
+    /// イベント追加・完了のたびに増える (購読側の起床用)
+    version: watch::Sender<u64>,
+    paused: watch::Sender<bool>,
+    /// 状態の保存先 (拡張子なしのパス。保存しないときは None)
+    state: Option<PathBuf>,
+    /// 前回までに終わった URL の結果 (正規化した URL ごと。再開時に取り出す)
+    restored: Mutex<HashMap<String, SavedPage>>,
+    /// 読み戻してまだ始めていないジョブのフォームと URL (再開時に取り出して実行する)
+    pending: Mutex<Option<SavedJob>>,
+}
+
+impl Job {
+    fn new(id: String, options: JobOptions, state: Option<PathBuf>, restored: HashMap<String, SavedPage>) -> Self {
+        Job {
+            id,
+            options,
+            events: Mutex::new(Vec::new()),
+            pages: Mutex::new(Vec::new()),
+            running: AtomicBool::new(false),
+            outcome: Mutex::new(None),
+            summary: Mutex::new(None),
+            upload: Mutex::new(None),
+            version: watch::channel(0).0,
+            paused: watch::channel(false).0,
+            state,
+            restored: Mutex::new(restored),
+            pending: Mutex::new(None),
+        }
+    }
+
+    pub fn progress(&self, index: usize, url: &str, stage: Stage, message: Option<String>) {
+        self.push_event(ProgressEvent {
+            index,
+            url: url.to_string(),
+            stage,
+            message,
+            subpages: None,
+        });
+    }
+
+    /// 1階層リンク先を done 件取得し終えた (取得中のまま)
+    pub fn subpages_done(&self, index: usize, url: &str, done: usize) {
+        self.push_event(ProgressEvent {
+            index,
+            url: url.to_string(),
+            stage: Stage::Fetching,
+            message: None,
+            subpages: Some(done),
+        });
+    }
+
+    fn push_event(&self, event: ProgressEvent) {
+        self.events.lock().unwrap().push(event);
+        self.version.send_modify(|v| *v += 1);
+    }
+
+    /// 終わった URL の結果を入力順に追加 (完了前の URL ごとのダウンロード用)
+    pub fn add_page(&self, url: &str, content: DomContent) {
+        let index = {
+            let mut pages = self.pages.lock().unwrap();
+            pages.push(content);
+            pages.len() - 1
+        };
+        self.progress(index, url, Stage::Ready, None);
+    }
+
+    /// index 番目の URL の結果を f に渡す (まだ終わっていなければ None)
+    pub fn with_page<R>(&self, index: usize, f: impl FnOnce(&DomContent) -> R) -> Option<R> {
+        if let Some(Ok(results)) = &*self.outcome.lock().unwrap() {
+            return results.get(index).map(f);
+        }
+        self.pages.lock().unwrap().get(index).map(f)
+    }
+
+    /// 入力 URL (サイトマップ展開後、入力順)
+    pub fn urls(&self) -> Vec<String> {
+        let events = self.events.lock().unwrap();
+        events.iter().filter(|e| e.stage == Stage::Queued).map(|e| e.url.clone()).collect()
+    }
+
+    /// 読み戻してまだ始めていないジョブで入力し直す項目
+    pub fn missing(&self) -> Vec<String> {
+        self.pending.lock().unwrap().as_ref().map(|saved| saved.missing.clone()).unwrap_or_default()
+    }
+
+    /// 取り出したフォームと URL を戻す (入力し直す項目が足りず再開できなかったとき)
+    pub fn set_pending(&self, saved: SavedJob) {
+        *self.pending.lock().unwrap() = Some(saved);
+    }
+
+    /// 読み戻したジョブのフォームと URL (まだ実行していなければ。取り出すのは 1 回だけ)
+    pub fn take_pending(&self) -> Option<SavedJob> {
+        self.pending.lock().unwrap().take()
+    }
+
+    /// 前回までに終わった URL の結果 (サイトマップが変わって順番がずれても URL で対応させる。取り出すのは 1 回だけ)
+    pub fn take_restored(&self, url: &str) -> Option<SavedPage> {
+        self.restored.lock().unwrap().remove(&page_key(url))
+    }
+
+    /// URL の結果を保存 (保存先がなければ何もしない。失敗しても変換は続ける)
+    pub fn save_page(&self, url: &str, content: &DomContent, failure: Option<&FailedUrl>) {
+        let Some(state) = &self.state else {
+            return;
+        };
+        let line = serde_json::json!({ "url": page_key(url), "content": content, "failure": failure });
+        let written = privatefile::open(&state.with_extension("ndjson"), true).and_then(|mut file| writeln!(file, "{line}"));
+        if let Err(e) = written {
+            tracing::warn!(job = %self.id, error = %e, "failed to save job state");
+        }
+    }
+
+    /// 一時停止 (取得中の URL は終わるまで続け、次の URL から止める。終わったジョブは false)
+    pub fn pause(&self) -> bool {
+        if self.is_done() {
+            return false;
+        }
+        self.paused.send_replace(true);
+        self.version.send_modify(|v| *v += 1);
+        true
+    }
+
+    /// 再開 (終わったジョブは false)
+    pub fn resume(&self) -> bool {
+        if self.is_done() {
+            return false;
+        }
+        self.paused.send_replace(false);
+        self.version.send_modify(|v| *v += 1);
+        true
+    }
+
+    /// 一時停止中なら再開されるまで待つ
+    pub async fn wait_resumed(&self) {
+        let mut rx = self.paused.subscribe();
+        let _ = rx.wait_for(|paused| !paused).await;
+    }
+
+    /// index 番目の入力 URL
+    pub fn url(&self, index: usize) -> Option<String> {
+        self.events.lock().unwrap().iter().find(|e| e.index == index).map(|e| e.url.clone())
+    }
+
+    /// 全 URL の変換が終わったときの集計
+    pub fn set_summary(&self, summary: JobSummary) {
+        *self.summary.lock().unwrap() = Some(summary);
+    }
+
+    pub fn summary(&self) -> Option<JobSummary> {
+        self.summary.lock().unwrap().clone()
+    }
+
+    /// アップロードの結果 (オブジェクトの URL かエラー)
+    pub fn set_upload(&self, upload: Result<Vec<String>, String>) {
+        *self.upload.lock().unwrap() = Some(upload);
+    }
+
+    pub fn finish(&self, outcome: JobOutcome) {
+        *self.outcome.lock().unwrap() = Some(outcome);
+        self.pages.lock().unwrap().clear();
+        // 終わったジョブは再開しない (結果は履歴に)
+        if let Some(state) = &self.state {
+            let _ = std::fs::remove_file(state.with_extension("json"));
+            let _ = std::fs::remove_file(state.with_extension("ndjson"));
+        }
+        self.version.send_modify(|v| *v += 1);
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.outcome.lock().unwrap().is_some()
+    }
+
+    pub fn status(&self) -> JobStatus {
+        match &*self.outcome.lock().unwrap() {
+            Some(Ok(_)) => JobStatus::Done,
+            Some(Err(_)) => JobStatus::Failed,
+            None if *self.paused.borrow() => JobStatus::Paused,
+            None if self.running.load(Ordering::Relaxed) => JobStatus::Running,
+            None => JobStatus::Queued,
+        }
+    }
+
+    /// 状態と URL ごとの進捗の集計
+    pub fn report(&self) -> JobReport {
+        let status = self.status();
+        let events = self.events.lock().unwrap();
+        let count = |stage: Stage| events.iter().filter(|e| e.stage == stage).count();
+        let upload = self.upload.lock().unwrap().clone();
+        JobReport {
+            id: self.id.clone(),
+            status,
+            total: count(Stage::Queued),
+            parsed: count(Stage::Parsed),
+            errors: count(Stage::Error),
+            error: self.with_outcome(|o| o.as_ref().err().cloned()).flatten(),
+            result_url: (status == JobStatus::Done).then(|| format!("/jobs/{}/result", self.id)),
+            html_url: (status == JobStatus::Done && self.options.html_archive.is_some()).then(|| format!("/jobs/{}/html", self.id)),
+            warc_url: (status == JobStatus::Done && self.options.warc.is_some()).then(|| format!("/jobs/{}/warc", self.id)),
+            summary: self.summary(),
+            uploaded: upload.clone().and_then(Result::ok).unwrap_or_default(),
+            upload_error: upload.and_then(Result::err),
+        }
+    }
+
+    /// from 件目以降のイベント
+    pub fn events_since(&self, from: usize) -> Vec<ProgressEvent> {
+        self.events.lock().unwrap().get(from..).map(<[_]>::to_vec).unwrap_or_default()
+    }
+
+    /// 完了していれば結果を f に渡す
+    pub fn with_outcome<R>(&self, f: impl FnOnce(&JobOutcome) -> R) -> Option<R> {
+        self.outcome.lock().unwrap().as_ref().map(f)
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.version.subscribe()
+    }
+}
+
+/// サーバー内のジョブ一覧
+#[derive(Debug)]
+pub struct JobStore {
+    jobs: Mutex<VecDeque<Arc<Job>>>,
+    counter: AtomicU64,
+    /// 同時に実行するジョブ数の枠
+    slots: Arc<Semaphore>,
+    /// ジョブの状態の保存先 (保存しないときは None)
+    dir: Option<PathBuf>,
+}
+
+impl JobStore {
+    /// max_running: 同時に実行するジョブ数 (0 で無制限)、dir: ジョブの状態の保存先 (空なら保存しない)
+    pub fn new(max_running: usize, dir: &str) -> Self {
+        let permits = if max_running == 0 { Semaphore::MAX_PERMITS } else { max_running };
+        JobStore {
+            jobs: Mutex::new(VecDeque::new()),
+            counter: AtomicU64::new(0),
+            slots: Arc::new(Semaphore::new(permits)),
+            dir: (!dir.is_empty()).then(|| PathBuf::from(dir)),
+        }
+    }
+
+    /// 新しいジョブを登録 (保存先があればフォームと URL を保存)
+    pub fn create(&self, options: JobOptions, form: &UrlForm, urls: &[String]) -> Arc<Job> {
+        let id = self.next_id();
+        let state = self.dir.as_ref().and_then(|dir| match save_job(dir, &id, form, urls) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                tracing::warn!(job = %id, error = %e, "failed to save job state");
+                None
+            }
+        });
+        self.insert(Job::new(id, options, state, HashMap::new()))
+    }
+
+    /// 保存したジョブを一時停止した状態で登録 (終わった URL の結果も読み戻す。実行は再開時に)
+    pub fn restore(&self, saved: SavedJob, options: JobOptions) -> Arc<Job> {
+        let state = self.dir.as_ref().map(|dir| dir.join(&saved.id));
+        let pages = state.as_ref().map(|state| load_pages(&state.with_extension("ndjson"))).unwrap_or_default();
+        let job = Job::new(saved.id.clone(), options, state, pages);
+        job.paused.send_replace(true);
+        *job.pending.lock().unwrap() = Some(saved);
+        self.insert(job)
+    }
+
+    /// 保存されている (終わっていない) ジョブ
+    pub fn saved(&self) -> Vec<SavedJob> {
+        let Some(entries) = self.dir.as_ref().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+            return vec![];
+        };
+        entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| {
+                let mut saved: SavedJob = serde_json::from_str(&std::fs::read_to_string(&path).ok()?).ok()?;
+                saved.id = path.file_stem()?.to_str()?.to_string();
+                Some(saved)
+            })
+            .collect()
+    }
+
+    /// 一時停止中のジョブの ID
+    pub fn paused(&self) -> Vec<String> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.iter().filter(|j| j.status() == JobStatus::Paused).map(|j| j.id.clone()).collect()
+    }
+
+    fn insert(&self, job: Job) -> Arc<Job> {
+        let job = Arc::new(job);
+        let mut jobs = self.jobs.lock().unwrap();
+        while jobs.len() >= MAX_JOBS {
+            match jobs.iter().position(|j| j.is_done()) {
+                Some(i) => jobs.remove(i),
+                None => break,
+            };
+        }
+        jobs.push_back(job.clone());
+        job
+    }
+
+    /// 枠が空くのを待ってから work を実行し、結果をジョブに記録する (通知先があれば通知。LocalSet 上で呼ぶ)
+    pub fn spawn(&self, job: Arc<Job>, work: impl Future<Output = JobOutcome> + 'static) {
+        let slots = self.slots.clone();
+        tokio::task::spawn_local(async move {
+            // 順番待ちの間に一時停止したジョブは再開されるまで枠を使わない
+            job.wait_resumed().await;
+            // Semaphore は閉じないので acquire は失敗しない
+            let permit = slots.acquire_owned().await;
+            job.running.store(true, Ordering::Relaxed);
+            job.version.send_modify(|v| *v += 1);
+            job.finish(work.await);
+            // 通知は枠を空けてから
+            drop(permit);
+            if let Some(webhook) = &job.options.webhook {
+                webhook::notify(webhook, &job).await;
+            }
+        });
+    }
+
+    pub fn counts(&self) -> JobCounts {
+        let mut counts = JobCounts::default();
+        for job in self.jobs.lock().unwrap().iter() {
+            match job.status() {
+                JobStatus::Queued => counts.queued += 1,
+                JobStatus::Running => counts.running += 1,
+                JobStatus::Paused => counts.paused += 1,
+                JobStatus::Done => counts.done += 1,
+                JobStatus::Failed => counts.failed += 1,
+            }
+        }
+        counts
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<Job>> {
+        self.jobs.lock().unwrap().iter().find(|j| j.id == id).cloned()
+    }
+
+    /// 推測しにくい ID (連番をランダムなキーでハッシュ)
+    fn next_id(&self) -> String {
+        let n = self.counter.fetch_add(1, Ordering::Relaxed);
+        format!("{:016x}", RandomState::new().hash_one(n))
+    }
+}
+
+/// フォーム (SECRET_FIELDS を除く) と URL を <dir>/<id>.json に書き出し、拡張子なしのパスを返す
+fn save_job(dir: &Path, id: &str, form: &UrlForm, urls: &[String]) -> Result<PathBuf, String> {
+    privatefile::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    let serde_json::Value::Object(mut fields) = serde_json::to_value(form).map_err(|e| e.to_string())? else {
+        return Err("Form must be an object".to_string());
+    };
+    let missing: Vec<&str> = SECRET_FIELDS
+        .iter()
+        .copied()
+        .filter(|name| fields.remove(*name).is_some_and(|v| v.as_str().is_some_and(|s| !s.trim().is_empty())))
+        .collect();
+    let path = dir.join(id);
+    let file = path.with_extension("json");
+    let json = serde_json::json!({ "form": fields, "urls": urls, "missing": missing });
+    privatefile::write(&file, json.to_string().as_bytes()).map_err(|e| format!("Failed to write {}: {e}", file.display()))?;
+    Ok(path)
+}
+
+
+/// 保存した結果を URL ごとに読む (書きかけの行があればそこまで。同じ URL は先の行)
+fn load_pages(path: &Path) -> HashMap<String, SavedPage> {
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    let mut pages = HashMap::new();
+    for page in text.lines().map_while(|line| serde_json::from_str::<SavedPage>(line).ok()) {
+        pages.entry(page.url.clone()).or_insert(page);
+    }
+    pages
+}
+
+/// 結果を対応させる URL のキー (正規化できなければそのまま)
+fn page_key(url: &str) -> String {
+    normalize::normalize_str(url, false).unwrap_or_else(|| url.to_string())
+}
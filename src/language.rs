@@ -0,0 +1,57 @@
+//! ページの言語 (<html lang>、なければ抽出したテキストから推定)
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// whatlang の ISO 639-3 → ISO 639-1 (lang 属性と揃える)
+const ISO_639_1: &[(&str, &str)] = &[
+    ("afr", "af"), ("aka", "ak"), ("amh", "am"), ("ara", "ar"), ("aze", "az"), ("bel", "be"), ("ben", "bn"), ("bul", "bg"),
+    ("cat", "ca"), ("ces", "cs"), ("cmn", "zh"), ("cym", "cy"), ("dan", "da"), ("deu", "de"), ("ell", "el"), ("eng", "en"),
+    ("epo", "eo"), ("est", "et"), ("fin", "fi"), ("fra", "fr"), ("guj", "gu"), ("heb", "he"), ("hin", "hi"), ("hrv", "hr"),
+    ("hun", "hu"), ("hye", "hy"), ("ind", "id"), ("ita", "it"), ("jav", "jv"), ("jpn", "ja"), ("kan", "kn"), ("kat", "ka"),
+    ("khm", "km"), ("kor", "ko"), ("lat", "la"), ("lav", "lv"), ("lit", "lt"), ("mal", "ml"), ("mar", "mr"), ("mkd", "mk"),
+    ("mya", "my"), ("nep", "ne"), ("nld", "nl"), ("nob", "nb"), ("ori", "or"), ("pan", "pa"), ("pes", "fa"), ("pol", "pl"),
+    ("por", "pt"), ("ron", "ro"), ("rus", "ru"), ("sin", "si"), ("slk", "sk"), ("slv", "sl"), ("sna", "sn"), ("spa", "es"),
+    ("srp", "sr"), ("swe", "sv"), ("tam", "ta"), ("tel", "te"), ("tgl", "tl"), ("tha", "th"), ("tuk", "tk"), ("tur", "tr"),
+    ("ukr", "uk"), ("urd", "ur"), ("uzb", "uz"), ("vie", "vi"), ("yid", "yi"), ("zul", "zu"),
+];
+
+/// ページの言語
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PageLanguage {
+    /// ISO 639-1 (en / ja など。lang 属性は地域などを除いた先頭部分を小文字で)
+    pub code: String,
+    /// html (lang 属性) / detected (テキストから推定)
+    pub source: String,
+    /// lang 属性の値そのまま (en-US など)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// 推定の確からしさ (0〜1)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+}
+
+/// lang 属性から (空なら None)
+pub fn from_lang_attr(lang: &str) -> Option<PageLanguage> {
+    let tag = lang.trim();
+    let code = tag.split(['-', '_']).next().unwrap_or("").to_ascii_lowercase();
+    (!code.is_empty()).then(|| PageLanguage {
+        code,
+        source: "html".to_string(),
+        tag: Some(tag.to_string()),
+        confidence: None,
+    })
+}
+
+/// テキストから推定 (短すぎる・確信が持てない場合は None)
+pub fn detect(text: &str) -> Option<PageLanguage> {
+    let info = whatlang::detect(text).filter(whatlang::Info::is_reliable)?;
+    let code3 = info.lang().code();
+    let code = ISO_639_1.iter().find(|(three, _)| *three == code3).map_or(code3, |(_, two)| *two);
+    Some(PageLanguage {
+        code: code.to_string(),
+        source: "detected".to_string(),
+        tag: None,
+        confidence: Some((info.confidence() * 100.0).round() / 100.0),
+    })
+}
@@ -0,0 +1,2791 @@
+//! HTML ページを JSON に変換するライブラリ (取得・解析・出力)
+//!
+//! Web サーバーと CLI は既定の feature "server" のバイナリ。ライブラリだけ使う場合は default-features = false
+
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+use regex::Regex;
+use tokio::task::{spawn_blocking};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures_util::stream::{self, StreamExt};
+use tracing::Instrument;
+use url::Url;
+use scraper::{Html, Selector, ElementRef};
+use scraper::node::Node;
+use ego_tree::iter::Children;
+use clap::ValueEnum;
+
+pub mod archive;
+pub mod cache;
+mod celltypes;
+pub mod charset;
+pub mod code;
+pub mod columnar;
+pub mod dedup;
+pub mod diff;
+pub mod diskcache;
+pub mod feed;
+pub mod fetch;
+pub mod filter;
+pub mod flat;
+pub mod forms;
+pub mod history;
+pub mod images;
+pub mod jobs;
+pub mod language;
+pub mod links;
+pub mod localfile;
+pub mod markdown;
+pub mod media;
+pub mod meta;
+pub mod metrics;
+pub mod normalize;
+pub mod output;
+mod pdf;
+pub mod plaintext;
+pub mod plugin;
+pub mod privatefile;
+mod readability;
+pub mod render;
+pub mod resource;
+pub mod rules;
+pub mod schema;
+pub mod script;
+pub mod search;
+mod sitemap;
+pub mod stats;
+pub mod storage;
+pub mod structured;
+pub mod subtree;
+pub mod summary;
+pub mod tables;
+pub mod url_result;
+pub mod warc;
+pub mod wayback;
+pub mod webhook;
+mod xml;
+mod xpath;
+
+use cache::ResponseCache;
+use fetch::{parse_header_lines, Credentials, Fetcher};
+use history::HistoryStore;
+use output::OutputFormat;
+use reqwest::header::HeaderMap;
+
+/// 複数URLを改行区切りで受け取るフォーム (API の JSON ボディ・テンプレートも同じ形)
+#[derive(Serialize, Deserialize)]
+pub struct UrlForm {
+    #[serde(default)]
+    pub urls: String,
+    /// 取得せずに変換する HTML (貼り付け・アップロード)
+    pub html: Option<String>,
+    /// html の URL (相対リンクの基準。空なら about:blank)
+    pub base_url: Option<String>,
+    pub include_subpages: Option<String>,
+    pub concurrency: Option<String>,
+    /// 1階層リンク先の同時取得数
+    pub subpage_concurrency: Option<String>,
+    /// 1階層リンク先の同一ホストへの同時取得数 (0 で無制限)
+    pub subpage_per_host: Option<String>,
+    /// 1 ページから取得するリンク先の数の上限 (0 で無制限)
+    pub max_subpages: Option<String>,
+    /// 1 回の変換全体で取得するリンク先の数の上限 (0 で無制限)
+    pub max_subpages_per_job: Option<String>,
+    pub timeout: Option<String>,
+    pub deadline: Option<String>,
+    pub max_attempts: Option<String>,
+    pub user_agent: Option<String>,
+    /// 追加ヘッダ ("Name: value" を改行区切り)
+    pub headers: Option<String>,
+    /// Cookie ("name=value; name2=value2" 形式)
+    pub cookies: Option<String>,
+    /// 認証情報 ("username:password" または Bearer トークン)
+    pub credentials: Option<String>,
+    /// 同一ホストへのリクエスト間隔 (ミリ秒)
+    pub host_delay_ms: Option<String>,
+    /// レスポンス本文の上限 (MB, 0 で無制限)
+    pub max_response_mb: Option<String>,
+    /// たどるリダイレクトの数の上限 (0 でたどらない)
+    pub max_redirects: Option<String>,
+    /// 別のホストへのリダイレクトをたどらない (エラーにする)
+    pub no_cross_host_redirects: Option<String>,
+    /// ディスクキャッシュを使わない (常に取得し直す)
+    pub no_cache: Option<String>,
+    /// ネットワークに出ず、保存したページ (REPLAY_DIR) とディスクキャッシュだけで変換する
+    pub offline: Option<String>,
+    /// 取得した HTML を保存する (ジョブの /jobs/{id}/html・テーブルの ZIP で返す)
+    pub save_html: Option<String>,
+    /// リクエスト・レスポンスを WARC に書き出す (ジョブの /jobs/{id}/warc で返す)
+    pub warc: Option<String>,
+    /// この日時に最も近い Wayback Machine のスナップショットを変換する (YYYY-MM-DD / YYYYMMDDhhmmss)
+    pub wayback_date: Option<String>,
+    /// ライブの取得に失敗したら Wayback Machine のスナップショットを変換する
+    pub wayback_fallback: Option<String>,
+    /// ヘッドレスブラウザで描画してから変換する
+    pub render_js: Option<String>,
+    /// 描画でネットワークが落ち着くまで待つ最大時間 (ミリ秒)
+    pub render_wait_ms: Option<String>,
+    /// 描画でこの要素が現れるまで待つ (CSS セレクタ)
+    pub wait_selector: Option<String>,
+    /// 1階層リンク先の範囲 (all / same-host / same-domain)
+    pub subpage_scope: Option<String>,
+    /// 1階層リンク先の重複ブロックの扱い (off / reference / drop)
+    pub dedup_subpages: Option<String>,
+    /// meta robots の nofollow・rel="nofollow" を無視してリンクをたどる (noindex のリンク先も含める)
+    pub ignore_robots: Option<String>,
+    /// meta robots が noindex の 1階層リンク先を出力しない
+    pub skip_noindex: Option<String>,
+    /// 1階層リンク先の URL が一致すべき正規表現 (改行区切り)
+    pub include_patterns: Option<String>,
+    /// 1階層リンク先の URL が一致してはならない正規表現 (改行区切り)
+    pub exclude_patterns: Option<String>,
+    /// サイトマップから展開する URL 数の上限
+    pub sitemap_max_urls: Option<String>,
+    /// フィードの各記事のリンク先も変換する
+    pub follow_feed_items: Option<String>,
+    /// URL ごとの取得の情報 (最終 URL・ステータス・サイズ・所要時間・日時) を含める
+    pub include_fetch_info: Option<String>,
+    /// URL ごとに {url, document} / {url, error} で出力
+    pub typed_results: Option<String>,
+    /// 末尾に集計 (成功・失敗・スキップの件数、受信バイト数、所要時間) を付ける
+    pub include_summary: Option<String>,
+    /// canonical URL が同じページは最初の 1 件だけ変換結果を残す
+    pub dedup_canonical: Option<String>,
+    /// URL からトラッキング用のクエリ (utm_* など) を除く
+    pub strip_tracking_params: Option<String>,
+    /// 履歴の結果との差分を出力 (履歴の id または latest)
+    pub diff_against: Option<String>,
+    /// ジョブ完了時に POST で通知する URL
+    pub callback_url: Option<String>,
+    /// 通知に結果そのものを含める (なければ状態と結果の URL)
+    pub callback_include_result: Option<String>,
+    /// 結果を設定ファイルのバケット (S3 / GCS) にアップロードする
+    pub upload: Option<String>,
+    /// Elasticsearch / OpenSearch に投入する単位 (pages / records)
+    pub search_index: Option<String>,
+    /// 本文のみ抽出する
+    pub main_content_only: Option<String>,
+    /// 画像 (<img>) も抽出する
+    pub include_images: Option<String>,
+    /// 動画・音声 (<video> / <audio>) も抽出する
+    pub include_media: Option<String>,
+    /// href を絶対 URL にする
+    pub absolute_hrefs: Option<String>,
+    /// フォーム (<form>) の構造も抽出する
+    pub include_forms: Option<String>,
+    /// ページごとの語数・文字数・見出し・リンク・テーブル数を含める
+    pub include_stats: Option<String>,
+    /// DOM ツリーの代わりにページごとの外向きリンクを出力
+    pub link_graph: Option<String>,
+    /// リンクグラフの各リンク先のステータス・リダイレクトを確かめる
+    pub check_links: Option<String>,
+    /// ノードに残す属性名 (カンマ区切り, "data-*" で前方一致)
+    pub capture_attributes: Option<String>,
+    /// 読み飛ばすタグ (カンマ区切り)
+    pub skip_tags: Option<String>,
+    /// ノードとして出力するタグ (カンマ区切り)
+    pub target_tags: Option<String>,
+    /// ノードの入れ子の深さの上限 (0 で無制限)
+    pub max_depth: Option<String>,
+    /// 抽出ルール ("キー: CSS セレクタ" を改行区切り)
+    pub extract_rules: Option<String>,
+    /// テーブルの見出し行の判定 (auto / first-row / none)
+    pub table_headers: Option<String>,
+    /// 見出しのないテーブルの行を配列で出力する
+    pub headerless_arrays: Option<String>,
+    /// テーブルのすべての行を配列で出力する
+    pub array_rows: Option<String>,
+    /// 複数の見出し行を複合名にする
+    pub join_header_rows: Option<String>,
+    /// テーブルのセルの型を推定する
+    pub infer_cell_types: Option<String>,
+    /// テーブルごとの行数の上限 (0 で無制限)
+    pub max_table_rows: Option<String>,
+    /// 出力形式 (json / markdown / ndjson / yaml / text, API では未指定なら Accept ヘッダで選択)
+    pub output_format: Option<String>,
+    /// テキスト出力で見出しに "#" を付ける
+    pub text_heading_markers: Option<String>,
+    /// 結果画面を作らず、完了した URL から順に書き出す
+    pub stream: Option<String>,
+    /// ツリーの代わりにフラットなレコードの配列で出力
+    pub flatten: Option<String>,
+    /// 結果のうち出力する部分木のパス (/html[1]/table[2] や JSON Pointer)
+    pub select: Option<String>,
+    /// 結果を置き換える WASM プラグインの名前 (プラグインのディレクトリの <名前>.wasm)
+    pub plugin: Option<String>,
+    /// URL ごとの結果を出力前に書き換える Rhai スクリプト
+    pub script: Option<String>,
+    /// URL ごとの結果に適用する JMESPath 式 (一致した部分だけを出力)
+    pub filter: Option<String>,
+    /// 適用する保存済みテンプレート名 (テンプレートの項目が優先)
+    pub template: Option<String>,
+    /// この名前でテンプレートとして保存する (フォームのみ)
+    pub save_template: Option<String>,
+}
+
+/// 設定ファイル名 (KEY=VALUE 形式)
+const SETTINGS_FILE: &str = "web-to-json-settings.txt";
+
+/// 同時取得数の上限
+pub const MAX_CONCURRENCY_LIMIT: usize = 64;
+
+/// 取得の最大試行回数の上限
+pub const MAX_ATTEMPTS_LIMIT: u32 = 10;
+
+/// フォーム・API から指定できる描画の待ち時間の上限 (ミリ秒)
+pub const MAX_RENDER_WAIT_MS: u64 = 60_000;
+
+/// 既定の Wayback Machine のベース URL
+pub const DEFAULT_WAYBACK_URL: &str = "https://web.archive.org";
+
+/// 既定のスキップ対象タグ (中身ごと読み飛ばす)
+pub const DEFAULT_SKIP_TAGS: &[&str] = &["script", "style", "meta", "link", "noscript", "svg", "iframe", "nav", "footer", "header"];
+
+/// 既定のパース対象タグ (ノードとして出力する)
+pub const DEFAULT_TARGET_TAGS: &[&str] = &[
+    "h1", "h2", "h3", "h4", "h5", "h6", "p", "ul", "ol", "li", "a", "blockquote", "dl", "dt", "dd", "figure", "figcaption",
+];
+
+/// ログの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum LogFormat {
+    /// 人が読むための 1 行テキスト
+    #[default]
+    Text,
+    /// 1 行 1 JSON (ログ収集基盤向け)
+    Json,
+}
+
+/// アプリ全体の設定
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// フォーム未指定時の同時取得数
+    pub max_concurrency: usize,
+    /// 1 リクエストのタイムアウト秒 (0 で無制限)
+    pub request_timeout_secs: u64,
+    /// ジョブ全体の期限秒 (0 で無制限)
+    pub job_deadline_secs: u64,
+    /// 取得の最大試行回数 (1 でリトライなし)
+    pub retry_max_attempts: u32,
+    /// リトライ初回の待ち時間 (ミリ秒, 以降倍々)
+    pub retry_backoff_ms: u64,
+    /// 既定の User-Agent (空なら reqwest の既定)
+    pub user_agent: String,
+    /// 同一ホストへのリクエスト間隔 (ミリ秒, 0 で間隔なし)
+    pub host_delay_ms: u64,
+    /// レスポンス本文の上限 (MB, 0 で無制限)
+    pub max_response_mb: u64,
+    /// たどるリダイレクトの数の上限 (0 でたどらない)
+    pub max_redirects: usize,
+    /// 別のホストへのリダイレクトをたどる
+    pub cross_host_redirects: bool,
+    /// ETag / Last-Modified 付きレスポンスを覚えておく件数 (0 で無効)
+    pub response_cache_entries: usize,
+    /// 取得した本文を保存するディレクトリ (空ならディスクキャッシュなし)
+    pub cache_dir: String,
+    /// ディスクキャッシュの有効期間 (秒, 0 で期限なし)
+    pub cache_ttl_secs: u64,
+    /// オフライン再生用に保存したページのディレクトリ (空なら使わない)
+    pub replay_dir: String,
+    /// Wayback Machine のベース URL
+    pub wayback_url: Url,
+    /// 1階層リンク先の既定の範囲
+    pub subpage_scope: SubpageScope,
+    /// 1階層リンク先の同時取得数 (ジョブ全体)
+    pub subpage_concurrency: usize,
+    /// 1階層リンク先の同一ホストへの同時取得数 (0 で無制限)
+    pub subpage_per_host: usize,
+    /// 1 ページから取得するリンク先の数 (異なる URL の数。0 で無制限)
+    pub max_subpages: usize,
+    /// 1 回の変換全体で取得するリンク先の数 (0 で無制限)
+    pub max_subpages_per_job: usize,
+    /// サイトマップから展開する URL 数の上限 (0 で無制限)
+    pub sitemap_max_urls: usize,
+    /// ノードに残す属性名の既定値
+    pub capture_attributes: Vec<String>,
+    /// ヘッドレスブラウザの実行ファイル (空なら chromium / google-chrome などを PATH から探す)
+    pub browser_path: String,
+    /// ブラウザに --no-sandbox を付ける
+    pub browser_no_sandbox: bool,
+    /// 描画でネットワークが落ち着くまで待つ最大時間の既定値 (ミリ秒)
+    pub render_wait_ms: u64,
+    /// 中身ごと読み飛ばすタグの既定値
+    pub skip_tags: Vec<String>,
+    /// ノードとして出力するタグの既定値
+    pub target_tags: Vec<String>,
+    /// ノードの入れ子の深さの上限の既定値 (0 で無制限)
+    pub max_depth: usize,
+    /// テーブルごとの行数の上限の既定値 (0 で無制限)
+    pub max_table_rows: usize,
+    /// 同時に実行するジョブ数 (0 で無制限)
+    pub max_running_jobs: usize,
+    /// クライアント IP ごとの 1 分あたりの POST 回数 (0 で無制限)
+    pub rate_limit_per_minute: u32,
+    /// 変換結果の履歴を保存する SQLite ファイル (空なら保存しない)
+    pub history_db: String,
+    /// 一時停止・再起動後に再開するためのジョブの状態の保存先 (空なら保存しない)
+    pub jobs_dir: String,
+    /// file:// URL で読んでよいディレクトリ (カンマ区切り。空なら読まない)
+    pub local_file_roots: String,
+    /// WASM プラグイン (<名前>.wasm) を置くディレクトリ
+    pub plugin_dir: String,
+    /// HTTPS の証明書 (PEM) のパス (TLS_KEY と両方指定で HTTPS)
+    pub tls_cert: String,
+    /// HTTPS の秘密鍵 (PEM) のパス
+    pub tls_key: String,
+    /// API キー (空でなければ X-API-Key か Authorization: Bearer で要求)
+    pub api_key: String,
+    /// Basic 認証の "ユーザー名:パスワード" (空なら Basic 認証なし)
+    pub basic_auth: String,
+    /// ログの出力レベル (error / warn / info / debug / trace。空ならサーバーは info、CLI は warn)
+    pub log_level: String,
+    pub log_format: LogFormat,
+    /// 結果のアップロード先 (s3://bucket/prefix または gs://bucket/prefix。空ならアップロードしない)
+    pub output_bucket: String,
+    /// S3 互換 API のエンドポイント (空なら AWS / GCS の既定)
+    pub output_bucket_endpoint: String,
+    /// 署名のリージョン (空なら S3 は us-east-1、GCS は auto)
+    pub output_bucket_region: String,
+    /// アクセスキー (空なら環境変数 AWS_ACCESS_KEY_ID)
+    pub output_bucket_access_key: String,
+    /// シークレットキー (空なら環境変数 AWS_SECRET_ACCESS_KEY)
+    pub output_bucket_secret_key: String,
+    /// Elasticsearch / OpenSearch の URL (例: http://127.0.0.1:9200)
+    pub elasticsearch_url: String,
+    /// 投入先のインデックス (空なら web-to-json)
+    pub elasticsearch_index: String,
+    /// 認証 ("user:password" / "Bearer token" / "ApiKey key")
+    pub elasticsearch_credentials: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            max_concurrency: 4,
+            request_timeout_secs: 30,
+            job_deadline_secs: 0,
+            retry_max_attempts: 1,
+            retry_backoff_ms: 500,
+            user_agent: String::new(),
+            host_delay_ms: 0,
+            max_response_mb: 50,
+            max_redirects: fetch::DEFAULT_MAX_REDIRECTS,
+            cross_host_redirects: true,
+            response_cache_entries: 500,
+            cache_dir: String::new(),
+            cache_ttl_secs: 86400,
+            replay_dir: String::new(),
+            wayback_url: Url::parse(DEFAULT_WAYBACK_URL).expect("valid default Wayback URL"),
+            subpage_scope: SubpageScope::All,
+            subpage_concurrency: 8,
+            subpage_per_host: 4,
+            max_subpages: 100,
+            max_subpages_per_job: 1000,
+            sitemap_max_urls: 500,
+            capture_attributes: vec![],
+            browser_path: String::new(),
+            browser_no_sandbox: false,
+            render_wait_ms: 5000,
+            skip_tags: DEFAULT_SKIP_TAGS.iter().map(|t| t.to_string()).collect(),
+            target_tags: DEFAULT_TARGET_TAGS.iter().map(|t| t.to_string()).collect(),
+            max_depth: 256,
+            max_table_rows: 10000,
+            max_running_jobs: 2,
+            rate_limit_per_minute: 60,
+            history_db: String::new(),
+            jobs_dir: String::new(),
+            local_file_roots: String::new(),
+            plugin_dir: plugin::DEFAULT_PLUGIN_DIR.to_string(),
+            tls_cert: String::new(),
+            tls_key: String::new(),
+            api_key: String::new(),
+            basic_auth: String::new(),
+            log_level: String::new(),
+            log_format: LogFormat::Text,
+            output_bucket: String::new(),
+            output_bucket_endpoint: String::new(),
+            output_bucket_region: String::new(),
+            output_bucket_access_key: String::new(),
+            output_bucket_secret_key: String::new(),
+            elasticsearch_url: String::new(),
+            elasticsearch_index: String::new(),
+            elasticsearch_credentials: String::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// 設定ファイルを読み込む (無ければデフォルト)
+    pub fn load() -> Self {
+        let mut settings = Settings::default();
+        let Ok(content) = std::fs::read_to_string(SETTINGS_FILE) else {
+            return settings;
+        };
+        for line in content.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "MAX_CONCURRENCY" => {
+                    if let Ok(n) = value.parse::<usize>() {
+                        settings.max_concurrency = n.clamp(1, MAX_CONCURRENCY_LIMIT);
+                    }
+                }
+                "REQUEST_TIMEOUT" => {
+                    if let Ok(n) = value.parse() {
+                        settings.request_timeout_secs = n;
+                    }
+                }
+                "JOB_DEADLINE" => {
+                    if let Ok(n) = value.parse() {
+                        settings.job_deadline_secs = n;
+                    }
+                }
+                "RETRY_MAX_ATTEMPTS" => {
+                    if let Ok(n) = value.parse::<u32>() {
+                        settings.retry_max_attempts = n.clamp(1, MAX_ATTEMPTS_LIMIT);
+                    }
+                }
+                "RETRY_BACKOFF_MS" => {
+                    if let Ok(n) = value.parse() {
+                        settings.retry_backoff_ms = n;
+                    }
+                }
+                "USER_AGENT" => settings.user_agent = value.to_string(),
+                "HOST_DELAY_MS" => {
+                    if let Ok(n) = value.parse() {
+                        settings.host_delay_ms = n;
+                    }
+                }
+                "MAX_RESPONSE_MB" => {
+                    if let Ok(n) = value.parse() {
+                        settings.max_response_mb = n;
+                    }
+                }
+                "MAX_REDIRECTS" => {
+                    if let Ok(n) = value.parse() {
+                        settings.max_redirects = n;
+                    }
+                }
+                "CROSS_HOST_REDIRECTS" => settings.cross_host_redirects = !value.eq_ignore_ascii_case("false"),
+                "RESPONSE_CACHE_ENTRIES" => {
+                    if let Ok(n) = value.parse() {
+                        settings.response_cache_entries = n;
+                    }
+                }
+                "CACHE_DIR" => settings.cache_dir = value.to_string(),
+                "CACHE_TTL" => {
+                    if let Ok(n) = value.parse() {
+                        settings.cache_ttl_secs = n;
+                    }
+                }
+                "REPLAY_DIR" => settings.replay_dir = value.to_string(),
+                "WAYBACK_URL" => {
+                    if let Ok(url) = Url::parse(value) {
+                        settings.wayback_url = url;
+                    }
+                }
+                "SITEMAP_MAX_URLS" => {
+                    if let Ok(n) = value.parse() {
+                        settings.sitemap_max_urls = n;
+                    }
+                }
+                "CAPTURE_ATTRIBUTES" => settings.capture_attributes = parse_attribute_list(value),
+                "BROWSER_PATH" => settings.browser_path = value.to_string(),
+                "BROWSER_NO_SANDBOX" => settings.browser_no_sandbox = value.eq_ignore_ascii_case("true"),
+                "RENDER_WAIT_MS" => {
+                    if let Ok(n) = value.parse() {
+                        settings.render_wait_ms = n;
+                    }
+                }
+                "SKIP_TAGS" => settings.skip_tags = parse_attribute_list(value),
+                "TARGET_TAGS" => settings.target_tags = parse_attribute_list(value),
+                "MAX_DEPTH" => {
+                    if let Ok(n) = value.parse() {
+                        settings.max_depth = n;
+                    }
+                }
+                "MAX_TABLE_ROWS" => {
+                    if let Ok(n) = value.parse() {
+                        settings.max_table_rows = n;
+                    }
+                }
+                "MAX_RUNNING_JOBS" => {
+                    if let Ok(n) = value.parse() {
+                        settings.max_running_jobs = n;
+                    }
+                }
+                "RATE_LIMIT_PER_MINUTE" => {
+                    if let Ok(n) = value.parse() {
+                        settings.rate_limit_per_minute = n;
+                    }
+                }
+                "HISTORY_DB" => settings.history_db = value.to_string(),
+                "JOBS_DIR" => settings.jobs_dir = value.to_string(),
+                "LOCAL_FILE_ROOTS" => settings.local_file_roots = value.to_string(),
+                "PLUGIN_DIR" => settings.plugin_dir = value.to_string(),
+                "TLS_CERT" => settings.tls_cert = value.to_string(),
+                "TLS_KEY" => settings.tls_key = value.to_string(),
+                "API_KEY" => settings.api_key = value.to_string(),
+                "BASIC_AUTH" => settings.basic_auth = value.to_string(),
+                "LOG_LEVEL" => settings.log_level = value.to_string(),
+                "OUTPUT_BUCKET" => settings.output_bucket = value.to_string(),
+                "OUTPUT_BUCKET_ENDPOINT" => settings.output_bucket_endpoint = value.to_string(),
+                "OUTPUT_BUCKET_REGION" => settings.output_bucket_region = value.to_string(),
+                "OUTPUT_BUCKET_ACCESS_KEY" => settings.output_bucket_access_key = value.to_string(),
+                "OUTPUT_BUCKET_SECRET_KEY" => settings.output_bucket_secret_key = value.to_string(),
+                "ELASTICSEARCH_URL" => settings.elasticsearch_url = value.to_string(),
+                "ELASTICSEARCH_INDEX" => settings.elasticsearch_index = value.to_string(),
+                "ELASTICSEARCH_CREDENTIALS" => settings.elasticsearch_credentials = value.to_string(),
+                "LOG_FORMAT" => {
+                    if let Ok(format) = LogFormat::from_str(value, true) {
+                        settings.log_format = format;
+                    }
+                }
+                "SUBPAGE_SCOPE" => {
+                    if let Ok(scope) = SubpageScope::from_str(value, true) {
+                        settings.subpage_scope = scope;
+                    }
+                }
+                "SUBPAGE_CONCURRENCY" => {
+                    if let Ok(n) = value.parse::<usize>() {
+                        settings.subpage_concurrency = n.clamp(1, MAX_CONCURRENCY_LIMIT);
+                    }
+                }
+                "SUBPAGE_PER_HOST" => {
+                    if let Ok(n) = value.parse() {
+                        settings.subpage_per_host = n;
+                    }
+                }
+                "MAX_SUBPAGES" => {
+                    if let Ok(n) = value.parse() {
+                        settings.max_subpages = n;
+                    }
+                }
+                "MAX_SUBPAGES_PER_JOB" => {
+                    if let Ok(n) = value.parse() {
+                        settings.max_subpages_per_job = n;
+                    }
+                }
+                _ => {}
+            }
+        }
+        settings
+    }
+}
+
+/// 1 ジョブ (URL 群の変換) のオプション
+#[derive(Debug, Clone)]
+pub struct JobOptions {
+    pub include_subpages: bool,
+    pub concurrency: usize,
+    /// 1 リクエストのタイムアウト
+    pub request_timeout: Option<Duration>,
+    /// ジョブ全体の期限
+    pub job_deadline: Option<Duration>,
+    pub retry: RetryPolicy,
+    pub user_agent: Option<String>,
+    /// 入力 URL のオリジンへのリクエストにだけ付与する追加ヘッダ
+    pub headers: HeaderMap,
+    /// 入力 URL のホストに送る Cookie ("name=value; ..." 形式)
+    pub cookies: Option<String>,
+    /// 入力 URL のホストに送る認証情報
+    pub credentials: Option<Credentials>,
+    /// 同一ホストへのリクエスト間隔
+    pub host_delay: Duration,
+    /// レスポンス本文の上限 (バイト, None で無制限)
+    pub max_response_bytes: Option<u64>,
+    /// たどるリダイレクトの数の上限 (0 でたどらない)
+    pub max_redirects: usize,
+    /// 別のホスト (入力 URL・リンク先と異なるホスト) へのリダイレクトをたどる (false ならエラー)
+    pub cross_host_redirects: bool,
+    /// 条件付きリクエスト用のキャッシュ (サーバーではジョブをまたいで共有)
+    pub response_cache: Option<Arc<ResponseCache>>,
+    /// 取得した本文のディスクキャッシュ (Web・CLI で同じディレクトリを共有できる)
+    pub disk_cache: Option<Arc<diskcache::DiskCache>>,
+    /// ネットワークに出ず、保存したページとディスクキャッシュだけを使う (ないものはすぐにエラー。描画・リンクのチェックもしない)
+    pub offline: bool,
+    /// オフライン再生用に保存したページのディレクトリ (localfile::replay の配置)
+    pub replay_dir: Option<PathBuf>,
+    /// 取得した HTML の保存先 (None なら保存しない)
+    pub html_archive: Option<Arc<archive::HtmlArchive>>,
+    /// HTTP のリクエスト・レスポンスの書き出し先 (None なら書き出さない)
+    pub warc: Option<Arc<warc::WarcWriter>>,
+    /// Wayback Machine のスナップショットを使う条件 (日時の指定・ライブの取得に失敗したとき)
+    pub wayback: wayback::WaybackOptions,
+    /// 取得数・エラー数などの集計先 (サーバーのみ)
+    pub metrics: Option<Arc<metrics::Metrics>>,
+    pub subpage_scope: SubpageScope,
+    /// 1階層リンク先の同時取得数 (ジョブ全体)
+    pub subpage_concurrency: usize,
+    /// 1階層リンク先の同一ホストへの同時取得数 (0 で無制限)
+    pub subpage_per_host: usize,
+    /// 1 ページから取得するリンク先の数 (異なる URL の数。0 で無制限)
+    pub max_subpages: usize,
+    /// 1 回の変換全体で取得するリンク先の数 (0 で無制限)
+    pub max_subpages_per_job: usize,
+    /// 1階層リンク先で元ページ・先のリンク先と同じブロックの扱い
+    pub dedup_subpages: dedup::DedupMode,
+    /// meta robots の nofollow・rel="nofollow" を無視する (skip_noindex も効かない)
+    pub ignore_robots: bool,
+    /// meta robots が noindex の 1階層リンク先を not_fetched にする
+    pub skip_noindex: bool,
+    /// 1階層リンク先の URL がいずれかに一致すること (空なら無条件)
+    pub link_include: Vec<Regex>,
+    /// 1階層リンク先の URL がどれにも一致しないこと
+    pub link_exclude: Vec<Regex>,
+    /// サイトマップから展開する URL 数の上限 (0 で無制限)
+    pub sitemap_max_urls: usize,
+    /// フィードの各記事のリンク先も変換する
+    pub follow_feed_items: bool,
+    /// URL ごとの取得の情報を page_meta.fetch (フィード・リソースは fetch) に含める
+    pub include_fetch_info: bool,
+    /// URL ごとに {url, document} / {url, error: {kind, message}} で出力 (エラーを擬似ノードにしない)
+    pub typed_results: bool,
+    /// 末尾に集計 ({"summary": {...}}) を付ける
+    pub include_summary: bool,
+    /// canonical URL が先に出たページと同じページを参照ノードに置き換える
+    pub dedup_canonical: bool,
+    /// 入力 URL・リンク先の URL からトラッキング用のクエリを除く
+    pub strip_tracking_params: bool,
+    /// リンクグラフの各リンク先を HEAD / GET で確かめる (リンク先のページは埋め込まない)
+    pub check_links: bool,
+    /// 結果の代わりに履歴の結果との差分を出力
+    pub diff_against: Option<diff::Baseline>,
+    /// 差分の基準を読む履歴
+    pub history: Option<Arc<HistoryStore>>,
+    /// URL ごとの進捗の報告先 (Web のバックグラウンドジョブのみ)
+    pub progress: Option<Arc<jobs::Job>>,
+    /// 完了時の通知先 (Web のバックグラウンドジョブのみ)
+    pub webhook: Option<webhook::Webhook>,
+    /// 結果のアップロード先 (逐次出力では使わない)
+    pub upload: Option<storage::Bucket>,
+    /// 結果を投入する Elasticsearch / OpenSearch
+    pub search_index: Option<search::SearchIndex>,
+    /// 取得せずに変換する HTML (その URL の入力だけ)
+    pub raw_html: Option<Arc<RawHtml>>,
+    /// file:// URL を読んでよい範囲
+    pub local_files: localfile::LocalFiles,
+    /// 結果のうち出力する部分木
+    pub select: Option<subtree::SubtreePath>,
+    /// 変換結果を置き換える WASM プラグイン (部分木の選択の後)
+    pub plugin: Option<plugin::Plugin>,
+    /// 変換結果の後処理スクリプト (プラグインの後)
+    pub script: Option<script::Script>,
+    /// 出力のフィルタ (スクリプトの後)
+    pub filter: Option<filter::Filter>,
+    /// ヘッドレスブラウザで描画する (None なら HTTP で取得した HTML をそのまま)
+    pub render: Option<render::RenderOptions>,
+    /// 描画する場合の既定 (設定ファイルの値)
+    pub render_defaults: render::RenderOptions,
+    pub parse: ParseOptions,
+    pub output_format: OutputFormat,
+    /// テキスト出力で見出しに "#" を付ける
+    pub text_heading_markers: bool,
+    /// JSON / YAML / NDJSON をフラットなレコード ({page, path, tag, text, href}) の配列で出力
+    pub flatten: bool,
+    /// サーバーで結果画面を作らず、完了した URL から順に書き出す (NDJSON は常に)
+    pub stream: bool,
+}
+
+/// HTML 解析のオプション (spawn_blocking に渡すため Clone)
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// 本文と推定される要素の中だけを変換する
+    pub main_content_only: bool,
+    /// <img> を画像ノードとして残す
+    pub include_images: bool,
+    /// <video> / <audio> を動画・音声ノードとして残す
+    pub include_media: bool,
+    /// <a> の href を (<base href> があればそれを基準に) 絶対 URL にする
+    pub absolute_hrefs: bool,
+    /// <form> の action / method / 入力項目をフォームノードとして残す
+    pub include_forms: bool,
+    /// page_meta.stats にページごとの数を入れる
+    pub include_stats: bool,
+    /// DOM ツリーの代わりにページごとの外向きリンク (リンクグラフ) を返す
+    pub link_graph: bool,
+    /// ノードに残す属性名 ("data-*" のように末尾 * で前方一致)
+    pub capture_attributes: Vec<String>,
+    /// 中身ごと読み飛ばすタグ
+    pub skip_tags: Vec<String>,
+    /// ノードとして出力するタグ (それ以外は中身だけ取り出す)
+    pub target_tags: Vec<String>,
+    /// ノードの入れ子の深さの上限 (超える要素は中身だけ取り出す。0 で無制限)
+    pub max_depth: usize,
+    /// CSS セレクタの抽出ルール (指定時は DOM ツリーの代わりにフラットなオブジェクトを返す)
+    pub rules: Vec<rules::ExtractRule>,
+    pub table: TableOptions,
+}
+
+/// テーブルの見出しの扱い
+#[derive(Debug, Clone, Default)]
+pub struct TableOptions {
+    pub headers: TableHeaders,
+    /// 見出し行がないテーブルの行を配列で出力 (false なら col0, col1 ... のキー)
+    pub headerless_arrays: bool,
+    /// すべての行を見出しと同じ並びの配列で出力 (見出しの重複も連番を付けずに残す)
+    pub array_rows: bool,
+    /// 見出し行が複数ある場合に "上 / 下" の複合名にする (false なら最後の行)
+    pub join_header_rows: bool,
+    /// セルの数値・真偽値・日付を型付きの値で出力 (false なら常に文字列)
+    pub infer_types: bool,
+    /// 見出し行を除いた行数の上限 (超えた分は出力しない。0 で無制限)
+    pub max_rows: usize,
+}
+
+/// 見出し行の判定方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum TableHeaders {
+    /// <thead> 内の行・<th> だけの行
+    #[default]
+    Auto,
+    /// 常に先頭行
+    FirstRow,
+    /// 見出しなし (すべてデータ行)
+    None,
+}
+
+impl JobOptions {
+    /// 結果を完了した URL から順に書き出すか (NDJSON は常に)
+    pub fn streams(&self) -> bool {
+        self.stream || self.output_format == OutputFormat::Ndjson
+    }
+
+    /// 逐次出力で使えないオプション (ジョブ全体の分をメモリに溜めるもの・結果全体のアップロード) が指定されていればエラー
+    pub fn check_streaming(&self) -> Result<(), String> {
+        if !self.streams() {
+            return Ok(());
+        }
+        let names: Vec<&str> = [
+            ("search_index", self.search_index.is_some()),
+            ("warc", self.warc.is_some()),
+            ("save_html", self.html_archive.is_some()),
+            ("upload", self.upload.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect();
+        if names.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("{} cannot be used with streamed output (stream or NDJSON)", names.join(", ")))
+        }
+    }
+
+    /// Cookie・認証情報・追加ヘッダを送るか (結果を他の変換と共有・履歴に保存しない)
+    pub fn sends_secrets(&self) -> bool {
+        self.cookies.is_some() || self.credentials.is_some() || !self.headers.is_empty()
+    }
+
+    /// base_url のページのリンク link_url を 1階層リンク先として取得するか
+    fn should_follow(&self, base_url: &Url, link_url: &Url) -> bool {
+        let link = link_url.as_str();
+        self.subpage_scope.allows(base_url, link_url)
+            && (self.link_include.is_empty() || self.link_include.iter().any(|re| re.is_match(link)))
+            && !self.link_exclude.iter().any(|re| re.is_match(link))
+    }
+}
+
+/// 改行区切りの正規表現リストをコンパイル (空行は無視)
+pub fn parse_patterns(text: &str) -> Result<Vec<Regex>, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(|l| Regex::new(l).map_err(|e| format!("Invalid pattern \"{l}\": {e}")))
+        .collect()
+}
+
+/// 1階層リンク先として取得するリンクの範囲
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SubpageScope {
+    /// すべてのリンク
+    All,
+    /// 元ページと同じホストのみ
+    SameHost,
+    /// 元ページと同じ登録ドメイン (example.co.jp 等) のみ
+    SameDomain,
+}
+
+impl SubpageScope {
+    /// base_url のページから link_url をたどってよいか
+    fn allows(self, base_url: &Url, link_url: &Url) -> bool {
+        match self {
+            SubpageScope::All => true,
+            SubpageScope::SameHost => base_url.host_str() == link_url.host_str(),
+            SubpageScope::SameDomain => {
+                match (registrable_domain(base_url), registrable_domain(link_url)) {
+                    (Some(a), Some(b)) => a.eq_ignore_ascii_case(&b),
+                    // IP アドレス等は同一ホストで判定
+                    _ => base_url.host_str() == link_url.host_str(),
+                }
+            }
+        }
+    }
+}
+
+/// 公開サフィックスリストに基づく登録ドメイン
+fn registrable_domain(url: &Url) -> Option<String> {
+    let url::Host::Domain(host) = url.host()? else {
+        return None;
+    };
+    psl::domain_str(host).map(|d| d.to_string())
+}
+
+/// 取得せずに変換する HTML
+pub struct RawHtml {
+    /// 入力 URL の代わり (相対リンクの基準)
+    pub url: String,
+    pub html: String,
+}
+
+/// 本文は長いので大きさだけ
+impl std::fmt::Debug for RawHtml {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RawHtml({}, {} bytes)", self.url, self.html.len())
+    }
+}
+
+/// 貼り付けた HTML の既定の URL
+pub const RAW_HTML_URL: &str = "about:blank";
+
+/// 一時的な取得失敗 (5xx / 429 / タイムアウト / 接続失敗) のリトライ方針
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// 最大試行回数 (1 でリトライなし)
+    pub max_attempts: u32,
+    /// 初回の待ち時間 (以降倍々)
+    pub backoff: Duration,
+}
+
+impl JobOptions {
+    /// 設定ファイルの既定値から作成
+    pub fn from_settings(settings: &Settings) -> Self {
+        JobOptions {
+            include_subpages: false,
+            concurrency: settings.max_concurrency,
+            request_timeout: secs_to_duration(settings.request_timeout_secs),
+            job_deadline: secs_to_duration(settings.job_deadline_secs),
+            retry: RetryPolicy {
+                max_attempts: settings.retry_max_attempts,
+                backoff: Duration::from_millis(settings.retry_backoff_ms),
+            },
+            user_agent: non_empty(&settings.user_agent),
+            headers: HeaderMap::new(),
+            cookies: None,
+            credentials: None,
+            host_delay: Duration::from_millis(settings.host_delay_ms),
+            max_response_bytes: mb_to_bytes(settings.max_response_mb),
+            max_redirects: settings.max_redirects,
+            cross_host_redirects: settings.cross_host_redirects,
+            response_cache: None,
+            disk_cache: non_empty(&settings.cache_dir)
+                .map(|dir| Arc::new(diskcache::DiskCache::new(dir, settings.cache_ttl_secs))),
+            offline: false,
+            replay_dir: non_empty(&settings.replay_dir).map(PathBuf::from),
+            html_archive: None,
+            warc: None,
+            wayback: wayback::WaybackOptions {
+                base: settings.wayback_url.clone(),
+                date: None,
+                fallback: false,
+            },
+            metrics: None,
+            subpage_scope: settings.subpage_scope,
+            subpage_concurrency: settings.subpage_concurrency,
+            subpage_per_host: settings.subpage_per_host,
+            max_subpages: settings.max_subpages,
+            max_subpages_per_job: settings.max_subpages_per_job,
+            dedup_subpages: dedup::DedupMode::Off,
+            ignore_robots: false,
+            skip_noindex: false,
+            link_include: vec![],
+            link_exclude: vec![],
+            sitemap_max_urls: settings.sitemap_max_urls,
+            follow_feed_items: false,
+            include_fetch_info: false,
+            typed_results: false,
+            include_summary: false,
+            dedup_canonical: false,
+            strip_tracking_params: false,
+            check_links: false,
+            diff_against: None,
+            history: None,
+            progress: None,
+            webhook: None,
+            upload: None,
+            search_index: None,
+            raw_html: None,
+            local_files: localfile::LocalFiles::from_setting(&settings.local_file_roots),
+            select: None,
+            plugin: None,
+            script: None,
+            filter: None,
+            render: None,
+            render_defaults: render::RenderOptions {
+                browser: settings.browser_path.clone(),
+                wait: Duration::from_millis(settings.render_wait_ms),
+                wait_selector: None,
+                no_sandbox: settings.browser_no_sandbox,
+            },
+            parse: ParseOptions {
+                capture_attributes: settings.capture_attributes.clone(),
+                skip_tags: settings.skip_tags.clone(),
+                target_tags: settings.target_tags.clone(),
+                max_depth: settings.max_depth,
+                table: TableOptions {
+                    max_rows: settings.max_table_rows,
+                    ..TableOptions::default()
+                },
+                ..ParseOptions::default()
+            },
+            output_format: OutputFormat::default(),
+            text_heading_markers: false,
+            flatten: false,
+            stream: false,
+        }
+    }
+}
+
+/// 設定ファイルを読まない既定値 (ライブラリから使う場合)
+impl Default for JobOptions {
+    fn default() -> Self {
+        JobOptions::from_settings(&Settings::default())
+    }
+}
+
+/// 空白のみなら None
+pub fn non_empty(s: &str) -> Option<String> {
+    let s = s.trim();
+    (!s.is_empty()).then(|| s.to_string())
+}
+
+/// 秒数 -> Duration (0 は無制限 = None)
+pub fn secs_to_duration(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// MB -> バイト (0 は無制限 = None)
+pub fn mb_to_bytes(mb: u64) -> Option<u64> {
+    (mb > 0).then(|| mb.saturating_mul(1024 * 1024))
+}
+
+/// JSON 出力用: 通常ノード or テーブル or フィード or 画像 or 動画・音声 or コード or フォーム or ルールの抽出結果 or HTML 以外のリソース
+/// (型付きの結果の指定時は URL ごとの {url, document} / {url, error}、集計の指定時は末尾に {summary})
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum DomContent {
+    Node(DomNode),
+    Table(TableData),
+    Feed(feed::FeedData),
+    Image(images::ImageData),
+    Media(media::MediaData),
+    /// <pre> / <code> (空白をそのまま残す)
+    Code(code::CodeBlock),
+    Form(forms::FormData),
+    /// 抽出ルール指定時のフラットなオブジェクト
+    Extracted(serde_json::Map<String, serde_json::Value>),
+    Resource(resource::ResourceData),
+    /// URL ごとの結果 (最上位のみ)
+    Result(Box<url_result::UrlResult>),
+    /// ジョブの集計 (集計を付ける指定時の末尾のみ)
+    Summary(summary::SummaryBlock),
+    /// リンクグラフモードのページごとのリンク
+    Links(Box<links::PageLinks>),
+    /// 履歴の結果との差分 (差分を出力する指定時)
+    Diff(Box<diff::PageDiff>),
+    /// フィルタ (JMESPath) の結果 (任意の JSON)
+    Filtered(serde_json::Value),
+}
+
+/// 出力の JSON から読み戻す (untagged なのでキーで種類を判定。どれにも当てはまらないオブジェクトは抽出ルールの結果、
+/// オブジェクト以外はフィルタの結果)
+impl<'de> Deserialize<'de> for DomContent {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        DomContent::from_value(value).map_err(serde::de::Error::custom)
+    }
+}
+
+/// 通常ノードのキー
+const NODE_FIELDS: &[&str] = &["tag", "href", "attrs", "text", "children", "link_subpage", "page_meta", "structured_data"];
+
+impl DomContent {
+    fn from_value(value: serde_json::Value) -> serde_json::Result<Self> {
+        use serde_json::from_value;
+        let serde_json::Value::Object(map) = &value else {
+            return Ok(DomContent::Filtered(value));
+        };
+        let has = |key: &str| map.contains_key(key);
+        let only = |fields: &[&str]| map.keys().all(|key| fields.contains(&key.as_str()));
+        let tag = map.get("tag").and_then(serde_json::Value::as_str);
+        Ok(if has("table_headers") && has("rows") {
+            DomContent::Table(from_value(value)?)
+        } else if has("feed_type") && has("items") {
+            DomContent::Feed(from_value(value)?)
+        } else if map.get("summary").is_some_and(|s| s.get("total").is_some()) {
+            DomContent::Summary(from_value(value)?)
+        } else if has("url") && (has("document") || has("error")) {
+            DomContent::Result(from_value(value)?)
+        } else if has("url") && has("links") {
+            DomContent::Links(from_value(value)?)
+        } else if has("url") && has("history_id") {
+            DomContent::Diff(from_value(value)?)
+        } else if matches!(tag, Some("binary" | "json")) && has("content_type") {
+            DomContent::Resource(from_value(value)?)
+        } else if tag == Some("form") && has("fields") {
+            DomContent::Form(from_value(value)?)
+        } else if tag == Some("img") && only(&["tag", "src", "alt", "width", "height", "srcset"]) {
+            DomContent::Image(from_value(value)?)
+        } else if matches!(tag, Some("video" | "audio")) && only(&["tag", "src", "poster", "width", "height", "sources", "tracks"]) {
+            DomContent::Media(from_value(value)?)
+        } else if matches!(tag, Some("pre" | "code")) && has("text") && only(&["tag", "language", "text"]) {
+            DomContent::Code(from_value(value)?)
+        } else if only(NODE_FIELDS) {
+            DomContent::Node(from_value(value)?)
+        } else {
+            DomContent::Extracted(from_value(value)?)
+        })
+    }
+}
+
+/// 通常ノード
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DomNode {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+
+    /// ホワイトリストに一致した属性 (id / class / data-* など)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attrs: Option<BTreeMap<String, String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<DomContent>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link_subpage: Option<Box<DomContent>>,
+
+    /// ページのメタ情報 (html ノードのみ)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page_meta: Option<Box<meta::PageMeta>>,
+
+    /// Microdata / RDFa (html ノードのみ)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured_data: Option<structured::StructuredData>,
+}
+
+/// テーブル構造
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TableData {
+    pub table_headers: Vec<String>,
+    pub rows: Vec<serde_json::Value>,
+    /// 行数の上限で打ち切った (rows は先頭から上限まで)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// 打ち切る前の行数 (打ち切ったときだけ)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_rows: Option<usize>,
+    /// 元の見出し (重複・空の見出しを置き換えて table_headers と異なるときだけ)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_headers: Option<Vec<String>>,
+}
+
+impl TableData {
+    /// 列名 (ヘッダにないキー (colN 等) や配列の行の列も後ろに追加)
+    fn columns(&self) -> Vec<String> {
+        let mut columns = self.table_headers.clone();
+        for row in &self.rows {
+            match row {
+                serde_json::Value::Object(map) => {
+                    for key in map.keys() {
+                        if !columns.contains(key) {
+                            columns.push(key.clone());
+                        }
+                    }
+                }
+                serde_json::Value::Array(cells) => {
+                    while columns.len() < cells.len() {
+                        columns.push(format!("col{}", columns.len()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        columns
+    }
+
+    /// 行のセルの文字列を columns の順に (無ければ空文字)
+    fn row_cells(row: &serde_json::Value, columns: &[String]) -> Vec<String> {
+        let text = |v: Option<&serde_json::Value>| match v {
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(serde_json::Value::Null) | None => String::new(),
+            Some(v) => v.to_string(),
+        };
+        columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| match row {
+                serde_json::Value::Array(cells) => text(cells.get(i)),
+                _ => text(row.get(column)),
+            })
+            .collect()
+    }
+}
+
+/// フォーム (API ボディ) からジョブのオプションを作成 (未指定・不正値は設定値)
+pub fn options_from_form(form: &UrlForm, settings: &Settings) -> Result<JobOptions, String> {
+    let mut options = JobOptions::from_settings(settings);
+    options.include_subpages = form.include_subpages.as_deref() == Some("true");
+    options.follow_feed_items = form.follow_feed_items.as_deref() == Some("true");
+    options.include_fetch_info = form.include_fetch_info.as_deref() == Some("true");
+    options.typed_results = form.typed_results.as_deref() == Some("true");
+    options.include_summary = form.include_summary.as_deref() == Some("true");
+    options.dedup_canonical = form.dedup_canonical.as_deref() == Some("true");
+    options.strip_tracking_params = form.strip_tracking_params.as_deref() == Some("true");
+    options.parse.main_content_only = form.main_content_only.as_deref() == Some("true");
+    options.parse.include_images = form.include_images.as_deref() == Some("true");
+    options.parse.include_media = form.include_media.as_deref() == Some("true");
+    options.parse.absolute_hrefs = form.absolute_hrefs.as_deref() == Some("true");
+    options.parse.include_forms = form.include_forms.as_deref() == Some("true");
+    options.parse.include_stats = form.include_stats.as_deref() == Some("true");
+    options.check_links = form.check_links.as_deref() == Some("true");
+    options.parse.link_graph = options.check_links || form.link_graph.as_deref() == Some("true");
+    if let Some(text) = &form.diff_against {
+        options.diff_against = diff::Baseline::parse(text)?;
+        // 履歴は変換した人ごとに分かれていないので、Web・API では他の人の結果を探す latest は使わない
+        if matches!(options.diff_against, Some(diff::Baseline::Latest)) {
+            return Err("diff_against \"latest\" is only available from the CLI (specify a history id)".to_string());
+        }
+    }
+    if let Some(mode) = form.search_index.as_deref().and_then(|s| search::IndexMode::from_str(s, true).ok()) {
+        options.search_index = Some(search::SearchIndex::from_settings(settings, mode)?);
+    }
+    if form.upload.as_deref() == Some("true") {
+        options.upload = Some(storage::Bucket::from_settings(settings)?.ok_or("OUTPUT_BUCKET is not configured")?);
+    }
+    if let (Some(url), Some(html)) = (raw_html_url(form), &form.html) {
+        Url::parse(&url).map_err(|e| format!("Invalid base URL \"{url}\": {e}"))?;
+        options.raw_html = Some(Arc::new(RawHtml { url, html: html.clone() }));
+    }
+    if let Some(path) = form.select.as_deref().filter(|s| !s.trim().is_empty()) {
+        options.select = Some(subtree::SubtreePath::parse(path)?);
+    }
+    if let Some(name) = form.plugin.as_deref().and_then(non_empty) {
+        options.plugin = Some(plugin::Plugin::load(&settings.plugin_dir, &name)?);
+    }
+    if let Some(source) = form.script.as_deref().filter(|s| !s.trim().is_empty()) {
+        options.script = Some(script::Script::compile(source)?);
+    }
+    if let Some(expression) = form.filter.as_deref().filter(|s| !s.trim().is_empty()) {
+        options.filter = Some(filter::Filter::compile(expression)?);
+    }
+    if let Some(url) = form.callback_url.as_deref().map(webhook::parse_url).transpose()?.flatten() {
+        options.webhook = Some(webhook::Webhook {
+            url,
+            include_result: form.callback_include_result.as_deref() == Some("true"),
+            origin: String::new(),
+        });
+    }
+    if let Some(text) = &form.capture_attributes {
+        options.parse.capture_attributes = parse_attribute_list(text);
+    }
+    if let Some(text) = &form.skip_tags {
+        options.parse.skip_tags = parse_attribute_list(text);
+    }
+    if let Some(text) = &form.target_tags {
+        options.parse.target_tags = parse_attribute_list(text);
+    }
+    if let Some(n) = form_limit(form.max_depth.as_deref(), "max_depth", settings.max_depth)? {
+        options.parse.max_depth = n;
+    }
+    if let Some(text) = &form.extract_rules {
+        options.parse.rules = rules::parse_rules(text)?;
+    }
+    if let Some(mode) = form.table_headers.as_deref().and_then(|s| TableHeaders::from_str(s, true).ok()) {
+        options.parse.table.headers = mode;
+    }
+    options.parse.table.headerless_arrays = form.headerless_arrays.as_deref() == Some("true");
+    options.parse.table.array_rows = form.array_rows.as_deref() == Some("true");
+    options.parse.table.join_header_rows = form.join_header_rows.as_deref() == Some("true");
+    options.parse.table.infer_types = form.infer_cell_types.as_deref() == Some("true");
+    if let Some(n) = form_limit(form.max_table_rows.as_deref(), "max_table_rows", settings.max_table_rows)? {
+        options.parse.table.max_rows = n;
+    }
+    options.text_heading_markers = form.text_heading_markers.as_deref() == Some("true");
+    options.flatten = form.flatten.as_deref() == Some("true");
+    options.stream = form.stream.as_deref() == Some("true");
+
+    if let Some(n) = form.concurrency.as_deref().and_then(|s| s.trim().parse::<usize>().ok()) {
+        options.concurrency = n.clamp(1, MAX_CONCURRENCY_LIMIT);
+    }
+    if let Some(n) = form.subpage_concurrency.as_deref().and_then(|s| s.trim().parse::<usize>().ok()) {
+        options.subpage_concurrency = n.clamp(1, MAX_CONCURRENCY_LIMIT);
+    }
+    if let Some(n) = form_limit(form.subpage_per_host.as_deref(), "subpage_per_host", MAX_CONCURRENCY_LIMIT)? {
+        options.subpage_per_host = n;
+    }
+    if let Some(n) = form_limit(form.max_subpages.as_deref(), "max_subpages", settings.max_subpages)? {
+        options.max_subpages = n;
+    }
+    if let Some(n) =
+        form_limit(form.max_subpages_per_job.as_deref(), "max_subpages_per_job", settings.max_subpages_per_job)?
+    {
+        options.max_subpages_per_job = n;
+    }
+    // タイムアウト・期限 (秒)
+    if let Some(n) = form_limit(form.timeout.as_deref(), "timeout", settings.request_timeout_secs)? {
+        options.request_timeout = secs_to_duration(n);
+    }
+    if let Some(n) = form_limit(form.deadline.as_deref(), "deadline", settings.job_deadline_secs)? {
+        options.job_deadline = secs_to_duration(n);
+    }
+    if let Some(n) = form.max_attempts.as_deref().and_then(|s| s.trim().parse::<u32>().ok()) {
+        options.retry.max_attempts = n.clamp(1, MAX_ATTEMPTS_LIMIT);
+    }
+    if let Some(ua) = form.user_agent.as_deref().and_then(non_empty) {
+        options.user_agent = Some(ua);
+    }
+    if let Some(lines) = &form.headers {
+        options.headers = parse_header_lines(lines)?;
+    }
+    options.cookies = form.cookies.as_deref().and_then(non_empty);
+    options.credentials = form.credentials.as_deref().and_then(Credentials::parse);
+    if let Some(n) = form.host_delay_ms.as_deref().and_then(|s| s.trim().parse().ok()) {
+        options.host_delay = Duration::from_millis(n);
+    }
+    if let Some(n) = form_limit(form.max_response_mb.as_deref(), "max_response_mb", settings.max_response_mb)? {
+        options.max_response_bytes = mb_to_bytes(n);
+    }
+    // 0 はリダイレクトをたどらない (無制限ではない) ので設定の上限までにするだけ
+    if let Some(n) = form.max_redirects.as_deref().and_then(|s| s.trim().parse::<usize>().ok()) {
+        options.max_redirects = n.min(settings.max_redirects);
+    }
+    if form.no_cross_host_redirects.as_deref() == Some("true") {
+        options.cross_host_redirects = false;
+    }
+    if form.no_cache.as_deref() == Some("true") {
+        options.disk_cache = None;
+    }
+    if form.offline.as_deref() == Some("true") {
+        options.offline = true;
+    }
+    if form.save_html.as_deref() == Some("true") {
+        options.html_archive = Some(Arc::new(archive::HtmlArchive::in_memory()));
+    }
+    if form.warc.as_deref() == Some("true") {
+        options.warc = Some(Arc::new(warc::WarcWriter::in_memory(true)));
+    }
+    if let Some(date) = form.wayback_date.as_deref().and_then(non_empty) {
+        options.wayback.date = Some(wayback::parse_date(&date)?);
+    }
+    if form.wayback_fallback.as_deref() == Some("true") {
+        options.wayback.fallback = true;
+    }
+    if form.render_js.as_deref() == Some("true") {
+        let mut render = options.render_defaults.clone();
+        if let Some(ms) = form.render_wait_ms.as_deref().and_then(|s| s.trim().parse::<u64>().ok()) {
+            render.wait = Duration::from_millis(ms.min(MAX_RENDER_WAIT_MS));
+        }
+        if let Some(selector) = form.wait_selector.as_deref().and_then(non_empty) {
+            render.wait_selector =
+                Some(Selector::parse(&selector).map_err(|e| format!("Invalid wait selector \"{selector}\": {e:?}"))?);
+        }
+        options.render = Some(render);
+    }
+    if let Some(scope) = form.subpage_scope.as_deref().and_then(|s| SubpageScope::from_str(s, true).ok()) {
+        options.subpage_scope = scope;
+    }
+    if let Some(mode) = form.dedup_subpages.as_deref().and_then(|s| dedup::DedupMode::from_str(s, true).ok()) {
+        options.dedup_subpages = mode;
+    }
+    options.ignore_robots = form.ignore_robots.as_deref() == Some("true");
+    options.skip_noindex = form.skip_noindex.as_deref() == Some("true");
+    if let Some(text) = &form.include_patterns {
+        options.link_include = parse_patterns(text)?;
+    }
+    if let Some(text) = &form.exclude_patterns {
+        options.link_exclude = parse_patterns(text)?;
+    }
+    if let Some(n) = form_limit(form.sitemap_max_urls.as_deref(), "sitemap_max_urls", settings.sitemap_max_urls)? {
+        options.sitemap_max_urls = n;
+    }
+    if let Some(format) = form.output_format.as_deref().and_then(|s| OutputFormat::from_str(s, true).ok()) {
+        options.output_format = format;
+    }
+    if options.offline && options.check_links {
+        return Err("Link checking is not available offline".to_string());
+    }
+    Ok(options)
+}
+
+/// フォーム・API から指定された上限 (設定の上限 max より大きい値は max にする。0 の無制限は設定ファイル・CLI だけなのでエラー)
+fn form_limit<T>(value: Option<&str>, name: &str, max: T) -> Result<Option<T>, String>
+where
+    T: std::str::FromStr + Ord + Default + Copy,
+{
+    let Some(n) = value.and_then(|s| s.trim().parse::<T>().ok()) else {
+        return Ok(None);
+    };
+    if n == T::default() {
+        return Err(format!("{name} must be at least 1"));
+    }
+    Ok(Some(if max == T::default() { n } else { n.min(max) }))
+}
+
+/// 改行区切りの URL リストを分割 (空行は除外)
+pub fn split_url_lines(text: &str) -> Vec<String> {
+    text.replace('\r', "")
+        .split('\n')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// フォームの入力 URL (貼り付けた HTML があればその URL を先頭に)
+pub fn form_urls(form: &UrlForm) -> Vec<String> {
+    let mut urls = split_url_lines(&form.urls);
+    if let Some(url) = raw_html_url(form).filter(|url| !urls.contains(url)) {
+        urls.insert(0, url);
+    }
+    urls
+}
+
+/// 貼り付けた HTML の URL (HTML がなければ None)
+fn raw_html_url(form: &UrlForm) -> Option<String> {
+    form.html.as_deref().filter(|html| !html.trim().is_empty())?;
+    Some(form.base_url.as_deref().and_then(non_empty).unwrap_or_else(|| RAW_HTML_URL.to_string()))
+}
+
+/// URL 群を並行で解析 (buffered なので入力順を保持)
+pub async fn run_job(url_list: &[String], options: &JobOptions) -> Result<Vec<DomContent>, reqwest::Error> {
+    let mut results = Vec::new();
+    run_job_each(url_list, options, |content| {
+        results.push(content);
+        std::future::ready(ControlFlow::Continue(()))
+    })
+    .await?;
+    Ok(results)
+}
+
+/// URL 群を並行で解析し、1 URL 分できるたびに (入力順で) emit に渡す
+///
+/// emit の Future が終わるまで次の URL は渡さず、Break を返したら残りの URL は取得しない。
+/// 戻り値は全体の集計 (集計を付ける指定時は最後に emit にも渡す)
+#[tracing::instrument(name = "job", skip_all, fields(job = options.progress.as_ref().map(|j| j.id.as_str()), urls = url_list.len()))]
+pub async fn run_job_each<F>(
+    url_list: &[String],
+    options: &JobOptions,
+    mut emit: impl FnMut(DomContent) -> F,
+) -> Result<summary::JobSummary, reqwest::Error>
+where
+    F: Future<Output = ControlFlow<()>>,
+{
+    let input_urls: Vec<Url> = url_list.iter().filter_map(|u| Url::parse(u).ok()).collect();
+    let fetcher = Fetcher::new(options, &input_urls)?;
+
+    // サイトマップはページ URL に展開 (失敗したものはエラーメッセージ付き)
+    let mut inputs: Vec<(String, Option<String>)> = Vec::new();
+    for url_str in url_list {
+        if sitemap::is_sitemap_url(url_str) {
+            match sitemap::expand(&fetcher, url_str, options.sitemap_max_urls).await {
+                Ok(pages) => inputs.extend(pages.into_iter().map(|page| (page, None))),
+                Err(e) => inputs.push((url_str.clone(), Some(format!("Sitemap error ({url_str}): {e}")))),
+            }
+        } else {
+            inputs.push((url_str.clone(), None));
+        }
+    }
+
+    // URL を正規化し、正規化した URL が先の入力と同じものは取得しない (貼り付けた HTML の URL はそのまま)
+    let mut summary = summary::JobSummary::default();
+    let mut firsts: HashMap<String, String> = HashMap::new();
+    let inputs: Vec<(String, Option<String>, Option<String>)> = inputs
+        .into_iter()
+        .map(|(url_str, sitemap_error)| {
+            let is_raw_html = options.raw_html.as_ref().is_some_and(|raw| raw.url == url_str);
+            let Some(url) = Url::parse(&url_str).ok().filter(|_| sitemap_error.is_none() && !is_raw_html) else {
+                return (url_str, sitemap_error, None);
+            };
+            let normalized = normalize::normalize(&url, options.strip_tracking_params);
+            if normalized.as_str() != url_str {
+                summary.normalized.push(summary::NormalizedUrl {
+                    url: url_str.clone(),
+                    normalized: normalized.to_string(),
+                });
+            }
+            match firsts.get(&normalize::dedup_key(&normalized)) {
+                Some(first) => (url_str, None, Some(first.clone())),
+                None => {
+                    firsts.insert(normalize::dedup_key(&normalized), normalized.to_string());
+                    (normalized.into(), None, None)
+                }
+            }
+        })
+        .collect();
+
+    tracing::info!("job started");
+    let started = Instant::now();
+    // canonical URL → 最初に出た入力 URL
+    let mut canonicals: HashMap<String, String> = HashMap::new();
+    // Elasticsearch に投入するドキュメント (最後にまとめて _bulk)
+    let mut search_documents = vec![];
+    let mut page_index = 0;
+    let mut baselines = match (options.diff_against, &options.history) {
+        (Some(baseline), Some(store)) => Some(diff::Baselines::new(store, baseline)),
+        _ => None,
+    };
+    let metrics = options.metrics.as_deref();
+    let _in_flight = metrics.map(metrics::Metrics::start_conversion);
+    let progress = options.progress.as_deref();
+    if let Some(job) = progress {
+        for (i, (url_str, _, _)) in inputs.iter().enumerate() {
+            job.progress(i, url_str, jobs::Stage::Queued, None);
+        }
+    }
+
+    let pages = stream::iter(inputs.into_iter().enumerate())
+        .map(|(i, (url_str, sitemap_error, duplicate_of))| {
+            let fetcher = &fetcher;
+            let span = tracing::info_span!("url", index = i, url = %url_str);
+            async move {
+                if let Some(first) = duplicate_of {
+                    if let Some(job) = progress {
+                        job.progress(i, &url_str, jobs::Stage::Parsed, None);
+                    }
+                    let failure = summary::FailedUrl::duplicate_url(&url_str, &first);
+                    return (url_str, duplicate_url_page(&first), Some(failure), false);
+                }
+                // 再開したジョブで前回までに終わった URL は取得し直さない
+                if let Some((job, saved)) = progress.and_then(|job| Some((job, job.take_restored(&url_str)?))) {
+                    match error_message(&saved.content) {
+                        Some(message) => job.progress(i, &url_str, jobs::Stage::Error, Some(message)),
+                        None => job.progress(i, &url_str, jobs::Stage::Parsed, None),
+                    }
+                    return (url_str, saved.content, saved.failure, true);
+                }
+                if let Some(job) = progress {
+                    // 一時停止中は再開されるまで次の URL を始めない
+                    job.wait_resumed().await;
+                    job.progress(i, &url_str, jobs::Stage::Fetching, None);
+                }
+                // 1階層リンク先が 1 件終わるたびに件数を進捗に
+                let subpages_done = AtomicUsize::new(0);
+                let on_subpage = || {
+                    if let Some(job) = progress {
+                        job.subpages_done(i, &url_str, subpages_done.fetch_add(1, Ordering::Relaxed) + 1);
+                    }
+                };
+                let content = match sitemap_error {
+                    None => convert_url(fetcher, &url_str, options, &on_subpage).await,
+                    Some(message) => error_node("ErrorSitemap", message),
+                };
+                if let Some(tag) = error_tag(&content) {
+                    tracing::warn!(tag, error = error_message(&content).as_deref(), "url failed");
+                    if let Some(metrics) = metrics {
+                        metrics.record_error(tag);
+                    }
+                }
+                if let Some(job) = progress {
+                    match error_message(&content) {
+                        Some(message) => job.progress(i, &url_str, jobs::Stage::Error, Some(message)),
+                        None => job.progress(i, &url_str, jobs::Stage::Parsed, None),
+                    }
+                }
+                let failure = error_tag(&content)
+                    .map(|tag| summary::FailedUrl::new(&url_str, tag, error_message(&content).unwrap_or_default()));
+                (url_str, content, failure, false)
+            }
+            .instrument(span)
+        })
+        .buffered(options.concurrency);
+    let mut pages = std::pin::pin!(pages);
+    let mut stopped = false;
+    while let Some((url_str, mut content, mut failure, restored)) = pages.next().await {
+        // canonical URL (なければ URL) が先に出たページと同じなら参照ノードに (入力順で最初の 1 件を残す)
+        if options.dedup_canonical && failure.is_none() {
+            let key = canonical_url(&content).unwrap_or(&url_str).to_string();
+            match canonicals.get(&key) {
+                Some(first) => {
+                    failure = Some(summary::FailedUrl::duplicate(&url_str, first));
+                    content = duplicate_page(first, key);
+                }
+                None => {
+                    canonicals.insert(key, url_str.clone());
+                }
+            }
+        }
+        // 履歴の結果との差分に置き換え (基準がなければエラー。読み戻した結果は置き換え済み)
+        if let (Some(baselines), None, false) = (&mut baselines, &failure, restored) {
+            match baselines.diff_page(&url_str, &content) {
+                Ok(diff) => content = DomContent::Diff(Box::new(diff)),
+                Err(message) => {
+                    failure = Some(summary::FailedUrl::new(&url_str, "ErrorDiff", message.clone()));
+                    content = error_node("ErrorDiff", message);
+                }
+            }
+        }
+        if let (Some(search), None) = (&options.search_index, &failure) {
+            search_documents.extend(search.documents(page_index, &url_str, &content));
+        }
+        if let (Some(job), false) = (progress, restored) {
+            job.save_page(&url_str, &content, failure.as_ref());
+        }
+        page_index += 1;
+        if options.typed_results {
+            content = DomContent::Result(Box::new(url_result::from_content(&url_str, content)));
+        }
+        summary.record(failure);
+        if let Some(job) = progress {
+            job.add_page(&url_str, content.clone());
+        }
+        // 書き出し先が閉じたら残りの URL は取得しない
+        if emit(content).await.is_break() {
+            tracing::info!("output closed, stopping job");
+            stopped = true;
+            break;
+        }
+    }
+    if let Some(search) = &options.search_index {
+        summary.index = Some(search.bulk(search_documents, options.user_agent.as_deref()).await);
+    }
+    summary.bytes = fetcher.downloaded();
+    summary.duration_ms = started.elapsed().as_millis() as u64;
+    tracing::info!(
+        succeeded = summary.succeeded,
+        failed = summary.failed,
+        skipped = summary.skipped,
+        bytes = summary.bytes,
+        duration_ms = summary.duration_ms,
+        "job finished"
+    );
+    if let Some(job) = progress {
+        job.set_summary(summary.clone());
+    }
+    if options.include_summary
+        && !stopped
+        && emit(DomContent::Summary(summary::SummaryBlock { summary: summary.clone() })).await.is_break()
+    {
+        tracing::info!("output closed before the summary");
+    }
+    Ok(summary)
+}
+
+/// HTML ページの canonical URL
+fn canonical_url(content: &DomContent) -> Option<&str> {
+    match content {
+        DomContent::Node(node) => node.page_meta.as_ref()?.canonical.as_deref(),
+        _ => None,
+    }
+}
+
+/// canonical URL が重複したページの代わりの参照ノード {"tag": "duplicate", "href": 最初に出た URL, "text"}
+fn duplicate_page(first_url: &str, canonical: String) -> DomContent {
+    DomContent::Node(DomNode {
+        tag: Some("duplicate".to_string()),
+        href: Some(first_url.to_string()),
+        attrs: None,
+        text: Some(format!("Same canonical URL as {first_url}")),
+        children: vec![],
+        link_subpage: None,
+        page_meta: Some(Box::new(meta::PageMeta {
+            canonical: Some(canonical),
+            ..Default::default()
+        })),
+        structured_data: None,
+    })
+}
+
+/// 正規化した URL が先の入力と同じ URL の代わりの参照ノード {"tag": "duplicate", "href": 最初の URL, "text"}
+fn duplicate_url_page(first_url: &str) -> DomContent {
+    DomContent::Node(DomNode {
+        tag: Some("duplicate".to_string()),
+        href: Some(first_url.to_string()),
+        attrs: None,
+        text: Some(format!("Same URL as {first_url}")),
+        children: vec![],
+        link_subpage: None,
+        page_meta: None,
+        structured_data: None,
+    })
+}
+
+/// エラーノードならそのメッセージ
+fn error_message(content: &DomContent) -> Option<String> {
+    match content {
+        DomContent::Node(node) if error_tag(content).is_some() => Some(node.text.clone().unwrap_or_default()),
+        _ => None,
+    }
+}
+
+/// エラーノードならそのタグ (ErrorFetch など)
+fn error_tag(content: &DomContent) -> Option<&str> {
+    match content {
+        DomContent::Node(node) => node.tag.as_deref().filter(|t| t.starts_with("Error")),
+        _ => None,
+    }
+}
+
+/// エラー用の擬似ノード
+pub fn error_node(tag: &str, message: String) -> DomContent {
+    DomContent::Node(DomNode {
+        tag: Some(tag.to_string()),
+        href: None,
+        attrs: None,
+        text: Some(message),
+        children: vec![],
+        link_subpage: None,
+        page_meta: None,
+        structured_data: None,
+    })
+}
+
+/// URL を 1 つ取得して変換 (サイトマップの展開・集計などは run_job)
+///
+/// 取得・解析の失敗は Err (kind は型付きの結果と同じ)
+pub async fn extract_url(url: &str, options: &JobOptions) -> Result<DomContent, url_result::UrlError> {
+    let input_urls: Vec<Url> = Url::parse(url).into_iter().collect();
+    let fetcher = Fetcher::new(options, &input_urls).map_err(|e| url_result::UrlError {
+        kind: "fetch".to_string(),
+        message: format!("HTTP client error: {e}"),
+        fetch: None,
+    })?;
+    match url_result::from_content(url, convert_url(&fetcher, url, options, &|| {}).await) {
+        url_result::UrlResult::Document { document, .. } => Ok(document),
+        url_result::UrlResult::Error { error, .. } => Err(error),
+    }
+}
+
+/// HTML を取得せずに変換 (page_url は相対リンクの基準。使うのは options.parse だけで、1階層リンク先はたどらない)
+pub fn extract_html(html: &str, page_url: &Url, options: &JobOptions) -> DomContent {
+    parse_html_sync(html, &options.parse, page_url)
+}
+
+/// 1 URL 分の取得～解析 (on_subpage は 1階層リンク先を 1 件取得し終えるたびに呼ぶ)
+async fn convert_url(fetcher: &Fetcher, url_str: &str, options: &JobOptions, on_subpage: &(dyn Fn() + Sync)) -> DomContent {
+    let Ok(parsed_url) = Url::parse(url_str) else {
+        // URL parse エラー
+        return error_node("ErrorURL", format!("URL parse error: {url_str}"));
+    };
+
+    let fetched = match options.raw_html.as_ref().filter(|raw| raw.url == url_str) {
+        // 貼り付けた HTML は取得しない
+        Some(raw) => fetch::Fetched {
+            content_type: Some("text/html".to_string()),
+            body: fetch::Body::Text(raw.html.clone()),
+            info: None,
+        },
+        // HTTP GET (描画指定時はブラウザで)
+        None => match get_page(fetcher, &parsed_url, options).await {
+            Ok(f) => f,
+            Err(e) => return e,
+        },
+    };
+    // スナップショットを使ったときは取得元が分かるよう常に付ける
+    let info = fetched.info.clone().filter(|info| options.include_fetch_info || info.wayback.is_some());
+    // プラグインには本文 (HTML・フィード等のテキスト) も渡す
+    let body = match (&options.plugin, &fetched.body) {
+        (Some(_), fetch::Body::Text(text)) => Some(text.clone()),
+        _ => None,
+    };
+    let mut content = convert_fetched(fetcher, fetched, parsed_url, options, on_subpage).await;
+    if let Some(info) = info {
+        attach_fetch_info(&mut content, info);
+    }
+    if options.select.is_some() || options.plugin.is_some() || options.script.is_some() || options.filter.is_some() {
+        let (select, plugin) = (options.select.clone(), options.plugin.clone());
+        let (script, filter) = (options.script.clone(), options.filter.clone());
+        let url = url_str.to_string();
+        content = match spawn_blocking(move || post_process(&url, content, body.as_deref(), select, plugin, script, filter)).await {
+            Ok(content) => content,
+            Err(e_spawn) => error_node("ErrorSpawnBlock", format!("spawn_blocking error: {e_spawn:?}")),
+        };
+    }
+    content
+}
+
+/// 部分木の選択 → WASM プラグイン → 後処理スクリプト → フィルタの順に結果を置き換える (エラーのノードはそのまま)
+fn post_process(
+    url: &str,
+    content: DomContent,
+    body: Option<&str>,
+    select: Option<subtree::SubtreePath>,
+    plugin: Option<plugin::Plugin>,
+    script: Option<script::Script>,
+    filter: Option<filter::Filter>,
+) -> DomContent {
+    let content = match select {
+        Some(path) if error_tag(&content).is_none() => path.select(content),
+        _ => content,
+    };
+    let content = match plugin {
+        Some(plugin) if error_tag(&content).is_none() => plugin.transform(url, content, body),
+        _ => content,
+    };
+    let content = match script {
+        Some(script) if error_tag(&content).is_none() => script.apply(url, content),
+        _ => content,
+    };
+    match filter {
+        Some(filter) if error_tag(&content).is_none() => filter.apply(&content),
+        _ => content,
+    }
+}
+
+/// 取得した本文の解析 (HTML・PDF・フィード・リソース)
+async fn convert_fetched(
+    fetcher: &Fetcher,
+    fetched: fetch::Fetched,
+    parsed_url: Url,
+    options: &JobOptions,
+    on_subpage: &(dyn Fn() + Sync),
+) -> DomContent {
+    // JSON・バイナリは HTML として解析しない
+    if let Some(resource) = resource_content(&fetched) {
+        return resource;
+    }
+    let content_type = fetched.content_type;
+    let resp_body = match fetched.body {
+        fetch::Body::Text(text) => text,
+        fetch::Body::Pdf(bytes) => return parse_pdf_blocking(bytes).await,
+        fetch::Body::Binary { .. } => return error_node("ErrorFetch", "Unexpected binary body".to_string()),
+    };
+
+    // RSS / Atom はフィードとして解析
+    if feed::looks_like_feed(&resp_body) {
+        let mut feed_data = match feed::parse_feed(&resp_body) {
+            Ok(f) => f,
+            Err(e) => return error_node("ErrorFeed", e),
+        };
+        if options.follow_feed_items {
+            feed::fetch_articles(fetcher, &mut feed_data, &parsed_url, options).await;
+        }
+        return DomContent::Feed(feed_data);
+    }
+
+    // 同期パース (1階層リンク先をたどる場合は rel="nofollow" のリンクだけのリンク先も)
+    let parse_options = options.parse.clone();
+    let page_url = parsed_url.clone();
+    let find_nofollow = options.include_subpages && !options.ignore_robots && !options.parse.link_graph;
+    let parse_started = Instant::now();
+    let parsed = spawn_blocking(move || {
+        let doc = Html::parse_document(&resp_body);
+        let nofollow = if find_nofollow {
+            links::nofollow_only(&doc, meta::base_url(&doc, &page_url).as_ref().unwrap_or(&page_url))
+        } else {
+            HashSet::new()
+        };
+        (parse_document(&doc, &parse_options, &page_url), nofollow)
+    })
+    .await;
+    let (mut root_content, nofollow) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e_spawn) => return error_node("ErrorSpawnBlock", format!("spawn_blocking error: {e_spawn:?}")),
+    };
+    if let Some(metrics) = &options.metrics {
+        metrics.record_parse(parse_started.elapsed());
+    }
+
+    if let (DomContent::Node(root), Some(content_type)) = (&mut root_content, content_type) {
+        root.page_meta.get_or_insert_with(Default::default).content_type = Some(content_type);
+    }
+
+    // リンク切れチェックはリンク先を埋め込まずにステータスだけ
+    if let (true, DomContent::Links(page)) = (options.check_links, &mut root_content) {
+        links::check_page(fetcher, page, options.concurrency).await;
+        return root_content;
+    }
+
+    // meta robots が nofollow のページのリンクはたどらない
+    let page_nofollow = !options.ignore_robots
+        && match &root_content {
+            DomContent::Node(root) => root.page_meta.as_ref().is_some_and(|m| m.nofollow()),
+            DomContent::Links(page) => meta::has_directive(&page.robots, "nofollow"),
+            _ => false,
+        };
+    if page_nofollow {
+        tracing::debug!(url = %parsed_url, "not following links (meta robots nofollow)");
+    }
+
+    // サブページ (リンクグラフはリンク先のリンクを subpages に)
+    let include_subpages = options.include_subpages && !page_nofollow;
+    if let (true, DomContent::Links(page)) = (include_subpages, &mut root_content) {
+        fetch_link_subpages(fetcher, options, page, &parsed_url, on_subpage).await;
+    } else if include_subpages {
+        // リンクは <base href> (なければページの URL) を基準に解決
+        let link_base = match &root_content {
+            DomContent::Node(root) => root.page_meta.as_ref().and_then(|m| m.base.as_deref()),
+            _ => None,
+        }
+        .and_then(|base| Url::parse(base).ok())
+        .unwrap_or_else(|| parsed_url.clone());
+        fetch_subpages_for_depth_one(fetcher, options, &mut root_content, &parsed_url, &link_base, &nofollow, on_subpage).await;
+        dedup::dedup_subpages(&mut root_content, &parsed_url, &link_base, options.dedup_subpages);
+    }
+
+    root_content
+}
+
+/// 取得の情報を結果に付ける (抽出ルールの結果には付けない)
+fn attach_fetch_info(content: &mut DomContent, info: fetch::FetchInfo) {
+    match content {
+        DomContent::Node(node) => node.page_meta.get_or_insert_with(Default::default).fetch = Some(info),
+        DomContent::Feed(feed) => feed.fetch = Some(info),
+        DomContent::Resource(resource) => resource.fetch = Some(info),
+        _ => {}
+    }
+}
+
+/// HTML 以外のレスポンスの出力 (HTML として解析するものは None)
+fn resource_content(fetched: &fetch::Fetched) -> Option<DomContent> {
+    Some(match resource::from_fetched(fetched)? {
+        Ok(resource) => DomContent::Resource(resource),
+        Err(e) => error_node("ErrorJson", e),
+    })
+}
+
+/// ページの取得 (描画指定時はヘッドレスブラウザの DOM, 失敗はエラーノード)
+///
+/// 保存の指定があれば、HTML として解析する本文を保存する
+async fn get_page(fetcher: &Fetcher, url: &Url, options: &JobOptions) -> Result<fetch::Fetched, DomContent> {
+    let fetched = fetch_page(fetcher, url, options).await?;
+    if let (Some(archive), fetch::Body::Text(html)) = (&options.html_archive, &fetched.body) {
+        if !fetched.content_type.as_deref().is_some_and(fetch::is_json_type) {
+            archive.add(url, html).await;
+        }
+    }
+    Ok(fetched)
+}
+
+async fn fetch_page(fetcher: &Fetcher, url: &Url, options: &JobOptions) -> Result<fetch::Fetched, DomContent> {
+    match &options.render {
+        // ローカルファイルは読める範囲を確かめるため、オフラインでは保存したページを使うため描画しない
+        Some(render) if url.scheme() != "file" && !options.offline => render::render(url, render, options.user_agent.as_deref(), options.request_timeout)
+            .await
+            .map(|html| fetch::Fetched {
+                content_type: Some("text/html".to_string()),
+                body: fetch::Body::Text(html),
+                info: None,
+            })
+            .map_err(|e| error_node("ErrorRender", e)),
+        _ => fetcher.get(url).await.map_err(|e| error_node(e.error_tag(), e.to_string())),
+    }
+}
+
+/// PDF のテキスト抽出 (別スレッドで)
+async fn parse_pdf_blocking(bytes: Vec<u8>) -> DomContent {
+    spawn_blocking(move || pdf::parse_pdf(&bytes))
+        .await
+        .unwrap_or_else(|e| error_node("ErrorPdf", format!("PDF parse error: {e}")))
+}
+
+/// HTMLを解析 (同期)
+fn parse_html_sync(body: &str, options: &ParseOptions, page_url: &Url) -> DomContent {
+    parse_document(&Html::parse_document(body), options, page_url)
+}
+
+fn parse_document(doc: &Html, options: &ParseOptions, page_url: &Url) -> DomContent {
+    // 相対 URL は <base href> があればそれを基準に
+    let base = meta::base_url(doc, page_url);
+    let document_url = page_url;
+    let page_url = base.as_ref().unwrap_or(page_url);
+    if !options.rules.is_empty() {
+        return DomContent::Extracted(rules::extract(doc, &options.rules, page_url));
+    }
+    if options.link_graph {
+        return DomContent::Links(Box::new(links::extract(doc, document_url, page_url)));
+    }
+    let sel_html = Selector::parse("html").unwrap();
+    if let Some(html_el) = doc.select(&sel_html).next() {
+        // 本文モードでは推定した本文要素の中だけを変換 (見つからなければ全体)
+        let content_root = if options.main_content_only {
+            readability::find_main_content(doc).unwrap_or(html_el)
+        } else {
+            html_el
+        };
+        let mut page_meta = meta::extract_page_meta(doc, page_url);
+        if let Some(base) = &base {
+            page_meta.get_or_insert_with(Default::default).base = Some(base.to_string());
+        }
+        let mut content = DomContent::Node(DomNode {
+            tag: Some("html".to_string()),
+            href: None,
+            attrs: None,
+            text: None,
+            children: parse_children(content_root, options, page_url),
+            link_subpage: None,
+            page_meta: page_meta.map(Box::new),
+            structured_data: structured::extract_structured_data(doc),
+        });
+        let lang_attr = html_el
+            .value()
+            .attr("lang")
+            .or_else(|| html_el.value().attr("xml:lang"))
+            .and_then(language::from_lang_attr);
+        let text = (lang_attr.is_none() || options.include_stats).then(|| plaintext::page_to_text(&content, false));
+        let page_stats = options.include_stats.then(|| stats::page_stats(&content, text.as_deref().unwrap_or("")));
+        // lang 属性がなければ抽出したテキストから推定
+        let language = lang_attr.or_else(|| text.as_deref().and_then(language::detect));
+        if let DomContent::Node(node) = &mut content {
+            if language.is_some() || page_stats.is_some() {
+                let page_meta = node.page_meta.get_or_insert_with(Default::default);
+                page_meta.language = language;
+                page_meta.stats = page_stats;
+            }
+        }
+        content
+    } else {
+        DomContent::Node(DomNode {
+            tag: Some("html".to_string()),
+            href: None,
+            attrs: None,
+            text: Some("(No <html> found)".to_string()),
+            children: vec![],
+            link_subpage: None,
+            page_meta: None,
+            structured_data: None,
+        })
+    }
+}
+
+/// 子を解析 (page_url は相対 URL の解決用)
+///
+/// 深い入れ子の HTML でスタックを使い切らないよう、再帰せずに要素のスタックでたどる。
+/// ノードの入れ子が options.max_depth に達したら、それより深い要素は中身だけ取り出す (0 で無制限)。
+fn parse_children(el: ElementRef, options: &ParseOptions, page_url: &Url) -> Vec<DomContent> {
+    // たどっている要素ごとの (残りの子, 変換した子, 子を変換し終えたら作るノード。None なら中身だけ親に)
+    let mut stack: Vec<(Children<Node>, Vec<DomContent>, Option<DomNode>)> = vec![(el.children(), Vec::new(), None)];
+    // 作りかけのノードの数 (出力の入れ子の深さ)
+    let mut depth = 0;
+
+    while let Some(frame) = stack.last_mut() {
+        let Some(child) = frame.0.next() else {
+            // 子を変換し終えた要素を親の結果に (el 自身ならそれが結果)
+            let (_, result, node) = stack.pop().expect("frame on the stack");
+            let Some(parent) = stack.last_mut() else {
+                return result;
+            };
+            match node {
+                Some(mut node) => {
+                    depth -= 1;
+                    node.children = result;
+                    parent.1.push(DomContent::Node(node));
+                }
+                None => parent.1.extend(result),
+            }
+            continue;
+        };
+        let result = &mut frame.1;
+        // 子をたどる要素 (と作るノード)
+        let mut descend: Option<(ElementRef, Option<DomNode>)> = None;
+
+        match child.value() {
+            Node::Element(e) => {
+                let tag_name = e.name().to_lowercase();
+                if options.skip_tags.contains(&tag_name) {
+                    continue;
+                }
+                if tag_name == "img" {
+                    if options.include_images {
+                        result.push(DomContent::Image(images::parse_image(e, page_url)));
+                    }
+                }
+                // 代替テキストは出力しない
+                else if (tag_name == "video" || tag_name == "audio") && options.include_media {
+                    if let Some(media_el) = ElementRef::wrap(child) {
+                        result.push(DomContent::Media(media::parse_media(media_el, page_url)));
+                    }
+                }
+                // フォームは構造を出力した上で、中身も通常どおり変換
+                else if tag_name == "form" && options.include_forms {
+                    if let Some(form_el) = ElementRef::wrap(child) {
+                        result.push(DomContent::Form(forms::parse_form(form_el, page_url)));
+                        descend = Some((form_el, None));
+                    }
+                }
+                else if tag_name == "pre" || tag_name == "code" {
+                    if let Some(code_el) = ElementRef::wrap(child) {
+                        result.push(DomContent::Code(code::parse_code(code_el)));
+                    }
+                }
+                else if tag_name == "table" {
+                    if let Some(tbl) = ElementRef::wrap(child) {
+                        let table_data = parse_table(tbl, &options.table);
+                        result.push(DomContent::Table(table_data));
+                    }
+                }
+                else if options.target_tags.contains(&tag_name) && (options.max_depth == 0 || depth < options.max_depth) {
+                    // a, p, h*, etc
+                    let mut link = None;
+                    if tag_name == "a" {
+                        for (attr_name, attr_value) in e.attrs() {
+                            if attr_name.eq_ignore_ascii_case("href") {
+                                link = Some(if options.absolute_hrefs {
+                                    page_url
+                                        .join(attr_value.trim())
+                                        .map(String::from)
+                                        .unwrap_or_else(|_| attr_value.to_string())
+                                } else {
+                                    attr_value.to_string()
+                                });
+                            }
+                        }
+                    }
+                    if let Some(sub_el) = ElementRef::wrap(child) {
+                        depth += 1;
+                        descend = Some((
+                            sub_el,
+                            Some(DomNode {
+                                tag: Some(tag_name),
+                                href: link,
+                                attrs: captured_attrs(e, &options.capture_attributes),
+                                text: None,
+                                children: vec![],
+                                link_subpage: None,
+                                page_meta: None,
+                                structured_data: None,
+                            }),
+                        ));
+                    }
+                }
+                else {
+                    // 中身だけ取り出す
+                    descend = ElementRef::wrap(child).map(|sub_el| (sub_el, None));
+                }
+            }
+            Node::Text(txt_node) => {
+                let c = clean_text(&txt_node.text);
+                if !c.is_empty() {
+                    result.push(DomContent::Node(DomNode {
+                        tag: None,
+                        href: None,
+                        attrs: None,
+                        text: Some(c),
+                        children: vec![],
+                        link_subpage: None,
+                        page_meta: None,
+                        structured_data: None,
+                    }));
+                }
+            }
+            _ => {}
+        }
+
+        if let Some((sub_el, node)) = descend {
+            stack.push((sub_el.children(), Vec::new(), node));
+        }
+    }
+    vec![]
+}
+
+/// テーブル解析
+fn parse_table(table_el: ElementRef, options: &TableOptions) -> TableData {
+    // 先頭から続く見出し行の数
+    let header_count = |grid: &[GridRow]| match options.headers {
+        TableHeaders::Auto => grid.iter().take_while(|r| r.is_header()).count(),
+        TableHeaders::FirstRow => grid.len().min(1),
+        TableHeaders::None => 0,
+    };
+    // 上限を超えた行はセルを展開しない (数だけ数える)
+    let (grid, skipped) =
+        table_grid(table_el, |grid| options.max_rows > 0 && grid.len() - header_count(grid) >= options.max_rows);
+    let header_count = header_count(&grid);
+    let header_rows = &grid[..header_count];
+    let headers = if options.join_header_rows {
+        compound_names(header_rows)
+    } else {
+        header_rows.last().map(|r| r.cells.clone()).unwrap_or_default()
+    };
+    // 配列の行では列の位置で対応するので見出しの重複はそのまま
+    let original_headers = headers.clone();
+    let headers = if options.array_rows { headers } else { unique_names(headers) };
+
+    let cell_value = |text: &String| {
+        if options.infer_types {
+            celltypes::infer(text)
+        } else {
+            serde_json::Value::String(text.clone())
+        }
+    };
+    let header_names: HashSet<&str> = headers.iter().map(String::as_str).collect();
+    let data_rows = &grid[header_count..];
+    let truncated = skipped > 0;
+    let rows = data_rows
+        .iter()
+        .map(|row| {
+            if options.array_rows || (headers.is_empty() && options.headerless_arrays) {
+                return serde_json::Value::Array(row.cells.iter().map(cell_value).collect());
+            }
+            // データ行 (ヘッダのない列は colN)
+            let mut obj_map = serde_json::Map::new();
+            for (i, val) in row.cells.iter().enumerate() {
+                let col_name = if i < headers.len() {
+                    headers[i].clone()
+                } else {
+                    extra_column_name(i, &header_names)
+                };
+                obj_map.insert(col_name, cell_value(val));
+            }
+            serde_json::Value::Object(obj_map)
+        })
+        .collect();
+
+    TableData {
+        rows,
+        truncated,
+        total_rows: truncated.then_some(data_rows.len() + skipped),
+        original_headers: (headers != original_headers).then_some(original_headers),
+        table_headers: headers,
+    }
+}
+
+/// 複数の見出し行を列ごとに "上 / 下" でつなぐ (rowspan で同じ値が続く場合は 1 つに)
+fn compound_names(header_rows: &[GridRow]) -> Vec<String> {
+    let width = header_rows.iter().map(|r| r.cells.len()).max().unwrap_or(0);
+    (0..width)
+        .map(|i| {
+            let mut parts: Vec<&str> = Vec::new();
+            for cell in header_rows.iter().filter_map(|r| r.cells.get(i)) {
+                if !cell.is_empty() && parts.last() != Some(&cell.as_str()) {
+                    parts.push(cell);
+                }
+            }
+            parts.join(" / ")
+        })
+        .collect()
+}
+
+/// 展開済みの 1 行
+struct GridRow {
+    cells: Vec<String>,
+    /// <thead> 内の行
+    in_thead: bool,
+    /// 行内のセルがすべて <th>
+    all_th: bool,
+}
+
+impl GridRow {
+    fn is_header(&self) -> bool {
+        self.in_thead || self.all_th
+    }
+}
+
+/// 重複した列名に連番を付け、空の列名を colN にする
+///
+/// 元からある名前はその列 (重複なら最初の列) に残し、連番・colN はそれと衝突させない
+fn unique_names(names: Vec<String>) -> Vec<String> {
+    let original: std::collections::HashSet<String> = names.iter().cloned().collect();
+    let mut used = std::collections::HashSet::new();
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let generated = name.trim().is_empty();
+            let base = if generated { format!("col{i}") } else { name };
+            let mut candidate = base.clone();
+            let mut count = 1;
+            while used.contains(&candidate) || ((generated || count > 1) && original.contains(&candidate)) {
+                count += 1;
+                candidate = format!("{base}_{count}");
+            }
+            used.insert(candidate.clone());
+            candidate
+        })
+        .collect()
+}
+
+/// 見出しのない列のキー colN (同じ名前の見出しがあれば colN_2, colN_3 ...)
+fn extra_column_name(i: usize, headers: &HashSet<&str>) -> String {
+    let base = format!("col{i}");
+    let mut candidate = base.clone();
+    let mut count = 1;
+    while headers.contains(candidate.as_str()) {
+        count += 1;
+        candidate = format!("{base}_{count}");
+    }
+    candidate
+}
+
+/// colspan の上限 (HTML の仕様と同じ)
+const MAX_COLSPAN: usize = 1000;
+/// rowspan の上限 (HTML の仕様と同じ)
+const MAX_ROWSPAN: usize = 65534;
+/// 1 つのテーブルで展開するセルの数の上限 (colspan / rowspan で同じテキストが繰り返されるため)
+const MAX_TABLE_CELLS: usize = 1_000_000;
+/// 1 つのテーブルで展開するセルのテキストの合計の上限 (バイト)
+const MAX_TABLE_TEXT_BYTES: usize = 64 * 1024 * 1024;
+
+/// 展開したセルの数とテキストの合計
+#[derive(Default)]
+struct GridBudget {
+    cells: usize,
+    bytes: usize,
+}
+
+impl GridBudget {
+    /// セルを 1 つ展開できるか (できるなら数える)
+    fn take(&mut self, text: &str) -> bool {
+        if self.cells >= MAX_TABLE_CELLS || self.bytes + text.len() > MAX_TABLE_TEXT_BYTES {
+            return false;
+        }
+        self.cells += 1;
+        self.bytes += text.len();
+        true
+    }
+}
+
+/// colspan / rowspan を展開した論理的なセルの格子 (入れ子のテーブルの行・セルと空の行は含めない)
+///
+/// stop が true を返すか、展開したセルが上限 (MAX_TABLE_CELLS・MAX_TABLE_TEXT_BYTES) に達したら
+/// それ以降の行は展開せず、空でない行の数だけを返す
+fn table_grid(table_el: ElementRef, stop: impl Fn(&[GridRow]) -> bool) -> (Vec<GridRow>, usize) {
+    let tr_sel = Selector::parse("tr").unwrap();
+    let nearest_table = |el: ElementRef| {
+        el.ancestors()
+            .filter_map(ElementRef::wrap)
+            .find(|a| a.value().name() == "table")
+            .map(|a| a.id())
+    };
+    let span = |cell: ElementRef, name: &str, max: usize| {
+        cell.value()
+            .attr(name)
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            // rowspan="0" は表の終わりまで
+            .map(|n| if n == 0 { max } else { n.min(max) })
+            .unwrap_or(1)
+    };
+
+    let mut grid = Vec::new();
+    // 列ごとの (残り行数, テキスト)
+    let mut pending: Vec<(usize, String)> = Vec::new();
+    // 打ち切った後の空でない行の数と、rowspan で埋まる残りの行数
+    let mut stopped = false;
+    let mut skipped = 0;
+    let mut spanned = 0usize;
+    let mut budget = GridBudget::default();
+    for tr_el in table_el.select(&tr_sel).filter(|tr| nearest_table(*tr) == Some(table_el.id())) {
+        let cells: Vec<ElementRef> = tr_el
+            .children()
+            .filter_map(ElementRef::wrap)
+            .filter(|c| matches!(c.value().name(), "td" | "th"))
+            .collect();
+        if stopped {
+            if !cells.is_empty() || spanned > 0 {
+                skipped += 1;
+            }
+            let row_span = cells.iter().map(|c| span(*c, "rowspan", MAX_ROWSPAN)).max().unwrap_or(0);
+            spanned = spanned.saturating_sub(1).max(row_span.saturating_sub(1));
+            continue;
+        }
+        let mut row: Vec<String> = Vec::new();
+        let all_th = !cells.is_empty() && cells.iter().all(|c| c.value().name() == "th");
+        let in_thead = tr_el
+            .parent()
+            .and_then(ElementRef::wrap)
+            .is_some_and(|p| p.value().name() == "thead");
+        let mut full = false;
+        'cells: for cell_el in cells {
+            if !fill_spans(&mut row, &mut pending, &mut budget) {
+                full = true;
+                break;
+            }
+            let text = clean_text(&cell_el.text().collect::<String>());
+            let colspan = span(cell_el, "colspan", MAX_COLSPAN);
+            let rowspan = span(cell_el, "rowspan", MAX_ROWSPAN);
+            for _ in 0..colspan {
+                if !budget.take(&text) {
+                    full = true;
+                    break 'cells;
+                }
+                let col = row.len();
+                if pending.len() <= col {
+                    pending.resize(col + 1, (0, String::new()));
+                }
+                pending[col] = (rowspan - 1, text.clone());
+                row.push(text.clone());
+            }
+        }
+        // 行末に残った上の行からの rowspan (間の空き列は空文字)
+        while !full {
+            if !fill_spans(&mut row, &mut pending, &mut budget) {
+                full = true;
+                break;
+            }
+            let more = pending.get(row.len()..).is_some_and(|rest| rest.iter().any(|(r, _)| *r > 0));
+            if !more {
+                break;
+            }
+            if !budget.take("") {
+                full = true;
+                break;
+            }
+            row.push(String::new());
+        }
+        // 上限に達した行は途中までで出力せず、この行から打ち切る
+        if full {
+            stopped = true;
+            skipped += 1;
+            spanned = pending.iter().map(|(r, _)| *r).max().unwrap_or(0);
+            continue;
+        }
+        if !row.is_empty() {
+            grid.push(GridRow { cells: row, in_thead, all_th });
+            if stop(&grid) {
+                stopped = true;
+                spanned = pending.iter().map(|(r, _)| *r).max().unwrap_or(0);
+            }
+        }
+    }
+    (grid, skipped)
+}
+
+/// 上の行から rowspan で続いているセルを row の末尾に埋める (展開の上限に達したら false)
+fn fill_spans(row: &mut Vec<String>, pending: &mut [(usize, String)], budget: &mut GridBudget) -> bool {
+    while let Some((remaining, text)) = pending.get_mut(row.len()).filter(|(r, _)| *r > 0) {
+        if !budget.take(text) {
+            return false;
+        }
+        row.push(text.clone());
+        *remaining -= 1;
+    }
+    true
+}
+
+/// aタグ => link_subpage (href は link_base、たどる範囲は base_url のページを基準に判定)
+///
+/// リンクの位置を集めてから同時に取得し (同時取得数はジョブ全体・ホストごとの上限まで)、
+/// 取得できたものを元の位置に付ける。同じ URL はジョブ内で 1 回だけ取得し、同じ結果を付ける
+///
+/// 取得する URL の数の上限 (ページごと・ジョブ全体) を超えたリンクには not_fetched ノードを付ける
+///
+/// nofollow は rel="nofollow" のリンクしかない URL (たどらない)
+async fn fetch_subpages_for_depth_one(
+    fetcher: &Fetcher,
+    options: &JobOptions,
+    content: &mut DomContent,
+    base_url: &Url,
+    link_base: &Url,
+    nofollow: &HashSet<String>,
+    on_subpage: &(dyn Fn() + Sync),
+) {
+    let targets: Vec<(NodePosition, Url)> = subpage_links(content, link_base, |sub_url| {
+        links::is_followable_scheme(base_url, sub_url)
+            && options.should_follow(base_url, sub_url)
+            && !nofollow.contains(sub_url.as_str())
+    })
+    .into_iter()
+    .map(|(position, sub_url)| (position, normalize::normalize(&sub_url, options.strip_tracking_params)))
+    .collect();
+    let (targets, over_limit) = limit_subpage_links(targets, options.max_subpages);
+    for position in over_limit {
+        let reason = format!("Not fetched (more than {} subpages on the page)", options.max_subpages);
+        graft_subpage(content, &position, not_fetched(reason));
+    }
+    let subpages = futures_util::future::join_all(
+        targets
+            .into_iter()
+            .map(|(position, sub_url)| async move {
+                let subpage = match fetcher.subpage(&sub_url, fetch_subpage_unless_noindex(fetcher, options, &sub_url)).await {
+                    Ok(subpage) => subpage,
+                    Err(limit) => Some(not_fetched(format!("Not fetched (more than {limit} subpages in the job)"))),
+                };
+                on_subpage();
+                (position, subpage)
+            }),
+    )
+    .await;
+    for (position, subpage) in subpages {
+        if let Some(subpage) = subpage {
+            graft_subpage(content, &position, subpage);
+        }
+    }
+}
+
+/// ルートからの children の番号の並び
+type NodePosition = Vec<usize>;
+
+/// たどるリンク (a タグの位置と解決した URL。文書順)
+fn subpage_links(content: &DomContent, link_base: &Url, follow: impl Fn(&Url) -> bool) -> Vec<(NodePosition, Url)> {
+    let mut targets = vec![];
+    let mut position = vec![];
+    collect_subpage_links(content, link_base, &follow, &mut position, &mut targets);
+    targets
+}
+
+fn collect_subpage_links(
+    content: &DomContent,
+    link_base: &Url,
+    follow: &impl Fn(&Url) -> bool,
+    position: &mut NodePosition,
+    targets: &mut Vec<(NodePosition, Url)>,
+) {
+    // ノード以外 (表・抽出結果など) の中のリンクはたどらない
+    let DomContent::Node(node) = content else {
+        return;
+    };
+    if node.tag.as_deref() == Some("a") {
+        let sub_url = node.href.as_deref().and_then(|href| link_base.join(href).ok());
+        if let Some(sub_url) = sub_url.filter(|u| follow(u)) {
+            targets.push((position.clone(), sub_url));
+        }
+    }
+    for (i, child) in node.children.iter().enumerate() {
+        position.push(i);
+        collect_subpage_links(child, link_base, follow, position, targets);
+        position.pop();
+    }
+}
+
+/// 文書順で最初の max 個の URL (normalize::dedup_key が異なるもの) へのリンクと、それ以外のリンクの位置 (max が 0 なら無制限)
+fn limit_subpage_links(targets: Vec<(NodePosition, Url)>, max: usize) -> (Vec<(NodePosition, Url)>, Vec<NodePosition>) {
+    if max == 0 {
+        return (targets, vec![]);
+    }
+    let mut urls = std::collections::HashSet::new();
+    let mut over_limit = vec![];
+    let mut kept = vec![];
+    for (position, url) in targets {
+        let key = normalize::dedup_key(&url);
+        if urls.contains(&key) || urls.len() < max {
+            urls.insert(key);
+            kept.push((position, url));
+        } else {
+            over_limit.push(position);
+        }
+    }
+    (kept, over_limit)
+}
+
+/// 上限で取得しなかったリンク先の印
+fn not_fetched(reason: String) -> DomContent {
+    DomContent::Node(DomNode {
+        tag: Some("not_fetched".to_string()),
+        href: None,
+        attrs: None,
+        text: Some(reason),
+        children: vec![],
+        link_subpage: None,
+        page_meta: None,
+        structured_data: None,
+    })
+}
+
+/// 位置のノードの link_subpage に付ける (位置にノードがなければ false)
+fn graft_subpage(content: &mut DomContent, position: &[usize], subpage: DomContent) -> bool {
+    let mut current = content;
+    for &i in position {
+        let DomContent::Node(node) = current else {
+            return false;
+        };
+        let Some(child) = node.children.get_mut(i) else {
+            return false;
+        };
+        current = child;
+    }
+    match current {
+        DomContent::Node(node) => {
+            node.link_subpage = Some(Box::new(subpage));
+            true
+        }
+        _ => false,
+    }
+}
+
+/// リンク先 1 件 (noindex を除く指定では noindex のページを not_fetched に)
+async fn fetch_subpage_unless_noindex(fetcher: &Fetcher, options: &JobOptions, sub_url: &Url) -> Option<DomContent> {
+    let subpage = fetch_subpage(fetcher, options, sub_url).await?;
+    let noindex = matches!(&subpage, DomContent::Node(node) if node.page_meta.as_ref().is_some_and(|m| m.noindex()));
+    Some(if noindex && options.skip_noindex && !options.ignore_robots {
+        not_fetched("Not included (meta robots noindex)".to_string())
+    } else {
+        subpage
+    })
+}
+
+/// リンク先 1 件の取得と変換 (取得できない・バイナリは None)
+async fn fetch_subpage(fetcher: &Fetcher, options: &JobOptions, sub_url: &Url) -> Option<DomContent> {
+    let fetched = {
+        let _permit = fetcher.subpage_permit(sub_url).await;
+        get_page(fetcher, sub_url, options).await.ok()?
+    };
+    if let Some(resource) = resource_content(&fetched) {
+        return Some(resource);
+    }
+    match fetched.body {
+        fetch::Body::Text(body) => {
+            let parse_options = options.parse.clone();
+            let page_url = sub_url.clone();
+            spawn_blocking(move || parse_html_sync(&body, &parse_options, &page_url)).await.ok()
+        }
+        fetch::Body::Pdf(bytes) => Some(parse_pdf_blocking(bytes).await),
+        fetch::Body::Binary { .. } => None,
+    }
+}
+
+/// リンクグラフの 1階層リンク先を取得し、それぞれのリンクを page.subpages に (同じ URL は 1 回、HTML 以外は飛ばす)
+async fn fetch_link_subpages(
+    fetcher: &Fetcher,
+    options: &JobOptions,
+    page: &mut links::PageLinks,
+    base_url: &Url,
+    on_subpage: &(dyn Fn() + Sync),
+) {
+    let mut seen = std::collections::HashSet::new();
+    let targets: Vec<Url> = page
+        .links
+        .iter()
+        .filter(|link| options.ignore_robots || !link.is_nofollow())
+        .filter_map(|link| Url::parse(&link.href).ok())
+        .map(|mut url| {
+            url.set_fragment(None);
+            url
+        })
+        .filter(|url| options.should_follow(base_url, url) && seen.insert(url.as_str().to_string()))
+        .collect();
+    for sub_url in targets {
+        let fetched = get_page(fetcher, &sub_url, options).await;
+        on_subpage();
+        let Ok(fetched) = fetched else {
+            continue;
+        };
+        if resource_content(&fetched).is_some() {
+            continue;
+        }
+        let fetch::Body::Text(body) = fetched.body else {
+            continue;
+        };
+        if feed::looks_like_feed(&body) {
+            continue;
+        }
+        let parse_options = options.parse.clone();
+        let page_url = sub_url.clone();
+        if let Ok(DomContent::Links(subpage)) = spawn_blocking(move || parse_html_sync(&body, &parse_options, &page_url)).await {
+            if options.skip_noindex && !options.ignore_robots && meta::has_directive(&subpage.robots, "noindex") {
+                continue;
+            }
+            page.subpages.push(*subpage);
+        }
+    }
+}
+
+/// ホワイトリストに一致する属性 (なければ None)
+fn captured_attrs(e: &scraper::node::Element, patterns: &[String]) -> Option<BTreeMap<String, String>> {
+    if patterns.is_empty() {
+        return None;
+    }
+    let attrs: BTreeMap<String, String> = e
+        .attrs()
+        .filter(|(name, _)| {
+            patterns.iter().any(|p| match p.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == p,
+            })
+        })
+        .map(|(name, value)| (name.to_string(), value.trim().to_string()))
+        .collect();
+    (!attrs.is_empty()).then_some(attrs)
+}
+
+/// カンマ・空白区切りの属性名・タグ名のリスト (小文字に揃える)
+pub fn parse_attribute_list(text: &str) -> Vec<String> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_ascii_lowercase())
+        .collect()
+}
+
+/// テキスト整形
+fn clean_text(raw: &str) -> String {
+    let replaced = raw.replace('\n', " ");
+    let re = Regex::new(r"\s+").unwrap();
+    re.replace_all(&replaced, " ").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(tag: &str, href: Option<&str>, children: Vec<DomContent>) -> DomContent {
+        DomContent::Node(DomNode {
+            tag: Some(tag.to_string()),
+            href: href.map(str::to_string),
+            attrs: None,
+            text: None,
+            children,
+            link_subpage: None,
+            page_meta: None,
+            structured_data: None,
+        })
+    }
+
+    fn link(href: &str) -> DomContent {
+        node("a", Some(href), vec![])
+    }
+
+    fn base() -> Url {
+        Url::parse("https://example.com/dir/page.html").unwrap()
+    }
+
+    /// html > [a(a.html), ul > [li > a(/b), li > a(https://other.example/)], table, p > a(c.html)]
+    fn page() -> DomContent {
+        node(
+            "html",
+            None,
+            vec![
+                link("a.html"),
+                node("ul", None, vec![node("li", None, vec![link("/b")]), node("li", None, vec![link("https://other.example/")])]),
+                DomContent::Table(TableData {
+                    table_headers: vec!["link".to_string()],
+                    rows: vec![serde_json::json!({"link": "x.html"})],
+                    truncated: false,
+                    total_rows: None,
+                    original_headers: None,
+                }),
+                node("p", None, vec![link("c.html")]),
+            ],
+        )
+    }
+
+    fn subpage_of<'a>(content: &'a DomContent, position: &[usize]) -> Option<&'a DomContent> {
+        let mut current = content;
+        for &i in position {
+            let DomContent::Node(node) = current else {
+                return None;
+            };
+            current = node.children.get(i)?;
+        }
+        match current {
+            DomContent::Node(node) => node.link_subpage.as_deref(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn subpage_links_are_in_document_order() {
+        let targets = subpage_links(&page(), &base(), |_| true);
+        let found: Vec<(Vec<usize>, &str)> = targets.iter().map(|(p, u)| (p.clone(), u.as_str())).collect();
+        assert_eq!(
+            found,
+            vec![
+                (vec![0], "https://example.com/dir/a.html"),
+                (vec![1, 0, 0], "https://example.com/b"),
+                (vec![1, 1, 0], "https://other.example/"),
+                (vec![3, 0], "https://example.com/dir/c.html"),
+            ]
+        );
+    }
+
+    #[test]
+    fn subpage_links_apply_the_filter() {
+        let targets = subpage_links(&page(), &base(), |u| u.host_str() == Some("example.com"));
+        let positions: Vec<Vec<usize>> = targets.into_iter().map(|(p, _)| p).collect();
+        assert_eq!(positions, vec![vec![0], vec![1, 0, 0], vec![3, 0]]);
+    }
+
+    #[test]
+    fn subpage_links_skip_links_without_a_valid_href() {
+        let content = node("html", None, vec![node("a", None, vec![]), link("http://[bad"), node("a", Some("ok.html"), vec![link("inner.html")])]);
+        let targets = subpage_links(&content, &base(), |_| true);
+        let positions: Vec<Vec<usize>> = targets.into_iter().map(|(p, _)| p).collect();
+        assert_eq!(positions, vec![vec![2], vec![2, 0]]);
+    }
+
+    #[test]
+    fn subpage_links_of_non_node_content_are_empty() {
+        let content = error_node("ErrorFetch", "unreachable".to_string());
+        assert!(subpage_links(&content, &base(), |_| true).is_empty());
+        let content = DomContent::Filtered(serde_json::json!(["a.html"]));
+        assert!(subpage_links(&content, &base(), |_| true).is_empty());
+    }
+
+    #[test]
+    fn graft_subpage_sets_link_subpage_at_each_position() {
+        let mut content = page();
+        let targets = subpage_links(&content, &base(), |_| true);
+        for (position, url) in &targets {
+            assert!(graft_subpage(&mut content, position, error_node("Sub", url.to_string())));
+        }
+        for (position, url) in &targets {
+            let Some(DomContent::Node(sub)) = subpage_of(&content, position) else {
+                panic!("no subpage at {position:?}");
+            };
+            assert_eq!(sub.text.as_deref(), Some(url.as_str()));
+        }
+        // 他のノードには付かない
+        assert!(subpage_of(&content, &[1]).is_none());
+        assert!(subpage_of(&content, &[3]).is_none());
+    }
+
+    #[test]
+    fn limit_subpage_links_counts_distinct_urls() {
+        let targets: Vec<(NodePosition, Url)> = ["a.html", "b.html#x", "a.html#top", "c.html", "b.html"]
+            .iter()
+            .enumerate()
+            .map(|(i, href)| (vec![i], base().join(href).unwrap()))
+            .collect();
+        let (kept, over_limit) = limit_subpage_links(targets.clone(), 2);
+        let kept: Vec<Vec<usize>> = kept.into_iter().map(|(p, _)| p).collect();
+        assert_eq!(kept, vec![vec![0], vec![1], vec![2], vec![4]]);
+        assert_eq!(over_limit, vec![vec![3]]);
+
+        let (kept, over_limit) = limit_subpage_links(targets, 0);
+        assert_eq!(kept.len(), 5);
+        assert!(over_limit.is_empty());
+    }
+
+    #[test]
+    fn graft_subpage_rejects_missing_positions() {
+        let mut content = page();
+        assert!(!graft_subpage(&mut content, &[9], link("x")));
+        assert!(!graft_subpage(&mut content, &[1, 0, 5], link("x")));
+        // 表の中には付けない
+        assert!(!graft_subpage(&mut content, &[2], link("x")));
+        assert!(!graft_subpage(&mut content, &[2, 0], link("x")));
+    }
+
+    #[test]
+    fn nodes_deeper_than_max_depth_keep_only_their_contents() {
+        let html = format!("<html><body>{}deep{}</body></html>", "<blockquote>".repeat(5), "</blockquote>".repeat(5));
+        let options = ParseOptions {
+            target_tags: vec!["blockquote".to_string()],
+            max_depth: 2,
+            ..ParseOptions::default()
+        };
+        let DomContent::Node(root) = parse_html_sync(&html, &options, &base()) else {
+            panic!("expected a node");
+        };
+        let mut current = &root.children[0];
+        let mut depth = 0;
+        while let DomContent::Node(DomNode { tag: Some(_), children, .. }) = current {
+            depth += 1;
+            current = &children[0];
+        }
+        assert_eq!(depth, 2);
+        assert!(matches!(current, DomContent::Node(DomNode { text: Some(text), .. }) if text == "deep"));
+    }
+
+    #[test]
+    fn duplicate_and_blank_headers_get_distinct_names() {
+        let names = |list: &[&str]| unique_names(list.iter().map(|s| s.to_string()).collect());
+        assert_eq!(names(&["Value", "Value", "", "Value_2"]), vec!["Value", "Value_3", "col2", "Value_2"]);
+        // 元の見出しの col0 はその列に残し、空の見出しの方をずらす
+        assert_eq!(names(&["", " ", "col0"]), vec!["col0_2", "col1", "col0"]);
+        let headers = HashSet::from(["a", "col2", "col2_2"]);
+        assert_eq!(extra_column_name(1, &headers), "col1");
+        assert_eq!(extra_column_name(2, &headers), "col2_3");
+    }
+}
@@ -0,0 +1,205 @@
+//! リンクグラフモード (DOM ツリーの代わりにページごとの外向きリンクを出力) とリンク切れチェック
+
+use std::collections::HashSet;
+
+use futures_util::{stream, StreamExt};
+use schemars::JsonSchema;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::fetch::{Fetcher, LinkCheck};
+use crate::url_result::UrlResult;
+use crate::{clean_text, meta, tables, DomContent, SubpageScope};
+
+/// 1 ページ分のリンク
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PageLinks {
+    /// ページの URL
+    pub url: String,
+    pub links: Vec<Link>,
+    /// <meta name="robots"> の指示 (小文字)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub robots: Vec<String>,
+    /// 取得できなかった (2xx 以外の) リンクの数 (リンク切れチェック時)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broken: Option<usize>,
+    /// 1階層リンク先のページのリンク (リンク先も含める指定時)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subpages: Vec<PageLinks>,
+}
+
+/// 外向きリンク 1 件
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Link {
+    /// 絶対 URL
+    pub href: String,
+    /// アンカーテキスト (なければリンク内の画像の alt)
+    pub text: String,
+    /// rel 属性 (nofollow / sponsored / ugc など)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rel: Vec<String>,
+    /// ページと同じ登録ドメインか
+    pub internal: bool,
+    /// リンク先のステータス (リンク切れチェック時)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check: Option<LinkCheck>,
+}
+
+/// たどれるリンクか (http / https。ローカルファイルのページからは file:// も)
+pub fn is_followable_scheme(page_url: &Url, href: &Url) -> bool {
+    match href.scheme() {
+        "http" | "https" => true,
+        "file" => page_url.scheme() == "file",
+        _ => false,
+    }
+}
+
+/// 文書の <a href> を抽出 (http / https とローカルファイルどうしのみ。同じページ内へのリンクは除く)
+pub fn extract(doc: &Html, page_url: &Url, link_base: &Url) -> PageLinks {
+    let sel_a = Selector::parse("a[href]").unwrap();
+    let links = doc
+        .select(&sel_a)
+        .filter_map(|a| {
+            let href = link_base.join(a.value().attr("href")?.trim()).ok()?;
+            if !is_followable_scheme(page_url, &href) || is_same_page(&href, page_url) {
+                return None;
+            }
+            let rel = a
+                .value()
+                .attr("rel")
+                .map(|rel| rel.split_ascii_whitespace().map(str::to_ascii_lowercase).collect())
+                .unwrap_or_default();
+            Some(Link {
+                internal: SubpageScope::SameDomain.allows(page_url, &href),
+                text: anchor_text(a),
+                href: href.to_string(),
+                rel,
+                check: None,
+            })
+        })
+        .collect();
+    PageLinks {
+        url: page_url.to_string(),
+        links,
+        robots: meta::robots(doc),
+        broken: None,
+        subpages: vec![],
+    }
+}
+
+impl Link {
+    /// rel="nofollow" のリンク
+    pub fn is_nofollow(&self) -> bool {
+        self.rel.iter().any(|r| r == "nofollow")
+    }
+}
+
+/// rel="nofollow" のリンクしかない URL (link_base で解決した絶対 URL。1階層リンク先でたどらない)
+pub fn nofollow_only(doc: &Html, link_base: &Url) -> HashSet<String> {
+    let sel_a = Selector::parse("a[href]").unwrap();
+    let mut nofollow = HashSet::new();
+    let mut followed = HashSet::new();
+    for a in doc.select(&sel_a) {
+        let Some(href) = a.value().attr("href").and_then(|href| link_base.join(href).ok()) else {
+            continue;
+        };
+        let is_nofollow = a
+            .value()
+            .attr("rel")
+            .is_some_and(|rel| rel.split_ascii_whitespace().any(|r| r.eq_ignore_ascii_case("nofollow")));
+        if is_nofollow {
+            nofollow.insert(href.to_string());
+        } else {
+            followed.insert(href.to_string());
+        }
+    }
+    nofollow.retain(|href| !followed.contains(href));
+    nofollow
+}
+
+/// ページのすべてのリンク先を確かめて check と broken を埋める (concurrency 件ずつ並行)
+pub async fn check_page(fetcher: &Fetcher, page: &mut PageLinks, concurrency: usize) {
+    let checks: Vec<Option<LinkCheck>> = stream::iter(&page.links)
+        .map(|link| async move {
+            let mut url = Url::parse(&link.href).ok()?;
+            url.set_fragment(None);
+            Some(fetcher.check_link(&url).await)
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+    for (link, check) in page.links.iter_mut().zip(checks) {
+        link.check = check;
+    }
+    page.broken = Some(page.links.iter().filter(|l| l.check.as_ref().is_some_and(|c| !c.ok)).count());
+}
+
+/// チェック結果の 1 行表示 (例: "404"、"200 via https://a/ -> https://b/"、"unreachable: ...")
+pub fn check_text(check: &LinkCheck) -> String {
+    let mut text = match (check.status, &check.error) {
+        (Some(status), _) => status.to_string(),
+        (None, Some(error)) => format!("unreachable: {error}"),
+        (None, None) => "unreachable".to_string(),
+    };
+    if !check.redirects.is_empty() {
+        text.push_str(&format!(" via {}", check.redirects.join(" -> ")));
+    }
+    text
+}
+
+/// フラグメントを除くと同じ URL
+fn is_same_page(href: &Url, page_url: &Url) -> bool {
+    href.as_str().split('#').next() == page_url.as_str().split('#').next()
+}
+
+fn anchor_text(a: ElementRef) -> String {
+    let text = clean_text(&a.text().collect::<String>());
+    if !text.is_empty() {
+        return text;
+    }
+    let sel_img = Selector::parse("img[alt]").unwrap();
+    a.select(&sel_img)
+        .filter_map(|img| img.value().attr("alt"))
+        .map(clean_text)
+        .find(|alt| !alt.is_empty())
+        .unwrap_or_default()
+}
+
+/// リンクグラフモードの結果を含むか
+pub fn has_link_graph(results: &[DomContent]) -> bool {
+    results.iter().any(|content| page_links(content).is_some())
+}
+
+/// 全ページ (リンク先を含む) のリンクのエッジリスト CSV (source,target,text,rel,internal)
+pub fn edges_csv(results: &[DomContent]) -> String {
+    let mut csv = String::new();
+    tables::push_record(&mut csv, ["source", "target", "text", "rel", "internal"].into_iter());
+    for page in results.iter().filter_map(page_links) {
+        push_edges(&mut csv, page);
+    }
+    csv
+}
+
+/// リンクグラフの結果 (型付きの結果の中も見る)
+fn page_links(content: &DomContent) -> Option<&PageLinks> {
+    match content {
+        DomContent::Links(page) => Some(page),
+        DomContent::Result(result) => match &**result {
+            UrlResult::Document { document, .. } => page_links(document),
+            UrlResult::Error { .. } => None,
+        },
+        _ => None,
+    }
+}
+
+fn push_edges(csv: &mut String, page: &PageLinks) {
+    for link in &page.links {
+        let rel = link.rel.join(" ");
+        let internal = if link.internal { "true" } else { "false" };
+        tables::push_record(csv, [page.url.as_str(), &link.href, &link.text, &rel, internal].into_iter());
+    }
+    for subpage in &page.subpages {
+        push_edges(csv, subpage);
+    }
+}
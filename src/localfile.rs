@@ -0,0 +1,205 @@
+//! ローカルファイル (file:// URL) の読み込み (サーバーでは設定ファイルの LOCAL_FILE_ROOTS の下だけ) と
+//! オフライン再生用に保存したページ (REPLAY_DIR) の読み込み
+
+use std::path::{Path, PathBuf};
+
+use url::Url;
+
+use crate::charset;
+use crate::fetch::{self, Body, FetchError, FetchInfo, Fetched};
+
+/// ディレクトリの URL で読むファイル
+const INDEX_FILE: &str = "index.html";
+
+/// file:// URL を読んでよい範囲
+#[derive(Debug, Clone, Default)]
+pub enum LocalFiles {
+    /// 読まない (サーバーの既定)
+    #[default]
+    Disabled,
+    /// どこでも (CLI)
+    Any,
+    /// このディレクトリの下だけ (シンボリックリンク・.. を解決した実パスで判定)
+    Roots(Vec<PathBuf>),
+}
+
+impl LocalFiles {
+    /// LOCAL_FILE_ROOTS (カンマ区切りのディレクトリ。空なら読まない)
+    pub fn from_setting(text: &str) -> Self {
+        let roots: Vec<PathBuf> = text
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .filter_map(|dir| match Path::new(dir).canonicalize() {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    tracing::warn!(dir, error = %e, "LOCAL_FILE_ROOTS: skipping directory");
+                    None
+                }
+            })
+            .collect();
+        if roots.is_empty() {
+            LocalFiles::Disabled
+        } else {
+            LocalFiles::Roots(roots)
+        }
+    }
+
+    /// 読んでよければ実パス
+    fn resolve(&self, url: &Url) -> Result<PathBuf, String> {
+        if let LocalFiles::Disabled = self {
+            return Err("Local files are not allowed (set LOCAL_FILE_ROOTS in the settings file)".to_string());
+        }
+        let path = url.to_file_path().map_err(|_| format!("Invalid file URL: {url}"))?;
+        let mut resolved = self.canonicalize(&path)?;
+        if resolved.is_dir() {
+            resolved = self.canonicalize(&resolved.join(INDEX_FILE))?;
+        }
+        match self {
+            LocalFiles::Roots(roots) if !roots.iter().any(|root| resolved.starts_with(root)) => {
+                Err(format!("{} is outside LOCAL_FILE_ROOTS", resolved.display()))
+            }
+            _ => Ok(resolved),
+        }
+    }
+
+    /// 実パス (範囲外のパスは存在するかどうかも返さない)
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, String> {
+        path.canonicalize().map_err(|e| match self {
+            LocalFiles::Roots(roots) if !roots.iter().any(|root| path.starts_with(root)) => {
+                format!("{} is outside LOCAL_FILE_ROOTS", path.display())
+            }
+            _ => format!("{}: {e}", path.display()),
+        })
+    }
+}
+
+/// file:// URL のファイルを HTTP の取得と同じ形で返す (Content-Type は拡張子から)
+pub async fn read(url: &Url, local_files: &LocalFiles, limit: Option<u64>) -> Result<Fetched, FetchError> {
+    let path = local_files.resolve(url).map_err(FetchError::Local)?;
+    let final_url = Url::from_file_path(&path).map_or_else(|_| url.to_string(), |u| u.to_string());
+    read_file(&path, final_url, limit).await
+}
+
+/// 保存したページ (なければ None)
+///
+/// <dir>/<ホスト>[:<ポート>]/<パス> (wget --mirror と同じ配置)。/ で終わるパスは index.html、クエリ付きは
+/// ファイル名の後に ?<クエリ> (/ と \ は %2F・%5C)。拡張子のないパスでファイルがなければ .html を付けたもの・<パス>/index.html も探す
+pub async fn replay(dir: &Path, url: &Url, limit: Option<u64>) -> Result<Option<Fetched>, FetchError> {
+    for path in replay_paths(dir, url) {
+        if tokio::fs::metadata(&path).await.is_ok_and(|m| m.is_file()) {
+            tracing::debug!(url = %url, path = %path.display(), "replay");
+            return read_file(&path, url.to_string(), limit).await.map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// 保存したページのパスの候補 (順に探す)
+fn replay_paths(dir: &Path, url: &Url) -> Vec<PathBuf> {
+    let Some((parents, last, query)) = replay_location(url) else {
+        return vec![];
+    };
+    let base = parents.iter().fold(dir.to_path_buf(), |path, segment| path.join(segment));
+    if last.is_empty() {
+        return vec![base.join(format!("{INDEX_FILE}{query}"))];
+    }
+    let mut paths = vec![base.join(format!("{last}{query}"))];
+    if !last.contains('.') {
+        paths.push(base.join(format!("{last}.html{query}")));
+        paths.push(base.join(last).join(format!("{INDEX_FILE}{query}")));
+    }
+    paths
+}
+
+/// 取得したページを保存するときの相対パス (/ 区切り。拡張子のないパスは .html を付ける。replay で読める配置)
+pub fn replay_file(url: &Url) -> Option<String> {
+    let (mut parents, last, query) = replay_location(url)?;
+    let name = match last {
+        "" => INDEX_FILE.to_string(),
+        last if !last.contains('.') => format!("{last}.html"),
+        last => last.to_string(),
+    };
+    parents.push(format!("{name}{query}"));
+    Some(parents.join("/"))
+}
+
+/// (ホスト[:ポート] と親ディレクトリ, 最後のセグメント, ?クエリ)
+///
+/// クエリの / と \ は %2F・%5C にする (url はクエリの / や .. をそのまま残すので、保存先のディレクトリの外を指さないように)
+fn replay_location(url: &Url) -> Option<(Vec<String>, &str, String)> {
+    let host = match (url.host_str()?, url.port()) {
+        (host, Some(port)) => format!("{host}:{port}"),
+        (host, None) => host.to_string(),
+    };
+    let segments: Vec<&str> = url.path_segments().map(Iterator::collect).unwrap_or_default();
+    let (last, parents) = segments.split_last().map_or(("", &[][..]), |(last, parents)| (*last, parents));
+    let dirs = std::iter::once(host).chain(parents.iter().filter(|s| !s.is_empty()).map(|s| s.to_string())).collect();
+    let query = url
+        .query()
+        .map(|q| format!("?{}", q.replace('/', "%2F").replace('\\', "%5C")))
+        .unwrap_or_default();
+    Some((dirs, last, query))
+}
+
+/// ファイルを取得結果の形で (final_url は取得情報に載せる URL)
+async fn read_file(path: &Path, final_url: String, limit: Option<u64>) -> Result<Fetched, FetchError> {
+    let metadata = tokio::fs::metadata(path).await.map_err(|e| FetchError::Local(format!("{}: {e}", path.display())))?;
+    let content_type = content_type(path).map(str::to_string);
+    let info = Some(FetchInfo {
+        final_url,
+        status: 200,
+        content_type: content_type.clone(),
+        size: Some(metadata.len()),
+        duration_ms: 0,
+        fetched_at: String::new(),
+        redirects: vec![],
+        cached: false,
+        wayback: None,
+    });
+    if content_type.as_deref().is_some_and(fetch::is_binary_type) && !content_type.as_deref().is_some_and(fetch::is_pdf_type) {
+        return Ok(Fetched {
+            content_type,
+            body: Body::Binary { size: Some(metadata.len()) },
+            info,
+        });
+    }
+    if let Some(limit) = limit.filter(|&limit| metadata.len() > limit) {
+        return Err(FetchError::TooLarge { limit });
+    }
+    let bytes = tokio::fs::read(path).await.map_err(|e| FetchError::Local(format!("{}: {e}", path.display())))?;
+    let body = match content_type.as_deref() {
+        Some(t) if fetch::is_pdf_type(t) => Body::Pdf(bytes),
+        _ => Body::Text(charset::decode(&bytes, content_type.as_deref())),
+    };
+    Ok(Fetched { content_type, body, info })
+}
+
+/// 拡張子からの Content-Type (分からなければ None。HTML として解析する)
+fn content_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "html" | "htm" | "shtml" => "text/html",
+        "xhtml" => "application/xhtml+xml",
+        "xml" => "application/xml",
+        "rss" => "application/rss+xml",
+        "atom" => "application/atom+xml",
+        "json" => "application/json",
+        "pdf" => "application/pdf",
+        "txt" | "md" => "text/plain",
+        "css" => "text/css",
+        "js" | "mjs" => "text/javascript",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "woff" | "woff2" => "font/woff2",
+        "zip" => "application/zip",
+        _ => return None,
+    })
+}
@@ -0,0 +1,45 @@
+//! サーバー側のログ (tracing。標準エラー出力にテキストか JSON で)
+
+use std::time::Instant;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::Error;
+use tracing::Instrument;
+use tracing_subscriber::EnvFilter;
+use web_to_json::LogFormat;
+
+/// ロガーを初期化 (環境変数 RUST_LOG があればそちらを優先)
+///
+/// level が "debug" のようにレベルだけなら、このツールのログだけをそのレベルにする (依存クレートは warn)。
+pub fn init(level: &str, format: LogFormat) {
+    let directives = if level.contains(['=', ',']) {
+        level.to_string()
+    } else {
+        format!("warn,web_to_json={level}")
+    };
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(directives));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter).with_writer(std::io::stderr);
+    let _ = match format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().flatten_event(true).with_current_span(true).try_init(),
+    };
+}
+
+/// リクエストごとのスパンを作り、完了時にステータスと所要時間を記録するミドルウェア
+pub async fn trace_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let span = tracing::info_span!("request", method = %req.method(), path = %req.path());
+    let started = Instant::now();
+    let result = next.call(req).instrument(span.clone()).await;
+    let _entered = span.enter();
+    let duration_ms = started.elapsed().as_millis() as u64;
+    match &result {
+        Ok(res) => tracing::info!(status = res.status().as_u16(), duration_ms, "request finished"),
+        Err(e) => tracing::warn!(error = %e, duration_ms, "request failed"),
+    }
+    result
+}
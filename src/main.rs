@@ -1,468 +1,912 @@
 use actix_web::{
-    web, App, HttpResponse, HttpServer, Responder,
+    http::header, middleware, web, App, FromRequest, HttpRequest, HttpResponse, HttpServer, Responder,
 };
-use serde::{Serialize, Deserialize};
-use regex::Regex;
-use tokio::task::{spawn_blocking};
-use std::time::Duration;
-use open;
-use url::Url;
+use serde::Deserialize;
+use std::net::{SocketAddr, TcpListener};
+use std::ops::{ControlFlow, RangeInclusive};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures_util::stream;
+use base64::Engine;
 use sanitize_filename::sanitize;
-use scraper::{Html, Selector, ElementRef};
-use scraper::node::Node;
-
-/// 複数URLを改行区切りで受け取るフォーム
-#[derive(Deserialize)]
-struct UrlForm {
-    urls: String,
-    include_subpages: Option<String>,
-}
-
-/// JSON 出力用: 通常ノード or テーブル
-#[derive(Debug, Serialize)]
-#[serde(untagged)]
-enum DomContent {
-    Node(DomNode),
-    Table(TableData),
-}
-
-/// 通常ノード
-#[derive(Debug, Serialize)]
-struct DomNode {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    tag: Option<String>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    href: Option<String>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    text: Option<String>,
+use clap::Parser;
+
+mod auth;
+mod cli;
+mod i18n;
+mod logging;
+mod pages;
+mod ratelimit;
+mod templates;
+mod tls;
+mod urlfile;
+
+use i18n::Lang;
+use templates::TemplateStore;
+use web_to_json::cache::ResponseCache;
+use web_to_json::history::HistoryStore;
+use web_to_json::output::OutputFormat;
+use web_to_json::{
+    columnar, error_node, form_urls, history, jobs, links, metrics, non_empty, options_from_form, output, plugin, run_job, run_job_each, schema,
+    storage, tables, DomContent, JobOptions, Settings, UrlForm,
+};
 
-    #[serde(default)]
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    children: Vec<DomContent>,
+/// サーバーの既定の待ち受けホスト
+const DEFAULT_HOST: &str = "127.0.0.1";
 
-    #[serde(skip_serializing_if = "Option::is_none")]
-    link_subpage: Option<Box<DomContent>>,
-}
+/// 既定で試すポート (先頭が使用中なら順に)
+const DEFAULT_PORTS: RangeInclusive<u16> = 8080..=8099;
 
-/// テーブル構造
-#[derive(Debug, Serialize)]
-struct TableData {
-    table_headers: Vec<String>,
-    rows: Vec<serde_json::Value>,
-}
+/// ストリーミングのレスポンスで送信待ちにしておくページ数 (超えたらクライアントが読むまで変換を待つ)
+const STREAM_BUFFER: usize = 4;
 
 // =================== メイン ===================
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> std::io::Result<()> {
-    let server = HttpServer::new(|| {
+    let mut args = cli::Cli::parse();
+    // サブコマンドなしでも標準入力がパイプなら変換 (cat urls.txt | web-to-json | jq)
+    if args.command.is_none() && args.bind.is_none() && cli::stdin_is_piped() {
+        args.command = cli::stdin_command();
+    }
+    let settings = Settings::load();
+    let default_level = if args.command.is_some() { "warn" } else { "info" };
+    let log_level = if settings.log_level.is_empty() { default_level } else { &settings.log_level };
+    logging::init(log_level, settings.log_format);
+
+    // サブコマンド指定時はサーバーを起動せずに処理
+    if let Some(command) = args.command {
+        return cli::run(command, &settings).await;
+    }
+
+    let response_cache = web::Data::new(ResponseCache::new(settings.response_cache_entries));
+    let templates = web::Data::new(TemplateStore::load(templates::TEMPLATES_FILE));
+    let job_store = web::Data::new(jobs::JobStore::new(settings.max_running_jobs, &settings.jobs_dir));
+    let rate_limiter = web::Data::new(ratelimit::RateLimiter::new(settings.rate_limit_per_minute));
+    let started = web::Data::new(Instant::now());
+    let metrics = web::Data::new(metrics::Metrics::default());
+    let history = web::Data::new(HistoryStore::open(&settings.history_db).map_err(std::io::Error::other)?);
+    // 前回終わらなかったジョブを一時停止した状態で読み戻す
+    for saved in job_store.saved() {
+        match restored_options(&saved.form, &settings, &response_cache, &metrics, &history) {
+            Ok(options) => {
+                let job = job_store.restore(saved, options);
+                tracing::info!(job = %job.id, "restored unfinished job (paused)");
+            }
+            Err(e) => tracing::warn!(job = %saved.id, error = %e, "failed to restore job"),
+        }
+    }
+    let tls_config = match (settings.tls_cert.as_str(), settings.tls_key.as_str()) {
+        ("", "") => None,
+        ("", _) | (_, "") => return Err(std::io::Error::other("TLS_CERT and TLS_KEY must be set together")),
+        (cert, key) => Some(tls::server_config(cert, key).map_err(std::io::Error::other)?),
+    };
+    let settings = web::Data::new(settings);
+    let listener = bind_listener(args.bind.as_deref())?;
+    let addr = listener.local_addr()?;
+    if !addr.ip().is_loopback() && settings.api_key.is_empty() && settings.basic_auth.is_empty() {
+        tracing::warn!(%addr, "listening without API_KEY or BASIC_AUTH; anyone on the network can use this server");
+    }
+    let server = HttpServer::new(move || {
         App::new()
+            .app_data(settings.clone())
+            .app_data(response_cache.clone())
+            .app_data(templates.clone())
+            .app_data(job_store.clone())
+            .app_data(history.clone())
+            .app_data(rate_limiter.clone())
+            .app_data(started.clone())
+            .app_data(metrics.clone())
+            // 後に wrap したものが外側 (認証を通ったリクエストだけ数える)
+            .wrap(middleware::from_fn(ratelimit::limit_posts))
+            .wrap(middleware::from_fn(auth::require_auth))
+            .wrap(middleware::from_fn(logging::trace_requests))
             .route("/", web::get().to(show_form))
             .route("/", web::post().to(process_form))
-    })
-    .bind(("127.0.0.1", 8080))?
+            .route("/jobs/{id}", web::get().to(show_job))
+            .route("/jobs/{id}/events", web::get().to(job_events))
+            .route("/jobs/{id}/pause", web::post().to(pause_job))
+            .route("/jobs/{id}/resume", web::post().to(resume_job))
+            .route("/jobs/{id}/result", web::get().to(job_result))
+            .route("/jobs/{id}/result/{index}", web::get().to(job_page_result))
+            .route("/jobs/{id}/html", web::get().to(job_html))
+            .route("/jobs/{id}/warc", web::get().to(job_warc))
+            .route("/api/jobs", web::post().to(api_jobs))
+            .route("/api/convert", web::post().to(api_convert))
+            .route("/api/tables", web::post().to(api_tables))
+            .route("/history", web::get().to(show_history))
+            .route("/api/history", web::get().to(list_history))
+            .route("/api/history/{id}", web::get().to(download_history))
+            .route("/api/history/{id}", web::delete().to(delete_history))
+            .route("/api/schema", web::get().to(output_schema))
+            .route("/healthz", web::get().to(healthz))
+            .route("/readyz", web::get().to(readyz))
+            .route("/metrics", web::get().to(show_metrics))
+            .route("/api/templates", web::get().to(list_templates))
+            .route("/api/templates/{name}", web::put().to(save_template))
+            .route("/api/templates/{name}", web::delete().to(delete_template))
+    });
+    let scheme = if tls_config.is_some() { "https" } else { "http" };
+    let server = match tls_config {
+        Some(config) => server.listen_rustls_0_23(listener, config)?,
+        None => server.listen(listener)?,
+    }
     .run();
 
-    // 起動後にブラウザを自動で開く
-    tokio::spawn(async {
+    // 実際に待ち受けたアドレスを表示して、起動後にブラウザを自動で開く
+    let url = browser_url(scheme, addr);
+    println!("Listening on {url}");
+    tokio::spawn(async move {
         tokio::time::sleep(Duration::from_secs(1)).await;
-        let _ = open::that("http://127.0.0.1:8080/");
+        let _ = open::that(url);
     });
 
     server.await
 }
 
-/// (GET) フォーム画面
-async fn show_form() -> impl Responder {
-    let html = r#"
-<!DOCTYPE html>
-<html><head><meta charset="UTF-8"/><title>Web to JSON (Multiple URLs)</title></head>
-<body>
-  <h1>複数URLを改行区切りで入力</h1>
-  <form action="/" method="post">
-    <textarea name="urls" rows="5" cols="80" placeholder="https://example.com&#10;https://example.org"></textarea>
-    <br/>
-    <label>
-      <input type="checkbox" name="include_subpages" value="true"/>
-      1階層リンク先を含める
-    </label>
-    <button type="submit">JSON変換</button>
-  </form>
-</body></html>
-    "#;
-    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(html)
+/// 待ち受けるソケット (アドレス省略時は 8080 から順に空いているポート、無ければ OS が割り当てたポート)
+fn bind_listener(bind: Option<&str>) -> std::io::Result<TcpListener> {
+    if let Some(bind) = bind {
+        return TcpListener::bind(bind)
+            .map_err(|e| std::io::Error::new(e.kind(), format!("Failed to bind {bind}: {e}")));
+    }
+    for port in DEFAULT_PORTS {
+        match TcpListener::bind((DEFAULT_HOST, port)) {
+            Ok(listener) => return Ok(listener),
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    TcpListener::bind((DEFAULT_HOST, 0))
 }
 
-/// (POST) 複数URL対応
-async fn process_form(form: web::Form<UrlForm>) -> impl Responder {
-    // 複数行 -> split
-    let lines = form.urls.replace('\r', "");
-    let url_list: Vec<_> = lines
-        .split('\n')
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .collect();
+/// 待ち受けアドレスに対応するブラウザで開く URL (0.0.0.0 などは 127.0.0.1 で開く)
+fn browser_url(scheme: &str, addr: SocketAddr) -> String {
+    if addr.ip().is_unspecified() {
+        format!("{scheme}://127.0.0.1:{}/", addr.port())
+    } else {
+        format!("{scheme}://{addr}/")
+    }
+}
 
-    let include_subpages = form.include_subpages.as_deref() == Some("true");
-
-    // 解析結果を格納
-    let mut results = Vec::new();
-
-    for url_str in &url_list {
-        let Ok(parsed_url) = Url::parse(url_str) else {
-            // URL parse エラー
-            let error_node = DomContent::Node(DomNode {
-                tag: Some("ErrorURL".to_string()),
-                href: None,
-                text: Some(format!("URL parse error: {url_str}")),
-                children: vec![],
-                link_subpage: None,
-            });
-            results.push(error_node);
-            continue;
-        };
-
-        // HTTP GET
-        let resp_body = match reqwest::get(parsed_url.clone()).await {
-            Ok(resp) => match resp.text().await {
-                Ok(b) => b,
-                Err(e) => {
-                    let error_node = DomContent::Node(DomNode {
-                        tag: Some("ErrorFetch".to_string()),
-                        href: None,
-                        text: Some(format!("Error reading response: {e}")),
-                        children: vec![],
-                        link_subpage: None,
-                    });
-                    results.push(error_node);
-                    continue;
-                }
-            },
-            Err(e) => {
-                let error_node = DomContent::Node(DomNode {
-                    tag: Some("ErrorFetch".to_string()),
-                    href: None,
-                    text: Some(format!("Request error: {e}")),
-                    children: vec![],
-                    link_subpage: None,
-                });
-                results.push(error_node);
-                continue;
+/// URL 入力フォーム (トップ画面と結果画面で共通)
+fn form<'a>(lang: Lang, settings: &'a Settings, templates: &TemplateStore) -> pages::Form<'a> {
+    pages::Form {
+        lang,
+        settings,
+        template_names: templates.names(),
+        plugin_names: plugin::list(&settings.plugin_dir),
+    }
+}
+
+/// (GET) フォーム画面
+async fn show_form(
+    req: HttpRequest,
+    settings: web::Data<Settings>,
+    templates: web::Data<TemplateStore>,
+    job_store: web::Data<jobs::JobStore>,
+) -> impl Responder {
+    let lang = Lang::from_request(&req);
+    pages::respond(&req, &pages::IndexPage {
+        lang,
+        paused_jobs: job_store.paused(),
+        form: form(lang, &settings, &templates),
+    })
+}
+
+/// (POST) JSON API: フォームと同じ項目の JSON を受け取り、結果の JSON 配列 (または他の出力形式) を返す
+async fn api_convert(
+    req: HttpRequest,
+    body: web::Json<UrlForm>,
+    settings: web::Data<Settings>,
+    response_cache: web::Data<ResponseCache>,
+    metrics: web::Data<metrics::Metrics>,
+    templates: web::Data<TemplateStore>,
+    history: web::Data<HistoryStore>,
+) -> impl Responder {
+    let body = match resolve_form(body.into_inner(), &templates) {
+        Ok(b) => b,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let mut options = match options_from_form(&body, &settings) {
+        Ok(o) => o,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    options.response_cache = Some(response_cache.into_inner());
+    options.metrics = Some(metrics.into_inner());
+    options.history = Some(history.clone().into_inner());
+    if body.output_format.is_none() {
+        let accept = req.headers().get(header::ACCEPT).and_then(|v| v.to_str().ok());
+        if let Some(format) = accept.and_then(OutputFormat::from_accept) {
+            options.output_format = format;
+        }
+    }
+    if let Err(e) = options.check_streaming() {
+        return HttpResponse::BadRequest().body(e);
+    }
+    let url_list = form_urls(&body);
+    if options.streams() {
+        return streaming_response(url_list, options);
+    }
+    let results = match run_job(&url_list, &options).await {
+        Ok(r) => r,
+        Err(e) => return HttpResponse::InternalServerError().body(format!("HTTP client error: {e}")),
+    };
+    match output::render(&results, &options) {
+        Ok(text) => {
+            if history.is_enabled() && !options.sends_secrets() {
+                let _ = history.record(&url_list, &history::form_options(&body), options.output_format, &text);
             }
-        };
-
-        // 同期パース
-        let mut root_content = match spawn_blocking({
-            let resp_body_clone = resp_body.clone(); // move でエラー回避
-            move || parse_html_sync(&resp_body_clone)
-        }).await {
-            Ok(dom) => dom,
-            Err(e_spawn) => {
-                let error_node = DomContent::Node(DomNode {
-                    tag: Some("ErrorSpawnBlock".to_string()),
-                    href: None,
-                    text: Some(format!("spawn_blocking error: {e_spawn:?}")),
-                    children: vec![],
-                    link_subpage: None,
-                });
-                results.push(error_node);
-                continue;
+            let mut response = HttpResponse::Ok();
+            // アップロードしたら結果全体のオブジェクトの URL をヘッダで返す
+            if let Some(bucket) = &options.upload {
+                match bucket.upload_results(&storage::upload_name(None), &url_list, &results, &options).await {
+                    Ok(uploaded) => {
+                        response.insert_header(("X-Upload-Location", uploaded[0].as_str()));
+                    }
+                    Err(e) => return HttpResponse::BadGateway().body(e),
+                }
             }
-        };
-
-        // サブページ
-        if include_subpages {
-            let _ = fetch_subpages_for_depth_one(&mut root_content, &parsed_url).await;
+            response.content_type(options.output_format.content_type()).body(text)
         }
-
-        // 追加
-        results.push(root_content);
+        Err(e) => HttpResponse::InternalServerError().body(e),
     }
+}
 
-    // 配列に
-    let json_arr = serde_json::Value::Array(
-        results.into_iter()
-            .map(|c| serde_json::to_value(c).unwrap_or(serde_json::Value::Null))
-            .collect()
-    );
+/// /api/tables のクエリ
+#[derive(Deserialize)]
+struct TablesQuery {
+    /// zip (既定) / sqlite / parquet
+    #[serde(default)]
+    format: String,
+}
 
-    let json_str = match serde_json::to_string(&json_arr) {
-        Ok(j) => j,
-        Err(e) => return HttpResponse::InternalServerError()
-                        .body(format!("JSON serialize error: {e}")),
+/// (POST) テーブル API: /api/convert と同じ JSON を受け取り、抽出したテーブルの CSV を ZIP で返す
+/// (?format=sqlite ならテーブルごとに 1 つのテーブルを持つ SQLite のファイル、
+/// ?format=parquet ならテーブルごとの Parquet とフラットなレコードの records.parquet の ZIP)
+async fn api_tables(
+    body: web::Json<UrlForm>,
+    query: web::Query<TablesQuery>,
+    settings: web::Data<Settings>,
+    response_cache: web::Data<ResponseCache>,
+    metrics: web::Data<metrics::Metrics>,
+    templates: web::Data<TemplateStore>,
+    history: web::Data<HistoryStore>,
+) -> impl Responder {
+    let body = match resolve_form(body.into_inner(), &templates) {
+        Ok(b) => b,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let mut options = match options_from_form(&body, &settings) {
+        Ok(o) => o,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    options.response_cache = Some(response_cache.into_inner());
+    options.metrics = Some(metrics.into_inner());
+    options.history = Some(history.into_inner());
+    let format = match query.format.as_str() {
+        "" => "zip",
+        format @ ("zip" | "sqlite" | "parquet") => format,
+        other => return HttpResponse::BadRequest().body(format!("Unknown format \"{other}\" (expected zip, sqlite or parquet)")),
     };
+    let url_list = form_urls(&body);
+    let results = match run_job(&url_list, &options).await {
+        Ok(r) => r,
+        Err(e) => return HttpResponse::InternalServerError().body(format!("HTTP client error: {e}")),
+    };
+    let tables = tables::collect_tables(&results);
+    // 保存の指定があれば ZIP に取得した HTML も html/ として入れる
+    let html_files = options.html_archive.as_ref().map(|archive| archive.files("html/")).unwrap_or_default();
+    let (written, content_type, file_name) = match format {
+        "sqlite" => (tables::to_sqlite(&tables), "application/vnd.sqlite3", "tables.sqlite"),
+        "parquet" => (
+            columnar::parquet_files(&results).and_then(|files| tables::zip_files(&[files, html_files].concat())),
+            "application/zip",
+            "parquet.zip",
+        ),
+        _ => (
+            tables::zip_files(&[tables::csv_files(&tables), html_files].concat()),
+            "application/zip",
+            "tables.zip",
+        ),
+    };
+    match written {
+        Ok(bytes) => HttpResponse::Ok()
+            .content_type(content_type)
+            .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"{file_name}\"")))
+            .body(bytes),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
 
-    // 総文字数
-    let total_chars = json_str.chars().count();
+/// テンプレート指定があれば適用したフォーム
+fn resolve_form(form: UrlForm, templates: &TemplateStore) -> Result<UrlForm, String> {
+    Ok(templates.apply(&form)?.unwrap_or(form))
+}
 
-    let escaped_json = json_str
-        .replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;");
+/// (GET) 生存確認 (プロセスが応答できれば 200)
+async fn healthz(started: web::Data<Instant>, job_store: web::Data<jobs::JobStore>) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION"),
+        "uptime_secs": started.elapsed().as_secs(),
+        "jobs": job_store.counts(),
+    }))
+}
 
-    // ダウンロード用ファイル名
-    let file_name = format!("multi_urls_{}.json", sanitize("result"));
+/// (GET) 受付可能か (履歴のデータベースに問い合わせできなければ 503)
+async fn readyz(history: web::Data<HistoryStore>) -> impl Responder {
+    match history.check() {
+        Ok(()) => HttpResponse::Ok().json(serde_json::json!({"status": "ready"})),
+        Err(e) => HttpResponse::ServiceUnavailable().json(serde_json::json!({"status": "unavailable", "error": e})),
+    }
+}
 
-    let msg_subpage = if include_subpages {
-        "（1階層リンク先含む）"
-    } else {
-        ""
+/// (GET) Prometheus 形式のメトリクス
+async fn show_metrics(metrics: web::Data<metrics::Metrics>, job_store: web::Data<jobs::JobStore>) -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(metrics.render(&job_store.counts()))
+}
+
+/// (GET) 出力 (/api/convert の JSON) の JSON Schema
+async fn output_schema() -> impl Responder {
+    HttpResponse::Ok().json(schema::output_schema())
+}
+
+/// (GET) 保存済みテンプレートの一覧
+async fn list_templates(templates: web::Data<TemplateStore>) -> impl Responder {
+    HttpResponse::Ok().json(templates.list())
+}
+
+/// (PUT) フォームと同じ項目の JSON をテンプレートとして保存
+async fn save_template(
+    name: web::Path<String>,
+    body: web::Json<UrlForm>,
+    settings: web::Data<Settings>,
+    templates: web::Data<TemplateStore>,
+) -> impl Responder {
+    // 不正な値 (正規表現・ルール等) は保存前に弾く
+    if let Err(e) = options_from_form(&body, &settings) {
+        return HttpResponse::BadRequest().body(e);
+    }
+    match templates.save(&name, &body) {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
+/// (DELETE) テンプレートを削除
+async fn delete_template(name: web::Path<String>, templates: web::Data<TemplateStore>) -> impl Responder {
+    match templates.delete(&name) {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().body(format!("Unknown template \"{name}\"")),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
+
+/// 履歴の一覧画面
+async fn show_history(req: HttpRequest, history: web::Data<HistoryStore>) -> impl Responder {
+    let entries = match history.list() {
+        Ok(e) => e,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
     };
+    pages::respond(&req, &pages::HistoryPage {
+        lang: Lang::from_request(&req),
+        enabled: history.is_enabled(),
+        entries,
+    })
+}
 
-    let html = format!(r#"
-<!DOCTYPE html>
-<html><head><meta charset="UTF-8"/><title>Web to JSON</title></head>
-<body>
-  <h1>結果</h1>
-  <p>複数URLを解析し、配列形式の JSON を生成しました。{msg_subpage}</p>
-  <p>総文字数: {total_chars}</p>
-
-  <button onclick="downloadJson()">JSONをダウンロード</button>
-  <hr/>
-  <textarea id="jsonText" rows="12" cols="90" style="white-space: pre;">{escaped_json}</textarea><br/>
-  <button onclick="copyToClipboard()">コピー</button>
-
-  <script>
-    function downloadJson() {{
-      const text = `{json_str}`;
-      const blob = new Blob([text], {{ type: 'application/json' }});
-      const url = URL.createObjectURL(blob);
-      const a = document.createElement('a');
-      a.href = url;
-      a.download = "{file_name}";
-      a.click();
-      URL.revokeObjectURL(url);
-    }}
-    function copyToClipboard() {{
-      const textArea = document.getElementById('jsonText');
-      navigator.clipboard.writeText(textArea.value)
-        .then(() => alert('クリップボードにコピーしました。'))
-        .catch(err => alert('コピー失敗: ' + err));
-    }}
-  </script>
-
-  <hr/>
-  <h2>再度URLを入力</h2>
-  <form action="/" method="post">
-    <textarea name="urls" rows="5" cols="80" placeholder="https://example.com&#10;https://example.org"></textarea>
-    <br/>
-    <label>
-      <input type="checkbox" name="include_subpages" value="true"/>
-      1階層リンク先を含める
-    </label>
-    <button type="submit">JSON変換</button>
-  </form>
-</body>
-</html>
-"#,
-        msg_subpage = msg_subpage,
-        total_chars = total_chars,
-        escaped_json = escaped_json,
-        json_str = json_str,
-        file_name = file_name
-    );
-
-    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(html)
-}
-
-/// HTMLを解析 (同期)
-fn parse_html_sync(body: &str) -> DomContent {
-    let doc = Html::parse_document(body);
-    let sel_html = Selector::parse("html").unwrap();
-    if let Some(html_el) = doc.select(&sel_html).next() {
-        DomContent::Node(DomNode {
-            tag: Some("html".to_string()),
-            href: None,
-            text: None,
-            children: parse_children(html_el),
-            link_subpage: None,
-        })
-    } else {
-        DomContent::Node(DomNode {
-            tag: Some("html".to_string()),
-            href: None,
-            text: Some("(No <html> found)".to_string()),
-            children: vec![],
-            link_subpage: None,
-        })
+/// (GET) 履歴の一覧 (新しい順、結果本体は含まない)
+async fn list_history(history: web::Data<HistoryStore>) -> impl Responder {
+    match history.list() {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => HttpResponse::InternalServerError().body(e),
     }
 }
 
-/// 再帰的に子を解析
-fn parse_children(el: ElementRef) -> Vec<DomContent> {
-    let mut result = Vec::new();
+/// (GET) 保存した結果をダウンロード
+async fn download_history(id: web::Path<i64>, history: web::Data<HistoryStore>) -> impl Responder {
+    match history.output(*id) {
+        Ok(Some(saved)) => HttpResponse::Ok()
+            .content_type(saved.format.content_type())
+            .insert_header((
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"history_{id}.{}\"", saved.format.extension()),
+            ))
+            .body(saved.output),
+        Ok(None) => HttpResponse::NotFound().body(format!("Unknown history entry {id}")),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
 
-    for child in el.children() {
-        match child.value() {
-            Node::Element(e) => {
-                let tag_name = e.name().to_lowercase();
-                if skip_tag(&tag_name) {
-                    continue;
-                }
-                if tag_name == "table" {
-                    if let Some(tbl) = ElementRef::wrap(child) {
-                        let table_data = parse_table(tbl);
-                        result.push(DomContent::Table(table_data));
-                    }
-                }
-                else if is_target_tag(&tag_name) {
-                    // a, p, h*, etc
-                    let mut link = None;
-                    if tag_name == "a" {
-                        for (attr_name, attr_value) in e.attrs() {
-                            if attr_name.eq_ignore_ascii_case("href") {
-                                link = Some(attr_value.to_string());
-                            }
-                        }
-                    }
-                    if let Some(sub_el) = ElementRef::wrap(child) {
-                        let children = parse_children(sub_el);
-                        result.push(DomContent::Node(DomNode {
-                            tag: Some(tag_name),
-                            href: link,
-                            text: None,
-                            children,
-                            link_subpage: None,
-                        }));
-                    }
-                }
-                else {
-                    // 中身だけ取り出す
-                    if let Some(sub_el) = ElementRef::wrap(child) {
-                        let sub = parse_children(sub_el);
-                        result.extend(sub);
-                    }
-                }
-            }
-            Node::Text(txt_node) => {
-                let c = clean_text(&txt_node.text);
-                if !c.is_empty() {
-                    result.push(DomContent::Node(DomNode {
-                        tag: None,
-                        href: None,
-                        text: Some(c),
-                        children: vec![],
-                        link_subpage: None,
-                    }));
-                }
-            }
-            _ => {}
+/// (DELETE) 履歴を削除
+async fn delete_history(id: web::Path<i64>, history: web::Data<HistoryStore>) -> impl Responder {
+    match history.delete(*id) {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().body(format!("Unknown history entry {id}")),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
+
+/// (POST) 複数URL対応
+#[allow(clippy::too_many_arguments)]
+async fn process_form(
+    req: HttpRequest,
+    payload: web::Payload,
+    settings: web::Data<Settings>,
+    response_cache: web::Data<ResponseCache>,
+    metrics: web::Data<metrics::Metrics>,
+    templates: web::Data<TemplateStore>,
+    job_store: web::Data<jobs::JobStore>,
+    history: web::Data<HistoryStore>,
+) -> impl Responder {
+    let form = match read_form(&req, payload).await.and_then(|form| resolve_form(form, &templates)) {
+        Ok(f) => f,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let mut options = match options_from_form(&form, &settings).and_then(|o| o.check_streaming().map(|()| o)) {
+        Ok(o) => o,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    // テンプレートとして保存 (適用済みの設定を、検証してから保存する)
+    if let Some(name) = form.save_template.as_deref().and_then(non_empty) {
+        if let Err(e) = templates.save(&name, &form) {
+            return HttpResponse::InternalServerError().body(e);
         }
     }
+    let url_list = form_urls(&form);
+
+    options.response_cache = Some(response_cache.into_inner());
+    options.metrics = Some(metrics.into_inner());
+    options.history = Some(history.clone().into_inner());
+
+    // NDJSON・逐次出力は HTML に埋め込まずそのまま流す
+    if options.streams() {
+        let disposition = format!("attachment; filename=\"multi_urls_result.{}\"", options.output_format.extension());
+        let mut response = streaming_response(url_list, options);
+        if let Ok(value) = disposition.parse() {
+            response.headers_mut().insert(header::CONTENT_DISPOSITION, value);
+        }
+        return response;
+    }
 
-    result
+    // バックグラウンドで変換し、進捗画面 (完了後は結果画面) へ
+    set_webhook_origin(&mut options, &req);
+    let job = start_job(&job_store, history.into_inner(), &form, url_list, options);
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, format!("/jobs/{}", job.id)))
+        .finish()
 }
 
-/// テーブル解析
-fn parse_table(table_el: ElementRef) -> TableData {
-    let mut headers = Vec::new();
-    let mut rows = Vec::new();
+/// フォームのボディ (URL エンコード、または URL リストのファイルを含む multipart)
+async fn read_form(req: &HttpRequest, payload: web::Payload) -> Result<UrlForm, String> {
+    let content_type = req.headers().get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    if content_type.starts_with("multipart/form-data") {
+        return urlfile::form_from_multipart(req.headers(), payload).await;
+    }
+    web::Form::<UrlForm>::from_request(req, &mut payload.into_inner())
+        .await
+        .map(web::Form::into_inner)
+        .map_err(|e| e.to_string())
+}
 
-    let tr_sel = Selector::parse("tr").unwrap();
-    let td_sel = Selector::parse("th,td").unwrap();
-    let mut first_row = true;
+/// 変換をジョブとして登録し、バックグラウンドで実行する
+fn start_job(
+    job_store: &jobs::JobStore,
+    history: Arc<HistoryStore>,
+    form: &UrlForm,
+    url_list: Vec<String>,
+    options: JobOptions,
+) -> Arc<jobs::Job> {
+    let job = job_store.create(options.clone(), form, &url_list);
+    spawn_job(job_store, job.clone(), history, form, url_list, options);
+    job
+}
 
-    for tr_el in table_el.select(&tr_sel) {
-        let mut cells = Vec::new();
-        for cell_el in tr_el.select(&td_sel) {
-            let raw_text = cell_el.text().collect::<String>();
-            let clean = clean_text(&raw_text);
-            cells.push(clean);
-        }
-        if cells.is_empty() {
-            continue;
+/// 登録したジョブをバックグラウンドで実行する (完了したら履歴に保存)
+fn spawn_job(
+    job_store: &jobs::JobStore,
+    job: Arc<jobs::Job>,
+    history: Arc<HistoryStore>,
+    form: &UrlForm,
+    url_list: Vec<String>,
+    mut options: JobOptions,
+) {
+    options.progress = Some(job.clone());
+    let form_options = history::form_options(form);
+    let progress = job.clone();
+    job_store.spawn(job.clone(), async move {
+        let results = run_job(&url_list, &options).await.map_err(|e| format!("HTTP client error: {e}"))?;
+        record_history(&history, &url_list, &form_options, &results, &options);
+        if let Some(bucket) = &options.upload {
+            let name = storage::upload_name(Some(&progress.id));
+            progress.set_upload(bucket.upload_results(&name, &url_list, &results, &options).await);
         }
-        if first_row {
-            // ヘッダ行
-            headers = cells;
-            first_row = false;
-        } else {
-            // データ行
-            let mut obj_map = serde_json::Map::new();
-            for (i, val) in cells.iter().enumerate() {
-                let col_name = if i < headers.len() {
-                    headers[i].clone()
-                } else {
-                    format!("col{i}")
-                };
-                obj_map.insert(col_name, serde_json::Value::String(val.clone()));
+        Ok(results)
+    });
+}
+
+/// 通知に載せる結果の URL の先頭を、ジョブを登録したリクエストのホストにする
+fn set_webhook_origin(options: &mut JobOptions, req: &HttpRequest) {
+    if let Some(webhook) = &mut options.webhook {
+        let info = req.connection_info();
+        webhook.origin = format!("{}://{}", info.scheme(), info.host());
+    }
+}
+
+/// 完了した変換を履歴に保存 (保存に失敗しても変換結果は返す)
+fn record_history(history: &HistoryStore, url_list: &[String], form_options: &serde_json::Value, results: &[DomContent], options: &JobOptions) {
+    // Cookie・認証情報・ヘッダを送った結果は他の人にも見える履歴に残さない
+    if !history.is_enabled() || options.sends_secrets() {
+        return;
+    }
+    if let Ok(output) = output::render(results, options) {
+        let _ = history.record(url_list, form_options, options.output_format, &output);
+    }
+}
+
+/// (POST) ジョブ API: /api/convert と同じ JSON を受け取り、ジョブを登録してすぐに ID を返す
+#[allow(clippy::too_many_arguments)]
+async fn api_jobs(
+    req: HttpRequest,
+    body: web::Json<UrlForm>,
+    settings: web::Data<Settings>,
+    response_cache: web::Data<ResponseCache>,
+    metrics: web::Data<metrics::Metrics>,
+    templates: web::Data<TemplateStore>,
+    job_store: web::Data<jobs::JobStore>,
+    history: web::Data<HistoryStore>,
+) -> impl Responder {
+    let body = match resolve_form(body.into_inner(), &templates) {
+        Ok(b) => b,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let mut options = match options_from_form(&body, &settings) {
+        Ok(o) => o,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    options.response_cache = Some(response_cache.into_inner());
+    options.metrics = Some(metrics.into_inner());
+    options.history = Some(history.clone().into_inner());
+    set_webhook_origin(&mut options, &req);
+    let job = start_job(&job_store, history.into_inner(), &body, form_urls(&body), options);
+    HttpResponse::Accepted()
+        .insert_header((header::LOCATION, format!("/jobs/{}", job.id)))
+        .json(job.report())
+}
+
+/// ジョブの画面 (実行中は進捗、完了後は結果)。Accept: application/json なら状態の JSON
+async fn show_job(
+    req: HttpRequest,
+    path: web::Path<String>,
+    settings: web::Data<Settings>,
+    templates: web::Data<TemplateStore>,
+    job_store: web::Data<jobs::JobStore>,
+) -> impl Responder {
+    let Some(job) = job_store.get(&path) else {
+        return HttpResponse::NotFound().body("Unknown job");
+    };
+    let accept = req.headers().get(header::ACCEPT).and_then(|v| v.to_str().ok()).unwrap_or("");
+    if accept.contains("application/json") {
+        return HttpResponse::Ok().json(job.report());
+    }
+    // report は結果のロックを取るので先に
+    let report = job.report();
+    job.with_outcome(|outcome| match outcome {
+        Ok(results) => result_page(&req, results, &job.options, &report, job.urls(), &settings, &templates),
+        Err(e) => HttpResponse::InternalServerError().body(e.clone()),
+    })
+    .unwrap_or_else(|| progress_page(&req, &job))
+}
+
+/// ジョブの結果 (ジョブの出力形式で返す。未完了なら 202 と状態の JSON)
+async fn job_result(path: web::Path<String>, job_store: web::Data<jobs::JobStore>) -> impl Responder {
+    let Some(job) = job_store.get(&path) else {
+        return HttpResponse::NotFound().body("Unknown job");
+    };
+    job.with_outcome(|outcome| match outcome {
+        Ok(results) => match output::render(results, &job.options) {
+            Ok(text) => HttpResponse::Ok().content_type(job.options.output_format.content_type()).body(text),
+            Err(e) => HttpResponse::InternalServerError().body(e),
+        },
+        Err(e) => HttpResponse::InternalServerError().body(e.clone()),
+    })
+    .unwrap_or_else(|| HttpResponse::Accepted().json(job.report()))
+}
+
+/// (GET) ジョブの index 番目の URL の結果 (ジョブの出力形式。実行中でも終わった URL から取得できる)
+async fn job_page_result(path: web::Path<(String, usize)>, job_store: web::Data<jobs::JobStore>) -> impl Responder {
+    let (id, index) = path.into_inner();
+    let Some(job) = job_store.get(&id) else {
+        return HttpResponse::NotFound().body("Unknown job");
+    };
+    let Some(url) = job.url(index) else {
+        return HttpResponse::NotFound().body("Unknown URL index");
+    };
+    let rendered = job.with_page(index, |content| output::render(std::slice::from_ref(content), &job.options));
+    // ダウンロード用ファイル名 (スキームを除いた URL の / と : を _ に)
+    let name = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest).replace(['/', ':'], "_");
+    let file_name = format!("{}.{}", sanitize(name), job.options.output_format.extension());
+    match rendered {
+        Some(Ok(text)) => HttpResponse::Ok()
+            .content_type(job.options.output_format.content_type())
+            .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"{file_name}\"")))
+            .body(text),
+        Some(Err(e)) => HttpResponse::InternalServerError().body(e),
+        None => HttpResponse::Accepted().json(job.report()),
+    }
+}
+
+/// (GET) ジョブで取得した HTML (<ホスト>/<パス> の配置の ZIP。保存を指定していなければ 404)
+async fn job_html(path: web::Path<String>, job_store: web::Data<jobs::JobStore>) -> impl Responder {
+    let Some(job) = job_store.get(&path) else {
+        return HttpResponse::NotFound().body("Unknown job");
+    };
+    let Some(archive) = &job.options.html_archive else {
+        return HttpResponse::NotFound().body("HTML was not saved for this job (save_html)");
+    };
+    if !job.is_done() {
+        return HttpResponse::Accepted().json(job.report());
+    }
+    match tables::zip_files(&archive.files("")) {
+        Ok(bytes) => HttpResponse::Ok()
+            .content_type("application/zip")
+            .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"html-{}.zip\"", job.id)))
+            .body(bytes),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
+
+/// (GET) ジョブの HTTP のリクエスト・レスポンスの WARC (.warc.gz。書き出しを指定していなければ 404)
+async fn job_warc(path: web::Path<String>, job_store: web::Data<jobs::JobStore>) -> impl Responder {
+    let Some(job) = job_store.get(&path) else {
+        return HttpResponse::NotFound().body("Unknown job");
+    };
+    let Some(writer) = &job.options.warc else {
+        return HttpResponse::NotFound().body("WARC was not recorded for this job (warc)");
+    };
+    if !job.is_done() {
+        return HttpResponse::Accepted().json(job.report());
+    }
+    HttpResponse::Ok()
+        .content_type("application/warc")
+        .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"crawl-{}.warc.gz\"", job.id)))
+        .body(writer.bytes())
+}
+
+/// (POST) ジョブの一時停止 (取得中の URL が終わったところで止まる)
+async fn pause_job(path: web::Path<String>, job_store: web::Data<jobs::JobStore>) -> impl Responder {
+    let Some(job) = job_store.get(&path) else {
+        return HttpResponse::NotFound().body("Unknown job");
+    };
+    if !job.pause() {
+        return HttpResponse::Conflict().body("Job has already finished");
+    }
+    HttpResponse::Ok().json(job.report())
+}
+
+/// (POST) 一時停止したジョブの再開 (再起動で読み戻したジョブはここで実行を始める)
+///
+/// 読み戻したジョブは保存しなかった項目 (HTML・ヘッダ・Cookie・認証情報) をフォームで入力し直す。
+#[allow(clippy::too_many_arguments)]
+async fn resume_job(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: Option<web::Form<jobs::ResumeForm>>,
+    settings: web::Data<Settings>,
+    response_cache: web::Data<ResponseCache>,
+    metrics: web::Data<metrics::Metrics>,
+    job_store: web::Data<jobs::JobStore>,
+    history: web::Data<HistoryStore>,
+) -> impl Responder {
+    let Some(job) = job_store.get(&path) else {
+        return HttpResponse::NotFound().body("Unknown job");
+    };
+    if job.is_done() {
+        return HttpResponse::Conflict().body("Job has already finished");
+    }
+    let Some(mut saved) = job.take_pending() else {
+        job.resume();
+        return HttpResponse::Ok().json(job.report());
+    };
+    let mut options = job.options.clone();
+    if !saved.missing.is_empty() {
+        let filled = body
+            .map(web::Form::into_inner)
+            .unwrap_or_default()
+            .fill(&mut saved)
+            .and_then(|_| restored_options(&saved.form, &settings, &response_cache, &metrics, &history));
+        match filled {
+            Ok(filled) => options = filled,
+            Err(e) => {
+                // 入力し直すまで読み戻した状態のまま
+                job.set_pending(saved);
+                return HttpResponse::BadRequest().body(e);
             }
-            rows.push(serde_json::Value::Object(obj_map));
         }
     }
+    job.resume();
+    set_webhook_origin(&mut options, &req);
+    spawn_job(&job_store, job.clone(), history.into_inner(), &saved.form, saved.urls, options);
+    HttpResponse::Ok().json(job.report())
+}
 
-    TableData {
-        table_headers: headers,
-        rows,
-    }
+/// 読み戻したジョブのオプション (共有のキャッシュ・メトリクス・履歴も付ける)
+fn restored_options(
+    form: &UrlForm,
+    settings: &Settings,
+    response_cache: &web::Data<ResponseCache>,
+    metrics: &web::Data<metrics::Metrics>,
+    history: &web::Data<HistoryStore>,
+) -> Result<JobOptions, String> {
+    let mut options = options_from_form(form, settings)?;
+    options.response_cache = Some(response_cache.clone().into_inner());
+    options.metrics = Some(metrics.clone().into_inner());
+    options.history = Some(history.clone().into_inner());
+    Ok(options)
 }
 
-/// aタグ => link_subpage
-async fn fetch_subpages_for_depth_one(content: &mut DomContent, base_url: &Url) -> Result<(), String> {
-    let mut stack = vec![content as *mut DomContent];
-    while let Some(ptr) = stack.pop() {
-        let node_content = unsafe { &mut *ptr };
-        match node_content {
-            DomContent::Table(_) => { /* skip table sub links */ }
-            DomContent::Node(node) => {
-                // BFS
-                for c in node.children.iter_mut() {
-                    stack.push(c as *mut DomContent);
+/// 実行中ジョブの進捗画面 (Server-Sent Events で URL ごとの状態を更新し、完了したら再読み込み)
+fn progress_page(req: &HttpRequest, job: &jobs::Job) -> HttpResponse {
+    pages::respond(req, &pages::ProgressPage {
+        lang: Lang::from_request(req),
+        id: &job.id,
+        queued: matches!(job.status(), jobs::JobStatus::Queued),
+        paused: matches!(job.status(), jobs::JobStatus::Paused),
+        missing: job.missing(),
+    })
+}
+
+/// ジョブの進捗 (Server-Sent Events)
+///
+/// 接続までに出たイベントも送り直し、完了したら "done" を送って閉じる。
+async fn job_events(path: web::Path<String>, job_store: web::Data<jobs::JobStore>) -> impl Responder {
+    let Some(job) = job_store.get(&path) else {
+        return HttpResponse::NotFound().body("Unknown job");
+    };
+    let rx = job.subscribe();
+    // (ジョブ, 更新通知, 送信済みイベント数, 完了を送ったか)
+    let body = stream::unfold((job, rx, 0usize, false), |(job, mut rx, sent, finished)| async move {
+        if finished {
+            return None;
+        }
+        loop {
+            // 完了を先に見てからイベントを取る (完了直前のイベントを取りこぼさない)
+            let done = job.is_done();
+            let events = job.events_since(sent);
+            if !events.is_empty() || done {
+                let mut chunk = String::new();
+                for event in &events {
+                    let data = serde_json::to_string(event).unwrap_or_default();
+                    chunk.push_str(&format!("event: progress\ndata: {data}\n\n"));
                 }
-                if let Some(t) = &node.tag {
-                    if t == "a" {
-                        if let Some(href) = &node.href {
-                            if let Ok(sub_url) = base_url.join(href) {
-                                if ["http","https"].contains(&sub_url.scheme()) {
-                                    let body = match reqwest::get(sub_url.clone()).await {
-                                        Ok(r) => match r.text().await {
-                                            Ok(tx) => tx,
-                                            Err(_e) => { continue; } // _e -> discard
-                                        },
-                                        Err(_e) => { continue; } // _e -> discard
-                                    };
-                                    let subdom = spawn_blocking({
-                                        let body_clone = body.clone();
-                                        move || parse_html_sync(&body_clone)
-                                    }).await.map_err(|e_spawn| format!("spawn_blocking: {e_spawn:?}"))?;
-                                    node.link_subpage = Some(Box::new(subdom));
-                                }
-                            }
-                        }
-                    }
+                if done {
+                    let error = job.with_outcome(|o| o.as_ref().err().cloned()).flatten();
+                    let data = serde_json::json!({ "error": error });
+                    chunk.push_str(&format!("event: done\ndata: {data}\n\n"));
                 }
+                let sent = sent + events.len();
+                return Some((Ok::<_, actix_web::Error>(web::Bytes::from(chunk)), (job, rx, sent, done)));
+            }
+            if rx.changed().await.is_err() {
+                return None;
             }
         }
-    }
-    Ok(())
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(body)
 }
 
-/// テキスト整形
-fn clean_text(raw: &str) -> String {
-    let replaced = raw.replace('\n', " ");
-    let re = Regex::new(r"\s+").unwrap();
-    re.replace_all(&replaced, " ").trim().to_string()
+/// 結果画面
+fn result_page(
+    req: &HttpRequest,
+    results: &[DomContent],
+    options: &JobOptions,
+    report: &jobs::JobReport,
+    urls: Vec<String>,
+    settings: &Settings,
+    templates: &TemplateStore,
+) -> HttpResponse {
+    let format = options.output_format;
+    let output = match output::render(results, options) {
+        Ok(t) => t,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+
+    // テーブルごとの CSV ダウンロード
+    let collected = tables::collect_tables(results);
+    let tables: Vec<pages::TableCsv> = collected
+        .iter()
+        .map(|(name, table)| pages::TableCsv {
+            name: name.clone(),
+            rows: table.rows.len(),
+            csv: tables::to_csv(table),
+        })
+        .collect();
+    // すべてのテーブルを 1 つの SQLite のファイルに (ページに埋め込んでダウンロード)
+    let sqlite = (!collected.is_empty())
+        .then(|| tables::to_sqlite(&collected).map(|bytes| base64::engine::general_purpose::STANDARD.encode(bytes)));
+
+    let lang = Lang::from_request(req);
+    pages::respond(req, &pages::ResultPage {
+        lang,
+        id: &report.id,
+        description: lang.t(match format {
+            OutputFormat::Json => "配列形式の JSON",
+            OutputFormat::Markdown => "Markdown",
+            OutputFormat::Ndjson => "NDJSON",
+            OutputFormat::Yaml => "YAML",
+            OutputFormat::Text => "プレーンテキスト",
+        }),
+        label: format.label(),
+        mime: format.content_type(),
+        // ダウンロード用ファイル名
+        file_name: format!("multi_urls_{}.{}", sanitize("result"), format.extension()),
+        include_subpages: options.include_subpages,
+        total_chars: output.chars().count(),
+        output,
+        summary: report.summary.as_ref(),
+        upload_error: report.upload_error.as_deref(),
+        uploaded: &report.uploaded,
+        urls,
+        tables,
+        sqlite,
+        // リンクグラフのエッジリスト
+        edges_csv: links::has_link_graph(results).then(|| links::edges_csv(results)),
+        html_count: options.html_archive.as_ref().map(|archive| archive.len()),
+        warc: options.warc.is_some(),
+        form: form(lang, settings, templates),
+    })
 }
 
-/// スキップ対象タグ
-fn skip_tag(tag_name: &str) -> bool {
-    matches!(
-        tag_name,
-        "script" | "style" | "meta" | "link" | "noscript" |
-        "svg" | "iframe" |
-        "nav" | "footer" | "header"
-    )
+/// 完了した URL から順に (入力順で) 書き出すレスポンス (結果全体をメモリにためない)
+///
+/// クライアントの読み出しが遅ければ変換も待ち、切断したら残りの URL は取得しない。
+fn streaming_response(url_list: Vec<String>, options: JobOptions) -> HttpResponse {
+    let content_type = options.output_format.content_type();
+    let (tx, rx) = tokio::sync::mpsc::channel::<String>(STREAM_BUFFER);
+    actix_web::rt::spawn(async move {
+        let mut renderer = output::StreamRenderer::new(&options);
+        if tx.send(renderer.start()).await.is_err() {
+            return;
+        }
+        let result = run_job_each(&url_list, &options, |content| {
+            let piece = render_page(&mut renderer, &content);
+            let tx = tx.clone();
+            async move {
+                match tx.send(piece).await {
+                    Ok(()) => ControlFlow::Continue(()),
+                    Err(_) => ControlFlow::Break(()),
+                }
+            }
+        })
+        .await;
+        if let Err(e) = result {
+            let content = error_node("ErrorFetch", format!("HTTP client error: {e}"));
+            if tx.send(render_page(&mut renderer, &content)).await.is_err() {
+                return;
+            }
+        }
+        if tx.send(renderer.finish()).await.is_err() {
+            tracing::info!("output closed before the end of the stream");
+        }
+    });
+    let body = stream::unfold(rx, |mut rx| async move {
+        rx.recv()
+            .await
+            .map(|piece| (Ok::<_, actix_web::Error>(web::Bytes::from(piece)), rx))
+    });
+    HttpResponse::Ok().content_type(content_type).streaming(body)
 }
 
-/// パース対象タグ
-fn is_target_tag(tag_name: &str) -> bool {
-    matches!(
-        tag_name,
-        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" |
-        "p" |
-        "ul" | "ol" | "li" |
-        "a"
-    )
+/// 1 ページ分の出力 (書き出せなければエラーノードに置き換える)
+fn render_page(renderer: &mut output::StreamRenderer, content: &DomContent) -> String {
+    renderer.page(content).unwrap_or_else(|e| {
+        tracing::warn!(error = %e, "failed to render page");
+        renderer.page(&error_node("ErrorOutput", e)).unwrap_or_default()
+    })
 }
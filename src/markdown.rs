@@ -0,0 +1,394 @@
+//! 解析結果を Markdown に変換 (見出し・リスト・リンク・引用・GFM テーブル・コードブロック)
+
+use crate::code::CodeBlock;
+use crate::diff::{self, PageDiff};
+use crate::feed::FeedData;
+use crate::filter;
+use crate::forms::FormData;
+use crate::images::ImageData;
+use crate::links::{self, PageLinks};
+use crate::media::MediaData;
+use crate::rules;
+use crate::summary::JobSummary;
+use crate::url_result::UrlResult;
+use crate::{DomContent, DomNode, TableData};
+
+/// 1 ページ分を Markdown に
+pub fn page_to_markdown(content: &DomContent) -> String {
+    let mut renderer = Renderer::default();
+    renderer.block(content);
+    // リンク先のページは本文の後ろに続ける
+    let subpages = std::mem::take(&mut renderer.subpages);
+    for (href, subpage) in subpages {
+        renderer.out.push_str(&format!("---\n\n<!-- subpage: {href} -->\n\n"));
+        renderer.out.push_str(&page_to_markdown(subpage));
+    }
+    renderer.out.trim_end().to_string() + "\n"
+}
+
+#[derive(Default)]
+struct Renderer<'a> {
+    out: String,
+    /// (href, リンク先ページ)
+    subpages: Vec<(String, &'a DomContent)>,
+}
+
+impl<'a> Renderer<'a> {
+    fn block(&mut self, content: &'a DomContent) {
+        match content {
+            DomContent::Node(node) => self.node_block(node),
+            DomContent::Table(table) => self.table(table),
+            DomContent::Feed(feed) => self.feed(feed),
+            DomContent::Image(image) => self.paragraph(&image_markdown(image)),
+            DomContent::Code(code) => self.paragraph(&code_markdown(code)),
+            DomContent::Media(media) => self.paragraph(&media_markdown(media)),
+            DomContent::Form(form) => self.form(form),
+            DomContent::Extracted(fields) => {
+                for (key, value) in fields {
+                    self.out.push_str(&format!("- **{key}**: {}\n", rules::field_text(value)));
+                }
+                self.out.push('\n');
+            }
+            DomContent::Resource(resource) => match &resource.data {
+                Some(data) => {
+                    let json = serde_json::to_string_pretty(data).unwrap_or_default();
+                    self.paragraph(&format!("```json\n{json}\n```"));
+                }
+                None => self.paragraph(&format!("> **{}**: {}", resource.tag, resource.content_type)),
+            },
+            DomContent::Result(result) => match &**result {
+                UrlResult::Document { document, .. } => self.block(document),
+                UrlResult::Error { error, .. } => self.paragraph(&format!("> **Error ({})**: {}", error.kind, error.message)),
+            },
+            DomContent::Summary(block) => self.summary(&block.summary),
+            DomContent::Links(page) => self.links(page),
+            DomContent::Diff(diff) => self.diff(diff),
+            DomContent::Filtered(value @ serde_json::Value::Object(_)) => {
+                let json = serde_json::to_string_pretty(value).unwrap_or_default();
+                self.paragraph(&format!("```json\n{json}\n```"));
+            }
+            DomContent::Filtered(serde_json::Value::Array(items)) => {
+                for item in items {
+                    self.out.push_str(&format!("- {}\n", rules::field_text(item)));
+                }
+                self.out.push('\n');
+            }
+            DomContent::Filtered(value) => self.paragraph(&filter::value_text(value)),
+        }
+    }
+
+    fn node_block(&mut self, node: &'a DomNode) {
+        match node.tag.as_deref() {
+            Some(tag @ ("h1" | "h2" | "h3" | "h4" | "h5" | "h6")) => {
+                let level: usize = tag[1..].parse().unwrap_or(1);
+                let text = self.inline_children(node);
+                self.paragraph(&format!("{} {text}", "#".repeat(level)));
+            }
+            Some("p") | Some("a") | None => {
+                let text = self.inline(node);
+                self.paragraph(&text);
+            }
+            Some("ul") | Some("ol") => {
+                self.list(node, 0);
+                self.out.push('\n');
+            }
+            Some("blockquote") => {
+                let inner = self.nested(&node.children);
+                let quoted: Vec<String> = inner
+                    .lines()
+                    .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {line}") })
+                    .collect();
+                self.paragraph(&quoted.join("\n"));
+            }
+            // 定義リストは "**用語**" と ": 説明" の行に
+            Some("dt") => {
+                let text = self.inline_children(node);
+                if !text.is_empty() {
+                    self.paragraph(&format!("**{text}**"));
+                }
+            }
+            Some("dd") => {
+                let inner = self.nested(&node.children);
+                let text = inner.trim().replace("\n", "\n  ");
+                if !text.is_empty() {
+                    self.paragraph(&format!(": {text}"));
+                }
+            }
+            Some("figcaption") => {
+                let text = self.inline_children(node);
+                if !text.is_empty() {
+                    self.paragraph(&format!("*{text}*"));
+                }
+            }
+            // エラーノード
+            Some(tag) if tag.starts_with("Error") => {
+                let text = node.text.as_deref().unwrap_or("");
+                self.paragraph(&format!("> **{tag}**: {text}"));
+            }
+            _ => {
+                if let Some(text) = &node.text {
+                    self.paragraph(text);
+                }
+                for child in &node.children {
+                    self.block(child);
+                }
+            }
+        }
+    }
+
+    /// 子要素だけを別に描画 (引用などで行頭に記号を付けるため)
+    fn nested(&mut self, children: &'a [DomContent]) -> String {
+        let mut inner = Renderer::default();
+        for child in children {
+            inner.block(child);
+        }
+        self.subpages.append(&mut inner.subpages);
+        inner.out.trim_end().to_string()
+    }
+
+    /// 集計 (失敗・スキップした URL は箇条書き)
+    fn summary(&mut self, summary: &JobSummary) {
+        self.paragraph(&format!("## Summary\n\n{}", summary.line()));
+        for failure in &summary.failures {
+            self.out.push_str(&format!("- <{}> ({}): {}\n", failure.url, failure.kind, failure.message));
+        }
+        self.out.push('\n');
+    }
+
+    /// 差分 (1 行の要約と変化の一覧)
+    fn diff(&mut self, diff: &PageDiff) {
+        self.paragraph(&format!("## Changes\n\n{}", diff::summary_line(diff)));
+        for change in &diff.changes {
+            self.out.push_str(&format!("- `{}`\n", diff::change_text(change).replace('`', "'")));
+        }
+        self.out.push('\n');
+    }
+
+    /// リンクグラフ (外部リンクには (external)、rel があれば併記。リンク先のページは見出しを付けて続ける)
+    fn links(&mut self, page: &PageLinks) {
+        for link in &page.links {
+            let label = if link.text.is_empty() { &link.href } else { &link.text };
+            let mut line = format!("- [{}]({})", escape_link_text(label), link.href.replace(' ', "%20"));
+            if !link.internal {
+                line.push_str(" (external)");
+            }
+            if !link.rel.is_empty() {
+                line.push_str(&format!(" rel={}", link.rel.join(" ")));
+            }
+            if let Some(check) = &link.check {
+                let marker = if check.ok { "" } else { "**broken** " };
+                line.push_str(&format!(" — {marker}{}", links::check_text(check)));
+            }
+            self.out.push_str(&line);
+            self.out.push('\n');
+        }
+        self.out.push('\n');
+        for subpage in &page.subpages {
+            self.paragraph(&format!("### <{}>", subpage.url));
+            self.links(subpage);
+        }
+    }
+
+    fn paragraph(&mut self, text: &str) {
+        let text = text.trim();
+        if !text.is_empty() {
+            self.out.push_str(text);
+            self.out.push_str("\n\n");
+        }
+    }
+
+    /// インライン要素として (テキスト・リンク)
+    fn inline(&mut self, node: &'a DomNode) -> String {
+        let mut text = escape_link_text(node.text.as_deref().unwrap_or(""));
+        let children = self.inline_children(node);
+        if !children.is_empty() {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(&children);
+        }
+        match (node.tag.as_deref(), &node.href) {
+            (Some("a"), Some(href)) => {
+                if let Some(subpage) = &node.link_subpage {
+                    self.subpages.push((href.clone(), subpage));
+                }
+                let label = if text.is_empty() { escape_link_text(href) } else { text };
+                format!("[{label}]({})", href.replace(' ', "%20"))
+            }
+            _ => text,
+        }
+    }
+
+    fn inline_children(&mut self, node: &'a DomNode) -> String {
+        let mut parts = Vec::new();
+        for child in &node.children {
+            match child {
+                DomContent::Node(n) => parts.push(self.inline(n)),
+                DomContent::Image(image) => parts.push(image_markdown(image)),
+                DomContent::Code(code) => parts.push(code_markdown(code)),
+                DomContent::Media(media) => parts.push(media_markdown(media)),
+                // 段落中のテーブル等は文字列化せず省略
+                DomContent::Table(_) | DomContent::Feed(_) | DomContent::Form(_) | DomContent::Extracted(_) | DomContent::Resource(_) | DomContent::Result(_) | DomContent::Summary(_) | DomContent::Links(_) | DomContent::Diff(_) | DomContent::Filtered(_) => {}
+            }
+        }
+        parts.retain(|p| !p.is_empty());
+        parts.join(" ")
+    }
+
+    /// 入れ子リスト (depth 段インデント)
+    fn list(&mut self, node: &'a DomNode, depth: usize) {
+        let ordered = node.tag.as_deref() == Some("ol");
+        let indent = "  ".repeat(depth);
+        let mut number = 1;
+        for child in &node.children {
+            let DomContent::Node(item) = child else {
+                continue;
+            };
+            // li 以外 (直下のテキスト等) も項目として扱う
+            let mut text_parts = Vec::new();
+            let mut nested = Vec::new();
+            if item.tag.as_deref() == Some("li") {
+                if let Some(t) = &item.text {
+                    text_parts.push(t.clone());
+                }
+                for c in &item.children {
+                    match c {
+                        DomContent::Node(n) if matches!(n.tag.as_deref(), Some("ul") | Some("ol")) => nested.push(n),
+                        DomContent::Node(n) => text_parts.push(self.inline(n)),
+                        _ => {}
+                    }
+                }
+            } else if matches!(item.tag.as_deref(), Some("ul") | Some("ol")) {
+                nested.push(item);
+            } else {
+                text_parts.push(self.inline(item));
+            }
+            text_parts.retain(|p| !p.is_empty());
+            if !text_parts.is_empty() {
+                let marker = if ordered { format!("{number}.") } else { "-".to_string() };
+                number += 1;
+                self.out.push_str(&format!("{indent}{marker} {}\n", text_parts.join(" ")));
+            }
+            for n in nested {
+                self.list(n, depth + 1);
+            }
+        }
+    }
+
+    /// GFM テーブル
+    fn table(&mut self, table: &TableData) {
+        let columns = table.columns();
+        if columns.is_empty() {
+            return;
+        }
+        let header: Vec<String> = columns.iter().map(|c| escape_cell(c)).collect();
+        self.out.push_str(&format!("| {} |\n", header.join(" | ")));
+        self.out.push_str(&format!("|{}\n", " --- |".repeat(columns.len())));
+        for row in &table.rows {
+            let cells: Vec<String> = TableData::row_cells(row, &columns).iter().map(|c| escape_cell(c)).collect();
+            self.out.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+        self.out.push('\n');
+        if let Some(total) = table.total_rows.filter(|_| table.truncated) {
+            self.paragraph(&format!("_Truncated: {} of {total} rows_", table.rows.len()));
+        }
+    }
+
+    /// フォームは "メソッド 送信先" と項目の一覧に
+    fn form(&mut self, form: &FormData) {
+        self.paragraph(&format!("**Form** `{} {}`", form.method, form.action));
+        for field in &form.fields {
+            let kind = field.input_type.as_deref().unwrap_or(&field.tag);
+            let mut line = match &field.name {
+                Some(name) => format!("- `{name}` ({kind})"),
+                None => format!("- ({kind})"),
+            };
+            if let Some(label) = &field.label {
+                line.push_str(&format!(": {label}"));
+            }
+            if !field.options.is_empty() {
+                let options: Vec<&str> = field.options.iter().map(|o| o.label.as_str()).collect();
+                line.push_str(&format!(" [{}]", options.join(" / ")));
+            }
+            self.out.push_str(&line);
+            self.out.push('\n');
+        }
+        self.out.push('\n');
+    }
+
+    fn feed(&mut self, feed: &'a FeedData) {
+        if let Some(title) = &feed.feed_title {
+            self.paragraph(&format!("# {title}"));
+        }
+        if let Some(description) = &feed.feed_description {
+            self.paragraph(description);
+        }
+        for item in &feed.items {
+            let title = item.title.as_deref().unwrap_or("(no title)");
+            let heading = match &item.link {
+                Some(link) => format!("## [{}]({link})", escape_link_text(title)),
+                None => format!("## {title}"),
+            };
+            self.paragraph(&heading);
+            if let Some(date) = &item.pub_date {
+                self.paragraph(&format!("*{date}*"));
+            }
+            if let Some(description) = &item.description {
+                self.paragraph(description);
+            }
+            if let Some(article) = &item.article {
+                self.block(article);
+            }
+        }
+    }
+}
+
+/// ![alt](src) (src がなければ alt のみ)
+fn image_markdown(image: &ImageData) -> String {
+    let alt = image.alt.as_deref().unwrap_or("");
+    match &image.src {
+        Some(src) => format!("![{}]({})", escape_link_text(alt), src.replace(' ', "%20")),
+        None => alt.to_string(),
+    }
+}
+
+fn escape_link_text(text: &str) -> String {
+    text.replace('[', "\\[").replace(']', "\\]")
+}
+
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+/// pre はフェンス付きコードブロック、code はインラインコード
+/// (本文中の ` の連続より長いフェンスを使う)
+fn code_markdown(code: &CodeBlock) -> String {
+    let longest = code
+        .text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    if code.tag == "pre" {
+        let fence = "`".repeat((longest + 1).max(3));
+        let language = code.language.as_deref().unwrap_or("");
+        format!("{fence}{language}\n{}\n{fence}", code.text)
+    } else {
+        let fence = "`".repeat(longest + 1);
+        let text = code.text.replace('\n', " ");
+        if text.starts_with('`') || text.ends_with('`') {
+            format!("{fence} {text} {fence}")
+        } else {
+            format!("{fence}{text}{fence}")
+        }
+    }
+}
+
+/// 動画・音声はファイルへのリンクに (src がなければ最初の <source>)
+fn media_markdown(media: &MediaData) -> String {
+    let src = media.src.as_deref().or_else(|| media.sources.first().map(|s| s.src.as_str()));
+    match src {
+        Some(src) => format!("[{}]({})", media.tag, src.replace(' ', "%20")),
+        None => String::new(),
+    }
+}
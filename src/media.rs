@@ -0,0 +1,95 @@
+//! <video> / <audio> 要素の抽出 (src・<source>・poster・字幕トラック)
+
+use schemars::JsonSchema;
+use scraper::{ElementRef, Selector};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::images::{parse_dimension, resolve};
+
+/// 動画・音声ノード
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MediaData {
+    /// "video" または "audio"
+    pub tag: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub src: Option<String>,
+    /// 動画のサムネイル
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poster: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// 子の <source>
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<MediaSource>,
+    /// 子の <track> (字幕・チャプター)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tracks: Vec<MediaTrack>,
+}
+
+/// <source>
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MediaSource {
+    pub src: String,
+    /// "video/mp4" など
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+}
+
+/// <track>
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MediaTrack {
+    pub src: String,
+    /// "subtitles" / "captions" / "chapters" など
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub srclang: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// <video> / <audio> を MediaData に (URL は絶対 URL に解決)
+pub fn parse_media(el: ElementRef, base_url: &Url) -> MediaData {
+    let e = el.value();
+    let attr = |name: &str| e.attr(name).map(str::trim).filter(|v| !v.is_empty());
+    let tag = if e.name().eq_ignore_ascii_case("audio") { "audio" } else { "video" };
+
+    let sel_source = Selector::parse("source").unwrap();
+    let sources = el
+        .select(&sel_source)
+        .filter_map(|source| {
+            let attr = |name: &str| source.value().attr(name).map(str::trim).filter(|v| !v.is_empty());
+            Some(MediaSource {
+                src: resolve(base_url, attr("src").or_else(|| attr("data-src"))?),
+                media_type: attr("type").map(str::to_string),
+            })
+        })
+        .collect();
+
+    let sel_track = Selector::parse("track").unwrap();
+    let tracks = el
+        .select(&sel_track)
+        .filter_map(|track| {
+            let attr = |name: &str| track.value().attr(name).map(str::trim).filter(|v| !v.is_empty());
+            Some(MediaTrack {
+                src: resolve(base_url, attr("src")?),
+                kind: attr("kind").map(str::to_string),
+                srclang: attr("srclang").map(str::to_string),
+                label: attr("label").map(str::to_string),
+            })
+        })
+        .collect();
+
+    MediaData {
+        tag: tag.to_string(),
+        src: attr("src").or_else(|| attr("data-src")).map(|s| resolve(base_url, s)),
+        poster: attr("poster").map(|s| resolve(base_url, s)),
+        width: attr("width").and_then(parse_dimension),
+        height: attr("height").and_then(parse_dimension),
+        sources,
+        tracks,
+    }
+}
@@ -0,0 +1,174 @@
+//! ページ単位のメタ情報 (title / description / canonical / hreflang / OpenGraph / Twitter カード)
+
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::clean_text;
+use crate::fetch::FetchInfo;
+use crate::language::PageLanguage;
+use crate::stats::PageStats;
+
+/// ページのメタ情報
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct PageMeta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// <link rel="canonical"> (絶対 URL)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canonical: Option<String>,
+    /// <link rel="alternate" hreflang> (言語ごとの別版)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternates: Vec<Alternate>,
+    /// og:* (同じプロパティが複数ある場合は最初のもの)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub open_graph: BTreeMap<String, String>,
+    /// twitter:*
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub twitter: BTreeMap<String, String>,
+    /// <meta name="robots"> の指示 (小文字。noindex / nofollow など)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub robots: Vec<String>,
+    /// ページの言語 (<html lang>、なければ本文から推定)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<PageLanguage>,
+    /// 語数・文字数・見出し・リンク・テーブル・画像の数 (集計を含める指定時)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<PageStats>,
+    /// <base href> (ページの URL で絶対 URL に解決したもの)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    /// レスポンスの Content-Type
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// 取得の情報 (取得情報を含める指定時)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetch: Option<FetchInfo>,
+}
+
+/// 言語ごとの別版のページ
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Alternate {
+    /// ja / en-US / x-default など
+    pub hreflang: String,
+    /// 絶対 URL
+    pub href: String,
+}
+
+impl PageMeta {
+    /// 検索結果に載せない指定 (noindex / none)
+    pub fn noindex(&self) -> bool {
+        has_directive(&self.robots, "noindex")
+    }
+
+    /// リンクをたどらない指定 (nofollow / none)
+    pub fn nofollow(&self) -> bool {
+        has_directive(&self.robots, "nofollow")
+    }
+
+    fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.description.is_none()
+            && self.canonical.is_none()
+            && self.alternates.is_empty()
+            && self.open_graph.is_empty()
+            && self.twitter.is_empty()
+            && self.robots.is_empty()
+            && self.language.is_none()
+            && self.stats.is_none()
+            && self.base.is_none()
+            && self.content_type.is_none()
+            && self.fetch.is_none()
+    }
+}
+
+/// 文書からメタ情報を抽出 (何もなければ None。リンクは page_url で絶対 URL に)
+pub fn extract_page_meta(doc: &Html, page_url: &Url) -> Option<PageMeta> {
+    let mut meta = PageMeta::default();
+
+    let sel_title = Selector::parse("title").unwrap();
+    meta.title = doc
+        .select(&sel_title)
+        .next()
+        .map(|t| clean_text(&t.text().collect::<String>()))
+        .filter(|t| !t.is_empty());
+
+    let sel_meta = Selector::parse("meta").unwrap();
+    for el in doc.select(&sel_meta) {
+        let attrs = el.value();
+        let Some(content) = attrs.attr("content").map(str::trim).filter(|c| !c.is_empty()) else {
+            continue;
+        };
+        // OpenGraph は property、Twitter カードは name が一般的だがどちらも許容
+        let key = attrs.attr("property").or_else(|| attrs.attr("name")).unwrap_or("").trim().to_ascii_lowercase();
+        if key == "description" {
+            meta.description.get_or_insert_with(|| content.to_string());
+        } else if key.starts_with("og:") {
+            meta.open_graph.entry(key).or_insert_with(|| content.to_string());
+        } else if key.starts_with("twitter:") {
+            meta.twitter.entry(key).or_insert_with(|| content.to_string());
+        } else if key == "robots" {
+            meta.robots.extend(parse_robots(content));
+        }
+    }
+
+    let sel_link = Selector::parse("link[rel][href]").unwrap();
+    let has_rel = |el: &scraper::ElementRef, name: &str| {
+        el.value()
+            .attr("rel")
+            .is_some_and(|rel| rel.split_ascii_whitespace().any(|r| r.eq_ignore_ascii_case(name)))
+    };
+    let resolve = |el: &scraper::ElementRef| {
+        let href = el.value().attr("href").unwrap_or("").trim();
+        page_url.join(href).map_or_else(|_| href.to_string(), String::from)
+    };
+    for el in doc.select(&sel_link) {
+        if has_rel(&el, "canonical") {
+            meta.canonical.get_or_insert_with(|| resolve(&el));
+        } else if has_rel(&el, "alternate") {
+            if let Some(hreflang) = el.value().attr("hreflang").map(str::trim).filter(|h| !h.is_empty()) {
+                meta.alternates.push(Alternate {
+                    hreflang: hreflang.to_string(),
+                    href: resolve(&el),
+                });
+            }
+        }
+    }
+
+    (!meta.is_empty()).then_some(meta)
+}
+
+/// 文書の <meta name="robots"> の指示 (リンクグラフ用)
+pub fn robots(doc: &Html) -> Vec<String> {
+    let sel_robots = Selector::parse("meta[name][content]").unwrap();
+    doc.select(&sel_robots)
+        .filter(|el| el.value().attr("name").is_some_and(|name| name.trim().eq_ignore_ascii_case("robots")))
+        .flat_map(|el| parse_robots(el.value().attr("content").unwrap_or("")))
+        .collect()
+}
+
+/// robots の指示 (none は noindex と nofollow の両方)
+pub fn has_directive(robots: &[String], directive: &str) -> bool {
+    robots.iter().any(|d| d == directive || d == "none")
+}
+
+/// "noindex, nofollow" → ["noindex", "nofollow"]
+fn parse_robots(content: &str) -> Vec<String> {
+    content
+        .split(',')
+        .map(|d| d.trim().to_ascii_lowercase())
+        .filter(|d| !d.is_empty())
+        .collect()
+}
+
+/// 最初の <base href> をページの URL で解決したもの (相対 URL の基準)
+pub fn base_url(doc: &Html, page_url: &Url) -> Option<Url> {
+    let sel_base = Selector::parse("base[href]").unwrap();
+    let href = doc.select(&sel_base).next()?.value().attr("href")?.trim();
+    page_url.join(href).ok()
+}
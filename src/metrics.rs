@@ -0,0 +1,154 @@
+//! Prometheus 形式のメトリクス (/metrics)
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::jobs::JobCounts;
+
+/// 所要時間のヒストグラムのバケット境界 (秒)
+const DURATION_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// 所要時間のヒストグラム
+#[derive(Debug)]
+struct Histogram {
+    /// バケットごとの件数 (境界以下に入る件数。累積は出力時に計算)
+    buckets: Vec<AtomicU64>,
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Histogram {
+            buckets: DURATION_BUCKETS.iter().map(|_| AtomicU64::new(0)).collect(),
+            sum_micros: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        if let Some(i) = DURATION_BUCKETS.iter().position(|&le| secs <= le) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.sum_micros.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn write(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} histogram");
+        let mut cumulative = 0;
+        for (le, bucket) in DURATION_BUCKETS.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{name}_bucket{{le=\"{le}\"}} {cumulative}");
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {count}\n{name}_sum {sum}\n{name}_count {count}");
+    }
+}
+
+/// サーバー起動中の累計
+#[derive(Debug)]
+pub struct Metrics {
+    pages_fetched: AtomicU64,
+    bytes_downloaded: AtomicU64,
+    fetch_duration: Histogram,
+    parse_duration: Histogram,
+    /// エラーノードのタグ (ErrorFetch など) → 件数
+    errors: Mutex<BTreeMap<String, u64>>,
+    conversions_in_flight: AtomicI64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            pages_fetched: AtomicU64::new(0),
+            bytes_downloaded: AtomicU64::new(0),
+            fetch_duration: Histogram::new(),
+            parse_duration: Histogram::new(),
+            errors: Mutex::new(BTreeMap::new()),
+            conversions_in_flight: AtomicI64::new(0),
+        }
+    }
+}
+
+impl Metrics {
+    /// 1 回の HTTP 取得 (本文のバイト数と所要時間)
+    pub fn record_fetch(&self, bytes: u64, duration: Duration) {
+        self.pages_fetched.fetch_add(1, Ordering::Relaxed);
+        self.bytes_downloaded.fetch_add(bytes, Ordering::Relaxed);
+        self.fetch_duration.observe(duration);
+    }
+
+    /// 1 ページの HTML 解析の所要時間
+    pub fn record_parse(&self, duration: Duration) {
+        self.parse_duration.observe(duration);
+    }
+
+    /// URL の結果がエラーノードだった
+    pub fn record_error(&self, tag: &str) {
+        *self.errors.lock().unwrap().entry(tag.to_string()).or_default() += 1;
+    }
+
+    /// 変換の開始 (戻り値を捨てると終了として数える)
+    pub fn start_conversion(&self) -> InFlight<'_> {
+        self.conversions_in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(self)
+    }
+
+    /// テキスト形式 (text/plain; version=0.0.4)
+    pub fn render(&self, jobs: &JobCounts) -> String {
+        let mut out = String::new();
+        let counter = |out: &mut String, name: &str, help: &str, value: u64| {
+            let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}");
+        };
+        counter(
+            &mut out,
+            "web_to_json_pages_fetched_total",
+            "HTTP responses fetched (including subpages and feed items).",
+            self.pages_fetched.load(Ordering::Relaxed),
+        );
+        counter(
+            &mut out,
+            "web_to_json_bytes_downloaded_total",
+            "Response body bytes received (bodies reused on 304 Not Modified included).",
+            self.bytes_downloaded.load(Ordering::Relaxed),
+        );
+        self.fetch_duration.write(&mut out, "web_to_json_fetch_duration_seconds", "Time to fetch one response.");
+        self.parse_duration.write(&mut out, "web_to_json_parse_duration_seconds", "Time to parse one HTML page.");
+
+        let _ = writeln!(
+            out,
+            "# HELP web_to_json_errors_total URLs that produced an error node, by tag.\n# TYPE web_to_json_errors_total counter"
+        );
+        for (tag, n) in self.errors.lock().unwrap().iter() {
+            let _ = writeln!(out, "web_to_json_errors_total{{type=\"{tag}\"}} {n}");
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP web_to_json_conversions_in_flight Conversions currently running (jobs, API and streaming).\n\
+             # TYPE web_to_json_conversions_in_flight gauge\n\
+             web_to_json_conversions_in_flight {}",
+            self.conversions_in_flight.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(out, "# HELP web_to_json_jobs Background jobs kept by the server, by status.\n# TYPE web_to_json_jobs gauge");
+        for (status, n) in [("queued", jobs.queued), ("running", jobs.running), ("paused", jobs.paused), ("done", jobs.done), ("failed", jobs.failed)] {
+            let _ = writeln!(out, "web_to_json_jobs{{status=\"{status}\"}} {n}");
+        }
+        out
+    }
+}
+
+/// 実行中の変換 (drop で終了)
+pub struct InFlight<'a>(&'a Metrics);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.conversions_in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,119 @@
+//! URL の正規化 (入力 URL・1階層リンク先の重複をまとめる)
+//!
+//! - フラグメントを除く
+//! - . / .. のセグメント・スキームとホスト名の大文字・既定のポートは URL の解析で正規化される
+//! - トラッキング用のクエリ (utm_* / fbclid / gclid など) を除く (指定時)
+//! - 重複の判定 (dedup_key) ではパスの末尾の / の有無を区別しない
+
+use url::Url;
+
+/// トラッキング用のクエリの名前 (utm_ で始まるものも)
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "yclid", "igshid", "mc_cid", "mc_eid", "_ga", "_gl",
+];
+
+/// 取得に使う URL (フラグメントなし。strip_tracking ならトラッキング用のクエリも除く)
+pub fn normalize(url: &Url, strip_tracking: bool) -> Url {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    if strip_tracking && url.query().is_some() {
+        let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        let kept: Vec<&(String, String)> = pairs.iter().filter(|(name, _)| !is_tracking_param(name)).collect();
+        // 除くものがなければクエリの書き方 (エンコード) はそのまま
+        if kept.len() < pairs.len() {
+            if kept.is_empty() {
+                url.set_query(None);
+            } else {
+                url.query_pairs_mut().clear().extend_pairs(kept);
+            }
+        }
+    }
+    if url.query() == Some("") {
+        url.set_query(None);
+    }
+    url
+}
+
+/// 文字列の URL を正規化 (URL として解析できなければ None)
+pub fn normalize_str(text: &str, strip_tracking: bool) -> Option<String> {
+    Url::parse(text).ok().map(|url| normalize(&url, strip_tracking).into())
+}
+
+/// 同じページとみなすためのキー (正規化した URL の、ルート以外のパスの末尾の / を除いたもの)
+pub fn dedup_key(url: &Url) -> String {
+    let mut key = url.clone();
+    key.set_fragment(None);
+    let path = key.path();
+    if path.len() > 1 && path.ends_with('/') {
+        let trimmed = path.trim_end_matches('/').to_string();
+        key.set_path(if trimmed.is_empty() { "/" } else { &trimmed });
+    }
+    key.into()
+}
+
+fn is_tracking_param(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(text: &str) -> Url {
+        Url::parse(text).unwrap()
+    }
+
+    #[test]
+    fn removes_fragment_and_parser_normalizes() {
+        let normalized = normalize(&url("HTTP://Example.COM:80/a/./b/../c#top"), false);
+        assert_eq!(normalized.as_str(), "http://example.com/a/c");
+        assert_eq!(normalize(&url("https://example.com:443/?#x"), false).as_str(), "https://example.com/");
+    }
+
+    #[test]
+    fn keeps_tracking_params_unless_asked() {
+        let original = url("https://example.com/?utm_source=x&id=1");
+        assert_eq!(normalize(&original, false).as_str(), "https://example.com/?utm_source=x&id=1");
+    }
+
+    #[test]
+    fn strips_tracking_params() {
+        let stripped = normalize(&url("https://example.com/p?UTM_Source=x&id=1&fbclid=y&_ga=z&page=2"), true);
+        assert_eq!(stripped.as_str(), "https://example.com/p?id=1&page=2");
+        // すべてトラッキング用ならクエリごと除く
+        let all_tracking = normalize(&url("https://example.com/p?gclid=1&utm_medium=a"), true);
+        assert_eq!(all_tracking.as_str(), "https://example.com/p");
+        // 名前が似ているだけのものは残す
+        let similar = "https://example.com/p?utm=1&gclid_x=2";
+        assert_eq!(normalize(&url(similar), true).as_str(), similar);
+    }
+
+    #[test]
+    fn keeps_query_encoding_when_nothing_is_stripped() {
+        // 除くものがなければ %20 を + に書き換えない
+        let original = "https://example.com/s?q=a%20b&flag";
+        assert_eq!(normalize(&url(original), true).as_str(), original);
+    }
+
+    #[test]
+    fn normalize_str_rejects_non_urls() {
+        assert_eq!(
+            normalize_str("https://example.com/a?utm_id=1#f", true).as_deref(),
+            Some("https://example.com/a")
+        );
+        assert_eq!(normalize_str("/relative/path", true), None);
+        assert_eq!(normalize_str("not a url", false), None);
+    }
+
+    #[test]
+    fn dedup_key_ignores_trailing_slash() {
+        assert_eq!(dedup_key(&url("https://example.com/a/")), dedup_key(&url("https://example.com/a")));
+        assert_eq!(dedup_key(&url("https://example.com/a//#x")), "https://example.com/a");
+        // ルートとクエリはそのまま
+        assert_eq!(dedup_key(&url("https://example.com")), "https://example.com/");
+        assert_eq!(dedup_key(&url("https://example.com//")), "https://example.com/");
+        assert_eq!(dedup_key(&url("https://example.com/a/?q=1/")), "https://example.com/a?q=1/");
+        assert_ne!(dedup_key(&url("https://example.com/a?q=1")), dedup_key(&url("https://example.com/a?q=2")));
+    }
+}
@@ -0,0 +1,183 @@
+//! 出力形式 (JSON / Markdown / NDJSON / YAML / テキスト) への変換
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{flat, markdown, plaintext, DomContent, JobOptions};
+
+/// 出力形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// ページごとの結果を並べた JSON 配列
+    #[default]
+    Json,
+    /// ページごとの Markdown (--- 区切り)
+    Markdown,
+    /// 1 行 1 URL の JSON (完了したものから順に出力)
+    Ndjson,
+    /// ページごとの結果を並べた YAML シーケンス
+    Yaml,
+    /// ページごとのプレーンテキスト (タグ構造なし)
+    Text,
+}
+
+impl OutputFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Markdown => "md",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Text => "txt",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "application/json",
+            OutputFormat::Markdown => "text/markdown; charset=utf-8",
+            OutputFormat::Ndjson => "application/x-ndjson",
+            OutputFormat::Yaml => "application/yaml",
+            OutputFormat::Text => "text/plain; charset=utf-8",
+        }
+    }
+
+    /// 画面表示用の名前
+    pub fn label(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "JSON",
+            OutputFormat::Markdown => "Markdown",
+            OutputFormat::Ndjson => "NDJSON",
+            OutputFormat::Yaml => "YAML",
+            OutputFormat::Text => "テキスト",
+        }
+    }
+
+    /// Accept ヘッダから形式を選ぶ (該当なしは None)
+    pub fn from_accept(accept: &str) -> Option<Self> {
+        accept.split(',').find_map(|media| {
+            match media.split(';').next()?.trim().to_ascii_lowercase().as_str() {
+                "application/json" => Some(OutputFormat::Json),
+                "text/markdown" => Some(OutputFormat::Markdown),
+                "application/x-ndjson" | "application/jsonl" => Some(OutputFormat::Ndjson),
+                "application/yaml" | "application/x-yaml" | "text/yaml" => Some(OutputFormat::Yaml),
+                "text/plain" => Some(OutputFormat::Text),
+                _ => None,
+            }
+        })
+    }
+}
+
+/// テキスト出力のページ区切り
+const TEXT_SEPARATOR: &str = "\n----------------------------------------\n\n";
+
+/// 解析結果をジョブで指定された形式の文字列に
+pub fn render(results: &[DomContent], options: &JobOptions) -> Result<String, String> {
+    let mut renderer = StreamRenderer::new(options);
+    let mut text = renderer.start();
+    for content in results {
+        text.push_str(&renderer.page(content)?);
+    }
+    text.push_str(&renderer.finish());
+    Ok(text)
+}
+
+/// 結果を 1 URL 分ずつ文字列にする (全体をメモリにためずに書き出す用)
+///
+/// start() + 各 page() + finish() をつなげると render() と同じ出力になる。
+pub struct StreamRenderer<'a> {
+    options: &'a JobOptions,
+    /// 次のページの番号
+    page: usize,
+    /// 要素 (配列の要素・Markdown / テキストのページ) を 1 つ以上書いたか
+    written: bool,
+}
+
+impl<'a> StreamRenderer<'a> {
+    pub fn new(options: &'a JobOptions) -> Self {
+        StreamRenderer {
+            options,
+            page: 0,
+            written: false,
+        }
+    }
+
+    /// 先頭 (JSON 配列の "[")
+    pub fn start(&self) -> String {
+        match self.options.output_format {
+            OutputFormat::Json => "[".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// 次の 1 URL 分 (区切りを含む)
+    pub fn page(&mut self, content: &DomContent) -> Result<String, String> {
+        let options = self.options;
+        let page = self.page;
+        self.page += 1;
+        match options.output_format {
+            OutputFormat::Json if options.flatten => {
+                flat::flatten_page(page, content).iter().map(|record| self.json_item(record)).collect()
+            }
+            OutputFormat::Json => self.json_item(content),
+            OutputFormat::Yaml if options.flatten => {
+                let records = flat::flatten_page(page, content);
+                if records.is_empty() {
+                    return Ok(String::new());
+                }
+                self.written = true;
+                serde_yaml::to_string(&records).map_err(|e| format!("YAML serialize error: {e}"))
+            }
+            OutputFormat::Yaml => {
+                self.written = true;
+                serde_yaml::to_string(&[content]).map_err(|e| format!("YAML serialize error: {e}"))
+            }
+            OutputFormat::Markdown => Ok(self.separated("\n---\n\n", markdown::page_to_markdown(content))),
+            OutputFormat::Text => {
+                Ok(self.separated(TEXT_SEPARATOR, plaintext::page_to_text(content, options.text_heading_markers)))
+            }
+            OutputFormat::Ndjson => Ok(ndjson_page(page, content, options)),
+        }
+    }
+
+    /// 末尾 (JSON 配列の "]"、空の YAML は "[]")
+    pub fn finish(&self) -> String {
+        match self.options.output_format {
+            OutputFormat::Json => "]".to_string(),
+            OutputFormat::Yaml if !self.written => "[]\n".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// JSON 配列の要素 (2 つ目以降は "," を前に)
+    fn json_item(&mut self, value: &impl Serialize) -> Result<String, String> {
+        let json = serde_json::to_string(value).map_err(|e| format!("JSON serialize error: {e}"))?;
+        let separator = if std::mem::replace(&mut self.written, true) { "," } else { "" };
+        Ok(format!("{separator}{json}"))
+    }
+
+    /// 2 ページ目以降は separator を前に
+    fn separated(&mut self, separator: &str, text: String) -> String {
+        if std::mem::replace(&mut self.written, true) {
+            format!("{separator}{text}")
+        } else {
+            text
+        }
+    }
+}
+
+/// 1 URL 分の NDJSON (改行付き。フラット出力なら 1 行 1 レコード)
+fn ndjson_page(page: usize, content: &DomContent, options: &JobOptions) -> String {
+    if options.flatten {
+        return flat::flatten_page(page, content).iter().map(ndjson_line).collect();
+    }
+    ndjson_line(content)
+}
+
+/// NDJSON の 1 行 (改行付き)
+fn ndjson_line(value: &impl Serialize) -> String {
+    // 出力する型のシリアライズは失敗しない (キーはすべて文字列)
+    let mut line = serde_json::to_string(value).unwrap_or_else(|_| "null".to_string());
+    line.push('\n');
+    line
+}
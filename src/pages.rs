@@ -0,0 +1,115 @@
+//! 画面の HTML (templates/ の askama のテンプレート。埋め込む値は HTML エスケープし、スクリプトには data 属性で渡す)
+
+use actix_web::{HttpRequest, HttpResponse};
+use askama::Template;
+use web_to_json::history::HistoryEntry;
+use web_to_json::summary::JobSummary;
+use web_to_json::{Settings, SubpageScope, MAX_ATTEMPTS_LIMIT, MAX_CONCURRENCY_LIMIT, MAX_RENDER_WAIT_MS};
+
+use crate::i18n::Lang;
+
+/// URL 入力フォーム (トップ画面と結果画面で共通)
+#[derive(Template)]
+#[template(path = "form.html")]
+pub struct Form<'a> {
+    pub lang: Lang,
+    pub settings: &'a Settings,
+    /// 保存済みテンプレートの名前
+    pub template_names: Vec<String>,
+    /// WASM プラグインの名前
+    pub plugin_names: Vec<String>,
+}
+
+/// トップ画面
+#[derive(Template)]
+#[template(path = "index.html")]
+pub struct IndexPage<'a> {
+    pub lang: Lang,
+    /// 一時停止中のジョブの ID
+    pub paused_jobs: Vec<String>,
+    pub form: Form<'a>,
+}
+
+/// 履歴の一覧画面
+#[derive(Template)]
+#[template(path = "history.html")]
+pub struct HistoryPage {
+    pub lang: Lang,
+    /// 履歴を保存する設定か
+    pub enabled: bool,
+    pub entries: Vec<HistoryEntry>,
+}
+
+/// 実行中ジョブの進捗画面
+#[derive(Template)]
+#[template(path = "progress.html")]
+pub struct ProgressPage<'a> {
+    pub lang: Lang,
+    pub id: &'a str,
+    /// 他の変換の完了を待っている
+    pub queued: bool,
+    /// 一時停止中
+    pub paused: bool,
+    /// 再開時に入力し直す項目 (読み戻したジョブで保存しなかったもの)
+    pub missing: Vec<String>,
+}
+
+/// 結果画面
+#[derive(Template)]
+#[template(path = "result.html")]
+pub struct ResultPage<'a> {
+    pub lang: Lang,
+    pub id: &'a str,
+    /// 出力形式の説明 (配列形式の JSON など。表示する言語に翻訳したもの)
+    pub description: &'static str,
+    /// ダウンロードボタンの表示名
+    pub label: &'static str,
+    pub mime: &'static str,
+    pub file_name: String,
+    pub include_subpages: bool,
+    pub total_chars: usize,
+    pub output: String,
+    pub summary: Option<&'a JobSummary>,
+    pub upload_error: Option<&'a str>,
+    pub uploaded: &'a [String],
+    /// 入力 URL (URL ごとの結果のダウンロード用)
+    pub urls: Vec<String>,
+    pub tables: Vec<TableCsv>,
+    /// すべてのテーブルの SQLite (base64。作成の失敗はメッセージ)
+    pub sqlite: Option<Result<String, String>>,
+    /// リンクグラフのエッジリスト
+    pub edges_csv: Option<String>,
+    /// 保存した HTML の件数 (保存の指定時)
+    pub html_count: Option<usize>,
+    /// WARC を記録したか
+    pub warc: bool,
+    pub form: Form<'a>,
+}
+
+/// 結果画面のテーブル 1 つ分の CSV
+pub struct TableCsv {
+    pub name: String,
+    pub rows: usize,
+    pub csv: String,
+}
+
+impl ResultPage<'_> {
+    /// ミリ秒 → 秒
+    fn seconds(&self, ms: &u64) -> f64 {
+        *ms as f64 / 1000.0
+    }
+}
+
+/// テンプレートを描画したレスポンス (描画の失敗は 500。?lang= で言語を選んだら Cookie に保存)
+pub fn respond(req: &HttpRequest, page: &impl Template) -> HttpResponse {
+    match page.render() {
+        Ok(html) => {
+            let mut resp = HttpResponse::Ok();
+            if let Some(cookie) = Lang::cookie(req) {
+                resp.cookie(cookie);
+            }
+            resp.content_type("text/html; charset=utf-8").body(html)
+        }
+        Err(e) => HttpResponse::InternalServerError().body(format!("Template error: {e}")),
+    }
+}
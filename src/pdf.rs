@@ -0,0 +1,44 @@
+//! PDF のテキスト抽出 (ページごとのノードに、段落は空行で区切る)
+
+use crate::meta::PageMeta;
+use crate::{clean_text, error_node, DomContent, DomNode};
+
+/// PDF を { tag: "pdf", children: [{ tag: "page", children: [p, ...] }, ...] } に
+pub fn parse_pdf(bytes: &[u8]) -> DomContent {
+    let pages = match pdf_extract::extract_text_from_mem_by_pages(bytes) {
+        Ok(pages) => pages,
+        Err(e) => return error_node("ErrorPdf", format!("PDF parse error: {e}")),
+    };
+    let children = pages
+        .iter()
+        .map(|text| DomContent::Node(node(Some("page"), None, paragraphs(text))))
+        .collect();
+    let mut pdf = node(Some("pdf"), None, children);
+    pdf.page_meta = Some(Box::new(PageMeta {
+        content_type: Some("application/pdf".to_string()),
+        ..Default::default()
+    }));
+    DomContent::Node(pdf)
+}
+
+/// 空行区切りの段落
+fn paragraphs(text: &str) -> Vec<DomContent> {
+    text.split("\n\n")
+        .map(clean_text)
+        .filter(|p| !p.is_empty())
+        .map(|p| DomContent::Node(node(Some("p"), None, vec![DomContent::Node(node(None, Some(p), vec![]))])))
+        .collect()
+}
+
+fn node(tag: Option<&str>, text: Option<String>, children: Vec<DomContent>) -> DomNode {
+    DomNode {
+        tag: tag.map(str::to_string),
+        href: None,
+        attrs: None,
+        text,
+        children,
+        link_subpage: None,
+        page_meta: None,
+        structured_data: None,
+    }
+}
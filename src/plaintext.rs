@@ -0,0 +1,227 @@
+//! 解析結果をプレーンテキストに平坦化 (タグ構造は捨てて本文だけ)
+
+use crate::diff;
+use crate::filter;
+use crate::links::{self, PageLinks};
+use crate::rules;
+use crate::url_result::UrlResult;
+use crate::{DomContent, DomNode, TableData};
+
+/// 1 ページ分をテキストに (heading_markers なら見出しに "#" を付ける)
+pub fn page_to_text(content: &DomContent, heading_markers: bool) -> String {
+    let mut flattener = Flattener { heading_markers, lines: Vec::new(), subpages: Vec::new() };
+    flattener.block(content);
+    let subpages = std::mem::take(&mut flattener.subpages);
+    let mut text = flattener.lines.join("\n");
+    for (href, subpage) in subpages {
+        text.push_str(&format!("\n\n===== {href} =====\n\n"));
+        text.push_str(&page_to_text(subpage, heading_markers));
+    }
+    text.trim_end().to_string() + "\n"
+}
+
+struct Flattener<'a> {
+    heading_markers: bool,
+    /// ブロックごとの行 (空行で段落を区切る)
+    lines: Vec<String>,
+    subpages: Vec<(String, &'a DomContent)>,
+}
+
+impl<'a> Flattener<'a> {
+    fn block(&mut self, content: &'a DomContent) {
+        match content {
+            DomContent::Node(node) => self.node_block(node),
+            DomContent::Table(table) => self.table(table),
+            // 画像は代替テキストのみ
+            DomContent::Image(image) => self.push_block(image.alt.clone().unwrap_or_default()),
+            DomContent::Code(code) => self.push_code(&code.text),
+            DomContent::Media(_) => {}
+            // フォームの中身 (ラベル等) は通常ノードとして別に出力される
+            DomContent::Form(_) => {}
+            DomContent::Extracted(fields) => {
+                let lines: Vec<String> = fields
+                    .iter()
+                    .map(|(key, value)| format!("{key}: {}", rules::field_text(value)))
+                    .collect();
+                self.push_block(lines.join("\n"));
+            }
+            // JSON は整形して、バイナリは Content-Type のみ
+            DomContent::Resource(resource) => match &resource.data {
+                Some(data) => self.push_block(serde_json::to_string_pretty(data).unwrap_or_default()),
+                None => self.push_block(format!("[{}]", resource.content_type)),
+            },
+            DomContent::Result(result) => match &**result {
+                UrlResult::Document { document, .. } => self.block(document),
+                UrlResult::Error { error, .. } => self.push_block(format!("[Error ({})] {}", error.kind, error.message)),
+            },
+            // 1 行 1 リンク (アンカーテキスト <URL>)
+            DomContent::Links(page) => self.links(page),
+            DomContent::Filtered(value) => self.push_block(filter::value_text(value)),
+            DomContent::Diff(diff) => {
+                self.push_block(self.heading(1, "Changes".to_string()));
+                let changes = diff.changes.iter().map(|c| format!("\n{}", diff::change_text(c)));
+                self.push_block(diff::summary_line(diff) + changes.collect::<String>().as_str());
+            }
+            DomContent::Summary(block) => {
+                let summary = &block.summary;
+                self.push_block(self.heading(1, "Summary".to_string()));
+                let failures = summary.failures.iter().map(|f| format!("\n{} ({}): {}", f.url, f.kind, f.message));
+                self.push_block(summary.line() + failures.collect::<String>().as_str());
+            }
+            DomContent::Feed(feed) => {
+                if let Some(title) = &feed.feed_title {
+                    self.push_block(self.heading(1, title.clone()));
+                }
+                if let Some(description) = &feed.feed_description {
+                    self.push_block(description.clone());
+                }
+                for item in &feed.items {
+                    let title = item.title.clone().unwrap_or_default();
+                    self.push_block(self.heading(2, title));
+                    for text in [&item.pub_date, &item.description].into_iter().flatten() {
+                        self.push_block(text.clone());
+                    }
+                    if let Some(article) = &item.article {
+                        self.block(article);
+                    }
+                }
+            }
+        }
+    }
+
+    fn node_block(&mut self, node: &'a DomNode) {
+        match node.tag.as_deref() {
+            Some(tag @ ("h1" | "h2" | "h3" | "h4" | "h5" | "h6")) => {
+                let level = tag[1..].parse().unwrap_or(1);
+                let text = self.inline(node);
+                self.push_block(self.heading(level, text));
+            }
+            Some("p") | Some("a") | None => {
+                let text = self.inline(node);
+                self.push_block(text);
+            }
+            Some("ul") | Some("ol") => {
+                let mut items = Vec::new();
+                self.list(node, 0, &mut items);
+                self.push_block(items.join("\n"));
+            }
+            Some(tag) if tag.starts_with("Error") => {
+                let text = node.text.clone().unwrap_or_default();
+                self.push_block(format!("[{tag}] {text}"));
+            }
+            _ => {
+                if let Some(text) = &node.text {
+                    self.push_block(text.clone());
+                }
+                for child in &node.children {
+                    self.block(child);
+                }
+            }
+        }
+    }
+
+    fn heading(&self, level: usize, text: String) -> String {
+        if self.heading_markers && !text.is_empty() {
+            format!("{} {text}", "#".repeat(level))
+        } else {
+            text
+        }
+    }
+
+    fn links(&mut self, page: &PageLinks) {
+        let lines: Vec<String> = page
+            .links
+            .iter()
+            .map(|link| {
+                let check = link.check.as_ref().map(|c| format!(" [{}]", links::check_text(c))).unwrap_or_default();
+                format!("{} <{}>{check}", link.text, link.href).trim().to_string()
+            })
+            .collect();
+        self.push_block(lines.join("\n"));
+        for subpage in &page.subpages {
+            self.push_block(format!("===== {} =====", subpage.url));
+            self.links(subpage);
+        }
+    }
+
+    fn push_block(&mut self, text: String) {
+        let text = text.trim();
+        if !text.is_empty() {
+            if !self.lines.is_empty() {
+                self.lines.push(String::new());
+            }
+            self.lines.push(text.to_string());
+        }
+    }
+
+    /// コードは行頭の空白も残す
+    fn push_code(&mut self, text: &str) {
+        let text = text.trim_end();
+        if !text.trim().is_empty() {
+            if !self.lines.is_empty() {
+                self.lines.push(String::new());
+            }
+            self.lines.push(text.trim_start_matches(['\n', '\r']).to_string());
+        }
+    }
+
+    /// 子孫のテキストを 1 行に (リンク先ページは後ろに回す)
+    fn inline(&mut self, node: &'a DomNode) -> String {
+        let mut parts: Vec<String> = node.text.iter().cloned().collect();
+        if let (Some(href), Some(subpage)) = (&node.href, &node.link_subpage) {
+            self.subpages.push((href.clone(), subpage));
+        }
+        for child in &node.children {
+            match child {
+                DomContent::Node(n) => parts.push(self.inline(n)),
+                DomContent::Image(image) => parts.extend(image.alt.clone()),
+                DomContent::Code(code) => parts.push(code.text.clone()),
+                DomContent::Table(_) | DomContent::Feed(_) | DomContent::Media(_) | DomContent::Form(_) | DomContent::Extracted(_) | DomContent::Resource(_) | DomContent::Result(_) | DomContent::Summary(_) | DomContent::Links(_) | DomContent::Diff(_) | DomContent::Filtered(_) => {}
+            }
+        }
+        parts.retain(|p| !p.is_empty());
+        parts.join(" ")
+    }
+
+    fn list(&mut self, node: &'a DomNode, depth: usize, out: &mut Vec<String>) {
+        let ordered = node.tag.as_deref() == Some("ol");
+        let mut number = 1;
+        for child in &node.children {
+            let DomContent::Node(item) = child else {
+                continue;
+            };
+            let mut text_parts: Vec<String> = item.text.iter().cloned().collect();
+            let mut nested = Vec::new();
+            for c in &item.children {
+                match c {
+                    DomContent::Node(n) if matches!(n.tag.as_deref(), Some("ul") | Some("ol")) => nested.push(n),
+                    DomContent::Node(n) => text_parts.push(self.inline(n)),
+                    _ => {}
+                }
+            }
+            text_parts.retain(|p| !p.is_empty());
+            if !text_parts.is_empty() {
+                let marker = if ordered { format!("{number}.") } else { "-".to_string() };
+                number += 1;
+                out.push(format!("{}{marker} {}", "  ".repeat(depth), text_parts.join(" ")));
+            }
+            for n in nested {
+                self.list(n, depth + 1, out);
+            }
+        }
+    }
+
+    /// 1 行 1 レコード (タブ区切り)
+    fn table(&mut self, table: &TableData) {
+        let columns = table.columns();
+        let mut rows = vec![columns.join("\t")];
+        for row in &table.rows {
+            let cells = TableData::row_cells(row, &columns);
+            rows.push(cells.join("\t"));
+        }
+        if let Some(total) = table.total_rows.filter(|_| table.truncated) {
+            rows.push(format!("(Truncated: {} of {total} rows)", table.rows.len()));
+        }
+        self.push_block(rows.join("\n"));
+    }
+}
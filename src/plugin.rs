@@ -0,0 +1,212 @@
+//! WASM プラグイン (サイトごとの抽出処理。変換結果と HTML を受け取り、置き換える JSON を返す)
+//!
+//! プラグインは import なし (WASI なし) のモジュールで、次をエクスポートする。
+//!
+//! - `memory`
+//! - `alloc(len: i32) -> i32`: 入力を書き込む領域を確保してその位置を返す
+//! - `transform(ptr: i32, len: i32) -> i64`: 入力の JSON ({"url", "document", "html"}) を受け取り、
+//!   出力の JSON の位置と長さを `(ptr << 32) | len` で返す
+//!
+//! 出力は変換結果の代わりにそのまま出力する (null なら元の結果のまま)。
+//! 呼び出しごとに新しいインスタンスで実行し、命令数 (fuel) とメモリに上限を設ける。
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::{error_node, DomContent};
+
+/// プラグインのディレクトリの既定値 (実行ディレクトリからの相対パス)
+pub const DEFAULT_PLUGIN_DIR: &str = "plugins";
+
+/// プラグインのファイルの拡張子
+const EXTENSION: &str = "wasm";
+
+/// 1 回の実行の命令数の上限 (無限ループ対策)
+#[cfg_attr(not(feature = "wasm-plugins"), allow(dead_code))]
+const FUEL_LIMIT: u64 = 2_000_000_000;
+
+/// 1 インスタンスのメモリの上限 (バイト)
+#[cfg_attr(not(feature = "wasm-plugins"), allow(dead_code))]
+const MAX_MEMORY_BYTES: usize = 256 * 1024 * 1024;
+
+/// 読み込んだ (コンパイル済みの) プラグイン
+#[derive(Clone)]
+pub struct Plugin {
+    name: String,
+    module: runtime::Module,
+}
+
+/// ログ等にはモジュールの中身を出さない
+impl fmt::Debug for Plugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Plugin({})", self.name)
+    }
+}
+
+impl Plugin {
+    /// プラグインのディレクトリの <name>.wasm (name は英数字・-・_ だけ)
+    pub fn load(dir: &str, name: &str) -> Result<Self, String> {
+        let name = name.trim();
+        if !is_valid_name(name) {
+            return Err(format!("Invalid plugin name \"{name}\" (letters, digits, - and _ only)"));
+        }
+        let path = Path::new(dir).join(format!("{name}.{EXTENSION}"));
+        if !path.is_file() {
+            return Err(format!("Unknown plugin \"{name}\" (no {})", path.display()));
+        }
+        Self::from_file(&path)
+    }
+
+    /// ファイルから (.wasm、または WebAssembly テキストの .wat)
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let name = path.file_stem().map_or_else(|| path.display().to_string(), |s| s.to_string_lossy().into_owned());
+        let module = runtime::compile(path)?;
+        Ok(Plugin { name, module })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 変換結果をプラグインで置き換える (html は HTML のページの本文)
+    pub fn transform(&self, url: &str, content: DomContent, html: Option<&str>) -> DomContent {
+        let input = json!({"url": url, "document": content, "html": html});
+        let output = match runtime::run(&self.module, input.to_string().as_bytes()) {
+            Ok(output) => output,
+            Err(e) => return error_node("ErrorPlugin", format!("Plugin \"{}\": {e}", self.name)),
+        };
+        match serde_json::from_slice::<Value>(&output) {
+            Ok(Value::Null) => content,
+            Ok(value) => serde_json::from_value(value).unwrap_or_else(|e| {
+                error_node("ErrorPlugin", format!("Plugin \"{}\" returned an invalid result: {e}", self.name))
+            }),
+            Err(e) => error_node("ErrorPlugin", format!("Plugin \"{}\" returned invalid JSON: {e}", self.name)),
+        }
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+}
+
+/// ディレクトリのプラグイン名 (<name>.wasm の name。名前順)
+pub fn list(dir: &str) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == EXTENSION) && path.is_file())
+        .filter_map(|path| path.file_stem().and_then(|s| s.to_str()).map(str::to_string))
+        .filter(|name| is_valid_name(name))
+        .collect();
+    names.sort();
+    names
+}
+
+/// CLI の --plugin (パスならそのファイル、名前ならプラグインのディレクトリから)
+pub fn load_spec(dir: &str, spec: &str) -> Result<Plugin, String> {
+    let path = PathBuf::from(spec);
+    if path.extension().is_some() || spec.contains(std::path::MAIN_SEPARATOR) || spec.contains('/') {
+        Plugin::from_file(&path)
+    } else {
+        Plugin::load(dir, spec)
+    }
+}
+
+#[cfg(feature = "wasm-plugins")]
+mod runtime {
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Mutex, OnceLock};
+    use std::time::SystemTime;
+
+    use wasmtime::{Config, Engine, Instance, Store, StoreLimits, StoreLimitsBuilder, Trap};
+
+    pub use wasmtime::Module;
+
+    use super::{FUEL_LIMIT, MAX_MEMORY_BYTES};
+
+    /// コンパイル済みのモジュールと、コンパイルしたときのファイルの更新日時
+    type Compiled = (Option<SystemTime>, Module);
+
+    /// パスごとのコンパイル済みのモジュール (ファイルの更新日時が変わればコンパイルし直す)
+    static MODULES: Mutex<BTreeMap<PathBuf, Compiled>> = Mutex::new(BTreeMap::new());
+
+    fn engine() -> &'static Engine {
+        static ENGINE: OnceLock<Engine> = OnceLock::new();
+        ENGINE.get_or_init(|| {
+            let mut config = Config::new();
+            config.consume_fuel(true);
+            Engine::new(&config).expect("valid wasmtime config")
+        })
+    }
+
+    pub fn compile(path: &Path) -> Result<Module, String> {
+        let path = path.canonicalize().map_err(|e| format!("{}: {e}", path.display()))?;
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let mut modules = MODULES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, module)) = modules.get(&path).filter(|(time, _)| *time == modified) {
+            return Ok(module.clone());
+        }
+        let module = Module::from_file(engine(), &path).map_err(|e| format!("Invalid plugin {}: {e:#}", path.display()))?;
+        modules.insert(path, (modified, module.clone()));
+        Ok(module)
+    }
+
+    /// 新しいインスタンスで transform を 1 回呼ぶ
+    pub fn run(module: &Module, input: &[u8]) -> Result<Vec<u8>, String> {
+        let limits = StoreLimitsBuilder::new().memory_size(MAX_MEMORY_BYTES).build();
+        let mut store: Store<StoreLimits> = Store::new(engine(), limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(FUEL_LIMIT).map_err(|e| e.to_string())?;
+        let instance = Instance::new(&mut store, module, &[]).map_err(|e| format!("Instantiation failed: {e:#}"))?;
+        let memory = instance.get_memory(&mut store, "memory").ok_or("No exported \"memory\"")?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "alloc")
+            .map_err(|e| format!("No exported alloc(i32) -> i32: {e}"))?;
+        let transform = instance
+            .get_typed_func::<(i32, i32), i64>(&mut store, "transform")
+            .map_err(|e| format!("No exported transform(i32, i32) -> i64: {e}"))?;
+
+        let len = i32::try_from(input.len()).map_err(|_| "Input is too large".to_string())?;
+        let ptr = alloc.call(&mut store, len).map_err(trap_message)?;
+        memory
+            .write(&mut store, ptr as u32 as usize, input)
+            .map_err(|_| "alloc returned an out-of-bounds pointer".to_string())?;
+        let packed = transform.call(&mut store, (ptr, len)).map_err(trap_message)? as u64;
+        let (out_ptr, out_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        memory
+            .data(&store)
+            .get(out_ptr..out_ptr + out_len)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| "transform returned an out-of-bounds result".to_string())
+    }
+
+    fn trap_message(e: wasmtime::Error) -> String {
+        match e.downcast_ref::<Trap>() {
+            Some(Trap::OutOfFuel) => format!("Exceeded the instruction limit ({FUEL_LIMIT})"),
+            _ => format!("{e:#}"),
+        }
+    }
+}
+
+#[cfg(not(feature = "wasm-plugins"))]
+mod runtime {
+    use std::path::Path;
+
+    /// wasm-plugins なしのビルドでは読み込めない
+    #[derive(Clone)]
+    pub enum Module {}
+
+    pub fn compile(_path: &Path) -> Result<Module, String> {
+        Err("WASM plugins are not enabled in this build (build with --features wasm-plugins)".to_string())
+    }
+
+    pub fn run(module: &Module, _input: &[u8]) -> Result<Vec<u8>, String> {
+        match *module {}
+    }
+}
@@ -0,0 +1,38 @@
+//! 所有者だけが読み書きできるファイル (0600) とディレクトリ (0700)
+//!
+//! ジョブの状態・履歴・ディスクキャッシュ・テンプレートなど、取得した本文や設定を保存するものに使う (unix 以外は既定の権限)。
+
+use std::fs::{DirBuilder, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// ディレクトリを途中も含めて作る (作ったものは 0700)
+pub fn create_dir_all(dir: &Path) -> io::Result<()> {
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)
+}
+
+/// ファイルを開く (無ければ作る。append でなければ切り詰める。既にあるファイルも 0600 にする)
+pub fn open(path: &Path, append: bool) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.create(true);
+    if append {
+        options.append(true);
+    } else {
+        options.write(true).truncate(true);
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(path)?;
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    Ok(file)
+}
+
+/// ファイルの中身を bytes で置き換える
+pub fn write(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    open(path, false)?.write_all(bytes)
+}
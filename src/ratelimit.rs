@@ -0,0 +1,77 @@
+//! POST リクエストのクライアント IP ごとの回数制限 (トークンバケット)
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, Method};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+
+/// これを超える IP を覚えたら満タンに戻ったものを捨てる
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// クライアントごとの残り回数
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// 1 分あたりの回数で制限する (0 で無制限)
+pub struct RateLimiter {
+    per_minute: u32,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        RateLimiter {
+            per_minute,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 1 回分を消費する。上限なら次に使えるまでの時間を返す
+    fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+        let capacity = f64::from(self.per_minute);
+        let per_sec = capacity / 60.0;
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            buckets.retain(|_, b| b.tokens + now.duration_since(b.updated).as_secs_f64() * per_sec < capacity);
+        }
+        let bucket = buckets.entry(ip).or_insert(Bucket { tokens: capacity, updated: now });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * per_sec).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_sec))
+        }
+    }
+}
+
+/// POST を制限し、超えたら 429 (Retry-After 付き) を返すミドルウェア
+pub async fn limit_posts(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let limited = match (req.method(), req.app_data::<web::Data<RateLimiter>>(), req.peer_addr()) {
+        (&Method::POST, Some(limiter), Some(peer)) => limiter.check(peer.ip()).err(),
+        _ => None,
+    };
+    let Some(wait) = limited else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+    let response = HttpResponse::TooManyRequests()
+        .insert_header((header::RETRY_AFTER, wait.as_secs().max(1).to_string()))
+        .body("Too many requests; try again later");
+    Ok(req.into_response(response).map_into_right_body())
+}
@@ -0,0 +1,89 @@
+//! 本文抽出 (Readability 風のスコアリングで記事本体の要素を選ぶ)
+
+use std::collections::HashMap;
+
+use ego_tree::NodeId;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+
+/// 本文らしい class / id
+const POSITIVE_HINTS: &str = r"(?i)article|body|content|entry|hentry|main|page|post|text|blog|story";
+/// 本文ではなさそうな class / id
+const NEGATIVE_HINTS: &str =
+    r"(?i)comment|footer|footnote|sidebar|sponsor|\bads?\b|advert|nav|menu|related|share|social|header|banner|promo|widget|breadcrumb|masthead";
+
+/// スコア対象にする段落の最小文字数
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+/// 本文を含む要素を推定 (見つからなければ None)
+pub fn find_main_content(doc: &Html) -> Option<ElementRef<'_>> {
+    let positive = Regex::new(POSITIVE_HINTS).unwrap();
+    let negative = Regex::new(NEGATIVE_HINTS).unwrap();
+    let sel_paragraphs = Selector::parse("p, pre, td, blockquote").unwrap();
+
+    // 段落のスコアを親 (満額) と祖父母 (半分) に加算
+    let mut scores: HashMap<NodeId, f64> = HashMap::new();
+    for para in doc.select(&sel_paragraphs) {
+        let text = para.text().collect::<String>();
+        let chars = text.trim().chars().count();
+        if chars < MIN_PARAGRAPH_CHARS {
+            continue;
+        }
+        let content_score = 1.0 + text.matches([',', '、', '，']).count() as f64 + (chars as f64 / 100.0).min(3.0);
+
+        let ancestors = para.ancestors().filter_map(ElementRef::wrap).take(2);
+        for (level, ancestor) in ancestors.enumerate() {
+            let score = scores
+                .entry(ancestor.id())
+                .or_insert_with(|| initial_score(ancestor, &positive, &negative));
+            *score += if level == 0 { content_score } else { content_score / 2.0 };
+        }
+    }
+
+    // リンクだらけの要素は割り引いて最高スコアを選ぶ
+    scores
+        .into_iter()
+        .filter_map(|(id, score)| {
+            let el = ElementRef::wrap(doc.tree.get(id)?)?;
+            Some((el, score * (1.0 - link_density(el))))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(el, _)| el)
+}
+
+/// タグと class / id による初期スコア
+fn initial_score(el: ElementRef, positive: &Regex, negative: &Regex) -> f64 {
+    let tag_score = match el.value().name() {
+        "article" | "main" => 10.0,
+        "div" => 5.0,
+        "section" | "pre" | "td" | "blockquote" => 3.0,
+        "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    let mut class_score = 0.0;
+    for hint in [el.value().attr("class"), el.value().attr("id")].into_iter().flatten() {
+        if negative.is_match(hint) {
+            class_score -= 25.0;
+        }
+        if positive.is_match(hint) {
+            class_score += 25.0;
+        }
+    }
+    tag_score + class_score
+}
+
+/// テキストのうちリンク内テキストが占める割合
+fn link_density(el: ElementRef) -> f64 {
+    let total = el.text().map(|t| t.trim().chars().count()).sum::<usize>();
+    if total == 0 {
+        return 0.0;
+    }
+    let sel_a = Selector::parse("a").unwrap();
+    let links = el
+        .select(&sel_a)
+        .flat_map(|a| a.text())
+        .map(|t| t.trim().chars().count())
+        .sum::<usize>();
+    links as f64 / total as f64
+}
@@ -0,0 +1,102 @@
+//! ヘッドレスブラウザでの描画 (JavaScript で本文を組み立てるページ用)
+//!
+//! Chrome / Chromium を --headless --dump-dom で起動し、描画後の DOM を受け取る。
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use scraper::{Html, Selector};
+use tokio::process::Command;
+use url::Url;
+
+/// パス未指定時に順に試すブラウザのコマンド名
+const BROWSER_CANDIDATES: &[&str] = &["chromium", "chromium-browser", "google-chrome", "google-chrome-stable", "chrome"];
+
+/// セレクタ待ちで描画をやり直す回数の上限 (待ち時間は毎回倍)
+const MAX_RENDER_ATTEMPTS: u32 = 4;
+
+/// 描画の設定
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// ブラウザの実行ファイル (空なら BROWSER_CANDIDATES から探す)
+    pub browser: String,
+    /// ネットワークが落ち着くまで待つ最大時間 (ブラウザの仮想時間)
+    pub wait: Duration,
+    /// この要素が現れるまで待つ (CSS セレクタ)
+    pub wait_selector: Option<Selector>,
+    /// --no-sandbox を付ける (root で動かすコンテナ用)
+    pub no_sandbox: bool,
+}
+
+/// url を描画した DOM (HTML) を返す
+pub async fn render(url: &Url, options: &RenderOptions, user_agent: Option<&str>, timeout: Option<Duration>) -> Result<String, String> {
+    let browser = find_browser(&options.browser)?;
+    let mut wait = options.wait;
+    let mut html = String::new();
+    for _ in 0..MAX_RENDER_ATTEMPTS {
+        html = dump_dom(&browser, url, wait, options.no_sandbox, user_agent, timeout).await?;
+        let Some(selector) = &options.wait_selector else {
+            break;
+        };
+        if Html::parse_document(&html).select(selector).next().is_some() {
+            break;
+        }
+        // 見つからなければ待ち時間を延ばして描画し直す (最後は見つからなくてもその DOM を使う)
+        wait = (wait * 2).max(Duration::from_millis(500));
+    }
+    Ok(html)
+}
+
+/// 1 回分の描画
+async fn dump_dom(
+    browser: &str,
+    url: &Url,
+    wait: Duration,
+    no_sandbox: bool,
+    user_agent: Option<&str>,
+    timeout: Option<Duration>,
+) -> Result<String, String> {
+    let mut command = Command::new(browser);
+    command
+        .args(["--headless=new", "--disable-gpu", "--no-first-run", "--hide-scrollbars", "--dump-dom"])
+        .arg(format!("--virtual-time-budget={}", wait.as_millis()))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(user_agent) = user_agent {
+        command.arg(format!("--user-agent={user_agent}"));
+    }
+    if no_sandbox {
+        command.arg("--no-sandbox");
+    }
+    command.arg(url.as_str());
+
+    let output = command.output();
+    let output = match timeout {
+        Some(limit) => tokio::time::timeout(limit, output)
+            .await
+            .map_err(|_| format!("Browser timed out after {}s", limit.as_secs()))?,
+        None => output.await,
+    }
+    .map_err(|e| format!("Failed to run browser \"{browser}\": {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let last_line = stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("");
+        return Err(format!("Browser exited with {}: {last_line}", output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// 実行するブラウザ (指定がなければ PATH から探す)
+fn find_browser(configured: &str) -> Result<String, String> {
+    if !configured.is_empty() {
+        return Ok(configured.to_string());
+    }
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    BROWSER_CANDIDATES
+        .iter()
+        .find(|name| std::env::split_paths(&path).any(|dir| dir.join(name).is_file()))
+        .map(|name| name.to_string())
+        .ok_or_else(|| "No headless browser found (set BROWSER_PATH in the settings file)".to_string())
+}
@@ -0,0 +1,50 @@
+//! HTML 以外のレスポンス (JSON はそのまま、画像などのバイナリは種類とサイズだけ)
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::fetch::{self, Body, FetchInfo, Fetched};
+
+/// HTML 以外のリソース
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ResourceData {
+    /// "json" または "binary"
+    pub tag: String,
+    pub content_type: String,
+    /// バイナリのサイズ (Content-Length がある場合)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// JSON の本文
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    /// 取得の情報 (取得情報を含める指定時)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetch: Option<FetchInfo>,
+}
+
+/// JSON・バイナリならリソースとして返す (HTML・PDF として解析するものは None)
+pub fn from_fetched(fetched: &Fetched) -> Option<Result<ResourceData, String>> {
+    let content_type = fetched.content_type.as_deref()?;
+    match &fetched.body {
+        Body::Binary { size } => Some(Ok(ResourceData {
+            tag: "binary".to_string(),
+            content_type: fetch::mime_type(content_type),
+            size: *size,
+            data: None,
+            fetch: None,
+        })),
+        Body::Text(text) if fetch::is_json_type(content_type) => Some(
+            serde_json::from_str(text)
+                .map(|data| ResourceData {
+                    tag: "json".to_string(),
+                    content_type: fetch::mime_type(content_type),
+                    size: None,
+                    data: Some(data),
+                    fetch: None,
+                })
+                .map_err(|e| format!("JSON parse error: {e}")),
+        ),
+        Body::Text(_) | Body::Pdf(_) => None,
+    }
+}
@@ -0,0 +1,132 @@
+//! CSS セレクタ / XPath による抽出ルール ("キー: セレクタ" → URL ごとのフラットなオブジェクト)
+
+use scraper::{ElementRef, Html, Selector};
+use serde_json::{Map, Value};
+use url::Url;
+
+use crate::clean_text;
+use crate::xpath::{XItem, XPath};
+
+/// 抽出ルール 1 行分
+///
+/// `price: .price span` / `image: img.main @src` / `tags[]: .tag` / `title: //h1[@class='name']`
+/// (キー末尾の [] はすべての一致を配列で、@属性名 はテキストの代わりに属性値を取る。"/" で始まれば XPath)
+#[derive(Debug, Clone)]
+pub struct ExtractRule {
+    pub key: String,
+    query: Query,
+    attr: Option<String>,
+    all: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Query {
+    Css(Selector),
+    XPath(XPath),
+}
+
+/// 改行区切りのルールを解析 (空行と # で始まる行は無視)
+pub fn parse_rules(text: &str) -> Result<Vec<ExtractRule>, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(parse_rule)
+        .collect()
+}
+
+fn parse_rule(line: &str) -> Result<ExtractRule, String> {
+    let Some((key, rest)) = line.split_once(':') else {
+        return Err(format!("Invalid rule \"{line}\": expected \"key: selector\""));
+    };
+    let key = key.trim();
+    let (key, all) = match key.strip_suffix("[]") {
+        Some(k) => (k.trim(), true),
+        None => (key, false),
+    };
+    if key.is_empty() {
+        return Err(format!("Invalid rule \"{line}\": empty key"));
+    }
+    // 末尾の "@属性名" を分離 (セレクタ中の [attr] とは区別)
+    let rest = rest.trim();
+    let (selector_text, attr) = match rest.rsplit_once(" @") {
+        Some((sel, attr)) if is_attr_name(attr.trim()) => (sel.trim(), Some(attr.trim().to_ascii_lowercase())),
+        _ => (rest, None),
+    };
+    let query = if selector_text.starts_with('/') {
+        Query::XPath(
+            XPath::parse(selector_text)
+                .map_err(|e| format!("Invalid XPath \"{selector_text}\" in rule \"{line}\": {e}"))?,
+        )
+    } else {
+        Query::Css(
+            Selector::parse(selector_text)
+                .map_err(|e| format!("Invalid selector \"{selector_text}\" in rule \"{line}\": {e:?}"))?,
+        )
+    };
+    Ok(ExtractRule {
+        key: key.to_string(),
+        query,
+        attr,
+        all,
+    })
+}
+
+fn is_attr_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ':'))
+}
+
+/// ルールを文書に適用 (一致しないキーは null / 空配列)
+pub fn extract(doc: &Html, rules: &[ExtractRule], page_url: &Url) -> Map<String, Value> {
+    let mut fields = Map::new();
+    for rule in rules {
+        let values: Vec<String> = match &rule.query {
+            Query::Css(selector) => doc
+                .select(selector)
+                .filter_map(|el| rule_value(el, rule, page_url))
+                .collect(),
+            // XPath は属性・テキストをパス内で指定する (@属性名 の指定は無視)
+            Query::XPath(xpath) => xpath
+                .select(doc)
+                .into_iter()
+                .map(|item| match item {
+                    XItem::Attr(name, value) => resolve_attr(&name, value, page_url),
+                    XItem::Element(text) | XItem::Text(text) => text,
+                })
+                .collect(),
+        };
+        let mut values = values.into_iter().map(Value::String);
+        let value = if rule.all {
+            Value::Array(values.collect())
+        } else {
+            values.next().unwrap_or(Value::Null)
+        };
+        fields.insert(rule.key.clone(), value);
+    }
+    fields
+}
+
+/// 要素のテキスト (または属性値, href / src は絶対 URL に)
+fn rule_value(el: ElementRef, rule: &ExtractRule, page_url: &Url) -> Option<String> {
+    match rule.attr.as_deref() {
+        Some(name) => el.value().attr(name).map(|v| resolve_attr(name, v.trim().to_string(), page_url)),
+        None => Some(clean_text(&el.text().collect::<String>())),
+    }
+}
+
+/// href / src の値は絶対 URL に
+fn resolve_attr(name: &str, value: String, page_url: &Url) -> String {
+    match name {
+        "href" | "src" => page_url.join(&value).map(String::from).unwrap_or(value),
+        _ => value,
+    }
+}
+
+/// 値の表示用文字列 (配列はカンマ区切り, null は空)
+pub fn field_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values.iter().map(field_text).collect::<Vec<_>>().join(", "),
+        v => v.to_string(),
+    }
+}
@@ -0,0 +1,12 @@
+//! 出力 (変換結果の JSON) の JSON Schema
+
+use serde_json::Value;
+
+use crate::DomContent;
+
+/// 変換結果 (URL ごとの DomContent の配列) の JSON Schema
+pub fn output_schema() -> Value {
+    let mut schema = schemars::schema_for!(Vec<DomContent>);
+    schema.insert("title".to_string(), "web-to-json output".into());
+    schema.to_value()
+}
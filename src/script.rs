@@ -0,0 +1,90 @@
+//! 変換結果の後処理スクリプト (Rhai。ノードの絞り込み・キーの付け替え・値の計算など)
+//!
+//! スクリプトには URL ごとの結果が doc (JSON と同じ形のマップ・配列)、その URL が url として渡る。
+//! 最後の式の値が結果になる (値を返さなければ doc をそのまま。書き換えてもよい)。
+//! ファイル・ネットワークには触れず、操作数・呼び出しの深さ・文字列や配列の大きさに上限がある。
+
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+use rhai::{Dynamic, Engine, Scope, AST};
+use serde_json::Value;
+
+use crate::{error_node, DomContent};
+
+/// 1 URL 分の実行の操作数の上限 (無限ループ対策)
+const MAX_OPERATIONS: u64 = 50_000_000;
+
+/// 関数呼び出しの深さの上限 (再帰でツリーをたどる分)
+const MAX_CALL_LEVELS: usize = 64;
+
+/// 式の入れ子の深さの上限 (トップレベル, 関数の中)
+const MAX_EXPR_DEPTHS: (usize, usize) = (128, 64);
+
+/// 文字列の上限 (バイト)
+const MAX_STRING_SIZE: usize = 16 * 1024 * 1024;
+
+/// 配列・マップの要素数の上限
+const MAX_COLLECTION_SIZE: usize = 1_000_000;
+
+/// コンパイル済みのスクリプト
+#[derive(Clone)]
+pub struct Script {
+    ast: Arc<AST>,
+}
+
+/// ログ等にはスクリプトの中身を出さない
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Script")
+    }
+}
+
+impl Script {
+    /// スクリプトの文法を確かめてコンパイル
+    pub fn compile(source: &str) -> Result<Self, String> {
+        let ast = engine().compile(source).map_err(|e| format!("Invalid script: {e}"))?;
+        Ok(Script { ast: Arc::new(ast) })
+    }
+
+    /// 結果を後処理
+    pub fn apply(&self, url: &str, content: DomContent) -> DomContent {
+        self.run(url, &content).unwrap_or_else(|e| error_node("ErrorScript", e))
+    }
+
+    fn run(&self, url: &str, content: &DomContent) -> Result<DomContent, String> {
+        let doc = rhai::serde::to_dynamic(content).map_err(|e| format!("Script input error: {e}"))?;
+        let mut scope = Scope::new();
+        scope.push("url", url.to_string());
+        scope.push("doc", doc);
+        let result: Dynamic = engine()
+            .eval_ast_with_scope(&mut scope, &self.ast)
+            .map_err(|e| format!("Script error: {e}"))?;
+        let result = if result.is_unit() {
+            scope.get_value::<Dynamic>("doc").unwrap_or_default()
+        } else {
+            result
+        };
+        let value: Value = rhai::serde::from_dynamic(&result).map_err(|e| format!("Script result error: {e}"))?;
+        serde_json::from_value(value).map_err(|e| format!("Script returned an invalid result: {e}"))
+    }
+}
+
+/// 上限付きのエンジン (import は使えない。print / debug はログに)
+fn engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(MAX_CALL_LEVELS)
+            .set_max_expr_depths(MAX_EXPR_DEPTHS.0, MAX_EXPR_DEPTHS.1)
+            .set_max_string_size(MAX_STRING_SIZE)
+            .set_max_array_size(MAX_COLLECTION_SIZE)
+            .set_max_map_size(MAX_COLLECTION_SIZE)
+            .set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new())
+            .on_print(|text| tracing::info!(text, "script print"))
+            .on_debug(|text, _, pos| tracing::debug!(text, %pos, "script debug"));
+        engine
+    })
+}
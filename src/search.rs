@@ -0,0 +1,243 @@
+//! Elasticsearch / OpenSearch への投入 (ページごと、またはフラットなレコードごとに _bulk で index)
+
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::fetch::Credentials;
+use crate::{flat, plaintext, DomContent, Settings};
+
+/// 既定のインデックス名
+pub const DEFAULT_INDEX: &str = "web-to-json";
+
+/// 1 回の _bulk で送るドキュメント数
+const BULK_SIZE: usize = 500;
+
+/// 1 回の _bulk のタイムアウト
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 集計に残すエラーの数
+const MAX_ERRORS: usize = 10;
+
+/// _id の上限 (バイト。超える場合は SHA-256)
+const MAX_ID_BYTES: usize = 512;
+
+/// 1 ドキュメントの単位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum IndexMode {
+    /// 1 URL 1 ドキュメント (タイトル・説明・言語・本文のテキスト)。_id は URL
+    Pages,
+    /// フラットな出力のレコード 1 件 1 ドキュメント。_id は URL とパス
+    Records,
+}
+
+/// 投入先 (設定ファイルの ELASTICSEARCH_*)
+#[derive(Debug, Clone)]
+pub struct SearchIndex {
+    /// http://127.0.0.1:9200 など
+    url: Url,
+    index: String,
+    auth: Option<Auth>,
+    pub mode: IndexMode,
+}
+
+/// 投入の結果 (集計の index に入れる)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct IndexReport {
+    pub index: String,
+    pub indexed: usize,
+    pub failed: usize,
+    /// 失敗の理由 (最初の 10 件)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// ELASTICSEARCH_CREDENTIALS ("user:password" / "Bearer token" / "ApiKey key")
+#[derive(Clone)]
+enum Auth {
+    Credentials(Credentials),
+    ApiKey(String),
+}
+
+/// ログ等に秘密情報を出さない
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Auth::Credentials(credentials) => credentials.fmt(f),
+            Auth::ApiKey(_) => write!(f, "ApiKey(***)"),
+        }
+    }
+}
+
+impl SearchIndex {
+    /// 設定から (ELASTICSEARCH_URL が空ならエラー)
+    pub fn from_settings(settings: &Settings, mode: IndexMode) -> Result<Self, String> {
+        let url = settings.elasticsearch_url.trim();
+        if url.is_empty() {
+            return Err("ELASTICSEARCH_URL is not configured".to_string());
+        }
+        let url = Url::parse(url).map_err(|e| format!("Invalid ELASTICSEARCH_URL \"{url}\": {e}"))?;
+        let index = match settings.elasticsearch_index.trim() {
+            "" => DEFAULT_INDEX.to_string(),
+            index => index.to_string(),
+        };
+        let credentials = settings.elasticsearch_credentials.trim();
+        let auth = match credentials.strip_prefix("ApiKey ") {
+            Some(key) => Some(Auth::ApiKey(key.trim().to_string())),
+            None => Credentials::parse(credentials).map(Auth::Credentials),
+        };
+        Ok(SearchIndex { url, index, auth, mode })
+    }
+
+    /// 1 URL 分の結果のドキュメント (_id, 本文)。エラー・集計は含めない
+    pub fn documents(&self, page: usize, url: &str, content: &DomContent) -> Vec<(String, Value)> {
+        let indexed_at = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+        match self.mode {
+            IndexMode::Pages => vec![(document_id(url), page_document(url, content, &indexed_at))],
+            IndexMode::Records => flat::flatten_page(page, content)
+                .into_iter()
+                .map(|record| {
+                    let id = document_id(&format!("{url}#{}", record.path));
+                    let doc = json!({
+                        "url": url,
+                        "path": record.path,
+                        "tag": record.tag,
+                        "text": record.text,
+                        "href": record.href,
+                        "indexed_at": indexed_at,
+                    });
+                    (id, without_nulls(doc))
+                })
+                .collect(),
+        }
+    }
+
+    /// BULK_SIZE 件ずつ _bulk で index (送れなかった分は failed に数える)
+    pub async fn bulk(&self, documents: Vec<(String, Value)>, user_agent: Option<&str>) -> IndexReport {
+        let mut report = IndexReport {
+            index: self.index.clone(),
+            ..Default::default()
+        };
+        let mut builder = reqwest::Client::builder().timeout(TIMEOUT);
+        if let Some(ua) = user_agent {
+            builder = builder.user_agent(ua);
+        }
+        let client = match builder.build() {
+            Ok(c) => c,
+            Err(e) => {
+                report.failed = documents.len();
+                report.errors.push(e.to_string());
+                return report;
+            }
+        };
+        let mut endpoint = self.url.clone();
+        endpoint.set_path(&format!("{}/_bulk", self.url.path().trim_end_matches('/')));
+        for chunk in documents.chunks(BULK_SIZE) {
+            let mut body = String::new();
+            for (id, doc) in chunk {
+                body.push_str(&json!({"index": {"_index": self.index, "_id": id}}).to_string());
+                body.push('\n');
+                body.push_str(&doc.to_string());
+                body.push('\n');
+            }
+            let mut request = client
+                .post(endpoint.clone())
+                .header(reqwest::header::CONTENT_TYPE, "application/x-ndjson")
+                .body(body);
+            request = match &self.auth {
+                Some(Auth::Credentials(Credentials::Basic { username, password })) => request.basic_auth(username, Some(password)),
+                Some(Auth::Credentials(Credentials::Bearer(token))) => request.bearer_auth(token),
+                Some(Auth::ApiKey(key)) => request.header(reqwest::header::AUTHORIZATION, format!("ApiKey {key}")),
+                None => request,
+            };
+            match send(request).await {
+                Ok(response) => report.record(chunk.len(), &response),
+                Err(e) => {
+                    report.failed += chunk.len();
+                    report.push_error(e);
+                }
+            }
+        }
+        tracing::info!(index = %report.index, indexed = report.indexed, failed = report.failed, "bulk indexed");
+        report
+    }
+}
+
+impl IndexReport {
+    /// _bulk のレスポンス ({"errors": bool, "items": [{"index": {"status", "error"}}]}) を数える
+    fn record(&mut self, sent: usize, response: &Value) {
+        let items = response.get("items").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+        let mut failed = 0;
+        for item in items {
+            let result = item.get("index").unwrap_or(item);
+            if let Some(error) = result.get("error") {
+                failed += 1;
+                let reason = error.get("reason").and_then(Value::as_str).map_or_else(|| error.to_string(), str::to_string);
+                let id = result.get("_id").and_then(Value::as_str).unwrap_or("");
+                self.push_error(format!("{id}: {reason}"));
+            }
+        }
+        // items が足りない分も失敗扱い
+        failed += sent.saturating_sub(items.len());
+        self.failed += failed;
+        self.indexed += sent.saturating_sub(failed);
+    }
+
+    fn push_error(&mut self, error: String) {
+        if self.errors.len() < MAX_ERRORS {
+            self.errors.push(error);
+        }
+    }
+}
+
+async fn send(request: reqwest::RequestBuilder) -> Result<Value, String> {
+    let response = request.send().await.map_err(|e| format!("Bulk request error: {e}"))?;
+    let status = response.status();
+    let text = response.text().await.map_err(|e| format!("Bulk response error: {e}"))?;
+    if !status.is_success() {
+        return Err(format!("Bulk request failed: {status} {}", text.chars().take(300).collect::<String>()));
+    }
+    serde_json::from_str(&text).map_err(|e| format!("Invalid bulk response: {e}"))
+}
+
+/// ページのドキュメント (抽出ルール指定時は fields に抽出結果)
+fn page_document(url: &str, content: &DomContent, indexed_at: &str) -> Value {
+    let mut doc = json!({
+        "url": url,
+        "text": plaintext::page_to_text(content, false),
+        "indexed_at": indexed_at,
+    });
+    if let DomContent::Node(node) = content {
+        if let Some(meta) = &node.page_meta {
+            doc["title"] = json!(meta.title);
+            doc["description"] = json!(meta.description);
+            doc["canonical"] = json!(meta.canonical);
+            doc["language"] = json!(meta.language.as_ref().map(|l| &l.code));
+        }
+    }
+    if let DomContent::Extracted(fields) = content {
+        doc["fields"] = Value::Object(fields.clone());
+    }
+    without_nulls(doc)
+}
+
+fn without_nulls(mut doc: Value) -> Value {
+    if let Value::Object(map) = &mut doc {
+        map.retain(|_, v| !v.is_null());
+    }
+    doc
+}
+
+/// URL (とパス) の _id (長すぎる場合は SHA-256 の 16 進)
+fn document_id(key: &str) -> String {
+    if key.len() <= MAX_ID_BYTES {
+        return key.to_string();
+    }
+    Sha256::digest(key.as_bytes()).iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -0,0 +1,79 @@
+//! sitemap.xml (サイトマップインデックス含む) を URL リストに展開
+
+use url::Url;
+
+use crate::fetch::Fetcher;
+use crate::xml;
+
+/// サイトマップインデックスをたどる深さの上限
+const MAX_INDEX_DEPTH: usize = 3;
+
+/// サイトマップとして扱う URL か (最後のパス要素が sitemap*.xml)
+pub fn is_sitemap_url(url_str: &str) -> bool {
+    let Ok(url) = Url::parse(url_str) else {
+        return false;
+    };
+    let last = url.path_segments().and_then(|mut s| s.next_back()).unwrap_or("");
+    let last = last.to_ascii_lowercase();
+    last.contains("sitemap") && last.ends_with(".xml")
+}
+
+/// サイトマップの中身
+enum Sitemap {
+    /// ページ URL の一覧
+    Urls(Vec<String>),
+    /// 子サイトマップの一覧
+    Index(Vec<String>),
+}
+
+fn parse_sitemap(body: &str) -> Result<Sitemap, String> {
+    let root = xml::parse(body)?;
+    let locs = |entry: &str| -> Vec<String> {
+        root.children_named(entry)
+            .filter_map(|e| e.child_text("loc"))
+            .map(|s| s.to_string())
+            .collect()
+    };
+    match root.name.as_str() {
+        "urlset" => Ok(Sitemap::Urls(locs("url"))),
+        "sitemapindex" => Ok(Sitemap::Index(locs("sitemap"))),
+        other => Err(format!("Not a sitemap (root element <{other}>)")),
+    }
+}
+
+/// サイトマップを取得してページ URL を列挙 (max_urls 件で打ち切り、0 は無制限)
+pub async fn expand(fetcher: &Fetcher, sitemap_url: &str, max_urls: usize) -> Result<Vec<String>, String> {
+    let mut pages = Vec::new();
+    // (サイトマップ URL, 深さ)
+    let mut pending = vec![(sitemap_url.to_string(), 0)];
+    let mut visited = std::collections::HashSet::new();
+
+    while let Some((url_str, depth)) = pending.pop() {
+        if !visited.insert(url_str.clone()) {
+            continue;
+        }
+        let url = Url::parse(&url_str).map_err(|e| format!("URL parse error: {url_str} ({e})"))?;
+        let body = match fetcher.get_text(&url).await {
+            Ok(b) => b,
+            // 入れ子のサイトマップの失敗は飛ばす
+            Err(_) if depth > 0 => continue,
+            Err(e) => return Err(e.to_string()),
+        };
+        match parse_sitemap(&body) {
+            Ok(Sitemap::Urls(urls)) => pages.extend(urls),
+            Ok(Sitemap::Index(children)) => {
+                if depth < MAX_INDEX_DEPTH {
+                    // 記載順に処理するため逆順で積む
+                    pending.extend(children.into_iter().rev().map(|c| (c, depth + 1)));
+                }
+            }
+            Err(_) if depth > 0 => continue,
+            Err(e) => return Err(e),
+        }
+        if max_urls > 0 && pages.len() >= max_urls {
+            pages.truncate(max_urls);
+            break;
+        }
+    }
+    Ok(pages)
+}
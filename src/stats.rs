@@ -0,0 +1,68 @@
+//! ページごとの集計 (語数・文字数・見出し・リンク・テーブル・画像の数)
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::DomContent;
+
+/// 1 ページ分の数 (リンク先のページは含まない)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct PageStats {
+    /// 空白区切りの語数 (日本語・中国語などは 1 文字 1 語)
+    pub words: usize,
+    /// 空白を除く文字数
+    pub characters: usize,
+    /// h1〜h6
+    pub headings: usize,
+    /// href のある a
+    pub links: usize,
+    pub tables: usize,
+    pub images: usize,
+}
+
+/// text はページの抽出済みテキスト (プレーンテキスト出力と同じもの)
+pub fn page_stats(content: &DomContent, text: &str) -> PageStats {
+    let mut stats = PageStats {
+        words: count_words(text),
+        characters: text.chars().filter(|c| !c.is_whitespace()).count(),
+        ..Default::default()
+    };
+    walk(content, &mut stats);
+    stats
+}
+
+fn walk(content: &DomContent, stats: &mut PageStats) {
+    match content {
+        DomContent::Node(node) => {
+            match node.tag.as_deref() {
+                Some("h1" | "h2" | "h3" | "h4" | "h5" | "h6") => stats.headings += 1,
+                Some("a") if node.href.is_some() => stats.links += 1,
+                _ => {}
+            }
+            for child in &node.children {
+                walk(child, stats);
+            }
+        }
+        DomContent::Table(_) => stats.tables += 1,
+        DomContent::Image(_) => stats.images += 1,
+        _ => {}
+    }
+}
+
+/// 空白区切りの語数 (分かち書きしない文字は 1 文字 1 語)
+fn count_words(text: &str) -> usize {
+    text.split_whitespace()
+        .map(|word| {
+            let unspaced = word.chars().filter(|&c| is_unspaced(c)).count();
+            let rest = word.chars().any(|c| !is_unspaced(c) && c.is_alphanumeric());
+            unspaced + usize::from(rest)
+        })
+        .sum()
+}
+
+/// 語を空白で区切らない文字 (ひらがな・カタカナ・CJK 統合漢字・タイ文字など)
+fn is_unspaced(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}'
+        | '\u{0E00}'..='\u{0E7F}' | '\u{0E80}'..='\u{0EFF}' | '\u{1000}'..='\u{109F}' | '\u{1780}'..='\u{17FF}')
+}
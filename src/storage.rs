@@ -0,0 +1,218 @@
+//! 変換結果のアップロード先 (S3 互換のバケット。GCS は HMAC キーで S3 互換 API を使う)
+
+use std::time::{Duration, SystemTime};
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::output::{self, OutputFormat};
+use crate::{DomContent, JobOptions, Settings};
+
+/// 1 オブジェクトのアップロードのタイムアウト
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// S3 の既定のリージョン
+const DEFAULT_S3_REGION: &str = "us-east-1";
+
+/// GCS の S3 互換 API のエンドポイント
+const GCS_ENDPOINT: &str = "https://storage.googleapis.com";
+
+/// アップロード先 (設定ファイルの OUTPUT_BUCKET*)
+#[derive(Debug, Clone)]
+pub struct Bucket {
+    /// https://s3.<region>.amazonaws.com など (パス形式で <endpoint>/<bucket>/<key> に PUT)
+    endpoint: Url,
+    bucket: String,
+    /// キーの先頭 (空か "/" で終わる)
+    prefix: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl Bucket {
+    /// 設定から (OUTPUT_BUCKET が空なら None)
+    ///
+    /// OUTPUT_BUCKET は s3://bucket/prefix または gs://bucket/prefix
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>, String> {
+        let location = settings.output_bucket.trim();
+        if location.is_empty() {
+            return Ok(None);
+        }
+        let (scheme, rest) = location
+            .split_once("://")
+            .ok_or_else(|| format!("OUTPUT_BUCKET must be s3://bucket/prefix or gs://bucket/prefix: {location}"))?;
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        let region = match (settings.output_bucket_region.as_str(), scheme) {
+            ("", "gs") => "auto".to_string(),
+            ("", _) => DEFAULT_S3_REGION.to_string(),
+            (region, _) => region.to_string(),
+        };
+        let endpoint = match (settings.output_bucket_endpoint.as_str(), scheme) {
+            ("", "s3") => format!("https://s3.{region}.amazonaws.com"),
+            ("", "gs") => GCS_ENDPOINT.to_string(),
+            ("", other) => return Err(format!("Unknown bucket scheme \"{other}\" (expected s3 or gs)")),
+            (endpoint, _) => endpoint.to_string(),
+        };
+        let endpoint = Url::parse(&endpoint).map_err(|e| format!("Invalid OUTPUT_BUCKET_ENDPOINT \"{endpoint}\": {e}"))?;
+        if bucket.is_empty() {
+            return Err(format!("OUTPUT_BUCKET has no bucket name: {location}"));
+        }
+        // 設定ファイルになければ AWS の環境変数
+        let access_key = non_empty_or_env(&settings.output_bucket_access_key, "AWS_ACCESS_KEY_ID");
+        let secret_key = non_empty_or_env(&settings.output_bucket_secret_key, "AWS_SECRET_ACCESS_KEY");
+        if access_key.is_empty() || secret_key.is_empty() {
+            return Err("OUTPUT_BUCKET_ACCESS_KEY and OUTPUT_BUCKET_SECRET_KEY are required".to_string());
+        }
+        let prefix = prefix.trim_matches('/');
+        Ok(Some(Bucket {
+            endpoint,
+            bucket: bucket.to_string(),
+            prefix: if prefix.is_empty() { String::new() } else { format!("{prefix}/") },
+            region,
+            access_key,
+            secret_key,
+        }))
+    }
+
+    /// 結果全体 (<prefix><name>/result.<拡張子>) と URL ごとのファイル (<prefix><name>/pages/0001-<URL>.<拡張子>) を
+    /// アップロードし、オブジェクトの URL を返す
+    pub async fn upload_results(
+        &self,
+        name: &str,
+        url_list: &[String],
+        results: &[DomContent],
+        options: &JobOptions,
+    ) -> Result<Vec<String>, String> {
+        let client = reqwest::Client::builder().timeout(TIMEOUT).build().map_err(|e| e.to_string())?;
+        let format = options.output_format;
+        let mut uploaded = vec![];
+        let output = output::render(results, options)?;
+        let key = format!("{}{name}/result.{}", self.prefix, format.extension());
+        uploaded.push(self.put(&client, &key, format, output.into_bytes()).await?);
+
+        // URL ごと (末尾の集計は除く。サイトマップ展開後は結果の順)
+        let pages = results.iter().filter(|content| !matches!(content, DomContent::Summary(_)));
+        for (i, page) in pages.enumerate() {
+            let url = page_url(page).or_else(|| url_list.get(i).map(String::as_str)).unwrap_or("page");
+            let output = output::render(std::slice::from_ref(page), options)?;
+            let key = format!("{}{name}/pages/{:04}-{}.{}", self.prefix, i + 1, file_stem(url), format.extension());
+            uploaded.push(self.put(&client, &key, format, output.into_bytes()).await?);
+        }
+        Ok(uploaded)
+    }
+
+    /// 1 オブジェクトを PUT (AWS Signature Version 4)
+    async fn put(&self, client: &reqwest::Client, key: &str, format: OutputFormat, body: Vec<u8>) -> Result<String, String> {
+        let mut url = self.endpoint.clone();
+        let path = format!("{}/{}/{}", url.path().trim_end_matches('/'), self.bucket, key);
+        url.set_path(&uri_encode(&path));
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(format!("Invalid bucket endpoint: {}", self.endpoint)),
+        };
+
+        let timestamp = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+        let amz_date: String = timestamp.chars().filter(|c| !matches!(c, '-' | ':')).collect();
+        let date = &amz_date[..8];
+        let payload_hash = hex(&Sha256::digest(&body));
+        let canonical_request = format!(
+            "PUT\n{}\n\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\nhost;x-amz-content-sha256;x-amz-date\n{payload_hash}",
+            url.path()
+        );
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let string_to_sign = format!("AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}", hex(&Sha256::digest(canonical_request)));
+        let mut signing_key = hmac(format!("AWS4{}", self.secret_key).as_bytes(), date.as_bytes());
+        for part in [self.region.as_str(), "s3", "aws4_request"] {
+            signing_key = hmac(&signing_key, part.as_bytes());
+        }
+        let signature = hex(&hmac(&signing_key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={signature}",
+            self.access_key
+        );
+
+        let response = client
+            .put(url.clone())
+            .header("x-amz-date", &amz_date)
+            .header("x-amz-content-sha256", &payload_hash)
+            .header(reqwest::header::AUTHORIZATION, authorization)
+            .header(reqwest::header::CONTENT_TYPE, format.content_type())
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| format!("Upload error ({url}): {e}"))?;
+        let status = response.status();
+        if !status.is_success() {
+            let detail = response.text().await.unwrap_or_default();
+            return Err(format!("Upload failed ({url}): {status} {}", detail.trim()));
+        }
+        tracing::info!(url = %url, "uploaded");
+        Ok(url.to_string())
+    }
+}
+
+/// 結果に含まれる URL ({url, document}・リンクグラフ・差分)
+fn page_url(content: &DomContent) -> Option<&str> {
+    match content {
+        DomContent::Result(result) => match &**result {
+            crate::url_result::UrlResult::Document { url, .. } | crate::url_result::UrlResult::Error { url, .. } => Some(url),
+        },
+        DomContent::Links(page) => Some(&page.url),
+        DomContent::Diff(diff) => Some(&diff.url),
+        _ => None,
+    }
+}
+
+/// URL からファイル名に使える部分 (スキームを除き、英数字・.-_ 以外は _ に。長すぎる分は切る)
+fn file_stem(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let stem: String = rest
+        .trim_end_matches('/')
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .take(100)
+        .collect();
+    if stem.is_empty() { "page".to_string() } else { stem }
+}
+
+/// パスの各部分を RFC 3986 の unreserved 以外 % エンコード ("/" はそのまま)
+fn uri_encode(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn non_empty_or_env(value: &str, env: &str) -> String {
+    match value.trim() {
+        "" => std::env::var(env).unwrap_or_default(),
+        value => value.to_string(),
+    }
+}
+
+/// アップロードのディレクトリ名 (UTC の日時。ジョブなら後ろに ID)
+pub fn upload_name(job_id: Option<&str>) -> String {
+    let timestamp = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+    let stamp: String = timestamp.chars().filter(|c| !matches!(c, '-' | ':')).collect();
+    match job_id {
+        Some(id) => format!("{stamp}-{id}"),
+        None => stamp,
+    }
+}
@@ -0,0 +1,125 @@
+//! schema.org 等の構造化データ (Microdata / RDFa Lite) の抽出
+
+use schemars::JsonSchema;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::clean_text;
+
+/// ページ内の構造化データ
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct StructuredData {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub microdata: Vec<Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rdfa: Vec<Value>,
+}
+
+/// 文書から構造化データを抽出 (何もなければ None)
+pub fn extract_structured_data(doc: &Html) -> Option<StructuredData> {
+    let data = StructuredData {
+        microdata: extract_microdata(doc),
+        rdfa: extract_rdfa(doc),
+    };
+    (!data.microdata.is_empty() || !data.rdfa.is_empty()).then_some(data)
+}
+
+// =================== Microdata ===================
+
+/// トップレベルのアイテム (itemscope かつ itemprop なし)
+fn extract_microdata(doc: &Html) -> Vec<Value> {
+    let sel = Selector::parse("[itemscope]:not([itemprop])").unwrap();
+    doc.select(&sel).map(microdata_item).collect()
+}
+
+/// {"type": [...], "id": ..., "properties": {name: [values]}}
+fn microdata_item(el: ElementRef) -> Value {
+    let mut item = Map::new();
+    if let Some(types) = el.value().attr("itemtype") {
+        let types: Vec<Value> = types.split_ascii_whitespace().map(|t| Value::String(t.to_string())).collect();
+        item.insert("type".to_string(), Value::Array(types));
+    }
+    if let Some(id) = el.value().attr("itemid") {
+        item.insert("id".to_string(), Value::String(id.trim().to_string()));
+    }
+    let mut props = Map::new();
+    collect_properties(el, &mut props, "itemprop", "itemscope", microdata_item);
+    item.insert("properties".to_string(), Value::Object(props));
+    Value::Object(item)
+}
+
+// =================== RDFa Lite ===================
+
+/// トップレベルのアイテム (typeof かつ property なし)
+fn extract_rdfa(doc: &Html) -> Vec<Value> {
+    let sel = Selector::parse("[typeof]:not([property])").unwrap();
+    doc.select(&sel).map(rdfa_item).collect()
+}
+
+/// {"type": "...", "vocab": "...", "resource": "...", "properties": {name: [values]}}
+fn rdfa_item(el: ElementRef) -> Value {
+    let mut item = Map::new();
+    if let Some(t) = el.value().attr("typeof") {
+        item.insert("type".to_string(), Value::String(t.trim().to_string()));
+    }
+    // vocab は祖先から継承
+    let vocab = std::iter::once(el)
+        .chain(el.ancestors().filter_map(ElementRef::wrap))
+        .find_map(|e| e.value().attr("vocab"));
+    if let Some(vocab) = vocab {
+        item.insert("vocab".to_string(), Value::String(vocab.trim().to_string()));
+    }
+    if let Some(resource) = el.value().attr("resource") {
+        item.insert("resource".to_string(), Value::String(resource.trim().to_string()));
+    }
+    let mut props = Map::new();
+    collect_properties(el, &mut props, "property", "typeof", rdfa_item);
+    item.insert("properties".to_string(), Value::Object(props));
+    Value::Object(item)
+}
+
+// =================== 共通 ===================
+
+/// アイテム直下のプロパティを集める (入れ子のアイテムの中には入らない)
+fn collect_properties(
+    el: ElementRef,
+    props: &mut Map<String, Value>,
+    prop_attr: &str,
+    scope_attr: &str,
+    item_fn: fn(ElementRef) -> Value,
+) {
+    for child in el.children().filter_map(ElementRef::wrap) {
+        let is_scope = child.value().attr(scope_attr).is_some();
+        if let Some(names) = child.value().attr(prop_attr) {
+            let value = if is_scope { item_fn(child) } else { property_value(child) };
+            for name in names.split_ascii_whitespace() {
+                let entry = props.entry(name.to_string()).or_insert_with(|| Value::Array(vec![]));
+                if let Value::Array(values) = entry {
+                    values.push(value.clone());
+                }
+            }
+        }
+        if !is_scope {
+            collect_properties(child, props, prop_attr, scope_attr, item_fn);
+        }
+    }
+}
+
+/// 要素の種類に応じたプロパティ値
+fn property_value(el: ElementRef) -> Value {
+    let e = el.value();
+    let attr = |name: &str| e.attr(name).map(|v| Value::String(v.trim().to_string()));
+    let value = match e.name() {
+        _ if e.attr("content").is_some() => attr("content"),
+        "audio" | "embed" | "iframe" | "img" | "source" | "track" | "video" => attr("src"),
+        "a" | "area" | "link" => attr("href"),
+        "object" => attr("data"),
+        "data" | "meter" => attr("value"),
+        "time" => attr("datetime"),
+        _ => None,
+    };
+    value
+        .or_else(|| attr("resource"))
+        .unwrap_or_else(|| Value::String(clean_text(&el.text().collect::<String>())))
+}
@@ -0,0 +1,216 @@
+//! 結果の一部 (部分木) だけを選ぶパス
+//!
+//! - タグのパス: フラットな出力の path と同じ /html[1]/table[2] の形 (番号は同じタグの兄弟の中での順番)。
+//!   先頭を //table[2] にすると、ツリー全体で 2 番目の table から。見出し (h1～h6) を選ぶとその節
+//!   (次の同じか上のレベルの見出しの前までの兄弟) を section ノードにまとめる
+//! - JSON Pointer (RFC 6901): 結果の JSON の /children/0/children/2 など
+
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::flat::{is_bare_text, tag_of};
+use crate::url_result::UrlResult;
+use crate::{error_node, DomContent, DomNode};
+
+/// 部分木のパス
+#[derive(Clone, PartialEq, Eq)]
+pub enum SubtreePath {
+    /// タグのパス (descendant は先頭が //)
+    Tags { descendant: bool, steps: Vec<(String, usize)> },
+    Pointer(String),
+}
+
+impl fmt::Debug for SubtreePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SubtreePath({self})")
+    }
+}
+
+impl fmt::Display for SubtreePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubtreePath::Tags { descendant, steps } => {
+                if *descendant {
+                    f.write_str("/")?;
+                }
+                for (tag, n) in steps {
+                    write!(f, "/{tag}[{n}]")?;
+                }
+                Ok(())
+            }
+            SubtreePath::Pointer(pointer) => f.write_str(pointer),
+        }
+    }
+}
+
+impl SubtreePath {
+    /// 各段が tag[n] ならタグのパス、それ以外は JSON Pointer
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if !text.starts_with('/') {
+            return Err(format!("Invalid path \"{text}\" (must start with /)"));
+        }
+        let (descendant, rest) = match text.strip_prefix("//") {
+            Some(rest) => (true, rest),
+            None => (false, &text[1..]),
+        };
+        let steps: Option<Vec<(String, usize)>> = rest.split('/').map(parse_step).collect();
+        match steps {
+            Some(steps) if !rest.is_empty() => Ok(SubtreePath::Tags { descendant, steps }),
+            _ if descendant => Err(format!("Invalid path \"{text}\" (// must be followed by tag[n] steps)")),
+            _ => Ok(SubtreePath::Pointer(text.to_string())),
+        }
+    }
+
+    /// 選んだ部分木 (見つからなければ ErrorSelect)
+    pub fn select(&self, content: DomContent) -> DomContent {
+        let selected = match self {
+            SubtreePath::Tags { descendant, steps } => {
+                locate(&content, *descendant, steps).and_then(|location| take(content, &location))
+            }
+            SubtreePath::Pointer(pointer) => serde_json::to_value(&content)
+                .ok()
+                .and_then(|mut value| value.pointer_mut(pointer).map(Value::take))
+                .map(|value| DomContent::from_value(value.clone()).unwrap_or(DomContent::Filtered(value))),
+        };
+        selected.unwrap_or_else(|| error_node("ErrorSelect", format!("Nothing at {self}")))
+    }
+}
+
+/// tag[n] (n は 1 以上)
+fn parse_step(step: &str) -> Option<(String, usize)> {
+    let (tag, rest) = step.split_once('[')?;
+    let n: usize = rest.strip_suffix(']')?.parse().ok()?;
+    let valid_tag = !tag.is_empty() && tag.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':'));
+    (valid_tag && n >= 1).then(|| (tag.to_string(), n))
+}
+
+/// ルートからの位置 (各段は子の並びの番号と並びの中の位置。最初の段はルート)
+type Location = Vec<(usize, usize)>;
+
+fn locate(content: &DomContent, descendant: bool, steps: &[(String, usize)]) -> Option<Location> {
+    let root = std::slice::from_ref(content);
+    let ((tag, n), rest) = steps.split_first()?;
+    let mut location = vec![];
+    if descendant {
+        let mut remaining = *n;
+        if !find_descendant(root, 0, tag, &mut remaining, &mut location) {
+            return None;
+        }
+    } else {
+        location.push(find_child(&[root], tag, *n)?);
+    }
+    for (tag, n) in rest {
+        let current = get(content, &location)?;
+        location.push(find_child(&child_lists(current), tag, *n)?);
+    }
+    Some(location)
+}
+
+/// 子の並び (フラットな出力と同じく、URL ごとの結果は中身、リンク先のページは別の並び)
+fn child_lists(content: &DomContent) -> Vec<&[DomContent]> {
+    match content {
+        DomContent::Node(node) => {
+            let mut lists = vec![node.children.as_slice()];
+            if let Some(subpage) = &node.link_subpage {
+                lists.push(std::slice::from_ref(&**subpage));
+            }
+            lists
+        }
+        DomContent::Result(result) => match &**result {
+            UrlResult::Document { document, .. } => child_lists(document),
+            UrlResult::Error { .. } => vec![],
+        },
+        _ => vec![],
+    }
+}
+
+/// child_lists の list 番目の並び (中身を取り出す)
+fn into_child_list(content: DomContent, list: usize) -> Option<Vec<DomContent>> {
+    match (content, list) {
+        (DomContent::Node(node), 0) => Some(node.children),
+        (DomContent::Node(node), 1) => node.link_subpage.map(|subpage| vec![*subpage]),
+        (DomContent::Result(result), list) => match *result {
+            UrlResult::Document { document, .. } => into_child_list(document, list),
+            UrlResult::Error { .. } => None,
+        },
+        _ => None,
+    }
+}
+
+/// 並びの中の n 番目の tag (並びごとに数える)
+fn find_child(lists: &[&[DomContent]], tag: &str, n: usize) -> Option<(usize, usize)> {
+    lists.iter().enumerate().find_map(|(list, contents)| {
+        contents
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !is_bare_text(c) && tag_of(c) == tag)
+            .nth(n - 1)
+            .map(|(i, _)| (list, i))
+    })
+}
+
+/// 文書順で n 番目の tag (remaining は残りの数。見つかれば location にその位置)
+fn find_descendant(contents: &[DomContent], list: usize, tag: &str, remaining: &mut usize, location: &mut Location) -> bool {
+    for (i, content) in contents.iter().enumerate() {
+        location.push((list, i));
+        if !is_bare_text(content) && tag_of(content) == tag {
+            *remaining -= 1;
+            if *remaining == 0 {
+                return true;
+            }
+        }
+        for (child_list, children) in child_lists(content).into_iter().enumerate() {
+            if find_descendant(children, child_list, tag, remaining, location) {
+                return true;
+            }
+        }
+        location.pop();
+    }
+    false
+}
+
+fn get<'a>(content: &'a DomContent, location: &[(usize, usize)]) -> Option<&'a DomContent> {
+    let mut current = content;
+    for &(list, i) in &location[1..] {
+        current = child_lists(current).get(list)?.get(i)?;
+    }
+    Some(current)
+}
+
+/// 位置のノードを取り出す (見出しなら次の同じか上のレベルの見出しまでを section に)
+fn take(content: DomContent, location: &[(usize, usize)]) -> Option<DomContent> {
+    let (mut siblings, mut index) = (vec![content], 0);
+    for &(list, i) in &location[1..] {
+        siblings = into_child_list(siblings.swap_remove(index), list)?;
+        index = i;
+    }
+    let Some(level) = siblings.get(index).and_then(heading_level) else {
+        return (index < siblings.len()).then(|| siblings.swap_remove(index));
+    };
+    let end = siblings[index + 1..]
+        .iter()
+        .position(|c| heading_level(c).is_some_and(|l| l <= level))
+        .map_or(siblings.len(), |p| index + 1 + p);
+    Some(DomContent::Node(DomNode {
+        tag: Some("section".to_string()),
+        href: None,
+        attrs: None,
+        text: None,
+        children: siblings.drain(index..end).collect(),
+        link_subpage: None,
+        page_meta: None,
+        structured_data: None,
+    }))
+}
+
+fn heading_level(content: &DomContent) -> Option<u8> {
+    let DomContent::Node(node) = content else {
+        return None;
+    };
+    match node.tag.as_deref()? {
+        tag @ ("h1" | "h2" | "h3" | "h4" | "h5" | "h6") => Some(tag.as_bytes()[1] - b'0'),
+        _ => None,
+    }
+}
@@ -0,0 +1,122 @@
+//! バッチ全体の集計 (成功・失敗・スキップの件数、受信バイト数、所要時間)
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::search::IndexReport;
+use crate::url_result;
+
+/// 取得しなかった扱いにするエラー (URL の形式が不正・サイズ上限超過)
+const SKIPPED_TAGS: &[&str] = &["ErrorURL", "ErrorTooLarge"];
+
+/// 出力の末尾に付ける集計 ({"summary": {...}})
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SummaryBlock {
+    pub summary: JobSummary,
+}
+
+/// ジョブ 1 回分の集計
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct JobSummary {
+    /// URL 数 (サイトマップ展開後)
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// 受信した本文の合計 (サブページを含む)
+    pub bytes: u64,
+    pub duration_ms: u64,
+    /// 失敗・スキップした URL (入力順)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<FailedUrl>,
+    /// 正規化で変わった入力 URL (入力順)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub normalized: Vec<NormalizedUrl>,
+    /// Elasticsearch / OpenSearch への投入の結果 (投入する指定時)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<IndexReport>,
+}
+
+/// 失敗・スキップした URL
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FailedUrl {
+    pub url: String,
+    /// url / fetch / too_large など (型付きの結果の kind と同じ)。URL・canonical URL の重複は duplicate
+    pub kind: String,
+    pub message: String,
+    pub skipped: bool,
+}
+
+/// 入力 URL と正規化した URL
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NormalizedUrl {
+    pub url: String,
+    pub normalized: String,
+}
+
+impl FailedUrl {
+    /// エラーノードのタグとメッセージから
+    pub fn new(url: &str, tag: &str, message: String) -> Self {
+        FailedUrl {
+            url: url.to_string(),
+            kind: url_result::error_kind(tag),
+            message,
+            skipped: SKIPPED_TAGS.contains(&tag),
+        }
+    }
+
+    /// canonical URL が first_url と同じでスキップした
+    pub fn duplicate(url: &str, first_url: &str) -> Self {
+        FailedUrl {
+            url: url.to_string(),
+            kind: "duplicate".to_string(),
+            message: format!("Same canonical URL as {first_url}"),
+            skipped: true,
+        }
+    }
+
+    /// 正規化した URL が先の入力 (first_url) と同じで取得しなかった
+    pub fn duplicate_url(url: &str, first_url: &str) -> Self {
+        FailedUrl {
+            url: url.to_string(),
+            kind: "duplicate".to_string(),
+            message: format!("Same URL as {first_url}"),
+            skipped: true,
+        }
+    }
+}
+
+impl JobSummary {
+    /// 1 URL 分の結果を数える (None は成功)
+    pub fn record(&mut self, failure: Option<FailedUrl>) {
+        self.total += 1;
+        match failure {
+            None => self.succeeded += 1,
+            Some(failure) => {
+                if failure.skipped {
+                    self.skipped += 1;
+                } else {
+                    self.failed += 1;
+                }
+                self.failures.push(failure);
+            }
+        }
+    }
+
+    /// 1 行の要約 (Markdown・テキスト出力用)
+    pub fn line(&self) -> String {
+        let mut line = format!(
+            "{} URLs: {} succeeded, {} failed, {} skipped, {} bytes, {:.1}s",
+            self.total,
+            self.succeeded,
+            self.failed,
+            self.skipped,
+            self.bytes,
+            self.duration_ms as f64 / 1000.0
+        );
+        if let Some(index) = &self.index {
+            line.push_str(&format!(", {} indexed to {} ({} failed)", index.indexed, index.index, index.failed));
+        }
+        line
+    }
+}
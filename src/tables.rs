@@ -0,0 +1,256 @@
+//! 抽出したテーブルの CSV 変換 (まとめてダウンロードする場合は ZIP か SQLite。Parquet は columnar)
+
+use std::io::{Cursor, Write};
+
+use rusqlite::{params_from_iter, Connection};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::url_result::UrlResult;
+use crate::{DomContent, TableData};
+
+/// Excel で文字化けしないよう先頭に付ける BOM
+const UTF8_BOM: &str = "\u{feff}";
+
+/// 結果に含まれるテーブルを (ファイル名, テーブル) で列挙
+/// (1階層リンク先・フィード記事内のテーブルも元ページの番号で数える)
+pub fn collect_tables(results: &[DomContent]) -> Vec<(String, &TableData)> {
+    let mut named = Vec::new();
+    for (page, content) in results.iter().enumerate() {
+        let mut tables = Vec::new();
+        walk(content, &mut tables);
+        for (i, table) in tables.into_iter().enumerate() {
+            named.push((format!("page{:03}_table{:02}.csv", page + 1, i + 1), table));
+        }
+    }
+    named
+}
+
+fn walk<'a>(content: &'a DomContent, out: &mut Vec<&'a TableData>) {
+    match content {
+        DomContent::Table(table) => out.push(table),
+        DomContent::Image(_) | DomContent::Media(_) | DomContent::Code(_) | DomContent::Form(_) | DomContent::Extracted(_) | DomContent::Resource(_) | DomContent::Summary(_) | DomContent::Links(_) | DomContent::Diff(_) | DomContent::Filtered(_) => {}
+        DomContent::Result(result) => {
+            if let UrlResult::Document { document, .. } = &**result {
+                walk(document, out);
+            }
+        }
+        DomContent::Node(node) => {
+            for child in &node.children {
+                walk(child, out);
+            }
+            if let Some(subpage) = &node.link_subpage {
+                walk(subpage, out);
+            }
+        }
+        DomContent::Feed(feed) => {
+            for article in feed.items.iter().filter_map(|item| item.article.as_deref()) {
+                walk(article, out);
+            }
+        }
+    }
+}
+
+/// table_headers をヘッダ行とした CSV (RFC 4180, CRLF 改行)
+pub fn to_csv(table: &TableData) -> String {
+    let columns = table.columns();
+    let mut csv = String::from(UTF8_BOM);
+    push_record(&mut csv, columns.iter().map(String::as_str));
+    for row in &table.rows {
+        let cells = TableData::row_cells(row, &columns);
+        push_record(&mut csv, cells.iter().map(String::as_str));
+    }
+    csv
+}
+
+/// CSV の 1 行 (CRLF 付き)
+pub fn push_record<'a>(csv: &mut String, fields: impl Iterator<Item = &'a str>) {
+    let fields: Vec<String> = fields.map(quote_field).collect();
+    csv.push_str(&fields.join(","));
+    csv.push_str("\r\n");
+}
+
+/// カンマ・引用符・改行を含むフィールドは "" で囲む
+fn quote_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// テーブルごとの CSV を ZIP にまとめる
+pub fn to_zip(tables: &[(String, &TableData)]) -> Result<Vec<u8>, String> {
+    zip_files(&csv_files(tables))
+}
+
+/// テーブルごとの (ファイル名, CSV)
+pub fn csv_files(tables: &[(String, &TableData)]) -> Vec<(String, Vec<u8>)> {
+    tables.iter().map(|(name, table)| (name.clone(), to_csv(table).into_bytes())).collect()
+}
+
+/// (ファイル名, 中身) を ZIP にまとめる
+pub fn zip_files(files: &[(String, Vec<u8>)]) -> Result<Vec<u8>, String> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let file_options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, bytes) in files {
+        zip.start_file(name.as_str(), file_options)
+            .and_then(|_| Ok(zip.write_all(bytes)?))
+            .map_err(|e| format!("ZIP write error: {e}"))?;
+    }
+    let cursor = zip.finish().map_err(|e| format!("ZIP write error: {e}"))?;
+    Ok(cursor.into_inner())
+}
+
+/// 列の型 (空でないセルがすべて整数なら INTEGER、数値なら REAL、それ以外は TEXT)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Integer,
+    Real,
+    Text,
+}
+
+impl ColumnType {
+    fn infer(cells: &[&str]) -> Self {
+        let values: Vec<&str> = cells.iter().map(|c| c.trim()).filter(|c| !c.is_empty()).collect();
+        if values.is_empty() {
+            ColumnType::Text
+        } else if values.iter().all(|v| is_integer(v)) {
+            ColumnType::Integer
+        } else if values.iter().all(|v| v.parse::<f64>().is_ok_and(f64::is_finite) && !has_leading_zero(v)) {
+            ColumnType::Real
+        } else {
+            ColumnType::Text
+        }
+    }
+
+    fn sql(self) -> &'static str {
+        match self {
+            ColumnType::Integer => "INTEGER",
+            ColumnType::Real => "REAL",
+            ColumnType::Text => "TEXT",
+        }
+    }
+
+    /// セルの値 (数値の列の空のセルは NULL)
+    fn value(self, cell: &str) -> rusqlite::types::Value {
+        use rusqlite::types::Value;
+        let trimmed = cell.trim();
+        match self {
+            ColumnType::Integer | ColumnType::Real if trimmed.is_empty() => Value::Null,
+            ColumnType::Integer => trimmed.parse().map_or(Value::Null, Value::Integer),
+            ColumnType::Real => trimmed.parse().map_or(Value::Null, Value::Real),
+            ColumnType::Text => Value::Text(cell.to_string()),
+        }
+    }
+}
+
+/// 先頭の 0 に意味がありそうな値 (郵便番号・コードなど) は数値にしない
+fn has_leading_zero(value: &str) -> bool {
+    let digits = value.trim_start_matches(['-', '+']);
+    digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.")
+}
+
+fn is_integer(value: &str) -> bool {
+    value.parse::<i64>().is_ok() && !has_leading_zero(value)
+}
+
+/// 列名と型を決めたテーブル (SQLite・Parquet 用)
+pub struct TypedTable {
+    /// 重複・空のない列名
+    pub columns: Vec<String>,
+    pub types: Vec<ColumnType>,
+    /// セルの文字列 (columns の順)
+    pub rows: Vec<Vec<String>>,
+}
+
+pub fn typed_table(table: &TableData) -> TypedTable {
+    let source_columns = table.columns();
+    let columns = column_names(&source_columns);
+    let rows: Vec<Vec<String>> = table.rows.iter().map(|row| TableData::row_cells(row, &source_columns)).collect();
+    let types = (0..columns.len())
+        .map(|i| ColumnType::infer(&rows.iter().map(|row| row[i].as_str()).collect::<Vec<_>>()))
+        .collect();
+    TypedTable { columns, types, rows }
+}
+
+/// テーブルごとに 1 つのテーブル (page001_table01 など) を持つ SQLite のファイル
+///
+/// 一覧は _tables (name, page, table_no, row_count, columns) に入れる
+pub fn to_sqlite(tables: &[(String, &TableData)]) -> Result<Vec<u8>, String> {
+    let db_error = |e: rusqlite::Error| format!("SQLite write error: {e}");
+    let mut conn = Connection::open_in_memory().map_err(db_error)?;
+    let tx = conn.transaction().map_err(db_error)?;
+    tx.execute_batch(
+        "CREATE TABLE _tables (
+            name TEXT PRIMARY KEY,
+            page INTEGER NOT NULL,
+            table_no INTEGER NOT NULL,
+            row_count INTEGER NOT NULL,
+            columns TEXT NOT NULL
+        );",
+    )
+    .map_err(db_error)?;
+    for (file_name, table) in tables {
+        let name = file_name.trim_end_matches(".csv");
+        let TypedTable { columns, types, rows } = typed_table(table);
+
+        let definitions: Vec<String> = columns
+            .iter()
+            .zip(&types)
+            .map(|(column, column_type)| format!("{} {}", quote_identifier(column), column_type.sql()))
+            .collect();
+        tx.execute_batch(&format!("CREATE TABLE {} ({});", quote_identifier(name), definitions.join(", ")))
+            .map_err(db_error)?;
+        if !columns.is_empty() {
+            let placeholders = vec!["?"; columns.len()].join(", ");
+            let mut insert = tx
+                .prepare(&format!("INSERT INTO {} VALUES ({placeholders})", quote_identifier(name)))
+                .map_err(db_error)?;
+            for row in &rows {
+                insert
+                    .execute(params_from_iter(row.iter().zip(&types).map(|(cell, column_type)| column_type.value(cell))))
+                    .map_err(db_error)?;
+            }
+        }
+
+        // page001_table02 → (1, 2)
+        let number = |prefix: &str| -> i64 {
+            name.split('_')
+                .find_map(|part| part.strip_prefix(prefix))
+                .and_then(|n| n.parse().ok())
+                .unwrap_or_default()
+        };
+        tx.execute(
+            "INSERT INTO _tables (name, page, table_no, row_count, columns) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![name, number("page"), number("table"), rows.len() as i64, serde_json::to_string(&columns).unwrap_or_default()],
+        )
+        .map_err(db_error)?;
+    }
+    tx.commit().map_err(db_error)?;
+    let data = conn.serialize(rusqlite::MAIN_DB).map_err(db_error)?;
+    Ok(data.to_vec())
+}
+
+/// SQL の列名 (空の見出しは colN、同じ名前 (大文字小文字を区別しない) は _2, _3 … を付ける)
+fn column_names(columns: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::with_capacity(columns.len());
+    for (i, column) in columns.iter().enumerate() {
+        let base = match column.trim() {
+            "" => format!("col{i}"),
+            name => name.to_string(),
+        };
+        let mut name = base.clone();
+        let mut n = 1;
+        while names.iter().any(|existing| existing.eq_ignore_ascii_case(&name)) {
+            n += 1;
+            name = format!("{base}_{n}");
+        }
+        names.push(name);
+    }
+    names
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
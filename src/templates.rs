@@ -0,0 +1,102 @@
+//! 名前付きの変換設定 (テンプレート) の保存と適用
+//!
+//! フォームの項目 (抽出ルール・属性・リンク先の範囲など) をそのまま JSON ファイル (0600) に保存する。
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde_json::{Map, Value};
+
+use web_to_json::{privatefile, UrlForm};
+
+/// テンプレートの保存先 (実行ディレクトリ)
+pub const TEMPLATES_FILE: &str = "web-to-json-templates.json";
+
+/// テンプレートに保存しない項目 (URL・HTML そのもの・秘密情報・テンプレート操作)
+const EXCLUDED_FIELDS: &[&str] = &["urls", "html", "credentials", "cookies", "headers", "template", "save_template"];
+
+pub type Template = Map<String, Value>;
+
+/// ファイルに永続化するテンプレート一覧
+pub struct TemplateStore {
+    path: String,
+    templates: Mutex<BTreeMap<String, Template>>,
+}
+
+impl TemplateStore {
+    /// ファイルから読み込む (無い・壊れている場合は空)
+    pub fn load(path: &str) -> Self {
+        let templates = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        TemplateStore {
+            path: path.to_string(),
+            templates: Mutex::new(templates),
+        }
+    }
+
+    pub fn list(&self) -> BTreeMap<String, Template> {
+        self.templates.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.templates.lock().unwrap_or_else(|e| e.into_inner()).keys().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Template> {
+        self.templates.lock().unwrap_or_else(|e| e.into_inner()).get(name).cloned()
+    }
+
+    /// フォームの項目を name として保存 (同名は上書き)
+    pub fn save(&self, name: &str, form: &UrlForm) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Template name is empty".to_string());
+        }
+        let Value::Object(mut fields) = serde_json::to_value(form).map_err(|e| e.to_string())? else {
+            return Err("Template must be an object".to_string());
+        };
+        fields.retain(|k, v| !EXCLUDED_FIELDS.contains(&k.as_str()) && !v.is_null());
+        let mut templates = self.templates.lock().unwrap_or_else(|e| e.into_inner());
+        templates.insert(name.to_string(), fields);
+        self.persist(&templates)
+    }
+
+    /// 削除 (存在しなければ false)
+    pub fn delete(&self, name: &str) -> Result<bool, String> {
+        let mut templates = self.templates.lock().unwrap_or_else(|e| e.into_inner());
+        if templates.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.persist(&templates).map(|_| true)
+    }
+
+    fn persist(&self, templates: &BTreeMap<String, Template>) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(templates).map_err(|e| e.to_string())?;
+        privatefile::write(Path::new(&self.path), json.as_bytes()).map_err(|e| format!("Failed to write {}: {e}", self.path))
+    }
+
+    /// form.template が指定されていれば、その項目で form を上書きしたものを返す
+    pub fn apply(&self, form: &UrlForm) -> Result<Option<UrlForm>, String> {
+        let Some(name) = form.template.as_deref().map(str::trim).filter(|n| !n.is_empty()) else {
+            return Ok(None);
+        };
+        let template = self.get(name).ok_or_else(|| format!("Unknown template \"{name}\""))?;
+        apply_template(form, &template).map(Some)
+    }
+}
+
+/// テンプレートの項目をフォームに上書き (URL と秘密情報はフォームのまま)
+pub fn apply_template(form: &UrlForm, template: &Template) -> Result<UrlForm, String> {
+    let Value::Object(mut fields) = serde_json::to_value(form).map_err(|e| e.to_string())? else {
+        return Err("Form must be an object".to_string());
+    };
+    for (key, value) in template {
+        if !EXCLUDED_FIELDS.contains(&key.as_str()) {
+            fields.insert(key.clone(), value.clone());
+        }
+    }
+    serde_json::from_value(Value::Object(fields)).map_err(|e| format!("Invalid template: {e}"))
+}
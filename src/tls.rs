@@ -0,0 +1,20 @@
+//! 内蔵サーバーの HTTPS (rustls)
+
+use rustls::ServerConfig;
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+
+/// PEM の証明書 (中間証明書を含むチェーン) と秘密鍵からサーバー設定を作る
+pub fn server_config(cert_path: &str, key_path: &str) -> Result<ServerConfig, String> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Failed to read certificate {cert_path}: {e}"))?;
+    if certs.is_empty() {
+        return Err(format!("No certificate found in {cert_path}"));
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| format!("Failed to read private key {key_path}: {e}"))?;
+    ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Invalid certificate or private key: {e}"))
+}
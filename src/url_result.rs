@@ -0,0 +1,72 @@
+//! URL ごとの結果を {url, document} / {url, error} で返す形式 (エラーを擬似ノードにしない)
+
+use std::fmt;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::fetch::FetchInfo;
+use crate::DomContent;
+
+/// 1 URL 分の結果
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum UrlResult {
+    Document {
+        /// 入力 (サイトマップ展開後) の URL
+        url: String,
+        document: DomContent,
+    },
+    Error {
+        url: String,
+        error: UrlError,
+    },
+}
+
+/// 変換に失敗した理由
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct UrlError {
+    /// url / fetch / redirect / offline / wayback / too_large / sitemap / feed / json / pdf / render / select / plugin / script / filter / internal
+    pub kind: String,
+    pub message: String,
+    /// 取得はできた場合の取得の情報 (取得情報を含める指定時)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetch: Option<FetchInfo>,
+}
+
+impl fmt::Display for UrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for UrlError {}
+
+/// 従来の結果 (エラーは tag が "Error..." のノード) を変換
+pub fn from_content(url: &str, content: DomContent) -> UrlResult {
+    let url = url.to_string();
+    match content {
+        DomContent::Node(node) if node.tag.as_deref().is_some_and(|t| t.starts_with("Error")) => {
+            let tag = node.tag.unwrap_or_default();
+            UrlResult::Error {
+                url,
+                error: UrlError {
+                    kind: error_kind(&tag),
+                    message: node.text.unwrap_or_default(),
+                    fetch: node.page_meta.and_then(|meta| meta.fetch),
+                },
+            }
+        }
+        document => UrlResult::Document { url, document },
+    }
+}
+
+/// エラーノードのタグ (ErrorFetch など) → kind
+pub fn error_kind(tag: &str) -> String {
+    match tag {
+        "ErrorURL" => "url".to_string(),
+        "ErrorTooLarge" => "too_large".to_string(),
+        "ErrorSpawnBlock" => "internal".to_string(),
+        other => other.trim_start_matches("Error").to_ascii_lowercase(),
+    }
+}
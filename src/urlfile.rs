@@ -0,0 +1,142 @@
+//! URL リストのファイル (テキスト・CSV / TSV) の読み込みと、ファイルを含むフォーム (multipart) の受け取り
+//! (HTML のファイルは貼り付けた HTML と同じ扱い)
+
+use actix_multipart::Multipart;
+use actix_web::{http::header::HeaderMap, web};
+use futures_util::StreamExt;
+use serde_json::{Map, Value};
+
+use web_to_json::{charset, split_url_lines, UrlForm};
+
+/// アップロードできるファイルの上限 (バイト)
+const MAX_FILE_BYTES: usize = 16 * 1024 * 1024;
+
+/// ファイル以外の項目の上限 (バイト)
+const MAX_FIELD_BYTES: usize = 1024 * 1024;
+
+/// フォーム全体 (すべての項目・ファイル) の上限 (バイト)
+const MAX_FORM_BYTES: usize = 32 * 1024 * 1024;
+
+/// 項目・ファイルの数の上限 (フォームの項目数より十分多く)
+const MAX_PARTS: usize = 256;
+
+/// ファイルの URL の一覧
+///
+/// 拡張子が .csv / .tsv のもの (または列の指定があるもの) は CSV / TSV として column の列を、それ以外は 1 行 1 URL として読む
+pub fn urls_from_file(file_name: &str, bytes: &[u8], column: &str) -> Result<Vec<String>, String> {
+    let text = charset::decode(bytes, None);
+    let extension = file_name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default();
+    match extension.as_str() {
+        "tsv" => urls_from_csv(&text, b'\t', column),
+        "csv" => urls_from_csv(&text, b',', column),
+        _ if !column.trim().is_empty() => urls_from_csv(&text, b',', column),
+        _ => Ok(split_url_lines(&text)),
+    }
+}
+
+/// CSV の列の URL (列は見出しか 1 始まりの番号。空なら見出しが url の列、なければ最初に URL が入っている列)
+///
+/// 選んだ列の 1 行目が URL でなければ見出し行として飛ばす
+pub fn urls_from_csv(text: &str, delimiter: u8, column: &str) -> Result<Vec<String>, String> {
+    let rows = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(text.as_bytes())
+        .records()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid CSV: {e}"))?;
+    let Some(first) = rows.first() else {
+        return Ok(vec![]);
+    };
+    let column = column.trim();
+    let index = if column.is_empty() {
+        first
+            .iter()
+            .position(|name| name.trim().eq_ignore_ascii_case("url"))
+            .or_else(|| rows.iter().take(2).find_map(|row| row.iter().position(looks_like_url)))
+            .ok_or("No URL column found in CSV (choose one by its header or number)")?
+    } else if let Ok(n) = column.parse::<usize>() {
+        n.checked_sub(1).ok_or("CSV column numbers start at 1")?
+    } else {
+        first.iter().position(|name| name.trim().eq_ignore_ascii_case(column)).ok_or_else(|| {
+            let names: Vec<&str> = first.iter().collect();
+            format!("CSV has no column \"{column}\" (columns: {})", names.join(", "))
+        })?
+    };
+    let has_header = !first.get(index).is_some_and(looks_like_url);
+    Ok(rows
+        .iter()
+        .skip(usize::from(has_header))
+        .filter_map(|row| row.get(index))
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+fn looks_like_url(value: &str) -> bool {
+    let value = value.trim().to_ascii_lowercase();
+    value.starts_with("http://") || value.starts_with("https://")
+}
+
+/// multipart のフォーム (url_file のファイルの URL はテキストエリアの URL の後ろに足す。CSV の列は url_column。
+/// html_file のファイルは文字コードを判定して html に)
+pub async fn form_from_multipart(headers: &HeaderMap, payload: web::Payload) -> Result<UrlForm, String> {
+    let mut multipart = Multipart::new(headers, payload);
+    let mut fields = Map::new();
+    let mut files = vec![];
+    let mut html_file = None;
+    let mut parts = 0;
+    let mut total = 0;
+    while let Some(field) = multipart.next().await {
+        let mut field = field.map_err(|e| format!("Invalid multipart body: {e}"))?;
+        parts += 1;
+        if parts > MAX_PARTS {
+            return Err(format!("The form has more than {MAX_PARTS} fields"));
+        }
+        let name = field.name().unwrap_or_default().to_string();
+        let file_name = field
+            .content_disposition()
+            .and_then(|cd| cd.get_filename())
+            .map(str::to_string);
+        let limit = if file_name.is_some() { MAX_FILE_BYTES } else { MAX_FIELD_BYTES };
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(|e| format!("Invalid multipart body: {e}"))?;
+            if bytes.len() + chunk.len() > limit {
+                return Err(format!("\"{name}\" is larger than {} MB", limit / 1024 / 1024));
+            }
+            total += chunk.len();
+            if total > MAX_FORM_BYTES {
+                return Err(format!("The form is larger than {} MB", MAX_FORM_BYTES / 1024 / 1024));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        match (name.as_str(), file_name) {
+            // ファイルを選ばなかったときは空のファイル名で送られてくる
+            ("url_file", Some(file_name)) if !file_name.is_empty() || !bytes.is_empty() => files.push((file_name, bytes)),
+            ("url_file", _) => {}
+            ("html_file", Some(_)) if !bytes.is_empty() => html_file = Some(charset::decode(&bytes, None)),
+            ("html_file", _) => {}
+            _ => {
+                fields.insert(name, Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+            }
+        }
+    }
+
+    // ファイルがあれば貼り付けた HTML より優先
+    if let Some(html) = html_file {
+        fields.insert("html".to_string(), Value::String(html));
+    }
+    let column = fields.remove("url_column").and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+    let mut form: UrlForm = serde_json::from_value(Value::Object(fields)).map_err(|e| format!("Invalid form: {e}"))?;
+    for (file_name, bytes) in files {
+        let urls = urls_from_file(&file_name, &bytes, &column).map_err(|e| format!("{file_name}: {e}"))?;
+        for url in urls {
+            form.urls.push('\n');
+            form.urls.push_str(&url);
+        }
+    }
+    Ok(form)
+}
@@ -0,0 +1,428 @@
+//! WARC (WARC/1.1) での書き出し (取得したリクエスト・レスポンスを pywb などのウェブアーカイブのツールで再生できるように)
+//!
+//! - 先頭に warcinfo、HTTP の取得 1 回 (リダイレクトは 1 段ごと) に response と request の組
+//! - レスポンスの本文は受け取ったバイト列のまま。chunked は解いた後なので Transfer-Encoding を除き、Content-Length を付け直す
+//! - 本文を読まないもの (画像などのバイナリ・リトライするステータス・本文の上限を超えたリダイレクト) はヘッダーだけで WARC-Truncated: length
+//! - リクエストの Authorization・Cookie は書かない
+//! - gzip ならレコードごとに gzip のメンバーにする (.warc.gz)
+
+use std::collections::hash_map::RandomState;
+use std::fs::File;
+use std::hash::BuildHasher;
+use std::io::{BufWriter, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use reqwest::header::{self, HeaderMap};
+
+/// 書き出し先
+enum Sink {
+    File(BufWriter<File>),
+    Memory(Vec<u8>),
+}
+
+/// 1 回の変換の WARC
+pub struct WarcWriter {
+    sink: Mutex<Sink>,
+    gzip: bool,
+    warcinfo_id: String,
+    /// 書き込みに失敗したらそれ以降は書かない (最初のエラー)
+    error: Mutex<Option<String>>,
+}
+
+impl std::fmt::Debug for WarcWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WarcWriter(gzip: {})", self.gzip)
+    }
+}
+
+/// 送ったリクエスト (リクエスト行とヘッダー)
+pub struct RequestHead {
+    url: String,
+    block: Vec<u8>,
+}
+
+impl RequestHead {
+    /// defaults はクライアントの既定のヘッダー (User-Agent など。リクエストのヘッダーが優先)
+    pub fn of(request: &reqwest::Request, defaults: &HeaderMap) -> Self {
+        let url = request.url();
+        let target = match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
+        };
+        let mut block = format!("{} {target} HTTP/1.1\r\n", request.method()).into_bytes();
+        let host = match url.port() {
+            Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        write_header(&mut block, "host", host.as_bytes());
+        let mut headers = defaults.clone();
+        headers.extend(request.headers().clone());
+        for (name, value) in &headers {
+            if *name != header::AUTHORIZATION && *name != header::COOKIE && *name != header::HOST {
+                write_header(&mut block, name.as_str(), value.as_bytes());
+            }
+        }
+        block.extend_from_slice(b"\r\n");
+        RequestHead {
+            url: url.to_string(),
+            block,
+        }
+    }
+}
+
+/// 受け取ったレスポンスのステータス行とヘッダー
+pub struct ResponseHead {
+    status_line: String,
+    headers: HeaderMap,
+    remote_addr: Option<SocketAddr>,
+}
+
+impl ResponseHead {
+    pub fn of(resp: &reqwest::Response) -> Self {
+        let status = resp.status();
+        ResponseHead {
+            status_line: format!(
+                "{:?} {} {}",
+                resp.version(),
+                status.as_u16(),
+                status.canonical_reason().unwrap_or_default()
+            ),
+            headers: resp.headers().clone(),
+            remote_addr: resp.remote_addr(),
+        }
+    }
+}
+
+impl WarcWriter {
+    /// ファイルに書き出す (.gz で終われば gzip)
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        let gzip = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("gz"));
+        Self::new(Sink::File(BufWriter::new(File::create(path)?)), gzip)
+    }
+
+    /// メモリに持つ (サーバーのジョブ)
+    pub fn in_memory(gzip: bool) -> Self {
+        Self::new(Sink::Memory(Vec::new()), gzip).expect("writing to memory does not fail")
+    }
+
+    fn new(sink: Sink, gzip: bool) -> std::io::Result<Self> {
+        let writer = WarcWriter {
+            sink: Mutex::new(sink),
+            gzip,
+            warcinfo_id: record_id(),
+            error: Mutex::new(None),
+        };
+        let info = format!(
+            "software: web-to-json/{}\r\nformat: WARC File Format 1.1\r\n",
+            env!("CARGO_PKG_VERSION")
+        );
+        let fields = [
+            ("WARC-Type", "warcinfo".to_string()),
+            ("WARC-Record-ID", writer.warcinfo_id.clone()),
+            ("WARC-Date", now()),
+            ("Content-Type", "application/warc-fields".to_string()),
+        ];
+        writer.write_record(&fields, info.as_bytes())?;
+        Ok(writer)
+    }
+
+    /// 1 回の取得を response・request のレコードに (body が None なら本文なしで WARC-Truncated)
+    pub fn write_exchange(&self, request: &RequestHead, response: &ResponseHead, body: Option<&[u8]>) {
+        if self.error.lock().unwrap().is_some() {
+            return;
+        }
+        let mut block = format!("{}\r\n", response.status_line).into_bytes();
+        for (name, value) in &response.headers {
+            if *name == header::TRANSFER_ENCODING || (body.is_some() && *name == header::CONTENT_LENGTH) {
+                continue;
+            }
+            write_header(&mut block, name.as_str(), value.as_bytes());
+        }
+        if let Some(body) = body {
+            write_header(&mut block, "content-length", body.len().to_string().as_bytes());
+        }
+        block.extend_from_slice(b"\r\n");
+        block.extend_from_slice(body.unwrap_or_default());
+
+        let (response_id, date) = (record_id(), now());
+        let mut fields = vec![
+            ("WARC-Type", "response".to_string()),
+            ("WARC-Record-ID", response_id.clone()),
+            ("WARC-Date", date.clone()),
+            ("WARC-Target-URI", request.url.clone()),
+            ("WARC-Warcinfo-ID", self.warcinfo_id.clone()),
+            ("Content-Type", "application/http;msgtype=response".to_string()),
+        ];
+        if let Some(addr) = response.remote_addr {
+            fields.push(("WARC-IP-Address", addr.ip().to_string()));
+        }
+        if body.is_none() {
+            fields.push(("WARC-Truncated", "length".to_string()));
+        }
+        let request_fields = [
+            ("WARC-Type", "request".to_string()),
+            ("WARC-Record-ID", record_id()),
+            ("WARC-Date", date),
+            ("WARC-Target-URI", request.url.clone()),
+            ("WARC-Concurrent-To", response_id),
+            ("WARC-Warcinfo-ID", self.warcinfo_id.clone()),
+            ("Content-Type", "application/http;msgtype=request".to_string()),
+        ];
+        let written = self
+            .write_record(&fields, &block)
+            .and_then(|_| self.write_record(&request_fields, &request.block));
+        if let Err(e) = written {
+            tracing::warn!(error = %e, "WARC write failed");
+            *self.error.lock().unwrap() = Some(e.to_string());
+        }
+    }
+
+    /// ファイルを書き終える (途中の書き込みエラーもここで返す)
+    pub fn finish(&self) -> std::io::Result<()> {
+        if let Some(e) = self.error.lock().unwrap().clone() {
+            return Err(std::io::Error::other(e));
+        }
+        match &mut *self.sink.lock().unwrap() {
+            Sink::File(file) => file.flush(),
+            Sink::Memory(_) => Ok(()),
+        }
+    }
+
+    /// メモリに持った WARC (ファイルに書き出す場合は空)
+    pub fn bytes(&self) -> Vec<u8> {
+        match &*self.sink.lock().unwrap() {
+            Sink::Memory(bytes) => bytes.clone(),
+            Sink::File(_) => vec![],
+        }
+    }
+
+    fn write_record(&self, fields: &[(&str, String)], block: &[u8]) -> std::io::Result<()> {
+        let mut record = b"WARC/1.1\r\n".to_vec();
+        for (name, value) in fields {
+            record.extend_from_slice(format!("{name}: {value}\r\n").as_bytes());
+        }
+        record.extend_from_slice(format!("Content-Length: {}\r\n\r\n", block.len()).as_bytes());
+        record.extend_from_slice(block);
+        record.extend_from_slice(b"\r\n\r\n");
+        if self.gzip {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&record)?;
+            record = encoder.finish()?;
+        }
+        match &mut *self.sink.lock().unwrap() {
+            Sink::File(file) => file.write_all(&record),
+            Sink::Memory(bytes) => {
+                bytes.extend_from_slice(&record);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn write_header(block: &mut Vec<u8>, name: &str, value: &[u8]) {
+    block.extend_from_slice(name.as_bytes());
+    block.extend_from_slice(b": ");
+    block.extend_from_slice(value);
+    block.extend_from_slice(b"\r\n");
+}
+
+fn now() -> String {
+    humantime::format_rfc3339_seconds(SystemTime::now()).to_string()
+}
+
+/// <urn:uuid:...> (バージョン 4 の形のランダムな UUID)
+fn record_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let (a, b) = (RandomState::new().hash_one(n), RandomState::new().hash_one(!n));
+    let bytes: Vec<u8> = a.to_be_bytes().into_iter().chain(b.to_be_bytes()).collect();
+    let hex: String = bytes
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| match i {
+            6 => format!("{:02x}", (byte & 0x0f) | 0x40),
+            8 => format!("{:02x}", (byte & 0x3f) | 0x80),
+            _ => format!("{byte:02x}"),
+        })
+        .collect();
+    format!("<urn:uuid:{}-{}-{}-{}-{}>", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    use flate2::read::MultiGzDecoder;
+    use reqwest::header::HeaderValue;
+
+    fn request(defaults: &HeaderMap) -> RequestHead {
+        let request = reqwest::Client::new()
+            .get("http://example.com:8080/a/b?q=1")
+            .header(header::AUTHORIZATION, "Bearer secret")
+            .header(header::COOKIE, "session=secret")
+            .header(header::ACCEPT, "text/html")
+            .build()
+            .unwrap();
+        RequestHead::of(&request, defaults)
+    }
+
+    fn response(headers: &[(header::HeaderName, &'static str)]) -> ResponseHead {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(name.clone(), HeaderValue::from_static(value));
+        }
+        ResponseHead {
+            status_line: "HTTP/1.1 200 OK".to_string(),
+            headers: map,
+            remote_addr: Some("192.0.2.1:8080".parse().unwrap()),
+        }
+    }
+
+    fn text(writer: &WarcWriter) -> String {
+        String::from_utf8(writer.bytes()).unwrap()
+    }
+
+    #[test]
+    fn request_head_skips_credentials() {
+        let mut defaults = HeaderMap::new();
+        defaults.insert(header::USER_AGENT, HeaderValue::from_static("web-to-json"));
+        defaults.insert(header::ACCEPT, HeaderValue::from_static("*/*"));
+        let head = request(&defaults);
+        let block = String::from_utf8(head.block).unwrap();
+        assert_eq!(head.url, "http://example.com:8080/a/b?q=1");
+        assert!(block.starts_with("GET /a/b?q=1 HTTP/1.1\r\nhost: example.com:8080\r\n"));
+        assert!(block.contains("user-agent: web-to-json\r\n"));
+        // リクエストのヘッダーが既定より優先
+        assert!(block.contains("accept: text/html\r\n"));
+        assert!(!block.contains("*/*"));
+        assert!(!block.contains("secret"));
+        assert!(block.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn writes_warcinfo_first() {
+        let writer = WarcWriter::in_memory(false);
+        let warc = text(&writer);
+        assert!(warc.starts_with("WARC/1.1\r\nWARC-Type: warcinfo\r\n"));
+        assert!(warc.contains(&format!("software: web-to-json/{}", env!("CARGO_PKG_VERSION"))));
+        assert_eq!(warc.matches("WARC/1.1\r\n").count(), 1);
+    }
+
+    #[test]
+    fn exchange_writes_response_then_request() {
+        let writer = WarcWriter::in_memory(false);
+        let head = response(&[
+            (header::CONTENT_TYPE, "text/html"),
+            (header::TRANSFER_ENCODING, "chunked"),
+            (header::CONTENT_LENGTH, "999"),
+        ]);
+        writer.write_exchange(&request(&HeaderMap::new()), &head, Some(b"<p>hi</p>"));
+        writer.finish().unwrap();
+        let warc = text(&writer);
+        let response_at = warc.find("WARC-Type: response").unwrap();
+        let request_at = warc.find("WARC-Type: request").unwrap();
+        assert!(response_at < request_at);
+        assert!(warc.contains("WARC-Target-URI: http://example.com:8080/a/b?q=1\r\n"));
+        assert!(warc.contains("WARC-IP-Address: 192.0.2.1\r\n"));
+        // Transfer-Encoding は外し、Content-Length は本文の長さに
+        assert!(!warc.contains("transfer-encoding"));
+        assert!(!warc.contains("999"));
+        assert!(warc.contains("HTTP/1.1 200 OK\r\ncontent-type: text/html\r\ncontent-length: 9\r\n\r\n<p>hi</p>"));
+        assert!(!warc.contains("WARC-Truncated"));
+
+        // request は response と同時のレコード
+        let id_of = |at: usize| {
+            let rest = &warc[at..];
+            let start = rest.find("WARC-Record-ID: ").unwrap() + "WARC-Record-ID: ".len();
+            rest[start..start + rest[start..].find("\r\n").unwrap()].to_string()
+        };
+        assert!(warc.contains(&format!("WARC-Concurrent-To: {}\r\n", id_of(response_at))));
+        assert_ne!(id_of(response_at), id_of(request_at));
+    }
+
+    #[test]
+    fn exchange_without_body_is_truncated() {
+        let writer = WarcWriter::in_memory(false);
+        let head = response(&[(header::CONTENT_LENGTH, "1234")]);
+        writer.write_exchange(&request(&HeaderMap::new()), &head, None);
+        let warc = text(&writer);
+        assert!(warc.contains("WARC-Truncated: length\r\n"));
+        // 本文がなければ元の Content-Length を残す
+        assert!(warc.contains("HTTP/1.1 200 OK\r\ncontent-length: 1234\r\n\r\n"));
+    }
+
+    #[test]
+    fn record_content_length_matches_block() {
+        let writer = WarcWriter::in_memory(false);
+        writer.write_exchange(&request(&HeaderMap::new()), &response(&[]), Some("日本語".as_bytes()));
+        let warc = writer.bytes();
+        let mut rest = &warc[..];
+        let mut records = 0;
+        while !rest.is_empty() {
+            let head_end = rest.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+            let head = std::str::from_utf8(&rest[..head_end]).unwrap();
+            let length: usize = head
+                .lines()
+                .find_map(|line| line.strip_prefix("Content-Length: "))
+                .unwrap()
+                .parse()
+                .unwrap();
+            let block_end = head_end + 4 + length;
+            assert_eq!(&rest[block_end..block_end + 4], b"\r\n\r\n");
+            rest = &rest[block_end + 4..];
+            records += 1;
+        }
+        assert_eq!(records, 3);
+    }
+
+    #[test]
+    fn gzip_compresses_each_record() {
+        let writer = WarcWriter::in_memory(true);
+        writer.write_exchange(&request(&HeaderMap::new()), &response(&[]), Some(b"body"));
+        let warc = writer.bytes();
+        assert_eq!(&warc[..2], [0x1f, 0x8b]);
+        // レコードごとの gzip メンバー
+        let members = warc.windows(3).filter(|w| *w == [0x1f, 0x8b, 0x08]).count();
+        assert!(members >= 3);
+        let mut plain = String::new();
+        MultiGzDecoder::new(&warc[..]).read_to_string(&mut plain).unwrap();
+        assert!(plain.starts_with("WARC/1.1\r\nWARC-Type: warcinfo\r\n"));
+        assert_eq!(plain.matches("WARC/1.1\r\n").count(), 3);
+        assert!(plain.contains("\r\n\r\nbody\r\n\r\n"));
+    }
+
+    #[test]
+    fn create_picks_gzip_from_extension() {
+        let dir = std::env::temp_dir().join(format!("web-to-json-warc-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, gzip) in [("a.warc", false), ("a.warc.GZ", true)] {
+            let path = dir.join(name);
+            let writer = WarcWriter::create(&path).unwrap();
+            assert_eq!(writer.gzip, gzip);
+            writer.finish().unwrap();
+            // ファイルに書き出すときはメモリに持たない
+            assert!(writer.bytes().is_empty());
+            let bytes = std::fs::read(&path).unwrap();
+            assert_eq!(bytes.starts_with(&[0x1f, 0x8b]), gzip);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn record_ids_are_uuid_v4() {
+        let (a, b) = (record_id(), record_id());
+        assert_ne!(a, b);
+        let uuid = a.strip_prefix("<urn:uuid:").unwrap().strip_suffix('>').unwrap();
+        let parts: Vec<&str> = uuid.split('-').collect();
+        assert_eq!(parts.iter().map(|p| p.len()).collect::<Vec<_>>(), [8, 4, 4, 4, 12]);
+        assert!(parts[2].starts_with('4'));
+        assert!(matches!(parts[3].chars().next(), Some('8' | '9' | 'a' | 'b')));
+    }
+}
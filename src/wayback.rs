@@ -0,0 +1,68 @@
+//! Internet Archive (Wayback Machine) のスナップショットの取得
+//!
+//! 日時を指定してその時点に最も近いスナップショットを取得するか、ライブの取得に失敗したとき
+//! (接続エラー・4xx / 5xx) の代わりに最新のスナップショットを取得する。
+//! <WAYBACK_URL>/web/<日時>id_/<URL> を取得し (id_ でツールバーやリンクの書き換えのない元の本文)、
+//! Wayback がリダイレクトした先の URL から実際のスナップショットの日時を得る。
+
+use std::time::SystemTime;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// スナップショットを使う条件
+#[derive(Debug, Clone)]
+pub struct WaybackOptions {
+    /// Wayback Machine のベース URL (設定ファイルの WAYBACK_URL)
+    pub base: Url,
+    /// この日時 (YYYYMMDDhhmmss の先頭の一部) に最も近いスナップショットを取得する (ライブでは取得しない)
+    pub date: Option<String>,
+    /// ライブの取得に失敗したらスナップショットを取得する
+    pub fallback: bool,
+}
+
+impl WaybackOptions {
+    /// スナップショットを使うか
+    pub fn is_enabled(&self) -> bool {
+        self.date.is_some() || self.fallback
+    }
+}
+
+/// 取得に使ったスナップショット (取得情報の wayback)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct WaybackSnapshot {
+    /// スナップショットの URL (リダイレクト後)
+    pub url: String,
+    /// スナップショットの日時 (YYYYMMDDhhmmss。URL から読めなければ None)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    /// date (日時の指定) / fallback (ライブの取得に失敗した代わり)
+    pub reason: String,
+}
+
+/// 日時の指定を YYYYMMDDhhmmss の先頭の一部に ("2024-01-02"・"2024-01-02T03:04:05Z"・"20240102" など)
+pub fn parse_date(text: &str) -> Result<String, String> {
+    let digits: String = text.trim().chars().filter(|c| !matches!(c, '-' | ':' | 'T' | 't' | ' ' | 'Z' | 'z')).collect();
+    if (4..=14).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_digit()) {
+        Ok(digits)
+    } else {
+        Err(format!("Invalid Wayback date \"{}\" (expected YYYY[MM[DD[hhmmss]]] or YYYY-MM-DD)", text.trim()))
+    }
+}
+
+/// <base>/web/<日時>id_/<url> (日時なしは現在に最も近い = 最新)
+pub fn snapshot_url(base: &Url, date: Option<&str>, url: &Url) -> Option<Url> {
+    let date = match date {
+        Some(date) => date.to_string(),
+        None => humantime::format_rfc3339_seconds(SystemTime::now()).to_string().chars().filter(char::is_ascii_digit).collect(),
+    };
+    Url::parse(&format!("{}/web/{date}id_/{url}", base.as_str().trim_end_matches('/'))).ok()
+}
+
+/// スナップショットの URL の日時 (/web/20240102030405id_/... の数字)
+pub fn snapshot_timestamp(snapshot: &str) -> Option<String> {
+    let rest = snapshot.split("/web/").nth(1)?;
+    let timestamp: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    (timestamp.len() == 14).then_some(timestamp)
+}
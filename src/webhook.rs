@@ -0,0 +1,100 @@
+//! ジョブ完了時の通知 (指定の URL に状態と結果の URL、または結果そのものを POST)
+
+use std::time::Duration;
+
+use reqwest::header::CONTENT_TYPE;
+use url::Url;
+
+use crate::jobs::Job;
+use crate::output;
+
+/// 通知の試行回数 (接続できない・5xx のとき)
+const MAX_ATTEMPTS: u32 = 3;
+
+/// 1 回の通知のタイムアウト
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 通知先
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    pub url: Url,
+    /// 結果そのもの (ジョブの出力形式) を送る。false なら状態と結果の URL の JSON
+    pub include_result: bool,
+    /// 結果の URL の先頭 (例: http://127.0.0.1:8080。ジョブを登録したリクエストのホスト)
+    pub origin: String,
+}
+
+/// 通知先の URL (空なら None、http / https 以外はエラー)
+pub fn parse_url(text: &str) -> Result<Option<Url>, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let url = Url::parse(text).map_err(|e| format!("Invalid callback URL \"{text}\": {e}"))?;
+    if !["http", "https"].contains(&url.scheme()) {
+        return Err(format!("Callback URL must be http or https: {text}"));
+    }
+    Ok(Some(url))
+}
+
+/// 完了したジョブを通知 (失敗はログに残すだけ)
+pub async fn notify(webhook: &Webhook, job: &Job) {
+    let mut report = job.report();
+    report.result_url = report.result_url.map(|path| format!("{}{path}", webhook.origin));
+    // 結果を送る指定でも、ジョブ全体が失敗したときは状態の JSON
+    let rendered = if webhook.include_result {
+        job.with_outcome(|o| o.as_ref().ok().map(|results| output::render(results, &job.options)))
+            .flatten()
+    } else {
+        None
+    };
+    let (content_type, body) = match rendered {
+        Some(Ok(text)) => (job.options.output_format.content_type(), text),
+        Some(Err(e)) => {
+            tracing::warn!(job = %job.id, error = %e, "webhook: failed to render result");
+            return;
+        }
+        None => ("application/json", serde_json::to_string(&report).unwrap_or_default()),
+    };
+
+    let mut builder = reqwest::Client::builder().timeout(TIMEOUT);
+    if let Some(ua) = &job.options.user_agent {
+        builder = builder.user_agent(ua);
+    }
+    let client = match builder.build() {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::warn!(job = %job.id, error = %e, "webhook: failed to build client");
+            return;
+        }
+    };
+    let status = format!("{:?}", report.status).to_ascii_lowercase();
+    for attempt in 1..=MAX_ATTEMPTS {
+        let result = client
+            .post(webhook.url.clone())
+            .header(CONTENT_TYPE, content_type)
+            .header("X-Job-Id", &job.id)
+            .header("X-Job-Status", &status)
+            .body(body.clone())
+            .send()
+            .await;
+        match result {
+            Ok(response) if response.status().is_success() => {
+                tracing::info!(job = %job.id, url = %webhook.url, status = response.status().as_u16(), "webhook sent");
+                return;
+            }
+            // 4xx は送り直しても同じなのでやめる
+            Ok(response) if response.status().is_client_error() => {
+                tracing::warn!(job = %job.id, url = %webhook.url, status = response.status().as_u16(), "webhook rejected");
+                return;
+            }
+            Ok(response) => {
+                tracing::warn!(job = %job.id, url = %webhook.url, status = response.status().as_u16(), attempt, "webhook failed");
+            }
+            Err(e) => tracing::warn!(job = %job.id, url = %webhook.url, error = %e, attempt, "webhook failed"),
+        }
+        if attempt < MAX_ATTEMPTS {
+            tokio::time::sleep(Duration::from_secs(1 << (attempt - 1))).await;
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! 簡易 XML ツリー (サイトマップ・フィード用)
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, XmlVersion};
+
+/// XML 要素 (名前は名前空間プレフィックスを除いたローカル名)
+#[derive(Debug, Default)]
+pub struct XmlElement {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    /// 直下のテキスト (CDATA 含む、前後の空白は除去)
+    pub text: String,
+    pub children: Vec<XmlElement>,
+}
+
+impl XmlElement {
+    /// 名前が一致する最初の子要素
+    pub fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// 名前が一致する子要素すべて
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlElement> + 'a {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+
+    /// 子要素のテキスト (空なら None)
+    pub fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|c| c.text.as_str()).filter(|t| !t.is_empty())
+    }
+}
+
+/// ルート要素を返す
+pub fn parse(xml: &str) -> Result<XmlElement, String> {
+    let mut reader = Reader::from_str(xml);
+    // 開いている要素のスタック (先頭はダミーの文書ノード)
+    let mut stack = vec![XmlElement::default()];
+
+    loop {
+        match reader.read_event().map_err(|e| format!("XML parse error: {e}"))? {
+            Event::Start(e) => stack.push(start_element(&e)),
+            Event::Empty(e) => {
+                let el = start_element(&e);
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(el);
+                }
+            }
+            Event::End(_) if stack.len() > 1 => {
+                let mut el = stack.pop().unwrap_or_default();
+                el.text = el.text.trim().to_string();
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(el);
+                }
+            }
+            Event::Text(t) => push_text(&mut stack, &t.xml10_content()),
+            Event::CData(c) => push_text(&mut stack, &c.xml10_content()),
+            Event::GeneralRef(r) => {
+                let entity = format!("&{};", &*r);
+                let resolved = quick_xml::escape::unescape(&entity)
+                    .map(|s| s.into_owned())
+                    .unwrap_or(entity);
+                push_text(&mut stack, &resolved);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    // 閉じられていない要素は親に畳み込む
+    while stack.len() > 1 {
+        let el = stack.pop().unwrap_or_default();
+        if let Some(parent) = stack.last_mut() {
+            parent.children.push(el);
+        }
+    }
+    stack
+        .pop()
+        .and_then(|doc| doc.children.into_iter().next())
+        .ok_or_else(|| "XML has no root element".to_string())
+}
+
+fn start_element(e: &BytesStart) -> XmlElement {
+    let name = e.local_name().as_ref().to_string();
+    let attrs = e
+        .attributes()
+        .flatten()
+        .map(|a| {
+            let key = a.key.local_name().as_ref().to_string();
+            let value = a
+                .normalized_value(XmlVersion::Implicit1_0)
+                .map(|v| v.into_owned())
+                .unwrap_or_else(|_| a.value.to_string());
+            (key, value)
+        })
+        .collect();
+    XmlElement { name, attrs, ..Default::default() }
+}
+
+fn push_text(stack: &mut [XmlElement], text: &str) {
+    if let Some(el) = stack.last_mut() {
+        el.text.push_str(text);
+    }
+}
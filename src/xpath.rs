@@ -0,0 +1,529 @@
+//! 抽出ルール用の XPath (よく使われるサブセットを scraper のツリー上で評価)
+//!
+//! 対応: `/` `//` `*` `.` `..` `@name` `@*` `text()`、述語 `[n]` `[last()]` `[@a]` `[@a='v']`
+//! `[contains(@class, 'x')]` `[starts-with(., 'x')]` `[normalize-space()='x']` `[not(...)]` `and` / `or`
+
+use std::collections::HashSet;
+
+use ego_tree::{NodeId, NodeRef};
+use scraper::node::Node;
+use scraper::Html;
+
+use crate::clean_text;
+
+/// 解析済みの XPath
+#[derive(Debug, Clone)]
+pub struct XPath {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone)]
+struct Step {
+    /// `//` (descendant-or-self) で始まるステップ
+    descendant: bool,
+    test: Test,
+    predicates: Vec<Expr>,
+}
+
+#[derive(Debug, Clone)]
+enum Test {
+    /// 要素名 (None は *)
+    Element(Option<String>),
+    /// 属性名 (None は @*)
+    Attr(Option<String>),
+    Text,
+    SelfNode,
+    Parent,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Eq(Box<Expr>, Box<Expr>, bool),
+    Attr(String),
+    Text,
+    Context,
+    Child(String),
+    Str(String),
+    Num(f64),
+    Contains(Box<Expr>, Box<Expr>),
+    StartsWith(Box<Expr>, Box<Expr>),
+    NormalizeSpace(Box<Expr>),
+    Position,
+    Last,
+}
+
+/// 評価結果の 1 件
+pub enum XItem {
+    /// 要素 (文字列値は子孫のテキスト)
+    Element(String),
+    Text(String),
+    /// (属性名, 値)
+    Attr(String, String),
+}
+
+/// 述語内の値
+enum XValue {
+    /// 文字列 (None は空のノード集合)
+    Str(Option<String>),
+    Num(f64),
+    Bool(bool),
+}
+
+impl XValue {
+    fn truthy(&self, position: usize) -> bool {
+        match self {
+            XValue::Str(s) => s.is_some(),
+            // [2] は position() = 2
+            XValue::Num(n) => *n == position as f64,
+            XValue::Bool(b) => *b,
+        }
+    }
+
+    fn string(&self) -> String {
+        match self {
+            XValue::Str(s) => s.clone().unwrap_or_default(),
+            XValue::Num(n) => n.to_string(),
+            XValue::Bool(b) => b.to_string(),
+        }
+    }
+}
+
+impl XPath {
+    /// "/" で始まる絶対パスを解析
+    pub fn parse(text: &str) -> Result<XPath, String> {
+        let mut parser = Parser { src: text.trim(), pos: 0 };
+        let steps = parser.path()?;
+        parser.skip_ws();
+        if parser.pos < parser.src.len() {
+            return Err(format!("unexpected \"{}\"", &parser.src[parser.pos..]));
+        }
+        Ok(XPath { steps })
+    }
+
+    /// 文書に適用 (文書順、重複なし)
+    pub fn select(&self, doc: &Html) -> Vec<XItem> {
+        let mut context: Vec<NodeRef<Node>> = vec![doc.tree.root()];
+        let mut terminal = Vec::new();
+        for (i, step) in self.steps.iter().enumerate() {
+            let mut next = Vec::new();
+            let mut seen = HashSet::new();
+            for ctx in &context {
+                let bases: Vec<NodeRef<Node>> = if step.descendant {
+                    ctx.descendants().collect()
+                } else {
+                    vec![*ctx]
+                };
+                for base in bases {
+                    match &step.test {
+                        Test::Attr(name) => {
+                            if let Node::Element(e) = base.value() {
+                                for (k, v) in e.attrs() {
+                                    if name.as_deref().is_none_or(|n| n == k) {
+                                        terminal.push(XItem::Attr(k.to_string(), v.trim().to_string()));
+                                    }
+                                }
+                            }
+                        }
+                        test => {
+                            let candidates = candidates(base, test);
+                            let size = candidates.len();
+                            let selected = candidates.into_iter().enumerate().filter(|(pos, node)| {
+                                step.predicates
+                                    .iter()
+                                    .all(|p| eval(p, *node, pos + 1, size).truthy(pos + 1))
+                            });
+                            for (_, node) in selected {
+                                if seen.insert(node.id()) {
+                                    next.push(node);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            // 属性は末尾のステップでのみ意味を持つ
+            if matches!(step.test, Test::Attr(_)) {
+                return if i + 1 == self.steps.len() { terminal } else { vec![] };
+            }
+            sort_document_order(&mut next);
+            context = next;
+        }
+        context
+            .into_iter()
+            .filter_map(|node| match node.value() {
+                Node::Text(t) => Some(XItem::Text(clean_text(&t.text))),
+                Node::Element(_) => Some(XItem::Element(string_value(node))),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// ステップの候補ノード
+fn candidates<'a>(base: NodeRef<'a, Node>, test: &Test) -> Vec<NodeRef<'a, Node>> {
+    match test {
+        Test::Element(name) => base
+            .children()
+            .filter(|c| match c.value() {
+                Node::Element(e) => name.as_deref().is_none_or(|n| e.name() == n),
+                _ => false,
+            })
+            .collect(),
+        Test::Text => base.children().filter(|c| c.value().is_text()).collect(),
+        Test::SelfNode => vec![base],
+        Test::Parent => base.parent().into_iter().collect(),
+        Test::Attr(_) => vec![],
+    }
+}
+
+/// 複数の文脈から集めたノードを文書順に
+fn sort_document_order(nodes: &mut [NodeRef<Node>]) {
+    let Some(first) = nodes.first() else {
+        return;
+    };
+    let root = first.ancestors().last().unwrap_or(*first);
+    let order: std::collections::HashMap<NodeId, usize> =
+        root.descendants().enumerate().map(|(i, n)| (n.id(), i)).collect();
+    nodes.sort_by_key(|n| order.get(&n.id()).copied().unwrap_or(usize::MAX));
+}
+
+/// ノードの文字列値 (子孫のテキストを連結して整形)
+fn string_value(node: NodeRef<Node>) -> String {
+    let text: String = node
+        .descendants()
+        .filter_map(|n| n.value().as_text().map(|t| t.text.to_string()))
+        .collect();
+    clean_text(&text)
+}
+
+fn eval(expr: &Expr, node: NodeRef<Node>, position: usize, size: usize) -> XValue {
+    let sub = |e: &Expr| eval(e, node, position, size);
+    match expr {
+        Expr::Or(a, b) => XValue::Bool(sub(a).truthy(position) || sub(b).truthy(position)),
+        Expr::And(a, b) => XValue::Bool(sub(a).truthy(position) && sub(b).truthy(position)),
+        Expr::Not(a) => XValue::Bool(!sub(a).truthy(position)),
+        Expr::Eq(a, b, negate) => {
+            let (a, b) = (sub(a), sub(b));
+            let equal = match (&a, &b) {
+                (XValue::Num(x), other) | (other, XValue::Num(x)) => {
+                    other.string().trim().parse::<f64>().is_ok_and(|y| y == *x)
+                }
+                // 空のノード集合はどの値とも等しくない
+                (XValue::Str(None), _) | (_, XValue::Str(None)) => false,
+                _ => a.string() == b.string(),
+            };
+            XValue::Bool(equal != *negate)
+        }
+        Expr::Attr(name) => XValue::Str(match node.value() {
+            Node::Element(e) => e.attr(name).map(str::to_string),
+            _ => None,
+        }),
+        Expr::Text => {
+            let text: String = node
+                .children()
+                .filter_map(|c| c.value().as_text().map(|t| t.text.to_string()))
+                .collect();
+            XValue::Str((!text.is_empty()).then_some(text))
+        }
+        Expr::Context => XValue::Str(Some(string_value(node))),
+        Expr::Child(name) => XValue::Str(
+            candidates(node, &Test::Element(Some(name.clone())))
+                .first()
+                .map(|c| string_value(*c)),
+        ),
+        Expr::Str(s) => XValue::Str(Some(s.clone())),
+        Expr::Num(n) => XValue::Num(*n),
+        Expr::Contains(a, b) => XValue::Bool(sub(a).string().contains(&sub(b).string())),
+        Expr::StartsWith(a, b) => XValue::Bool(sub(a).string().starts_with(&sub(b).string())),
+        Expr::NormalizeSpace(a) => XValue::Str(Some(clean_text(&sub(a).string()))),
+        Expr::Position => XValue::Num(position as f64),
+        Expr::Last => XValue::Num(size as f64),
+    }
+}
+
+// =================== 構文解析 ===================
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.src[self.pos..]
+    }
+
+    fn skip_ws(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.src.len() - trimmed.len();
+    }
+
+    /// 空白を飛ばして token があれば消費
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_ws();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(format!("expected \"{token}\" at \"{}\"", self.rest()))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        self.skip_ws();
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '.')))
+            .unwrap_or(self.rest().len());
+        if len == 0 {
+            return Err(format!("expected a name at \"{}\"", self.rest()));
+        }
+        let name = self.rest()[..len].to_ascii_lowercase();
+        self.pos += len;
+        Ok(name)
+    }
+
+    fn path(&mut self) -> Result<Vec<Step>, String> {
+        let mut steps = Vec::new();
+        loop {
+            let descendant = if self.eat("//") {
+                true
+            } else if self.eat("/") {
+                false
+            } else if steps.is_empty() {
+                return Err("XPath must start with \"/\"".to_string());
+            } else {
+                return Ok(steps);
+            };
+            steps.push(self.step(descendant)?);
+        }
+    }
+
+    fn step(&mut self, descendant: bool) -> Result<Step, String> {
+        let test = if self.eat("@") {
+            Test::Attr(if self.eat("*") { None } else { Some(self.name()?) })
+        } else if self.eat("text()") {
+            Test::Text
+        } else if self.eat("..") {
+            Test::Parent
+        } else if self.eat(".") {
+            Test::SelfNode
+        } else if self.eat("*") {
+            Test::Element(None)
+        } else {
+            Test::Element(Some(self.name()?))
+        };
+        let mut predicates = Vec::new();
+        while self.eat("[") {
+            predicates.push(self.or_expr()?);
+            self.expect("]")?;
+        }
+        Ok(Step { descendant, test, predicates })
+    }
+
+    fn or_expr(&mut self) -> Result<Expr, String> {
+        let mut left = self.and_expr()?;
+        while self.eat_keyword("or") {
+            left = Expr::Or(Box::new(left), Box::new(self.and_expr()?));
+        }
+        Ok(left)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, String> {
+        let mut left = self.cmp_expr()?;
+        while self.eat_keyword("and") {
+            left = Expr::And(Box::new(left), Box::new(self.cmp_expr()?));
+        }
+        Ok(left)
+    }
+
+    /// 単語としての and / or (名前の一部は除く)
+    fn eat_keyword(&mut self, word: &str) -> bool {
+        self.skip_ws();
+        let rest = self.rest();
+        let boundary = rest[word.len().min(rest.len())..]
+            .chars()
+            .next()
+            .is_none_or(|c| c.is_whitespace() || c == '(' || c == '@' || c == '\'' || c == '"');
+        if rest.starts_with(word) && boundary {
+            self.pos += word.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn cmp_expr(&mut self) -> Result<Expr, String> {
+        let left = self.operand()?;
+        if self.eat("!=") {
+            Ok(Expr::Eq(Box::new(left), Box::new(self.operand()?), true))
+        } else if self.eat("=") {
+            Ok(Expr::Eq(Box::new(left), Box::new(self.operand()?), false))
+        } else {
+            Ok(left)
+        }
+    }
+
+    fn operand(&mut self) -> Result<Expr, String> {
+        self.skip_ws();
+        if self.eat("(") {
+            let e = self.or_expr()?;
+            self.expect(")")?;
+            return Ok(e);
+        }
+        if self.eat("@") {
+            return Ok(Expr::Attr(self.name()?));
+        }
+        if let Some(quote) = self.rest().chars().next().filter(|c| *c == '\'' || *c == '"') {
+            self.pos += 1;
+            let end = self.rest().find(quote).ok_or("unterminated string literal")?;
+            let s = self.rest()[..end].to_string();
+            self.pos += end + 1;
+            return Ok(Expr::Str(s));
+        }
+        let digits = self.rest().find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(self.rest().len());
+        if digits > 0 && !self.rest().starts_with('.') {
+            let n = self.rest()[..digits].parse().map_err(|_| format!("invalid number at \"{}\"", self.rest()))?;
+            self.pos += digits;
+            return Ok(Expr::Num(n));
+        }
+        if self.eat("text()") {
+            return Ok(Expr::Text);
+        }
+        if self.eat("position()") {
+            return Ok(Expr::Position);
+        }
+        if self.eat("last()") {
+            return Ok(Expr::Last);
+        }
+        if self.eat("not(") {
+            let e = self.or_expr()?;
+            self.expect(")")?;
+            return Ok(Expr::Not(Box::new(e)));
+        }
+        if self.eat("normalize-space(") {
+            let arg = if self.eat(")") {
+                Expr::Context
+            } else {
+                let e = self.or_expr()?;
+                self.expect(")")?;
+                e
+            };
+            return Ok(Expr::NormalizeSpace(Box::new(arg)));
+        }
+        for (func, make) in [
+            ("contains(", Expr::Contains as fn(Box<Expr>, Box<Expr>) -> Expr),
+            ("starts-with(", Expr::StartsWith),
+        ] {
+            if self.eat(func) {
+                let a = self.or_expr()?;
+                self.expect(",")?;
+                let b = self.or_expr()?;
+                self.expect(")")?;
+                return Ok(make(Box::new(a), Box::new(b)));
+            }
+        }
+        if self.eat(".") {
+            return Ok(Expr::Context);
+        }
+        Ok(Expr::Child(self.name()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 評価結果を文字列に (属性は "名前=値")
+    fn select(xpath: &str, html: &str) -> Vec<String> {
+        let doc = Html::parse_document(html);
+        XPath::parse(xpath)
+            .unwrap()
+            .select(&doc)
+            .into_iter()
+            .map(|item| match item {
+                XItem::Element(s) | XItem::Text(s) => s,
+                XItem::Attr(k, v) => format!("{k}={v}"),
+            })
+            .collect()
+    }
+
+    const DOC: &str = r#"<html><body>
+        <div id="a"><p class="x b">one</p><p>two</p><div><p class="b">nested</p></div></div>
+        <p>three</p>
+        <a href="/x" title=" t ">link</a>
+    </body></html>"#;
+
+    #[test]
+    fn child_descendant_self_and_parent_axes() {
+        assert_eq!(select("/html/body/p", DOC), vec!["three"]);
+        assert_eq!(select("//p", DOC), vec!["one", "two", "nested", "three"]);
+        assert_eq!(select("/html/body/div/*", DOC), vec!["one", "two", "nested"]);
+        // 入れ子の div から重複なく文書順に
+        assert_eq!(select("//div//p", DOC), vec!["one", "two", "nested"]);
+        assert_eq!(select("//div/p/text()", DOC), vec!["one", "two", "nested"]);
+        assert_eq!(select("/html/body/p/.", DOC), vec!["three"]);
+        assert_eq!(select("//p[@class='x b']/../@id", DOC), vec!["id=a"]);
+    }
+
+    #[test]
+    fn attribute_steps() {
+        assert_eq!(select("//a/@href", DOC), vec!["href=/x"]);
+        assert_eq!(select("//a/@*", DOC).len(), 2);
+        assert_eq!(select("//a/@title", DOC), vec!["title=t"]);
+        // 属性の後にステップは続かない
+        assert!(select("//a/@href/text()", DOC).is_empty());
+    }
+
+    #[test]
+    fn positional_predicates_are_per_context() {
+        assert_eq!(select("//div/p[1]", DOC), vec!["one", "nested"]);
+        assert_eq!(select("//div/p[last()]", DOC), vec!["two", "nested"]);
+        assert_eq!(select("/html/body/div/p[position() != 1]", DOC), vec!["two"]);
+    }
+
+    #[test]
+    fn value_predicates() {
+        assert_eq!(select("//p[@class]", DOC), vec!["one", "nested"]);
+        assert_eq!(select("//p[not(@class)]", DOC), vec!["two", "three"]);
+        assert_eq!(select("//p[contains(@class, 'b')]", DOC), vec!["one", "nested"]);
+        assert_eq!(select("//p[starts-with(., 't')]", DOC), vec!["two", "three"]);
+        assert_eq!(select("//p[@class='b' or .='two']", DOC), vec!["two", "nested"]);
+        assert_eq!(select("//p[@class and contains(., 'e')]", DOC), vec!["one", "nested"]);
+        assert_eq!(select("//div[p='one']/@id", DOC), vec!["id=a"]);
+        assert_eq!(
+            select("//span[normalize-space()='a b']", "<span>  a \n b </span><span>ab</span>"),
+            vec!["a b"]
+        );
+        // 空のノード集合はどの値とも等しくない
+        assert!(select("//p[@missing='']", DOC).is_empty());
+    }
+
+    #[test]
+    fn names_are_case_insensitive_and_keywords_need_a_boundary() {
+        assert_eq!(select("//P[@CLASS='b']", DOC), vec!["nested"]);
+        assert_eq!(select("//order", "<order>1</order>"), vec!["1"]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = |text: &str| XPath::parse(text).unwrap_err();
+        assert!(err("p").contains("must start"));
+        assert!(err("/").contains("expected a name"));
+        assert!(err("//p[1").contains("expected \"]\""));
+        assert!(err("//p[@a='x]").contains("unterminated"));
+        assert!(err("//p)").contains("unexpected"));
+        assert!(err("//p[contains(@a)]").contains("expected \",\""));
+    }
+}